@@ -0,0 +1,258 @@
+/// 重试退避策略模块
+///
+/// 为注册重试与呼叫重试提供统一的退避算法（`Backoff`），
+/// 避免各处各自实现 sleep 循环
+use std::time::Duration;
+
+/// 退避策略接口
+pub trait BackoffStrategy: Send + Sync {
+    /// 返回第 `attempt` 次重试（从 0 开始）前需要等待的时长
+    fn delay(&self, attempt: u32) -> Duration;
+}
+
+/// 对基础延迟施加 ±`jitter` 比例的随机抖动
+///
+/// `jitter` 取值范围为 `0.0..=1.0`，超出范围会被截断
+fn apply_jitter(base: Duration, jitter: f64) -> Duration {
+    let jitter = jitter.clamp(0.0, 1.0);
+    if jitter == 0.0 || base.is_zero() {
+        return base;
+    }
+    let factor = 1.0 + rand::random_range(-jitter..=jitter);
+    base.mul_f64(factor)
+}
+
+/// 指数退避：`initial * multiplier^attempt`，不超过 `max`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExponentialBackoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub multiplier: f64,
+    pub jitter: f64,
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+            multiplier: 2.0,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl BackoffStrategy for ExponentialBackoff {
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(attempt.min(i32::MAX as u32) as i32);
+        let base = (self.initial.as_secs_f64() * factor).min(self.max.as_secs_f64());
+        apply_jitter(Duration::from_secs_f64(base), self.jitter)
+    }
+}
+
+/// 线性退避：`initial + step * attempt`，不超过 `max`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearBackoff {
+    pub initial: Duration,
+    pub step: Duration,
+    pub max: Duration,
+    pub jitter: f64,
+}
+
+impl Default for LinearBackoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            step: Duration::from_secs(2),
+            max: Duration::from_secs(30),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl BackoffStrategy for LinearBackoff {
+    fn delay(&self, attempt: u32) -> Duration {
+        let base = self
+            .initial
+            .saturating_add(self.step.saturating_mul(attempt))
+            .min(self.max);
+        apply_jitter(base, self.jitter)
+    }
+}
+
+/// 固定间隔退避
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedBackoff {
+    pub delay: Duration,
+    pub jitter: f64,
+}
+
+impl Default for FixedBackoff {
+    fn default() -> Self {
+        Self {
+            delay: Duration::from_secs(5),
+            jitter: 0.0,
+        }
+    }
+}
+
+impl BackoffStrategy for FixedBackoff {
+    fn delay(&self, _attempt: u32) -> Duration {
+        apply_jitter(self.delay, self.jitter)
+    }
+}
+
+/// 可配置的退避策略
+///
+/// 作为注册重试的配置项（`Config` / `SipClientConfig` 的 `register_backoff`）
+/// 以及 `RetryPolicy` 的退避参数使用，默认使用指数退避
+#[derive(Debug, Clone, PartialEq)]
+pub enum Backoff {
+    Exponential(ExponentialBackoff),
+    Linear(LinearBackoff),
+    Fixed(FixedBackoff),
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::Exponential(ExponentialBackoff::default())
+    }
+}
+
+impl BackoffStrategy for Backoff {
+    fn delay(&self, attempt: u32) -> Duration {
+        match self {
+            Backoff::Exponential(b) => b.delay(attempt),
+            Backoff::Linear(b) => b.delay(attempt),
+            Backoff::Fixed(b) => b.delay(attempt),
+        }
+    }
+}
+
+/// 呼叫重试策略
+///
+/// 用于 `CallOptions::retry`：仅在可恢复错误时按 `backoff` 退避重试，
+/// 最多尝试 `max_attempts` 次（含首次）
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// 最多尝试次数（含首次），0 按 1 处理
+    pub max_attempts: u32,
+    /// 两次尝试之间的退避策略
+    pub backoff: Backoff,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Backoff::Exponential(ExponentialBackoff {
+                initial: Duration::from_millis(500),
+                max: Duration::from_secs(8),
+                multiplier: 2.0,
+                jitter: 0.0,
+            }),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+}
+
+impl BackoffStrategy for RetryPolicy {
+    fn delay(&self, attempt: u32) -> Duration {
+        self.backoff.delay(attempt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_sequence() {
+        let backoff = ExponentialBackoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_millis(1000),
+            multiplier: 2.0,
+            jitter: 0.0,
+        };
+        let delays: Vec<u128> = (0..6).map(|i| backoff.delay(i).as_millis()).collect();
+        assert_eq!(delays, vec![100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_linear_sequence() {
+        let backoff = LinearBackoff {
+            initial: Duration::from_millis(500),
+            step: Duration::from_millis(250),
+            max: Duration::from_millis(1200),
+            jitter: 0.0,
+        };
+        let delays: Vec<u128> = (0..5).map(|i| backoff.delay(i).as_millis()).collect();
+        assert_eq!(delays, vec![500, 750, 1000, 1200, 1200]);
+    }
+
+    #[test]
+    fn test_fixed_sequence() {
+        let backoff = FixedBackoff {
+            delay: Duration::from_millis(300),
+            jitter: 0.0,
+        };
+        assert!((0..5).all(|i| backoff.delay(i) == Duration::from_millis(300)));
+    }
+
+    #[test]
+    fn test_jitter_bounds() {
+        let backoff = Backoff::Fixed(FixedBackoff {
+            delay: Duration::from_millis(1000),
+            jitter: 0.25,
+        });
+        for attempt in 0..200 {
+            let delay = backoff.delay(attempt).as_millis();
+            assert!((750..=1250).contains(&delay), "延迟超出抖动范围: {}", delay);
+        }
+    }
+
+    #[test]
+    fn test_jitter_clamped() {
+        let backoff = ExponentialBackoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 5.0,
+        };
+        for _ in 0..200 {
+            assert!(backoff.delay(1) <= Duration::from_millis(400));
+        }
+    }
+
+    #[test]
+    fn test_retry_policy_delays() {
+        let delays: Vec<u128> = (0..6).map(|i| RetryPolicy::default().delay(i).as_millis()).collect();
+        assert_eq!(delays, vec![500, 1000, 2000, 4000, 8000, 8000]);
+
+        let policy = RetryPolicy::default().with_backoff(Backoff::Linear(LinearBackoff {
+            initial: Duration::from_millis(200),
+            step: Duration::from_millis(200),
+            max: Duration::from_millis(500),
+            jitter: 0.0,
+        }));
+        let delays: Vec<u128> = (0..4).map(|i| policy.delay(i).as_millis()).collect();
+        assert_eq!(delays, vec![200, 400, 500, 500]);
+    }
+
+    #[test]
+    fn test_default_is_exponential() {
+        assert!(matches!(Backoff::default(), Backoff::Exponential(_)));
+    }
+}
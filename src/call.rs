@@ -0,0 +1,360 @@
+/// 通话句柄模块
+///
+/// 将已建立的 SIP 对话与其媒体会话绑定，提供通话中的操作（如视频升级/降级）
+use crate::dtmf::{collect_with_barge_in, DtmfCollection};
+use crate::error::{CallError, CallResult};
+use crate::rtp_play::{MediaPlayer, RtpPlayer};
+use crate::sip_body::response_sdp;
+use crate::sip_dialog;
+use crate::sip_headers::RejectHeaders;
+use crate::sip_transport::SdpAttributes;
+use rustrtc::media::MediaKind;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use rsipstack::dialog::client_dialog::ClientInviteDialog;
+use rsipstack::dialog::dialog::{DialogState, DialogStateReceiver};
+use rsipstack::dialog::server_dialog::ServerInviteDialog;
+use tokio::sync::watch;
+use tracing::{info, warn, Instrument};
+
+/// 已建立通话的句柄
+pub struct CallHandle {
+    dialog: ClientInviteDialog,
+    rtp_player: RtpPlayer,
+    video_active: bool,
+}
+
+impl CallHandle {
+    /// 使用已建立的对话和媒体会话创建通话句柄
+    pub fn new(dialog: ClientInviteDialog, rtp_player: RtpPlayer) -> Self {
+        Self {
+            dialog,
+            rtp_player,
+            video_active: false,
+        }
+    }
+
+    /// 获取 DTMF 按键输入端（即媒体会话的 [`RtpPlayer::dtmf_sender`]）
+    ///
+    /// SIP INFO 等带外 DTMF 来源将收到的按键写入此通道，与 RFC 4733 按键一起供 `prompt_and_collect` 读取
+    pub fn dtmf_sender(&self) -> UnboundedSender<char> {
+        self.rtp_player.dtmf_sender()
+    }
+
+    /// 播放提示音并收集 DTMF 按键（IVR 基本操作）
+    ///
+    /// 按键来自媒体会话的 [`RtpPlayer::dtmf_events`]，包括 RFC 4733 按键与写入
+    /// [`dtmf_sender`](Self::dtmf_sender) 的带外按键。收到首个按键时立即停止提示音（barge-in）。
+    ///
+    /// # 参数
+    /// - `prompt`: 提示音播放器
+    /// - `max_digits`: 最大按键数，为 0 时不限制
+    /// - `timeout`: 提示音播完（或被打断）后等待首个按键、以及两次按键之间的最长时间
+    /// - `terminator`: 结束键（如 `#`），不计入结果
+    ///
+    /// # 返回
+    /// - `Err(CallError::Media)` - 提示音播放失败，或按键通道已被 `dtmf_events()` 取走
+    pub async fn prompt_and_collect(
+        &mut self,
+        prompt: Box<dyn MediaPlayer>,
+        max_digits: usize,
+        timeout: Duration,
+        terminator: Option<char>,
+    ) -> CallResult<DtmfCollection> {
+        let (mut rx, listener) = self.rtp_player.lend_dtmf_events()?;
+        let playback = prompt.spawn_playback(self.rtp_player.peer_connection());
+        let result = collect_with_barge_in(&mut rx, playback, max_digits, timeout, terminator).await;
+        self.rtp_player.return_dtmf_events(rx, listener);
+
+        let result = result?;
+        info!("DTMF 收集结束: {:?} ({:?})", result.digits, result.reason);
+        Ok(result)
+    }
+
+    /// 获取 SIP 对话
+    pub fn dialog(&self) -> &ClientInviteDialog {
+        &self.dialog
+    }
+
+    /// 获取媒体会话
+    pub fn rtp_player(&mut self) -> &mut RtpPlayer {
+        &mut self.rtp_player
+    }
+
+    /// 对话当前的远端目标（对端 Contact URI）
+    pub fn remote_target(&self) -> Option<rsip::Uri> {
+        sip_dialog::remote_target(&self.dialog)
+    }
+
+    /// 手动设置远端目标，后续 BYE / re-INVITE / INFO 等请求发往新地址
+    pub fn set_remote_target(&self, contact: rsip::headers::Contact) -> bool {
+        sip_dialog::set_remote_target(&self.dialog, contact)
+    }
+
+    /// 对端在最近一次 answer 中接受的媒体流
+    pub fn accepted_media(&self) -> &[MediaKind] {
+        self.rtp_player.accepted_media()
+    }
+
+    /// 视频是否已协商成功
+    pub fn has_video(&self) -> bool {
+        self.video_active
+    }
+
+    /// 通过 re-INVITE 将通话升级为音视频
+    ///
+    /// # 参数
+    /// - `video_source`: 协商成功后用于发送视频的播放器
+    ///
+    /// # 返回
+    /// - `Ok(true)` - 对端接受视频，视频流已启动
+    /// - `Ok(false)` - 对端拒绝视频（非 2xx 或 answer 中视频端口为 0），通话保持纯音频
+    pub async fn add_video(&mut self, mut video_source: Box<dyn MediaPlayer>) -> CallResult<bool> {
+        if self.video_active {
+            return Ok(true);
+        }
+
+        let offer = self.rtp_player.add_video_track().await?;
+        info!("发送 re-INVITE 添加视频流");
+        if !self.renegotiate(offer).await? {
+            warn!("对端拒绝视频升级");
+            return Ok(false);
+        }
+
+        if !self.rtp_player.accepted_media().contains(&MediaKind::Video) {
+            // answer 以端口 0 拒绝了视频 m 行：停用视频收发器并重新协商，使双方的会话描述一致
+            warn!("对端在 answer 中拒绝了视频流，保持纯音频通话");
+            let offer = self.rtp_player.remove_video_track().await?;
+            if !self.renegotiate(offer).await? {
+                warn!("停用视频流的 re-INVITE 被拒绝，沿用上一次协商结果");
+            }
+            return Ok(false);
+        }
+
+        video_source
+            .play_to_remote(self.rtp_player.peer_connection())
+            .await?;
+
+        self.video_active = true;
+        info!("✅ 视频流已添加");
+        Ok(true)
+    }
+
+    /// 通过 re-INVITE 移除视频流，恢复为纯音频通话
+    pub async fn remove_video(&mut self) -> CallResult<()> {
+        if !self.video_active {
+            return Ok(());
+        }
+
+        let offer = self.rtp_player.remove_video_track().await?;
+        info!("发送 re-INVITE 移除视频流");
+        let response = self
+            .dialog
+            .reinvite(Some(vec![sdp_content_type()]), Some(offer.into_bytes()))
+            .await?;
+        self.video_active = false;
+
+        match response {
+            Some(resp) if resp.status_code.kind() == rsip::StatusCodeKind::Successful => {
+                sip_dialog::update_remote_target(&self.dialog, &resp.headers);
+                let answer = response_sdp(&resp).unwrap_or_default();
+                self.rtp_player.apply_answer(&answer).await?;
+                info!("✅ 视频流已移除");
+                Ok(())
+            }
+            Some(resp) => {
+                // 本地已停止发送视频，仅恢复信令状态
+                self.rollback_video().await;
+                Err(CallError::CallRejected {
+                    code: resp.status_code.code(),
+                    phrase: resp.status_code.to_string(),
+                })
+            }
+            None => {
+                self.rollback_video().await;
+                Err(CallError::NotConnected)
+            }
+        }
+    }
+
+    /// 发送携带 `offer` 的 re-INVITE 并应用 2xx 中的 answer
+    ///
+    /// 对端拒绝或无响应时恢复上一次协商结果并返回 `false`
+    async fn renegotiate(&mut self, offer: String) -> CallResult<bool> {
+        let response = self
+            .dialog
+            .reinvite(Some(vec![sdp_content_type()]), Some(offer.into_bytes()))
+            .await?;
+        match response {
+            Some(resp) if resp.status_code.kind() == rsip::StatusCodeKind::Successful => {
+                sip_dialog::update_remote_target(&self.dialog, &resp.headers);
+                let answer = response_sdp(&resp).unwrap_or_default();
+                self.rtp_player.apply_answer(&answer).await?;
+                Ok(true)
+            }
+            other => {
+                if let Some(resp) = other {
+                    info!("re-INVITE 被拒绝: {}", resp.status_code);
+                }
+                self.rollback_video().await;
+                Ok(false)
+            }
+        }
+    }
+
+    async fn rollback_video(&mut self) {
+        if let Err(e) = self.rtp_player.rollback_offer(rustrtc::MediaKind::Video).await {
+            warn!("恢复媒体协商状态失败: {}", e);
+        }
+    }
+}
+
+/// 待应答的呼入通话
+pub struct IncomingCall {
+    dialog: ServerInviteDialog,
+    sdp_attributes: SdpAttributes,
+    reject_headers: RejectHeaders,
+    /// 对话最近一次进入的 Confirmed / Terminated 状态
+    dialog_state: Option<watch::Receiver<Option<DialogState>>>,
+}
+
+impl IncomingCall {
+    /// 使用服务端 INVITE 对话创建呼入通话
+    pub fn new(dialog: ServerInviteDialog) -> Self {
+        Self {
+            dialog,
+            sdp_attributes: SdpAttributes::default(),
+            reject_headers: RejectHeaders::default(),
+            dialog_state: None,
+        }
+    }
+
+    /// 使用客户端消息检查器共享的暂存表，使拒绝响应能够携带额外头部
+    pub(crate) fn with_reject_headers(mut self, reject_headers: RejectHeaders) -> Self {
+        self.reject_headers = reject_headers;
+        self
+    }
+
+    /// 跟踪对话的状态事件，应答时据此等待对端的 ACK，拒绝时等待对话结束
+    pub(crate) fn with_dialog_states(mut self, mut states: DialogStateReceiver) -> Self {
+        let (sender, receiver) = watch::channel(None);
+        let watcher = async move {
+            while let Some(state) = states.recv().await {
+                match state {
+                    DialogState::Confirmed(..) => {
+                        sender.send_replace(Some(state));
+                    }
+                    DialogState::Terminated(..) => {
+                        sender.send_replace(Some(state));
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        };
+        tokio::spawn(watcher.in_current_span());
+        self.dialog_state = Some(receiver);
+        self
+    }
+
+    /// 设置需要注入到 SDP answer 中的额外属性
+    pub fn set_sdp_attributes(&mut self, attributes: SdpAttributes) {
+        self.sdp_attributes = attributes;
+    }
+
+    /// 获取 SIP 对话
+    pub fn dialog(&self) -> &ServerInviteDialog {
+        &self.dialog
+    }
+
+    /// 对端 INVITE 中携带的 SDP offer
+    pub fn offer_sdp(&self) -> String {
+        String::from_utf8_lossy(&self.dialog.initial_request().body).to_string()
+    }
+
+    /// 生成本地 SDP answer 并以 200 OK 应答
+    ///
+    /// answer 的编解码器为 offer 与本地支持的交集；没有交集时以 488 拒绝。
+    /// 收到对端的 ACK（对话进入 Confirmed）后返回
+    ///
+    /// # 返回
+    /// 返回已完成协商、可直接用于媒体收发的 `RtpPlayer`
+    pub async fn answer(&self) -> CallResult<RtpPlayer> {
+        let offer = self.offer_sdp();
+        let (mut player, answer) = match RtpPlayer::new_answerer(&offer).await {
+            Ok(result) => result,
+            Err(e) => {
+                warn!("无法应答呼入通话: {}", e);
+                self.reject(rsip::StatusCode::NotAcceptableHere, None).await?;
+                return Err(CallError::invalid_sdp(e.to_string()));
+            }
+        };
+
+        player.set_sdp_attributes(self.sdp_attributes.clone());
+        self.answer_with_media(&answer).await?;
+        Ok(player)
+    }
+
+    /// 以 200 OK 应答，并携带调用方提供的 SDP answer
+    ///
+    /// 收到对端的 ACK（对话进入 Confirmed）后返回，此时即可开始媒体收发
+    ///
+    /// # 返回
+    /// - `Err(CallError::NotConnected)` - 对端取消呼叫或始终未发送 ACK，对话已结束
+    pub async fn answer_with_media(&self, sdp_answer: &str) -> CallResult<()> {
+        if sdp_answer.trim().is_empty() {
+            return Err(CallError::invalid_sdp("SDP answer 为空"));
+        }
+        let answer = self.sdp_attributes.apply(sdp_answer);
+        self.dialog
+            .accept(Some(vec![sdp_content_type()]), Some(answer.into_bytes()))?;
+        match self.wait_dialog_state(|state| matches!(state, DialogState::Confirmed(..) | DialogState::Terminated(..))).await {
+            Some(DialogState::Terminated(_, reason)) => {
+                warn!("呼入通话在确认前结束: {:?}", reason);
+                Err(CallError::NotConnected)
+            }
+            _ => {
+                info!("✅ 已应答呼入通话: {}", self.dialog.id());
+                Ok(())
+            }
+        }
+    }
+
+    /// 以指定状态码拒绝呼入通话（如 486 Busy Here、603 Decline），对话结束后返回
+    ///
+    /// `retry_after` 为秒数，设置时响应携带 `Retry-After` 头部（如过载时的 503）。
+    /// 只接受 3xx-6xx 状态码，其他状态码返回 `CallError::InvalidConfig`
+    pub async fn reject(&self, status: rsip::StatusCode, retry_after: Option<u32>) -> CallResult<()> {
+        let code = status.code();
+        if !(300..700).contains(&code) {
+            return Err(CallError::invalid_config(format!(
+                "拒绝状态码 {}（仅允许 3xx-6xx）",
+                code
+            )));
+        }
+        if let Some(seconds) = retry_after {
+            self.reject_headers.lock().unwrap().insert(
+                self.dialog.id().call_id,
+                vec![rsip::Header::RetryAfter(seconds.to_string().into())],
+            );
+        }
+        self.dialog.reject(Some(status.clone()), None)?;
+        self.wait_dialog_state(|state| matches!(state, DialogState::Terminated(..))).await;
+        info!("已拒绝呼入通话 ({}): {}", status, self.dialog.id());
+        Ok(())
+    }
+
+    /// 等待对话进入满足条件的状态；未跟踪对话状态或跟踪任务已结束时返回 `None`
+    async fn wait_dialog_state(&self, done: impl Fn(&DialogState) -> bool) -> Option<DialogState> {
+        let mut states = self.dialog_state.clone()?;
+        let state = states
+            .wait_for(|state| state.as_ref().is_some_and(&done))
+            .await
+            .ok()?;
+        state.clone()
+    }
+}
+
+fn sdp_content_type() -> rsip::Header {
+    rsip::Header::ContentType("application/sdp".into())
+}
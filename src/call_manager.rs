@@ -0,0 +1,211 @@
+/// 多路呼叫管理模块
+///
+/// 在 `SipClient` 之上按 `DialogId` 跟踪同时进行的多路外呼，
+/// 支持查询、单独挂断，以及关闭时批量挂断
+use crate::error::{CallError, CallResult};
+use crate::sip_client::SipClient;
+use futures_util::future::join_all;
+use rsip::Response;
+use rsipstack::dialog::client_dialog::ClientInviteDialog;
+use rsipstack::dialog::dialog::DialogState;
+use rsipstack::dialog::DialogId;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// 受管理呼叫的状态
+///
+/// 呼叫在 INVITE 收到 2xx 后才纳入管理，因此只有接通与结束两种状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallState {
+    /// 已接通
+    Confirmed,
+    /// 已结束
+    Terminated,
+}
+
+impl From<&DialogState> for CallState {
+    fn from(state: &DialogState) -> Self {
+        match state {
+            DialogState::Terminated(_, _) => CallState::Terminated,
+            _ => CallState::Confirmed,
+        }
+    }
+}
+
+/// 受管理呼叫的信息快照
+#[derive(Debug, Clone)]
+pub struct CallInfo {
+    pub id: DialogId,
+    pub remote_uri: String,
+    pub started_at: Instant,
+    pub state: CallState,
+}
+
+impl CallInfo {
+    /// 自呼叫接通以来的时长
+    pub fn duration(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+}
+
+struct ManagedCall {
+    dialog: ClientInviteDialog,
+    remote_uri: String,
+    started_at: Instant,
+}
+
+/// 呼叫管理器
+///
+/// 只跟踪已接通的呼叫；对端挂断的呼叫在下次查询时自动移除
+pub struct CallManager {
+    client: Arc<SipClient>,
+    calls: Mutex<HashMap<DialogId, ManagedCall>>,
+}
+
+impl CallManager {
+    /// 创建呼叫管理器
+    pub fn new(client: Arc<SipClient>) -> Self {
+        Self {
+            client,
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 底层 SIP 客户端
+    pub fn client(&self) -> &Arc<SipClient> {
+        &self.client
+    }
+
+    /// 发起呼叫，接通后纳入管理
+    pub async fn make_call(
+        &self,
+        target: &str,
+        sdp_offer: &str,
+    ) -> CallResult<(ClientInviteDialog, Option<Response>)> {
+        let (dialog, response) = self.client.make_call(target, sdp_offer).await?;
+        if dialog.state().is_confirmed() {
+            let remote_uri = self
+                .client
+                .target_uri(target)
+                .ok_or_else(|| CallError::invalid_target(target))?;
+            let call = ManagedCall {
+                dialog: dialog.clone(),
+                remote_uri,
+                started_at: Instant::now(),
+            };
+            self.calls.lock().unwrap().insert(dialog.id(), call);
+        }
+        Ok((dialog, response))
+    }
+
+    /// 按 `DialogId` 查找受管理的对话
+    pub fn get(&self, id: &DialogId) -> Option<ClientInviteDialog> {
+        self.calls.lock().unwrap().get(id).map(|call| call.dialog.clone())
+    }
+
+    /// 列出仍在进行的呼叫
+    pub fn calls(&self) -> Vec<CallInfo> {
+        let mut calls = self.calls.lock().unwrap();
+        calls.retain(|_, call| CallState::from(&call.dialog.state()) != CallState::Terminated);
+        calls
+            .iter()
+            .map(|(id, call)| CallInfo {
+                id: id.clone(),
+                remote_uri: call.remote_uri.clone(),
+                started_at: call.started_at,
+                state: CallState::from(&call.dialog.state()),
+            })
+            .collect()
+    }
+
+    /// 仍在进行的呼叫数
+    pub fn len(&self) -> usize {
+        self.calls().len()
+    }
+
+    /// 是否没有进行中的呼叫
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 挂断指定呼叫并停止管理
+    ///
+    /// # 返回
+    /// - `Err(CallError::InvalidTarget)` - 没有该 `DialogId` 的受管理呼叫
+    pub async fn hangup(&self, id: &DialogId) -> CallResult<()> {
+        let call = self.calls.lock().unwrap().remove(id);
+        let Some(call) = call else {
+            return Err(CallError::InvalidTarget {
+                target: id.to_string(),
+            });
+        };
+        self.client.hangup(&call.dialog).await
+    }
+
+    /// 向所有受管理的呼叫并发发送 BYE，返回成功挂断的数量
+    pub async fn hangup_all(&self) -> usize {
+        let calls: Vec<ManagedCall> = self.calls.lock().unwrap().drain().map(|(_, call)| call).collect();
+        if calls.is_empty() {
+            return 0;
+        }
+        info!("📴 批量挂断 {} 路呼叫", calls.len());
+        let results = join_all(calls.iter().map(|call| self.client.hangup(&call.dialog))).await;
+        results
+            .into_iter()
+            .zip(&calls)
+            .filter(|(result, call)| match result {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("挂断 {} 失败: {}", call.dialog.id(), e);
+                    false
+                }
+            })
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sip_client::tests::{spawn_uas_stub, test_config, test_ip, TEST_SDP};
+
+    #[tokio::test]
+    async fn test_track_and_hangup_calls() {
+        let ip = test_ip();
+        let (uas_addr, _methods) = spawn_uas_stub(ip).await;
+        let client = Arc::new(SipClient::new(test_config(uas_addr)).await.unwrap());
+        let manager = CallManager::new(client.clone());
+
+        let mut ids = Vec::new();
+        for target in ["bob", "carol", "dave"] {
+            let (dialog, _) = tokio::time::timeout(
+                Duration::from_secs(5),
+                manager.make_call(target, TEST_SDP),
+            )
+            .await
+            .expect("INVITE 超时")
+            .unwrap();
+            ids.push(dialog.id());
+        }
+
+        let calls = manager.calls();
+        assert_eq!(calls.len(), 3);
+        assert!(calls.iter().all(|c| c.state == CallState::Confirmed));
+        let dave = format!("sip:dave@{}", uas_addr);
+        assert!(calls.iter().any(|c| c.remote_uri == dave));
+        assert!(manager.get(&ids[0]).is_some());
+
+        manager.hangup(&ids[0]).await.unwrap();
+        assert!(manager.get(&ids[0]).is_none());
+        assert!(matches!(
+            manager.hangup(&ids[0]).await,
+            Err(CallError::InvalidTarget { .. })
+        ));
+
+        assert_eq!(manager.hangup_all().await, 2);
+        assert!(manager.is_empty());
+        client.shutdown().await;
+    }
+}
@@ -0,0 +1,328 @@
+/// 呼叫调度模块
+///
+/// 面向外呼场景，在 `SipClient` 之上按配置的每秒呼叫数（CPS）和最大并发数
+/// 对新呼叫进行节流，超出部分排队等待，避免压垮中继或触发运营商限流
+use crate::error::{CallError, CallResult, ConfigError};
+use crate::sip_client::SipClient;
+use rsip::Response;
+use rsipstack::dialog::client_dialog::ClientInviteDialog;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+
+/// 呼叫调度配置
+#[derive(Debug, Clone)]
+pub struct CallSchedulerConfig {
+    /// 每秒允许发起的呼叫数，必须是有限的正数
+    pub calls_per_second: f64,
+    /// 令牌桶容量（允许的突发呼叫数）
+    pub burst: u32,
+    /// 最大同时进行的呼叫数
+    pub max_concurrent: usize,
+}
+
+impl Default for CallSchedulerConfig {
+    fn default() -> Self {
+        Self {
+            calls_per_second: 10.0,
+            burst: 1,
+            max_concurrent: 100,
+        }
+    }
+}
+
+/// 调度器统计信息快照
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SchedulerMetrics {
+    /// 正在排队等待发起的呼叫数
+    pub queued: u64,
+    /// 已发起且尚未结束的呼叫数
+    pub active: u64,
+    /// 已结束的呼叫数
+    pub completed: u64,
+    /// 发起失败（`make_call` 返回错误）的呼叫数
+    pub failed: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    queued: AtomicU64,
+    active: AtomicU64,
+    completed: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// 单次等待令牌的最长时间，极低速率下分多次等待
+const MAX_TOKEN_WAIT: Duration = Duration::from_secs(60);
+
+/// 校验每秒呼叫数：NaN、无穷、零与负数均无效
+fn validate_rate(rate: f64) -> Result<f64, ConfigError> {
+    if rate.is_finite() && rate > 0.0 {
+        Ok(rate)
+    } else {
+        Err(ConfigError::Invalid(format!("每秒呼叫数必须是有限的正数: {}", rate)))
+    }
+}
+
+struct BucketState {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+/// 令牌桶限速器
+struct TokenBucket {
+    state: Mutex<BucketState>,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, burst: u32) -> Result<Self, ConfigError> {
+        let capacity = burst.max(1) as f64;
+        Ok(Self {
+            state: Mutex::new(BucketState {
+                rate: validate_rate(rate)?,
+                capacity,
+                tokens: capacity,
+                last: Instant::now(),
+            }),
+        })
+    }
+
+    fn refill(state: &mut BucketState) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * state.rate).min(state.capacity);
+        state.last = now;
+    }
+
+    /// 获取一个令牌，不足时等待
+    async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().await;
+                Self::refill(&mut state);
+                if state.tokens >= 1.0 {
+                    state.tokens -= 1.0;
+                    return;
+                }
+                Duration::try_from_secs_f64((1.0 - state.tokens) / state.rate)
+                    .map_or(MAX_TOKEN_WAIT, |wait| wait.min(MAX_TOKEN_WAIT))
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    async fn set_rate(&self, rate: f64) -> Result<(), ConfigError> {
+        let rate = validate_rate(rate)?;
+        let mut state = self.state.lock().await;
+        Self::refill(&mut state);
+        state.rate = rate;
+        Ok(())
+    }
+
+    async fn rate(&self) -> f64 {
+        self.state.lock().await.rate
+    }
+}
+
+/// 呼叫准入控制：令牌桶 + 并发信号量 + 统计
+struct CallPacer {
+    bucket: TokenBucket,
+    semaphore: Arc<Semaphore>,
+    counters: Arc<Counters>,
+}
+
+impl CallPacer {
+    fn new(config: &CallSchedulerConfig) -> Result<Self, ConfigError> {
+        Ok(Self {
+            bucket: TokenBucket::new(config.calls_per_second, config.burst)?,
+            semaphore: Arc::new(Semaphore::new(config.max_concurrent.max(1))),
+            counters: Arc::new(Counters::default()),
+        })
+    }
+
+    /// 排队等待并发名额和速率令牌
+    async fn admit(&self) -> CallResult<CallSlot> {
+        self.counters.queued.fetch_add(1, Ordering::Relaxed);
+        let permit = self.semaphore.clone().acquire_owned().await;
+        let permit = match permit {
+            Ok(permit) => permit,
+            Err(_) => {
+                self.counters.queued.fetch_sub(1, Ordering::Relaxed);
+                return Err(CallError::NotConnected);
+            }
+        };
+        self.bucket.acquire().await;
+        self.counters.queued.fetch_sub(1, Ordering::Relaxed);
+        self.counters.active.fetch_add(1, Ordering::Relaxed);
+        Ok(CallSlot {
+            _permit: permit,
+            counters: self.counters.clone(),
+            failed: false,
+        })
+    }
+
+    fn metrics(&self) -> SchedulerMetrics {
+        SchedulerMetrics {
+            queued: self.counters.queued.load(Ordering::Relaxed),
+            active: self.counters.active.load(Ordering::Relaxed),
+            completed: self.counters.completed.load(Ordering::Relaxed),
+            failed: self.counters.failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 并发名额，释放时计为一次完成（或标记失败时计为失败）的呼叫
+struct CallSlot {
+    _permit: OwnedSemaphorePermit,
+    counters: Arc<Counters>,
+    failed: bool,
+}
+
+impl Drop for CallSlot {
+    fn drop(&mut self) {
+        self.counters.active.fetch_sub(1, Ordering::Relaxed);
+        let counter = if self.failed { &self.counters.failed } else { &self.counters.completed };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// 经调度发起的呼叫
+///
+/// 持有期间占用一个并发名额，drop 时释放名额并计入已完成
+pub struct ScheduledCall {
+    pub dialog: ClientInviteDialog,
+    pub response: Option<Response>,
+    _slot: CallSlot,
+}
+
+/// 呼叫调度器
+pub struct CallScheduler {
+    client: Arc<SipClient>,
+    pacer: CallPacer,
+}
+
+impl CallScheduler {
+    /// 创建呼叫调度器
+    ///
+    /// `calls_per_second` 不是有限的正数时返回 `ConfigError::Invalid`
+    pub fn new(client: Arc<SipClient>, config: CallSchedulerConfig) -> Result<Self, ConfigError> {
+        Ok(Self {
+            client,
+            pacer: CallPacer::new(&config)?,
+        })
+    }
+
+    /// 按速率和并发限制发起呼叫，超出限制时排队等待
+    ///
+    /// `make_call` 返回错误时计入 `failed` 而不是 `completed`
+    pub async fn make_call(&self, target: &str, sdp_offer: &str) -> CallResult<ScheduledCall> {
+        let mut slot = self.pacer.admit().await?;
+        debug!("调度器放行呼叫: {}", target);
+        match self.client.make_call(target, sdp_offer).await {
+            Ok((dialog, response)) => Ok(ScheduledCall {
+                dialog,
+                response,
+                _slot: slot,
+            }),
+            Err(e) => {
+                slot.failed = true;
+                Err(e)
+            }
+        }
+    }
+
+    /// 动态调整每秒呼叫数，无效值返回 `ConfigError::Invalid` 并保持原速率
+    pub async fn set_calls_per_second(&self, calls_per_second: f64) -> Result<(), ConfigError> {
+        self.pacer.bucket.set_rate(calls_per_second).await
+    }
+
+    /// 当前每秒呼叫数
+    pub async fn calls_per_second(&self) -> f64 {
+        self.pacer.bucket.rate().await
+    }
+
+    /// 获取统计信息快照
+    pub fn metrics(&self) -> SchedulerMetrics {
+        self.pacer.metrics()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_token_bucket_paces_calls() {
+        let bucket = TokenBucket::new(20.0, 1).unwrap();
+        let start = Instant::now();
+        for _ in 0..3 {
+            bucket.acquire().await;
+        }
+        // 首个令牌立即可用，后两个各需约 50ms
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    #[tokio::test]
+    async fn test_token_bucket_set_rate() {
+        let bucket = TokenBucket::new(1.0, 1).unwrap();
+        bucket.acquire().await;
+        bucket.set_rate(100.0).await.unwrap();
+        let start = Instant::now();
+        bucket.acquire().await;
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(bucket.rate().await, 100.0);
+
+        // 非有限或非正的速率被拒绝，保持原速率
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(TokenBucket::new(rate, 1).is_err());
+            assert!(bucket.set_rate(rate).await.is_err());
+        }
+        assert_eq!(bucket.rate().await, 100.0);
+
+        // 极低速率的等待时间被截断而不是溢出
+        let slow = TokenBucket::new(f64::MIN_POSITIVE, 1).unwrap();
+        slow.acquire().await;
+        assert!(tokio::time::timeout(Duration::from_millis(50), slow.acquire()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pacer_metrics_and_concurrency() {
+        let pacer = Arc::new(
+            CallPacer::new(&CallSchedulerConfig {
+                calls_per_second: 1000.0,
+                burst: 10,
+                max_concurrent: 1,
+            })
+            .unwrap(),
+        );
+
+        let first = pacer.admit().await.unwrap();
+        assert_eq!(pacer.metrics().active, 1);
+
+        let waiting = {
+            let pacer = pacer.clone();
+            tokio::spawn(async move { pacer.admit().await.map(|_| ()) })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(pacer.metrics().queued, 1);
+
+        drop(first);
+        waiting.await.unwrap().unwrap();
+        let mut failed = pacer.admit().await.unwrap();
+        failed.failed = true;
+        drop(failed);
+        assert_eq!(
+            pacer.metrics(),
+            SchedulerMetrics {
+                queued: 0,
+                active: 0,
+                completed: 2,
+                failed: 1,
+            }
+        );
+    }
+}
@@ -0,0 +1,570 @@
+/// 音频编解码辅助模块
+///
+/// 提供 G.711（PCMU/PCMA）与线性 PCM 之间的编解码及电平计算，
+/// 启用 `g722` 特性时另提供 G.722（64 kbit/s）编码器；另提供线性 PCM 增益调整、
+/// 声道混合与重采样，以及基于能量的语音活动检测
+#[cfg(feature = "g722")]
+mod g722 {
+    /// G.722 QMF 滤波器系数
+    const QMF_COEFFS: [i32; 12] = [3, -11, 12, 32, -210, 951, 3876, -805, 362, -156, 53, -11];
+    const Q6: [i32; 32] = [
+        0, 35, 72, 110, 150, 190, 233, 276, 323, 370, 422, 473, 530, 587, 650, 714, 786, 858, 940,
+        1023, 1121, 1219, 1339, 1458, 1612, 1765, 1980, 2195, 2557, 2919, 0, 0,
+    ];
+    const ILN: [i32; 32] = [
+        0, 63, 62, 31, 30, 29, 28, 27, 26, 25, 24, 23, 22, 21, 20, 19, 18, 17, 16, 15, 14, 13, 12, 11,
+        10, 9, 8, 7, 6, 5, 4, 0,
+    ];
+    const ILP: [i32; 32] = [
+        0, 61, 60, 59, 58, 57, 56, 55, 54, 53, 52, 51, 50, 49, 48, 47, 46, 45, 44, 43, 42, 41, 40, 39,
+        38, 37, 36, 35, 34, 33, 32, 0,
+    ];
+    const WL: [i32; 8] = [-60, -30, 58, 172, 334, 538, 1198, 3042];
+    const RL42: [usize; 16] = [0, 7, 6, 5, 4, 3, 2, 1, 7, 6, 5, 4, 3, 2, 1, 0];
+    const ILB: [i32; 32] = [
+        2048, 2093, 2139, 2186, 2233, 2282, 2332, 2383, 2435, 2489, 2543, 2599, 2656, 2714, 2774, 2834,
+        2896, 2960, 3025, 3091, 3158, 3228, 3298, 3371, 3444, 3520, 3597, 3676, 3756, 3838, 3922, 4008,
+    ];
+    const QM4: [i32; 16] = [
+        0, -20456, -12896, -8968, -6288, -4240, -2584, -1200, 20456, 12896, 8968, 6288, 4240, 2584,
+        1200, 0,
+    ];
+    const QM2: [i32; 4] = [-7408, -1616, 7408, 1616];
+    const IHN: [i32; 3] = [0, 1, 0];
+    const IHP: [i32; 3] = [0, 3, 2];
+    const WH: [i32; 3] = [0, -214, 798];
+    const RH2: [usize; 4] = [2, 1, 2, 1];
+
+    fn saturate(value: i32) -> i32 {
+        value.clamp(i16::MIN as i32, i16::MAX as i32)
+    }
+
+    /// G.722 单个子带的 ADPCM 预测器状态
+    #[derive(Debug, Clone, Default)]
+    struct G722Band {
+        s: i32,
+        sp: i32,
+        sz: i32,
+        r: [i32; 3],
+        a: [i32; 3],
+        ap: [i32; 3],
+        p: [i32; 3],
+        d: [i32; 7],
+        b: [i32; 7],
+        bp: [i32; 7],
+        sg: [i32; 7],
+        nb: i32,
+        det: i32,
+    }
+
+    impl G722Band {
+        fn new(det: i32) -> Self {
+            Self { det, ..Default::default() }
+        }
+
+        /// 更新预测器系数并计算下一个预测值（G.722 Block 4）
+        fn update(&mut self, d: i32) {
+            self.d[0] = d;
+            self.r[0] = saturate(self.s + d);
+            self.p[0] = saturate(self.sz + d);
+
+            // UPPOL2
+            for i in 0..3 {
+                self.sg[i] = self.p[i] >> 15;
+            }
+            let wd1 = saturate(self.a[1] << 2);
+            let wd2 = if self.sg[0] == self.sg[1] { -wd1 } else { wd1 }.min(32767);
+            let mut wd3 = (wd2 >> 7) + if self.sg[0] == self.sg[2] { 128 } else { -128 };
+            wd3 += (self.a[2] * 32512) >> 15;
+            self.ap[2] = wd3.clamp(-12288, 12288);
+
+            // UPPOL1
+            let wd1 = if self.sg[0] == self.sg[1] { 192 } else { -192 };
+            let wd2 = (self.a[1] * 32640) >> 15;
+            let limit = saturate(15360 - self.ap[2]);
+            self.ap[1] = saturate(wd1 + wd2).clamp(-limit, limit);
+
+            // UPZERO
+            let wd1 = if d == 0 { 0 } else { 128 };
+            self.sg[0] = d >> 15;
+            for i in 1..7 {
+                self.sg[i] = self.d[i] >> 15;
+                let wd2 = if self.sg[i] == self.sg[0] { wd1 } else { -wd1 };
+                let wd3 = (self.b[i] * 32640) >> 15;
+                self.bp[i] = saturate(wd2 + wd3);
+            }
+
+            // DELAYA
+            for i in (1..7).rev() {
+                self.d[i] = self.d[i - 1];
+                self.b[i] = self.bp[i];
+            }
+            for i in (1..3).rev() {
+                self.r[i] = self.r[i - 1];
+                self.p[i] = self.p[i - 1];
+                self.a[i] = self.ap[i];
+            }
+
+            // FILTEP / FILTEZ / PREDIC
+            let wd1 = (self.a[1] * saturate(self.r[1] + self.r[1])) >> 15;
+            let wd2 = (self.a[2] * saturate(self.r[2] + self.r[2])) >> 15;
+            self.sp = saturate(wd1 + wd2);
+            let sz: i32 = (1..7)
+                .map(|i| (self.b[i] * saturate(self.d[i] + self.d[i])) >> 15)
+                .sum();
+            self.sz = saturate(sz);
+            self.s = saturate(self.sp + self.sz);
+        }
+
+        /// 按对数量化因子更新量化步长（G.722 Block 3 SCALEL/SCALEH）
+        fn scale(&mut self, shift: i32) {
+            let wd1 = ((self.nb >> 6) & 31) as usize;
+            let wd2 = shift - (self.nb >> 11);
+            let wd3 = if wd2 < 0 { ILB[wd1] << -wd2 } else { ILB[wd1] >> wd2 };
+            self.det = wd3 << 2;
+        }
+    }
+
+    /// G.722 编码器（64 kbit/s 模式）
+    ///
+    /// 输入 16 kHz 线性 PCM，每两个样本输出一个字节；编码器带有预测状态，
+    /// 同一路媒体流应复用同一个实例
+    #[derive(Debug, Clone)]
+    pub struct G722Encoder {
+        x: [i32; 24],
+        low: G722Band,
+        high: G722Band,
+        /// 上一次输入为奇数个样本时留下的最后一个样本
+        pending: Option<i16>,
+    }
+
+    impl Default for G722Encoder {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl G722Encoder {
+        /// 创建处于初始状态的编码器
+        pub fn new() -> Self {
+            Self {
+                x: [0; 24],
+                low: G722Band::new(32),
+                high: G722Band::new(8),
+                pending: None,
+            }
+        }
+
+        /// 编码 16 kHz 线性 PCM
+        ///
+        /// 奇数个样本时最后一个样本暂存，与下一次输入的第一个样本组成一对编码，
+        /// 分段输入与一次性输入的编码结果相同
+        pub fn encode(&mut self, samples: &[i16]) -> Vec<u8> {
+            let mut out = Vec::with_capacity(samples.len().div_ceil(2));
+            let mut rest = samples;
+            if let Some(first) = self.pending.take() {
+                match rest.split_first() {
+                    Some((&second, tail)) => {
+                        out.push(self.encode_pair(first, second));
+                        rest = tail;
+                    }
+                    None => self.pending = Some(first),
+                }
+            }
+            let mut pairs = rest.chunks_exact(2);
+            out.extend(pairs.by_ref().map(|pair| self.encode_pair(pair[0], pair[1])));
+            if let [last] = pairs.remainder() {
+                self.pending = Some(*last);
+            }
+            out
+        }
+
+        fn encode_pair(&mut self, first: i16, second: i16) -> u8 {
+            // 发送端 QMF：分离高低子带，输出降采样到 8 kHz
+            self.x.copy_within(2.., 0);
+            self.x[22] = first as i32;
+            self.x[23] = second as i32;
+            let (mut sum_even, mut sum_odd) = (0, 0);
+            for i in 0..12 {
+                sum_odd += self.x[2 * i] * QMF_COEFFS[i];
+                sum_even += self.x[2 * i + 1] * QMF_COEFFS[11 - i];
+            }
+            let xlow = (sum_even + sum_odd) >> 14;
+            let xhigh = (sum_even - sum_odd) >> 14;
+
+            // 低子带：6 bit 自适应量化
+            let el = saturate(xlow - self.low.s);
+            let wd = if el >= 0 { el } else { -(el + 1) };
+            let i = (1..30)
+                .find(|&i| wd < (Q6[i] * self.low.det) >> 12)
+                .unwrap_or(30);
+            let ilow = if el < 0 { ILN[i] } else { ILP[i] };
+            let ril = (ilow >> 2) as usize;
+            let dlow = (self.low.det * QM4[ril]) >> 15;
+            let nb = ((self.low.nb * 127) >> 7) + WL[RL42[ril]];
+            self.low.nb = nb.clamp(0, 18432);
+            self.low.scale(8);
+            self.low.update(dlow);
+
+            // 高子带：2 bit 自适应量化
+            let eh = saturate(xhigh - self.high.s);
+            let wd = if eh >= 0 { eh } else { -(eh + 1) };
+            let mih = if wd >= (564 * self.high.det) >> 12 { 2 } else { 1 };
+            let ihigh = if eh < 0 { IHN[mih] } else { IHP[mih] };
+            let dhigh = (self.high.det * QM2[ihigh as usize]) >> 15;
+            let nb = ((self.high.nb * 127) >> 7) + WH[RH2[ihigh as usize]];
+            self.high.nb = nb.clamp(0, 22528);
+            self.high.scale(10);
+            self.high.update(dhigh);
+
+            ((ihigh << 6) | ilow) as u8
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn tone(len: usize) -> Vec<i16> {
+            (0..len)
+                .map(|n| ((n as f64 * 2.0 * std::f64::consts::PI * 400.0 / 16000.0).sin() * 8000.0) as i16)
+                .collect()
+        }
+
+        #[test]
+        fn test_g722_encoder() {
+            let mut encoder = G722Encoder::new();
+            // 20 ms、16 kHz 的帧编码为 160 字节
+            let silence = encoder.encode(&[0; 320]);
+            assert_eq!(silence.len(), 160);
+
+            let tone = tone(320);
+            let mut encoder = G722Encoder::new();
+            let first = encoder.encode(&tone);
+            assert_eq!(first.len(), 160);
+            assert!(first.iter().any(|b| *b != silence[0]));
+            // 相同输入在相同状态下得到相同输出
+            assert_eq!(G722Encoder::new().encode(&tone), first);
+        }
+
+        #[test]
+        fn test_g722_reset_state_vectors() {
+            // 复位状态下静音的编码：低子带量化区间 ILP[4] = 0x3A，高子带 IHP[1] = 3，即 0xFA
+            assert_eq!(G722Encoder::new().encode(&[0; 8]), vec![0xFA; 4]);
+
+            // 400 Hz、-12 dBFS 正弦前 16 个码字（回归向量）
+            let expected = [
+                0xFA, 0x9B, 0x2E, 0x91, 0x24, 0x8D, 0xA0, 0xA0, 0xE0, 0xE0, 0xE0, 0xE7, 0x6D, 0xF3, 0x79, 0xDE,
+            ];
+            assert_eq!(G722Encoder::new().encode(&tone(32)), expected);
+        }
+
+        #[test]
+        fn test_g722_odd_samples_carried() {
+            let tone = tone(321);
+            let whole = G722Encoder::new().encode(&tone[..320]);
+
+            let mut encoder = G722Encoder::new();
+            let mut split = encoder.encode(&tone[..3]);
+            assert_eq!(split.len(), 1);
+            assert!(encoder.encode(&[]).is_empty());
+            split.extend(encoder.encode(&tone[3..101]));
+            split.extend(encoder.encode(&tone[101..320]));
+            assert_eq!(split, whole);
+            // 末尾的奇数样本等待下一次输入
+            assert!(encoder.encode(&tone[320..]).is_empty());
+            assert_eq!(encoder.encode(&[0]).len(), 1);
+        }
+    }
+}
+
+#[cfg(feature = "g722")]
+pub use g722::G722Encoder;
+
+/// G.711 μ-law 解码为 16 位线性 PCM
+pub fn ulaw_to_linear(ulaw: u8) -> i16 {
+    let u = !ulaw;
+    let sign = u & 0x80;
+    let exponent = (u >> 4) & 0x07;
+    let mantissa = (u & 0x0F) as i32;
+    let sample = ((((mantissa << 3) + 0x84) << exponent) - 0x84) as i16;
+    if sign != 0 {
+        -sample
+    } else {
+        sample
+    }
+}
+
+/// G.711 A-law 解码为 16 位线性 PCM
+pub fn alaw_to_linear(alaw: u8) -> i16 {
+    let a = alaw ^ 0x55;
+    let exponent = (a >> 4) & 0x07;
+    let mantissa = (a & 0x0F) as i32;
+    let magnitude = if exponent == 0 {
+        (mantissa << 4) + 8
+    } else {
+        ((mantissa << 4) + 0x108) << (exponent - 1)
+    };
+    // A-law 中符号位为 1 表示正数
+    if a & 0x80 != 0 {
+        magnitude as i16
+    } else {
+        -(magnitude as i16)
+    }
+}
+
+/// 16 位线性 PCM 编码为 G.711 μ-law
+pub fn linear_to_ulaw(sample: i16) -> u8 {
+    const BIAS: i32 = 0x84;
+    const CLIP: i32 = 32635;
+    let mut pcm = sample as i32;
+    let sign = if pcm < 0 {
+        pcm = -pcm;
+        0x80
+    } else {
+        0
+    };
+    let pcm = pcm.min(CLIP) + BIAS;
+    // pcm 最高位位于第 7~14 位，对应段号 0~7
+    let exponent = (31 - pcm.leading_zeros() as i32 - 7) as u8;
+    let mantissa = ((pcm >> (exponent + 3)) & 0x0F) as u8;
+    !(sign | (exponent << 4) | mantissa)
+}
+
+/// 16 位线性 PCM 编码为 G.711 A-law
+pub fn linear_to_alaw(sample: i16) -> u8 {
+    let mut pcm = (sample as i32) >> 3;
+    let mask = if pcm >= 0 {
+        0xD5
+    } else {
+        pcm = -pcm - 1;
+        0x55
+    };
+    let segment = (0..8).find(|seg| pcm <= (0x1F << seg) | ((1 << seg) - 1));
+    let Some(segment) = segment else {
+        return 0x7F ^ mask;
+    };
+    let shift = if segment < 2 { 1 } else { segment };
+    let aval = ((segment as u8) << 4) | ((pcm >> shift) & 0x0F) as u8;
+    aval ^ mask
+}
+
+/// 按载荷类型将线性 PCM 编码为 G.711 数据，不支持的载荷类型返回 `None`
+pub fn encode_g711(payload_type: u8, samples: &[i16]) -> Option<Vec<u8>> {
+    match payload_type {
+        0 => Some(samples.iter().map(|s| linear_to_ulaw(*s)).collect()),
+        8 => Some(samples.iter().map(|s| linear_to_alaw(*s)).collect()),
+        _ => None,
+    }
+}
+
+/// 按载荷类型将 G.711 数据解码为线性 PCM，不支持的载荷类型返回 `None`
+pub fn decode_g711(payload_type: u8, payload: &[u8]) -> Option<Vec<i16>> {
+    match payload_type {
+        0 => Some(payload.iter().map(|b| ulaw_to_linear(*b)).collect()),
+        8 => Some(payload.iter().map(|b| alaw_to_linear(*b)).collect()),
+        _ => None,
+    }
+}
+
+/// 允许的最小增益（dB）
+pub const MIN_GAIN_DB: f32 = -30.0;
+/// 允许的最大增益（dB）
+pub const MAX_GAIN_DB: f32 = 30.0;
+
+/// 将增益限制在 [`MIN_GAIN_DB`, `MAX_GAIN_DB`] 范围内，NaN 视为 0 dB
+pub fn clamp_gain_db(gain_db: f32) -> f32 {
+    if gain_db.is_nan() {
+        return 0.0;
+    }
+    gain_db.clamp(MIN_GAIN_DB, MAX_GAIN_DB)
+}
+
+/// 按 dB 增益缩放线性 PCM，超出 16 位范围的样本削波到边界值
+pub fn apply_gain(samples: &mut [i16], gain_db: f32) {
+    let gain_db = clamp_gain_db(gain_db);
+    if gain_db == 0.0 {
+        return;
+    }
+    let factor = 10f32.powf(gain_db / 20.0);
+    for sample in samples.iter_mut() {
+        *sample = (*sample as f32 * factor)
+            .round()
+            .clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+    }
+}
+
+/// 将交织排列的多声道 PCM 平均混合为单声道
+pub fn downmix_to_mono(samples: &[i16], channels: u16) -> Vec<i16> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+    samples
+        .chunks_exact(channels as usize)
+        .map(|frame| (frame.iter().map(|s| *s as i32).sum::<i32>() / channels as i32) as i16)
+        .collect()
+}
+
+/// 线性插值重采样单声道 PCM
+///
+/// 适用于提示音等语音素材；降采样前不做抗混叠滤波
+pub fn resample_linear(samples: &[i16], from_rate: u32, to_rate: u32) -> Vec<i16> {
+    if from_rate == to_rate || samples.is_empty() || from_rate == 0 || to_rate == 0 {
+        return samples.to_vec();
+    }
+    let out_len = (samples.len() as u64 * to_rate as u64 / from_rate as u64) as usize;
+    let step = from_rate as f64 / to_rate as f64;
+    (0..out_len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let index = pos as usize;
+            let frac = pos - index as f64;
+            let a = samples[index] as f64;
+            let b = *samples.get(index + 1).unwrap_or(&samples[index]) as f64;
+            (a + (b - a) * frac).round() as i16
+        })
+        .collect()
+}
+
+/// 计算线性 PCM 的电平，单位为 -dBov（0 最响，127 为静音）
+pub fn level_dbov(samples: &[i16]) -> u8 {
+    if samples.is_empty() {
+        return 127;
+    }
+    let energy: f64 = samples.iter().map(|s| (*s as f64).powi(2)).sum();
+    let rms = (energy / samples.len() as f64).sqrt();
+    if rms < 1.0 {
+        return 127;
+    }
+    let dbov = 20.0 * (rms / 32768.0).log10();
+    (-dbov).round().clamp(0.0, 127.0) as u8
+}
+
+/// 语音结束后继续判定为活动的帧数（20 ms 帧，共 200 ms）
+pub const VAD_HANGOVER_FRAMES: u32 = 10;
+
+/// 基于能量的语音活动检测（VAD）
+///
+/// 帧电平不低于阈值时判定为语音；语音结束后在挂起窗口内仍判定为活动，避免截断语音尾部
+#[derive(Debug, Clone)]
+pub struct VoiceActivityDetector {
+    threshold_dbfs: f32,
+    hangover_frames: u32,
+    remaining: u32,
+}
+
+impl VoiceActivityDetector {
+    /// 使用 dBFS 阈值（如 -45.0）与默认挂起窗口创建检测器
+    pub fn new(threshold_dbfs: f32) -> Self {
+        Self {
+            threshold_dbfs,
+            hangover_frames: VAD_HANGOVER_FRAMES,
+            remaining: 0,
+        }
+    }
+
+    /// 设置挂起窗口的帧数
+    pub fn with_hangover(mut self, frames: u32) -> Self {
+        self.hangover_frames = frames;
+        self
+    }
+
+    /// 检测阈值（dBFS）
+    pub fn threshold_dbfs(&self) -> f32 {
+        self.threshold_dbfs
+    }
+
+    /// 处理一帧线性 PCM，返回该帧是否应视为语音
+    pub fn process(&mut self, samples: &[i16]) -> bool {
+        let level = -(level_dbov(samples) as f32);
+        if level >= self.threshold_dbfs {
+            self.remaining = self.hangover_frames;
+            return true;
+        }
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_g711_silence_decodes_near_zero() {
+        assert_eq!(ulaw_to_linear(0xFF), 0);
+        assert!(alaw_to_linear(0xD5).abs() <= 8);
+        assert_eq!(ulaw_to_linear(0x00), -32124);
+        assert_eq!(ulaw_to_linear(0x80), 32124);
+        assert_eq!(alaw_to_linear(0xAA), 32256);
+    }
+
+    #[test]
+    fn test_g711_encode_round_trip() {
+        assert_eq!(linear_to_ulaw(0), 0xFF);
+        assert_eq!(linear_to_alaw(0), 0xD5);
+        for sample in [-32768i16, -20000, -1000, -100, -1, 1, 100, 1000, 20000, 32767] {
+            let tolerance = (sample as i32).abs() / 16 + 16;
+            let ulaw = ulaw_to_linear(linear_to_ulaw(sample)) as i32;
+            let alaw = alaw_to_linear(linear_to_alaw(sample)) as i32;
+            assert!((ulaw - sample as i32).abs() <= tolerance.max(132), "ulaw {sample} -> {ulaw}");
+            assert!((alaw - sample as i32).abs() <= tolerance, "alaw {sample} -> {alaw}");
+        }
+        assert_eq!(encode_g711(8, &[0, 0]), Some(vec![0xD5, 0xD5]));
+        assert_eq!(encode_g711(111, &[0]), None);
+    }
+
+    #[test]
+    fn test_apply_gain_clips() {
+        let mut samples = [1000i16, -1000, 20000, -20000];
+        apply_gain(&mut samples, 6.0);
+        assert_eq!(&samples[..2], &[1995, -1995]);
+        assert_eq!(&samples[2..], &[i16::MAX, i16::MIN]);
+
+        // 超出范围的增益被限制为 -30 dB
+        let mut samples = [10000i16];
+        apply_gain(&mut samples, -100.0);
+        assert_eq!(samples, [316]);
+        assert_eq!(clamp_gain_db(f32::NAN), 0.0);
+        assert_eq!(clamp_gain_db(45.0), MAX_GAIN_DB);
+    }
+
+    #[test]
+    fn test_level_dbov() {
+        assert_eq!(level_dbov(&[]), 127);
+        assert_eq!(level_dbov(&[0; 160]), 127);
+        assert_eq!(level_dbov(&[i16::MAX, i16::MIN]), 0);
+        let quiet = vec![328i16; 160];
+        assert_eq!(level_dbov(&quiet), 40);
+    }
+
+    #[test]
+    fn test_vad_hangover() {
+        let speech = vec![3000i16; 160];
+        let hiss = vec![20i16; 160];
+        let mut vad = VoiceActivityDetector::new(-45.0).with_hangover(2);
+        assert!(!vad.process(&hiss));
+        assert!(vad.process(&speech));
+        // 语音结束后挂起 2 帧
+        assert!(vad.process(&hiss));
+        assert!(vad.process(&hiss));
+        assert!(!vad.process(&hiss));
+        assert!(!vad.process(&[]));
+    }
+
+    #[test]
+    fn test_downmix_and_resample() {
+        assert_eq!(downmix_to_mono(&[100, 300, -50, 50], 2), vec![200, 0]);
+        assert_eq!(downmix_to_mono(&[1, 2, 3], 1), vec![1, 2, 3]);
+
+        let ramp: Vec<i16> = (0..48).map(|i| i * 100).collect();
+        let down = resample_linear(&ramp, 48000, 8000);
+        assert_eq!(down, vec![0, 600, 1200, 1800, 2400, 3000, 3600, 4200]);
+        let up = resample_linear(&[0, 100], 8000, 16000);
+        assert_eq!(up, vec![0, 50, 100, 100]);
+        assert_eq!(resample_linear(&ramp, 8000, 8000), ramp);
+    }
+}
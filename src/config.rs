@@ -1,7 +1,12 @@
 /// 传输协议配置模块
 ///
 /// 支持的 SIP 传输协议：UDP、TCP、WebSocket 和 TLS
+use crate::backoff::Backoff;
+use crate::error::ConfigError;
+use crate::sip_transport::TlsOptions;
+use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
+use std::time::Duration;
 
 /// SIP 传输协议类型
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -15,6 +20,8 @@ pub enum Protocol {
     Ws,
     /// WebSocket Secure (TLS) 传输协议
     Wss,
+    /// TLS 传输协议（TLS over TCP，`sips:` 或 `transport=tls`）
+    Tls,
 }
 
 impl Protocol {
@@ -25,6 +32,7 @@ impl Protocol {
             Protocol::Tcp => "tcp",
             Protocol::Ws => "ws",
             Protocol::Wss => "wss",
+            Protocol::Tls => "tls",
         }
     }
 
@@ -36,13 +44,14 @@ impl Protocol {
             Protocol::Tcp => 5060,
             Protocol::Ws => 80,
             Protocol::Wss => 443,
+            Protocol::Tls => 5061,
         }
     }
 
     /// 判断是否为安全协议
     #[cfg(test)]
     pub fn is_secure(&self) -> bool {
-        matches!(self, Protocol::Wss)
+        matches!(self, Protocol::Wss | Protocol::Tls)
     }
 
     /// 判断是否为 WebSocket 协议
@@ -61,8 +70,9 @@ impl FromStr for Protocol {
             "tcp" => Ok(Protocol::Tcp),
             "ws" | "websocket" => Ok(Protocol::Ws),
             "wss" | "websocket-secure" => Ok(Protocol::Wss),
+            "tls" => Ok(Protocol::Tls),
             _ => Err(format!(
-                "无效的协议类型 '{}', 支持的协议: udp, tcp, ws, wss",
+                "无效的协议类型 '{}', 支持的协议: udp, tcp, ws, wss, tls",
                 s
             )),
         }
@@ -83,7 +93,7 @@ impl From<rsip::transport::Transport> for Protocol {
             rsip::transport::Transport::Tcp => Protocol::Tcp,
             rsip::transport::Transport::Ws => Protocol::Ws,
             rsip::transport::Transport::Wss => Protocol::Wss,
-            rsip::transport::Transport::Tls => Protocol::Tcp, // TLS over TCP
+            rsip::transport::Transport::Tls => Protocol::Tls,
             rsip::transport::Transport::Sctp => Protocol::Udp, // Fallback to UDP
             rsip::transport::Transport::TlsSctp => Protocol::Tcp, // Fallback to TCP
         }
@@ -98,6 +108,236 @@ impl From<Protocol> for rsip::transport::Transport {
             Protocol::Tcp => rsip::transport::Transport::Tcp,
             Protocol::Ws => rsip::transport::Transport::Ws,
             Protocol::Wss => rsip::transport::Transport::Wss,
+            Protocol::Tls => rsip::transport::Transport::Tls,
+        }
+    }
+}
+
+/// 未指定时 REGISTER 请求的注册时长（秒）
+pub const DEFAULT_REGISTER_EXPIRES: u32 = 3600;
+
+/// 允许请求的最长注册时长（秒，7 天）
+pub const MAX_REGISTER_EXPIRES: u32 = 7 * 24 * 3600;
+
+/// 校验注册时长：0 应使用注销，超过 [`MAX_REGISTER_EXPIRES`] 视为配置错误
+pub fn validate_register_expires(expires: u32) -> Result<u32, ConfigError> {
+    match expires {
+        0 => Err(ConfigError::Invalid(
+            "注册时长不能为 0，注销请使用 unregister".to_string(),
+        )),
+        e if e > MAX_REGISTER_EXPIRES => Err(ConfigError::Invalid(format!(
+            "注册时长 {} 秒超过上限 {} 秒",
+            e, MAX_REGISTER_EXPIRES
+        ))),
+        e => Ok(e),
+    }
+}
+
+/// 拆分 `user` 或 `user@domain` 形式的地址，返回用户部分与可选的域名部分
+///
+/// 两部分都不能为空或包含空白、`<`、`>`、`"`，且最多只有一个 `@`
+pub fn split_user_domain(value: &str) -> Result<(&str, Option<&str>), ConfigError> {
+    let (user, domain) = match value.split_once('@') {
+        Some((user, domain)) => (user, Some(domain)),
+        None => (value, None),
+    };
+    let malformed = |part: &str| {
+        part.is_empty() || part.chars().any(|c| c.is_whitespace() || c.is_control() || "<>\"@".contains(c))
+    };
+    if malformed(user) || domain.is_some_and(malformed) {
+        return Err(ConfigError::Invalid(format!("无效的 user@domain 地址: {:?}", value)));
+    }
+    Ok((user, domain))
+}
+
+/// 校验自定义 Contact URI：必须是带主机的 `sip:` / `sips:` URI
+pub fn validate_contact_uri(uri: &rsip::Uri) -> Result<(), ConfigError> {
+    if !matches!(uri.scheme, Some(rsip::Scheme::Sip) | Some(rsip::Scheme::Sips)) {
+        return Err(ConfigError::Invalid(format!(
+            "Contact URI 必须使用 sip: 或 sips: 方案: {}",
+            uri
+        )));
+    }
+    if uri.host_with_port.host.to_string().is_empty() {
+        return Err(ConfigError::Invalid(format!("Contact URI 缺少主机: {}", uri)));
+    }
+    Ok(())
+}
+
+/// 解析媒体的外部地址（`IP` 或 `IP:端口`）
+///
+/// 只给出 IP 时使用本地绑定的 `port`
+pub fn parse_external_addr(value: &str, port: u16) -> Result<SocketAddr, ConfigError> {
+    value
+        .parse::<SocketAddr>()
+        .or_else(|_| value.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, port)))
+        .map_err(|_| ConfigError::Invalid(format!("无效的外部地址: {:?}", value)))
+}
+
+/// 注册请求中 expires 的携带方式
+///
+/// 部分注册服务器只识别 Contact 的 `expires` 参数，部分只识别 `Expires` 头
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExpiresMode {
+    /// 仅使用 `Expires` 头
+    Header,
+    /// 仅使用 Contact 的 `expires` 参数
+    ContactParam,
+    /// 同时使用两者（默认，兼容性最好）
+    #[default]
+    Both,
+}
+
+impl ExpiresMode {
+    /// 返回模式的字符串表示
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExpiresMode::Header => "header",
+            ExpiresMode::ContactParam => "contact",
+            ExpiresMode::Both => "both",
+        }
+    }
+
+    /// 是否发送 `Expires` 头
+    pub fn use_header(&self) -> bool {
+        matches!(self, ExpiresMode::Header | ExpiresMode::Both)
+    }
+
+    /// 是否在 Contact 上携带 `expires` 参数
+    pub fn use_contact_param(&self) -> bool {
+        matches!(self, ExpiresMode::ContactParam | ExpiresMode::Both)
+    }
+}
+
+impl FromStr for ExpiresMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "header" => Ok(ExpiresMode::Header),
+            "contact" | "contact-param" => Ok(ExpiresMode::ContactParam),
+            "both" => Ok(ExpiresMode::Both),
+            _ => Err(format!(
+                "无效的 expires 模式 '{}', 支持的模式: header, contact, both",
+                s
+            )),
+        }
+    }
+}
+
+/// 认证模式
+///
+/// 请求（REGISTER/INVITE）首次发送时总是不携带凭证，
+/// 两种模式的区别在于收到 401/407 挑战时是否执行 Digest 认证
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AuthMode {
+    /// Digest 认证（默认）：收到挑战时使用用户名和密码应答
+    #[default]
+    Digest,
+    /// 基于源 IP 的认证：未受挑战的 200 OK 即视为成功；
+    /// 仅在配置了非空密码且收到挑战时才回退到 Digest
+    Ip,
+}
+
+impl AuthMode {
+    /// 返回模式的字符串表示
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthMode::Digest => "digest",
+            AuthMode::Ip => "ip",
+        }
+    }
+
+    /// 按认证模式构造凭证
+    ///
+    /// 返回 `None` 时请求不会应答任何挑战，401/407 将直接作为失败返回
+    pub fn credential(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Option<rsipstack::dialog::authenticate::Credential> {
+        if *self == AuthMode::Ip && password.is_empty() {
+            return None;
+        }
+        Some(rsipstack::dialog::authenticate::Credential {
+            username: username.to_string(),
+            password: password.to_string(),
+            realm: None, // 将从 401/407 响应自动提取
+        })
+    }
+}
+
+impl FromStr for AuthMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "digest" => Ok(AuthMode::Digest),
+            "ip" => Ok(AuthMode::Ip),
+            _ => Err(format!("无效的认证模式 '{}', 支持的模式: digest, ip", s)),
+        }
+    }
+}
+
+/// Contact 的 q 值（RFC 3261 qvalue），表示同一 AOR 下多个绑定的优先级
+///
+/// 取值范围 [0, 1]，最多三位小数；内部以千分之一为单位保存
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct QValue(u16);
+
+impl QValue {
+    /// 最高优先级 `q=1`
+    pub const MAX: QValue = QValue(1000);
+
+    /// 以千分之一为单位创建，超出 [0, 1000] 时返回错误
+    pub fn from_millis(millis: u16) -> Result<Self, crate::error::ConfigError> {
+        if millis > 1000 {
+            return Err(crate::error::ConfigError::Invalid(format!(
+                "q 值超出范围 [0, 1]: {}",
+                f64::from(millis) / 1000.0
+            )));
+        }
+        Ok(Self(millis))
+    }
+
+    /// 以千分之一为单位的值
+    pub fn millis(&self) -> u16 {
+        self.0
+    }
+}
+
+impl FromStr for QValue {
+    type Err = crate::error::ConfigError;
+
+    /// 按 RFC 3261 语法解析：`0[.0-3 位数字]` 或 `1[.0-3 个 0]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || crate::error::ConfigError::Invalid(format!("无效的 q 值: '{}'", s));
+        let s = s.trim();
+        let (int, frac) = match s.split_once('.') {
+            Some((int, frac)) => (int, frac),
+            None => (s, ""),
+        };
+        if frac.len() > 3 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let frac_millis = format!("{:0<3}", frac).parse::<u16>().map_err(|_| invalid())?;
+        match int {
+            "0" => Ok(Self(frac_millis)),
+            "1" if frac_millis == 0 => Ok(Self::MAX),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl std::fmt::Display for QValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            1000 => write!(f, "1"),
+            0 => write!(f, "0"),
+            millis => {
+                let frac = format!("{:03}", millis);
+                write!(f, "0.{}", frac.trim_end_matches('0'))
+            }
         }
     }
 }
@@ -112,15 +352,36 @@ pub struct Config {
     pub port: u16,
     pub transport: Protocol,
     pub user_agent: String,
+    pub expires_mode: ExpiresMode,
+    pub register_expires: u32,
+    pub register_backoff: Backoff,
+    pub auth_mode: AuthMode,
+    pub stale_nonce_retry: bool,
+    pub rport: bool,
+    pub contact_q: Option<QValue>,
+    pub contact_uri: Option<rsip::Uri>,
+    pub transfer_timeout: Duration,
+    pub register_timeout: Duration,
+    pub tls: TlsOptions,
+    pub local_bind_addr: Option<SocketAddr>,
+    pub stun_server: Option<String>,
+    pub session_expires: Option<u32>,
+    pub bye_on_shutdown: bool,
+    pub dscp_sip: Option<u8>,
+    pub enable_100rel: bool,
+    pub tcp_fresh_connection: bool,
 }
 
 impl Config {
     /// 创建新的配置
+    ///
+    /// `user` 可以是 `user` 或 `user@domain` 形式，格式错误时返回 `ConfigError::Invalid`
     pub fn new(
         server: &str,
         user: &str,
         password: &str,
     ) -> Result<Self, crate::error::ConfigError> {
+        split_user_domain(user)?;
         let (domain, port, transport) = Self::parse_server(server)?;
 
         Ok(Self {
@@ -131,9 +392,43 @@ impl Config {
             port,
             transport,
             user_agent: "sip-caller/0.1.0".to_string(),
+            expires_mode: ExpiresMode::default(),
+            register_expires: DEFAULT_REGISTER_EXPIRES,
+            register_backoff: Backoff::default(),
+            auth_mode: AuthMode::default(),
+            stale_nonce_retry: true,
+            rport: true,
+            contact_q: None,
+            contact_uri: None,
+            transfer_timeout: Duration::from_secs(30),
+            register_timeout: Duration::from_secs(32),
+            tls: TlsOptions::default(),
+            local_bind_addr: None,
+            stun_server: None,
+            session_expires: None,
+            bye_on_shutdown: true,
+            dscp_sip: None,
+            enable_100rel: false,
+            tcp_fresh_connection: false,
         })
     }
 
+    /// 用户部分：`username` 为 `user@domain` 形式时取 `@` 之前的部分
+    ///
+    /// # 返回
+    /// - `Err(ConfigError::Invalid)` - `username` 为空或格式错误
+    pub fn username(&self) -> Result<&str, ConfigError> {
+        split_user_domain(&self.username).map(|(user, _)| user)
+    }
+
+    /// 用户所属的 SIP 域：`username` 为 `user@domain` 形式时取其中的域名，否则为服务器域名
+    ///
+    /// # 返回
+    /// - `Err(ConfigError::Invalid)` - `username` 为空或格式错误
+    pub fn domain(&self) -> Result<&str, ConfigError> {
+        split_user_domain(&self.username).map(|(_, domain)| domain.unwrap_or(&self.domain))
+    }
+
     /// 解析服务器地址
     fn parse_server(server: &str) -> Result<(String, u16, Protocol), crate::error::ConfigError> {
         let parts: Vec<&str> = server.split(';').collect();
@@ -144,9 +439,9 @@ impl Config {
             let domain = split.next().ok_or("Missing domain")?.to_string();
             let port_str = split.next().ok_or("Missing port")?;
             let port = port_str.parse::<u16>().map_err(|_| "Invalid port number")?;
-            (domain, port)
+            (domain, Some(port))
         } else {
-            (addr_part.to_string(), 5060)
+            (addr_part.to_string(), None)
         };
 
         let transport = parts
@@ -156,6 +451,8 @@ impl Config {
             .unwrap_or("udp")
             .parse::<Protocol>()
             .map_err(|e| crate::error::ConfigError::Invalid(format!("Invalid transport: {}", e)))?;
+        // 未指定端口时 TLS 使用 5061，其余协议使用 5060
+        let port = port.unwrap_or(if transport == Protocol::Tls { 5061 } else { 5060 });
 
         Ok((domain, port, transport))
     }
@@ -165,6 +462,37 @@ impl Config {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_validate_register_expires() {
+        assert_eq!(validate_register_expires(DEFAULT_REGISTER_EXPIRES).unwrap(), 3600);
+        assert_eq!(validate_register_expires(MAX_REGISTER_EXPIRES).unwrap(), MAX_REGISTER_EXPIRES);
+        assert!(validate_register_expires(0).is_err());
+        assert!(validate_register_expires(MAX_REGISTER_EXPIRES + 1).is_err());
+        assert!(validate_register_expires(u32::MAX).is_err());
+    }
+
+    #[test]
+    fn test_validate_contact_uri() {
+        let uri = |s: &str| -> rsip::Uri { s.try_into().unwrap() };
+        assert!(validate_contact_uri(&uri("sip:alice@203.0.113.7:5070")).is_ok());
+        assert!(validate_contact_uri(&uri("sips:alice@example.com;transport=tls")).is_ok());
+        let mut tel = uri("sip:+8613800000000@example.com");
+        tel.scheme = Some(rsip::Scheme::Other("tel".into()));
+        assert!(validate_contact_uri(&tel).is_err());
+        let mut hostless = uri("sip:alice@example.com");
+        hostless.host_with_port = rsip::Domain::from("").into();
+        assert!(validate_contact_uri(&hostless).is_err());
+    }
+
+    #[test]
+    fn test_parse_external_addr() {
+        assert_eq!(parse_external_addr("203.0.113.5", 20000).unwrap(), "203.0.113.5:20000".parse().unwrap());
+        assert_eq!(parse_external_addr("203.0.113.5:4000", 20000).unwrap(), "203.0.113.5:4000".parse().unwrap());
+        assert_eq!(parse_external_addr("[2001:db8::1]:4000", 20000).unwrap(), "[2001:db8::1]:4000".parse().unwrap());
+        assert!(matches!(parse_external_addr("example.com", 20000), Err(ConfigError::Invalid(_))));
+        assert!(parse_external_addr("", 20000).is_err());
+    }
+
     #[test]
     fn test_protocol_from_str() {
         assert_eq!("udp".parse::<Protocol>().unwrap(), Protocol::Udp);
@@ -173,6 +501,7 @@ mod tests {
         assert_eq!("ws".parse::<Protocol>().unwrap(), Protocol::Ws);
         assert_eq!("websocket".parse::<Protocol>().unwrap(), Protocol::Ws);
         assert_eq!("wss".parse::<Protocol>().unwrap(), Protocol::Wss);
+        assert_eq!("TLS".parse::<Protocol>().unwrap(), Protocol::Tls);
         assert!("http".parse::<Protocol>().is_err());
     }
 
@@ -182,6 +511,7 @@ mod tests {
         assert_eq!(Protocol::Tcp.default_port(), 5060);
         assert_eq!(Protocol::Ws.default_port(), 80);
         assert_eq!(Protocol::Wss.default_port(), 443);
+        assert_eq!(Protocol::Tls.default_port(), 5061);
     }
 
     #[test]
@@ -190,6 +520,7 @@ mod tests {
         assert!(!Protocol::Tcp.is_secure());
         assert!(!Protocol::Ws.is_secure());
         assert!(Protocol::Wss.is_secure());
+        assert!(Protocol::Tls.is_secure());
     }
 
     #[test]
@@ -200,6 +531,77 @@ mod tests {
         assert!(Protocol::Wss.is_websocket());
     }
 
+    #[test]
+    fn test_expires_mode_from_str() {
+        assert_eq!("header".parse::<ExpiresMode>().unwrap(), ExpiresMode::Header);
+        assert_eq!("contact".parse::<ExpiresMode>().unwrap(), ExpiresMode::ContactParam);
+        assert_eq!("BOTH".parse::<ExpiresMode>().unwrap(), ExpiresMode::Both);
+        assert_eq!(ExpiresMode::default(), ExpiresMode::Both);
+        assert!(ExpiresMode::Both.use_header() && ExpiresMode::Both.use_contact_param());
+        assert!(!ExpiresMode::Header.use_contact_param());
+        assert!(!ExpiresMode::ContactParam.use_header());
+        assert!("never".parse::<ExpiresMode>().is_err());
+    }
+
+    #[test]
+    fn test_user_domain_split() {
+        let config = Config::new("10.0.0.1:5060", "alice@example.com", "secret").unwrap();
+        assert_eq!(config.username().unwrap(), "alice");
+        assert_eq!(config.domain().unwrap(), "example.com");
+        // 不含域名时使用服务器域名
+        let config = Config::new("sip.example.org:5070", "bob", "secret").unwrap();
+        assert_eq!(config.username().unwrap(), "bob");
+        assert_eq!(config.domain().unwrap(), "sip.example.org");
+
+        assert_eq!(split_user_domain("1001@pbx:5080").unwrap(), ("1001", Some("pbx:5080")));
+        for malformed in ["", "@example.com", "alice@", "a@b@c", "al ice@example.com", "<alice>"] {
+            assert!(matches!(split_user_domain(malformed), Err(ConfigError::Invalid(_))), "{:?}", malformed);
+        }
+        assert!(matches!(
+            Config::new("10.0.0.1", "alice@", "secret"),
+            Err(ConfigError::Invalid(_))
+        ));
+        let mut config = Config::new("10.0.0.1", "alice", "secret").unwrap();
+        config.username = "a@b@c".to_string();
+        assert!(config.username().is_err() && config.domain().is_err());
+    }
+
+    #[test]
+    fn test_auth_mode_credential() {
+        assert_eq!("IP".parse::<AuthMode>().unwrap(), AuthMode::Ip);
+        assert_eq!(AuthMode::default(), AuthMode::Digest);
+        assert!(AuthMode::Digest.credential("alice", "").is_some());
+        assert!(AuthMode::Ip.credential("alice", "").is_none());
+
+        let fallback = AuthMode::Ip.credential("alice", "secret").unwrap();
+        assert_eq!(fallback.username, "alice");
+        assert_eq!(fallback.password, "secret");
+    }
+
+    #[test]
+    fn test_qvalue_parse_and_format() {
+        assert_eq!("0.5".parse::<QValue>().unwrap().to_string(), "0.5");
+        assert_eq!("0.125".parse::<QValue>().unwrap().millis(), 125);
+        assert_eq!("1.000".parse::<QValue>().unwrap(), QValue::MAX);
+        assert_eq!("0".parse::<QValue>().unwrap().to_string(), "0");
+        assert_eq!("0.100".parse::<QValue>().unwrap().to_string(), "0.1");
+        assert!("1.5".parse::<QValue>().is_err());
+        assert!("0.1234".parse::<QValue>().is_err());
+        assert!("-0.1".parse::<QValue>().is_err());
+        assert!(".5".parse::<QValue>().is_err());
+        assert!(QValue::from_millis(1001).is_err());
+    }
+
+    #[test]
+    fn test_tls_server_default_port() {
+        let config = Config::new("example.com;transport=tls", "alice", "secret").unwrap();
+        assert_eq!(config.transport, Protocol::Tls);
+        assert_eq!(config.port, 5061);
+        let config = Config::new("example.com:5071;transport=tls", "alice", "secret").unwrap();
+        assert_eq!(config.port, 5071);
+        assert_eq!(Config::new("example.com", "alice", "secret").unwrap().port, 5060);
+    }
+
     #[test]
     fn test_protocol_display() {
         assert_eq!(Protocol::Udp.to_string(), "UDP");
@@ -0,0 +1,338 @@
+/// DTMF 辅助模块
+///
+/// 提供 DTMF 按键校验、`application/dtmf-relay` 消息体解析、
+/// RFC 4733 电话事件解析，以及 IVR 场景下的按键收集
+use crate::rtp_play::{MediaPlayError, PlaybackHandle};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// 合法的 DTMF 按键
+pub const DTMF_DIGITS: &str = "0123456789*#ABCD";
+
+/// 判断字符是否为合法的 DTMF 按键（A-D 不区分大小写）
+pub fn is_valid_dtmf(digit: char) -> bool {
+    DTMF_DIGITS.contains(digit.to_ascii_uppercase())
+}
+
+/// 解析 `application/dtmf-relay` 消息体中的按键
+///
+/// 消息体形如 `Signal=5\r\nDuration=160`
+pub fn parse_dtmf_relay(body: &str) -> Option<char> {
+    body.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        if !key.trim().eq_ignore_ascii_case("Signal") {
+            return None;
+        }
+        let mut chars = value.trim().chars();
+        match (chars.next(), chars.next()) {
+            (Some(digit), None) if is_valid_dtmf(digit) => Some(digit.to_ascii_uppercase()),
+            _ => None,
+        }
+    })
+}
+
+/// 生成 `application/dtmf-relay` 消息体
+pub fn format_dtmf_relay(digit: char, duration_ms: u32) -> String {
+    format!(
+        "Signal={}\r\nDuration={}\r\n",
+        digit.to_ascii_uppercase(),
+        duration_ms
+    )
+}
+
+/// RFC 4733 电话事件载荷
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TelephoneEvent {
+    pub event: u8,
+    /// 结束标记（E 位）
+    pub end: bool,
+    pub volume: u8,
+    pub duration: u16,
+}
+
+/// 解析 RFC 4733 电话事件载荷，长度不足 4 字节时返回 `None`
+pub fn parse_telephone_event(payload: &[u8]) -> Option<TelephoneEvent> {
+    let [event, flags, d0, d1, ..] = *payload else {
+        return None;
+    };
+    Some(TelephoneEvent {
+        event,
+        end: flags & 0x80 != 0,
+        volume: flags & 0x3F,
+        duration: u16::from_be_bytes([d0, d1]),
+    })
+}
+
+/// 将电话事件编号转换为 DTMF 按键（0-9、*、#、A-D），其他事件返回 `None`
+pub fn event_to_digit(event: u8) -> Option<char> {
+    match event {
+        0..=9 => Some((b'0' + event) as char),
+        10 => Some('*'),
+        11 => Some('#'),
+        12..=15 => Some((b'A' + event - 12) as char),
+        _ => None,
+    }
+}
+
+/// RFC 2833/4733 按键检测器
+///
+/// 同一次按键的所有包共享 RTP 时间戳，结束包通常重发三次；
+/// 检测器只在首次收到某个时间戳的结束包时上报按键
+#[derive(Debug, Default)]
+pub struct TelephoneEventDetector {
+    last_reported: Option<u32>,
+}
+
+impl TelephoneEventDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 处理一个电话事件包，按键结束时返回对应按键
+    pub fn process(&mut self, rtp_timestamp: u32, payload: &[u8]) -> Option<char> {
+        let event = parse_telephone_event(payload)?;
+        if !event.end || self.last_reported == Some(rtp_timestamp) {
+            return None;
+        }
+        self.last_reported = Some(rtp_timestamp);
+        event_to_digit(event.event)
+    }
+}
+
+/// 按键收集结束的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectEndReason {
+    /// 达到最大按键数
+    MaxDigits,
+    /// 收到结束键（结束键不计入结果）
+    Terminator,
+    /// 等待按键超时
+    Timeout,
+    /// 按键来源已关闭（如通话结束）
+    Closed,
+}
+
+/// 按键收集结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DtmfCollection {
+    pub digits: String,
+    pub reason: CollectEndReason,
+}
+
+/// 从按键通道收集 DTMF
+///
+/// # 参数
+/// - `max_digits`: 最大按键数，达到后立即结束；为 0 时不限制
+/// - `timeout`: 等待首个按键以及两次按键之间的最长时间
+/// - `terminator`: 结束键（如 `#`），为 `None` 时不使用
+pub async fn collect_digits(
+    rx: &mut UnboundedReceiver<char>,
+    max_digits: usize,
+    timeout: Duration,
+    terminator: Option<char>,
+) -> DtmfCollection {
+    continue_collect(rx, String::new(), max_digits, timeout, terminator).await
+}
+
+/// 播放提示音的同时收集按键（barge-in）
+///
+/// 播放期间收到的首个按键立即停止提示音；等待首个按键的 `timeout` 从提示音播完或被打断时
+/// 开始计时，之后两次按键之间同样以 `timeout` 为限。提示音播放失败时返回播放错误
+pub(crate) async fn collect_with_barge_in(
+    rx: &mut UnboundedReceiver<char>,
+    playback: PlaybackHandle,
+    max_digits: usize,
+    timeout: Duration,
+    terminator: Option<char>,
+) -> Result<DtmfCollection, MediaPlayError> {
+    let barge_in = tokio::select! {
+        digit = rx.recv() => Some(digit),
+        _ = playback.finished() => None,
+    };
+    playback.stop();
+    playback.await_completion().await?;
+
+    let collection = match barge_in {
+        None => collect_digits(rx, max_digits, timeout, terminator).await,
+        Some(Some(digit)) if Some(digit) == terminator => DtmfCollection {
+            digits: String::new(),
+            reason: CollectEndReason::Terminator,
+        },
+        Some(Some(digit)) => continue_collect(rx, digit.to_string(), max_digits, timeout, terminator).await,
+        Some(None) => DtmfCollection {
+            digits: String::new(),
+            reason: CollectEndReason::Closed,
+        },
+    };
+    Ok(collection)
+}
+
+/// 在已收集到的按键基础上继续收集
+async fn continue_collect(
+    rx: &mut UnboundedReceiver<char>,
+    mut digits: String,
+    max_digits: usize,
+    timeout: Duration,
+    terminator: Option<char>,
+) -> DtmfCollection {
+    let reason = loop {
+        if max_digits > 0 && digits.chars().count() >= max_digits {
+            break CollectEndReason::MaxDigits;
+        }
+        match tokio::time::timeout(timeout, rx.recv()).await {
+            Ok(Some(digit)) if Some(digit) == terminator => break CollectEndReason::Terminator,
+            Ok(Some(digit)) => digits.push(digit),
+            Ok(None) => break CollectEndReason::Closed,
+            Err(_) => break CollectEndReason::Timeout,
+        }
+    };
+    DtmfCollection { digits, reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtp_play::{MediaPlayer, RtpPlayer};
+    use rustrtc::media::MediaKind;
+    use rustrtc::PeerConnection;
+    use std::sync::Arc;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    #[test]
+    fn test_parse_dtmf_relay() {
+        assert_eq!(parse_dtmf_relay("Signal=5\r\nDuration=160"), Some('5'));
+        assert_eq!(parse_dtmf_relay("signal= #\r\n"), Some('#'));
+        assert_eq!(parse_dtmf_relay("Signal=a"), Some('A'));
+        assert_eq!(parse_dtmf_relay("Signal=12"), None);
+        assert_eq!(parse_dtmf_relay("Duration=160"), None);
+        assert!(is_valid_dtmf('d') && !is_valid_dtmf('E'));
+
+        let body = format_dtmf_relay('b', 160);
+        assert_eq!(body, "Signal=B\r\nDuration=160\r\n");
+        assert_eq!(parse_dtmf_relay(&body), Some('B'));
+    }
+
+    #[test]
+    fn test_telephone_event_detector() {
+        let start = [5u8, 0x0A, 0x00, 0xA0];
+        let end = [5u8, 0x8A, 0x03, 0x20];
+        assert_eq!(
+            parse_telephone_event(&end),
+            Some(TelephoneEvent { event: 5, end: true, volume: 10, duration: 800 })
+        );
+        assert_eq!(parse_telephone_event(&end[..3]), None);
+        assert_eq!(event_to_digit(11), Some('#'));
+        assert_eq!(event_to_digit(15), Some('D'));
+        assert_eq!(event_to_digit(16), None);
+
+        let mut detector = TelephoneEventDetector::new();
+        assert_eq!(detector.process(1000, &start), None);
+        assert_eq!(detector.process(1000, &end), Some('5'));
+        // 重发的结束包不重复上报
+        assert_eq!(detector.process(1000, &end), None);
+        assert_eq!(detector.process(1000, &end), None);
+        // 相同按键的下一次按下使用新的时间戳
+        assert_eq!(detector.process(2600, &end), Some('5'));
+    }
+
+    #[tokio::test]
+    async fn test_collect_until_terminator() {
+        let (tx, mut rx) = unbounded_channel();
+        for digit in "12#3".chars() {
+            tx.send(digit).unwrap();
+        }
+        let result = collect_digits(&mut rx, 10, Duration::from_secs(1), Some('#')).await;
+        assert_eq!(result.digits, "12");
+        assert_eq!(result.reason, CollectEndReason::Terminator);
+    }
+
+    #[tokio::test]
+    async fn test_collect_max_digits_and_timeout() {
+        let (tx, mut rx) = unbounded_channel();
+        for digit in "1234".chars() {
+            tx.send(digit).unwrap();
+        }
+        let result = collect_digits(&mut rx, 3, Duration::from_secs(1), None).await;
+        assert_eq!(result.digits, "123");
+        assert_eq!(result.reason, CollectEndReason::MaxDigits);
+
+        let result = collect_digits(&mut rx, 3, Duration::from_millis(50), None).await;
+        assert_eq!(result.digits, "4");
+        assert_eq!(result.reason, CollectEndReason::Timeout);
+
+        drop(tx);
+        let result = collect_digits(&mut rx, 3, Duration::from_secs(1), None).await;
+        assert_eq!(result.reason, CollectEndReason::Closed);
+    }
+
+    /// 播放固定时长后结束（或失败）的提示音
+    struct TimedPrompt {
+        duration: Duration,
+        fail: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl MediaPlayer for TimedPrompt {
+        fn media_kind(&self) -> MediaKind {
+            MediaKind::Audio
+        }
+
+        fn payload_type(&self) -> u8 {
+            0
+        }
+
+        fn clock_rate(&self) -> u32 {
+            8000
+        }
+
+        async fn play_to_remote(&mut self, _: Arc<PeerConnection>) -> Result<(), MediaPlayError> {
+            tokio::time::sleep(self.duration).await;
+            if self.fail {
+                return Err(MediaPlayError::Rtp("提示音发送失败".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    fn spawn_prompt(pc: &Arc<PeerConnection>, duration: Duration, fail: bool) -> PlaybackHandle {
+        Box::new(TimedPrompt { duration, fail }).spawn_playback(pc.clone())
+    }
+
+    #[tokio::test]
+    async fn test_collect_with_barge_in() {
+        let pc = RtpPlayer::new(MediaKind::Audio).await.unwrap().peer_connection();
+        let (tx, mut rx) = unbounded_channel();
+
+        // 首个按键的超时从提示音播完时开始计时
+        let playback = spawn_prompt(&pc, Duration::from_millis(300), false);
+        let sender = tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(400)).await;
+            sender.send('5').unwrap();
+        });
+        let result = collect_with_barge_in(&mut rx, playback, 1, Duration::from_millis(200), None)
+            .await
+            .unwrap();
+        assert_eq!(result, DtmfCollection { digits: "5".into(), reason: CollectEndReason::MaxDigits });
+
+        // 播放期间的按键立即打断提示音
+        let playback = spawn_prompt(&pc, Duration::from_secs(30), false);
+        tx.send('1').unwrap();
+        tx.send('#').unwrap();
+        let result = tokio::time::timeout(
+            Duration::from_secs(1),
+            collect_with_barge_in(&mut rx, playback, 0, Duration::from_secs(5), Some('#')),
+        )
+        .await
+        .expect("提示音未被打断")
+        .unwrap();
+        assert_eq!(result, DtmfCollection { digits: "1".into(), reason: CollectEndReason::Terminator });
+
+        // 播放失败时返回播放错误
+        let playback = spawn_prompt(&pc, Duration::ZERO, true);
+        assert!(matches!(
+            collect_with_barge_in(&mut rx, playback, 1, Duration::from_secs(1), None).await,
+            Err(MediaPlayError::Rtp(_))
+        ));
+    }
+}
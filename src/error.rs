@@ -69,6 +69,28 @@ impl From<&str> for ConfigError {
     }
 }
 
+/// SDP 校验错误
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SdpValidationError {
+    #[error("缺少 {0}= 行")]
+    MissingLine(char),
+
+    #[error("缺少 m= 行")]
+    NoMedia,
+
+    #[error("m 行端口无效: {line}")]
+    InvalidPort { line: String },
+
+    #[error("m 行缺少负载类型: {line}")]
+    MissingPayloadType { line: String },
+}
+
+impl From<SdpValidationError> for CallError {
+    fn from(err: SdpValidationError) -> Self {
+        CallError::invalid_sdp(err.to_string())
+    }
+}
+
 /// SIP呼叫操作的Result类型别名
 pub type CallResult<T> = Result<T, CallError>;
 
@@ -117,6 +139,10 @@ pub enum CallError {
     #[error("呼叫正在进行中")]
     CallInProgress,
 
+    /// 本地绑定地址已被占用
+    #[error("本地地址已被占用: {addr}")]
+    AddressInUse { addr: String },
+
     /// 系统错误
     #[error("系统错误: {0}")]
     System(#[from] std::io::Error),
@@ -124,6 +150,9 @@ pub enum CallError {
     #[error("序列化错误: {0}")]
     Serialization(String),
 
+    #[error("媒体错误: {0}")]
+    Media(#[from] crate::rtp_play::MediaPlayError),
+
     #[error("其他错误: {0}")]
     Other(#[from] Box<dyn std::error::Error + Send + Sync>),
 }
@@ -137,15 +166,17 @@ impl CallError {
             CallError::SipProtocol(_) => false,
             CallError::UriParse(_) => false,
             CallError::CallRejected { .. } => false,
-            CallError::AuthenticationFailed { .. } => true,
+            CallError::AuthenticationFailed { .. } => false,
             CallError::NotInitialized => false,
             CallError::NotConnected => true,
             CallError::InvalidTarget { .. } => false,
             CallError::InvalidSdp { .. } => false,
             CallError::InvalidConfig { .. } => false,
             CallError::CallInProgress => false,
+            CallError::AddressInUse { .. } => false,
             CallError::System(_) => true,
             CallError::Serialization(_) => false,
+            CallError::Media(_) => false,
             CallError::Other(_) => false,
         }
     }
@@ -162,10 +193,12 @@ impl CallError {
             CallError::NotInitialized => "NOT_INITIALIZED",
             CallError::NotConnected => "NOT_CONNECTED",
             CallError::CallInProgress => "CALL_IN_PROGRESS",
+            CallError::AddressInUse { .. } => "ADDRESS_IN_USE",
             CallError::AuthenticationFailed { .. } => "AUTHENTICATION_FAILED",
             CallError::InvalidConfig { .. } => "INVALID_CONFIG",
             CallError::System(_) => "SYSTEM_ERROR",
             CallError::Serialization(_) => "SERIALIZATION_ERROR",
+            CallError::Media(_) => "MEDIA_ERROR",
             CallError::Other(_) => "UNKNOWN_ERROR",
             CallError::UriParse(_) => "URI_PARSE_ERROR",
         }
@@ -187,6 +220,14 @@ impl CallError {
         }
     }
 
+    /// 由最终响应状态码创建呼叫被拒绝错误
+    pub fn rejected(status: &rsip::StatusCode) -> Self {
+        CallError::CallRejected {
+            code: status.code(),
+            phrase: reason_phrase(status),
+        }
+    }
+
     /// 创建网络超时错误
     pub fn network_timeout(duration_ms: u64) -> Self {
         CallError::NetworkTimeout {
@@ -228,6 +269,32 @@ impl CallError {
     }
 }
 
+/// 状态码的原因短语
+///
+/// rsip 解析已知状态码时丢弃原始短语，这里由枚举名还原（如 `BusyHere` -> `Busy Here`）
+pub fn reason_phrase(status: &rsip::StatusCode) -> String {
+    let text = status.to_string();
+    let name = text.split_once(' ').map(|(_, name)| name).unwrap_or_default();
+    if let rsip::StatusCode::Other(_, reason) = status {
+        return reason.clone();
+    }
+    let chars: Vec<char> = name.chars().collect();
+    let mut phrase = String::with_capacity(name.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        // 小写后接大写，或缩写末尾（大写后接大写+小写）处断词
+        let boundary = i > 0
+            && c.is_ascii_uppercase()
+            && (chars[i - 1].is_ascii_lowercase()
+                || chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase())
+                    && chars[i - 1].is_ascii_uppercase());
+        if boundary {
+            phrase.push(' ');
+        }
+        phrase.push(c);
+    }
+    phrase
+}
+
 // 为了方便转换，实现从常见错误类型的转换
 impl From<tokio::time::error::Elapsed> for CallError {
     fn from(_: tokio::time::error::Elapsed) -> Self {
@@ -242,3 +309,25 @@ impl From<uuid::Error> for CallError {
         CallError::Other(Box::new(err))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejected_reason_phrase() {
+        let err = CallError::rejected(&rsip::StatusCode::BusyHere);
+        assert!(matches!(&err, CallError::CallRejected { code: 486, phrase } if phrase == "Busy Here"));
+        assert_eq!(err.to_string(), "呼叫被拒绝: 486 Busy Here");
+        assert_eq!(reason_phrase(&rsip::StatusCode::OK), "OK");
+        assert_eq!(reason_phrase(&rsip::StatusCode::ServiceUnavailable), "Service Unavailable");
+        assert_eq!(
+            reason_phrase(&rsip::StatusCode::Other(499, "Custom Reason".into())),
+            "Custom Reason"
+        );
+        assert_eq!(err.sip_status_code(), Some(486));
+        assert!(!err.is_recoverable());
+        // 凭据错误重试不会成功
+        assert!(!CallError::AuthenticationFailed { reason: "401".into() }.is_recoverable());
+    }
+}
@@ -1,23 +1,60 @@
 // 声明所有模块
+pub mod backoff;
+pub mod call;
+pub mod call_manager;
+pub mod call_scheduler;
+pub mod codec;
 pub mod config;
+pub mod dtmf;
 pub mod error;
+pub mod metrics;
+#[cfg(feature = "mp3")]
+pub mod mp3;
 pub mod rtp;
+pub mod rtp_ext;
 pub mod rtp_play;
+pub mod session_timer;
+#[cfg(feature = "aka")]
+pub mod sip_aka;
+pub mod sip_auth;
+pub mod sip_body;
 pub mod sip_client;
 pub mod sip_dialog;
+pub mod sip_fork;
+pub mod sip_headers;
+pub mod sip_prack;
+pub mod sip_registration;
+pub mod sip_subscription;
 pub mod sip_transport;
 pub mod utils;
+pub mod wav;
 
 /// 重新导出thiserror错误类型
-pub use crate::error::{SipError, RtpError, ConfigError, CallError, CallResult};
+pub use crate::error::{SipError, RtpError, ConfigError, CallError, CallResult, SdpValidationError};
 pub use crate::rtp_play::MediaPlayError;
 
 /// 主要API重新导出，简化使用
-pub use crate::config::Config as SipConfig;
-pub use crate::rtp::{build_rtp_conn, play_audio_file, play_echo, MediaSessionOption};
-pub use crate::rtp_play::{MediaPlayer, MediaPlayerFactory, RtpPlayer};
+pub use crate::backoff::{Backoff, BackoffStrategy, RetryPolicy};
+pub use crate::call::{CallHandle, IncomingCall};
+pub use crate::call_manager::{CallInfo, CallManager, CallState};
+pub use crate::dtmf::{CollectEndReason, DtmfCollection, TelephoneEventDetector};
+pub use crate::metrics::{Metrics, MetricsHandle};
+pub use crate::call_scheduler::{CallScheduler, CallSchedulerConfig};
+pub use crate::config::{Config as SipConfig, QValue};
+pub use crate::rtp::{build_rtp_conn, play_audio_file, play_echo, play_echo_with_extensions, MediaSessionOption};
+pub use crate::rtp_ext::{AudioLevel, AudioLevelMonitor, NegotiatedExtensions, RtpExtension};
+pub use crate::rtp_play::{
+    AudioCodec, MediaPlayer, MediaPlayerFactory, MediaSessionState, PlaybackControl, PlaybackHandle,
+    PlayerOptions, PlaylistPlayer, RtcpEvent, RtpPlayer, RtpStats, RtpTransportOptions, SharedRtpPlayer,
+    SsrcSelection,
+};
 pub use rustrtc::media::MediaKind;
-pub use crate::sip_client::SipClient;
+pub use crate::sip_auth::CredentialProvider;
+pub use crate::sip_body::{BodyError, BodyPart, MultipartBody};
+pub use crate::sip_client::{
+    CallAnswer, CallEvent, CallOptions, ClientStatus, IncomingCallHandler, RegistrationStatus, SipClient,
+};
+pub use crate::sip_transport::{MediaDirection, SdpAttributes};
 pub use crate::utils as utils_mod;
 
 /// SIP Caller库的版本信息
@@ -36,6 +73,16 @@ pub async fn create_sip_client_with_proxy(
     outbound_proxy: Option<&str>
 ) -> Result<SipClient, SipError> {
     let config = crate::config::Config::new(server, user, password)?;
+    create_sip_client_with_config(config, outbound_proxy).await
+}
+
+/// 便捷函数：按完整配置（如 TLS 证书选项）创建SIP客户端
+pub async fn create_sip_client_with_config(
+    config: crate::config::Config,
+    outbound_proxy: Option<&str>,
+) -> Result<SipClient, SipError> {
+    let username = config.username()?.to_string();
+    let domain = config.domain()?.to_string();
     let server_uri: rsip::Uri = format!("sip:{}", config.server)
         .try_into()
         .map_err(|e| SipError::Protocol(format!("Invalid server URI: {}", e)))?;
@@ -51,9 +98,29 @@ pub async fn create_sip_client_with_proxy(
     let sip_client_config = sip_client::SipClientConfig {
         server: server_uri,
         outbound_proxy: proxy_uri,
-        username: config.username,
+        username,
+        domain: Some(domain),
+        auth_username: Some(config.username),
         password: config.password,
         user_agent: config.user_agent,
+        expires_mode: config.expires_mode,
+        register_expires: config.register_expires,
+        register_backoff: config.register_backoff,
+        auth_mode: config.auth_mode,
+        stale_nonce_retry: config.stale_nonce_retry,
+        rport: config.rport,
+        contact_q: config.contact_q,
+        contact_uri: config.contact_uri,
+        transfer_timeout: config.transfer_timeout,
+        register_timeout: config.register_timeout,
+        tls: config.tls,
+        local_bind_addr: config.local_bind_addr,
+        stun_server: config.stun_server,
+        session_expires: config.session_expires,
+        bye_on_shutdown: config.bye_on_shutdown,
+        dscp_sip: config.dscp_sip,
+        enable_100rel: config.enable_100rel,
+        tcp_fresh_connection: config.tcp_fresh_connection,
     };
     Ok(SipClient::new(sip_client_config).await?)
 }
@@ -83,7 +150,7 @@ pub async fn create_echo_player() -> Result<Box<dyn MediaPlayer>, MediaPlayError
 }
 
 /// 便捷函数：创建RTP会话
-pub async fn create_rtp_session<'a>(
+pub async fn create_rtp_session(
     media_type: MediaKind,
 ) -> Result<(RtpPlayer, String), MediaPlayError> {
     let player = RtpPlayer::new(media_type).await?;
@@ -1,12 +1,17 @@
 use clap::Parser;
-use sip_caller::{create_sip_client_with_proxy, create_audio_player, create_video_player, create_rtp_session, MediaKind, utils};
+use sip_caller::{create_sip_client_with_config, create_audio_player, create_video_player, create_rtp_session, CallEvent, MediaKind, utils};
 use sip_caller::rtp_play::{AudioEchoPlayer, MediaPlayer};
+use sip_caller::config::Config;
+use sip_caller::sip_client::SipClient;
+use sip_caller::utils::LogFormat;
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
 use std::time::Duration;
-use rsipstack::dialog::dialog::{DialogState, TerminatedReason};
+use rsipstack::dialog::dialog::TerminatedReason;
+use tokio::sync::broadcast::error::RecvError;
 
-use tracing::{info, error};
+use tracing::{info, error, info_span, Instrument};
 
 
 /// SIP Caller CLI Application
@@ -37,7 +42,7 @@ struct Args {
     #[arg(long, default_value = "auto")]
     media_type: String,
     
-    /// Operation mode (call/echo/media)
+    /// Operation mode (call/echo/media/check)
     #[arg(short, long, default_value = "call")]
     mode: String,
     
@@ -48,23 +53,59 @@ struct Args {
     /// Log level
     #[arg(short, long, default_value = "info")]
     log_level: String,
+
+    /// Log output format (pretty/json)
+    #[arg(long, default_value = "pretty")]
+    log_format: LogFormat,
+
+    /// Read the remote SDP from this file instead of prompting on stdin
+    #[arg(long)]
+    remote_sdp_file: Option<PathBuf>,
+
+    /// Also write the local SDP to this file
+    #[arg(long)]
+    local_sdp_out: Option<PathBuf>,
+
+    /// Print the check mode report as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// PEM file with the CA certificates used to verify the TLS server (defaults to the bundled web PKI roots)
+    #[arg(long)]
+    tls_ca_file: Option<PathBuf>,
+
+    /// Accept any TLS server certificate, including self-signed ones (testing only)
+    #[arg(long)]
+    tls_allow_self_signed: bool,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
-    utils::initialize_logging(args.log_level.as_str());
+    utils::initialize_logging(args.log_level.as_str(), args.log_format);
     match args.mode.as_str() {
         "call" => run_call_mode(&args).await,
         "echo" => run_echo_mode(&args).await,
         "media" => run_media_mode(&args).await,
+        "check" => run_check_mode(&args).await,
         _ => {
-            eprintln!("Invalid mode. Use 'call', 'echo', or 'media'");
+            eprintln!("Invalid mode. Use 'call', 'echo', 'media', or 'check'");
             Ok(())
         }
     }
 }
 
+/// Create the SIP client with the outbound proxy and TLS options from the command line
+async fn connect(args: &Args, server: &str, user: &str, password: &str) -> Result<SipClient, Box<dyn std::error::Error>> {
+    let mut config = Config::new(server, user, password)?;
+    if let Some(path) = &args.tls_ca_file {
+        config.tls = config.tls.with_ca_file(path)
+            .map_err(|e| format!("Failed to read TLS CA file {}: {}", path.display(), e))?;
+    }
+    config.tls = config.tls.with_allow_self_signed(args.tls_allow_self_signed);
+    Ok(create_sip_client_with_config(config, args.outbound_proxy.as_deref()).await?)
+}
+
 async fn run_call_mode(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
     let server = args.server.clone()
         .or_else(|| std::env::var("SIP_SERVER").ok())
@@ -84,12 +125,7 @@ async fn run_call_mode(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
 
     info!("Creating SIP client for {}: {}", server, user);
     
-    let client = create_sip_client_with_proxy(
-        &server, 
-        &user, 
-        &password, 
-        args.outbound_proxy.as_deref()
-    ).await?;
+    let client = connect(args, &server, &user, &password).await?;
     
     if let Some(media_path) = &args.media {
         let media_type = detect_media_type(media_path, &args.media_type)?;
@@ -99,11 +135,9 @@ async fn run_call_mode(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
         
         println!("Local SDP:");
         println!("{}", local_sdp);
+        write_local_sdp(args.local_sdp_out.as_deref(), &local_sdp)?;
         
-        print!("Enter remote SDP: ");
-        io::stdout().flush()?;
-        let mut remote_sdp = String::new();
-        io::stdin().read_line(&mut remote_sdp)?;
+        let remote_sdp = read_remote_sdp(args.remote_sdp_file.as_deref(), "Enter remote SDP: ")?;
         
         let media_player = match media_type {
             MediaKind::Audio => create_audio_player(media_path).await?,
@@ -167,12 +201,7 @@ async fn run_echo_mode(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
 
     info!("Creating SIP client for echo mode: {}@{}", user, server);
 
-    let client = create_sip_client_with_proxy(
-        &server, 
-        &user, 
-        &password, 
-        args.outbound_proxy.as_deref()
-    ).await?;
+    let client = connect(args, &server, &user, &password).await?;
     
     // Register with SIP server
     match client.register().await {
@@ -188,50 +217,47 @@ async fn run_echo_mode(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
         }
     
     // Handle echo calls
-    run_echo_mode_real(&client,&target).await?;
+    run_echo_mode_real(&client, &target, args).await?;
     Ok(())
 }
 
-async fn run_echo_mode_real(client: &sip_caller::SipClient, target: &str) -> Result<(), Box<dyn std::error::Error>> {
+async fn run_echo_mode_real(
+    client: &sip_caller::SipClient,
+    target: &str,
+    args: &Args,
+) -> Result<(), Box<dyn std::error::Error>> {
     info!("Echo mode: making call to: {}", target);
 
     // Create echo player
     let (mut echo_player, local_sdp) = AudioEchoPlayer::new().await?;
+    write_local_sdp(args.local_sdp_out.as_deref(), &local_sdp)?;
+
+    // Subscribe before calling so no call event is missed
+    let mut events = client.events();
 
     // Make call to target with SDP offer
     info!("Making echo call to: {}", target);
-    match client.make_call(&target, &local_sdp).await {
-        Ok((dialog, response)) => {
+    match client.make_call_with_answer(target, &local_sdp).await {
+        Ok((dialog, answer)) => {
             info!("Call initiated successfully");
             info!("Dialog ID: {:?}", dialog.id());
-            
-            // Process response to get remote SDP
-            let remote_sdp = if let Some(resp) = response {
-                info!("Received response: {}", resp.status_code);
-                
-                // Extract SDP answer from response
-                let sdp_answer = extract_sdp_from_response(&resp)?;
-                if !sdp_answer.is_empty() {
-                    info!("Received SDP answer in response: {}", sdp_answer);
-                    Some(sdp_answer)
-                } else {
-                    // We'll need to wait for a re-INVITE with SDP or for SDP in ACK
-                    info!("No SDP in 200 OK, waiting for subsequent messages");
-                    None
-                }
-            } else {
-                info!("No response received");
-                None
-            };
+            info!("Received response: {}", answer.status);
+            if answer.status.kind() != rsip::StatusCodeKind::Successful {
+                return Err(format!("Call failed with status: {}", answer.status).into());
+            }
+
+            // Falls back to the 183 early-media SDP when the 200 OK carries none
+            let remote_sdp = answer.sdp_text();
+            match &remote_sdp {
+                Some(sdp_answer) => info!("Received SDP answer (early: {}): {}", answer.early, sdp_answer),
+                None => info!("No SDP in response, waiting for subsequent messages"),
+            }
             
             // If we got SDP, parse it to get the remote RTP address, otherwise wait for it
             let final_remote_sdp = if let Some(sdp) = remote_sdp {
                 sdp
             } else {
-                info!("Enter remote SDP for echo: ");
-                io::stdout().flush()?;
-                let mut input = String::new();
-                io::stdin().read_line(&mut input)?;
+                let input = read_remote_sdp(args.remote_sdp_file.as_deref(), "Enter remote SDP for echo: ")?;
                 if input.trim().is_empty() {
                     error!("No SDP provided");
                     return Ok(());
@@ -239,6 +265,9 @@ async fn run_echo_mode_real(client: &sip_caller::SipClient, target: &str) -> Res
                 input
             };
 
+            // Run the echo loop in a span tagged with Call-ID/Dialog-ID so its logs correlate with the call
+            let span = call_span(&dialog.id());
+
             // Initialize echo player
             if let Err(e) = echo_player.initialize().await {
                 error!("Failed to initialize echo player: {}", e);
@@ -246,7 +275,7 @@ async fn run_echo_mode_real(client: &sip_caller::SipClient, target: &str) -> Res
             }
             
             // Set remote SDP
-            if let Err(e) = echo_player.set_remote_sdp(&final_remote_sdp).await {
+            if let Err(e) = echo_player.set_remote_sdp(&final_remote_sdp).instrument(span.clone()).await {
                 error!("Failed to set remote SDP: {}", e);
                 return Err(format!("Failed to set remote SDP: {}", e).into());
             }
@@ -256,29 +285,32 @@ async fn run_echo_mode_real(client: &sip_caller::SipClient, target: &str) -> Res
 
             // Start echo mode using AudioEchoPlayer
             // AudioEchoPlayer uses its internal PeerConnection
-            if let Err(e) = echo_player.start_echo().await {
+            if let Err(e) = echo_player.start_echo().instrument(span).await {
                 error!("Echo mode failed: {}", e);
                 return Err(format!("Echo mode failed: {}", e).into());
             }
             info!("Echo mode active: audio will be echoed back to the caller");
-            // Wait for cancellation
+            // Wait for the call to end
+            let dialog_id = dialog.id();
             loop {
-                match dialog.state() {
-                    DialogState::Terminated(_, TerminatedReason::UasBye) => {
-                        info!("对端主动挂断");
+                match events.recv().await {
+                    Ok(CallEvent::Answered { dialog_id: id, setup_time, .. }) if id == dialog_id => {
+                        info!("Call answered after {:?}", setup_time);
+                    }
+                    Ok(CallEvent::Terminated { dialog_id: id, reason }) if id == dialog_id => {
+                        match reason {
+                            TerminatedReason::UasBye => info!("对端主动挂断"),
+                            reason => info!("通话结束: {:?}", reason),
+                        }
                         echo_player.stop_echo();
                         break;
                     }
-                    DialogState::Terminated(_, reason) => {
-                        info!("通话结束: {:?}", reason);
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => {
                         echo_player.stop_echo();
                         break;
                     }
-                    _ => {
-                        // 继续等待
-                    }
                 }
-                tokio::time::sleep(Duration::from_millis(500)).await;
             }
             info!("Echo mode completed");
             Ok(())
@@ -286,27 +318,7 @@ async fn run_echo_mode_real(client: &sip_caller::SipClient, target: &str) -> Res
         Err(e) => {
             error!("Failed to make echo call: {}", e);
             error!("Error code: {}", e.error_code());
-            return Err(format!("Echo call failed: {}", e).into());
-        }
-    }
-}
-
-// Helper function to extract SDP from response
-fn extract_sdp_from_response(response: &rsip::Response) -> Result<String, Box<dyn std::error::Error>> {
-    match response.status_code {
-        rsip::StatusCode::OK => {
-            let body = response.body();
-            if !body.is_empty() {
-                Ok(String::from_utf8_lossy(&body).to_string())
-            } else {
-                Err("No SDP in OK response".into())
-            }
-        }
-        rsip::StatusCode::Ringing => {
-            Err("Call is still ringing, no SDP yet".into())
-        }
-        _ => {
-            Err(format!("Call failed with status: {}", response.status_code).into())
+            Err(format!("Echo call failed: {}", e).into())
         }
     }
 }
@@ -333,12 +345,7 @@ async fn run_media_mode(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
     
     info!("Creating SIP client for media mode: {}@{}", user, server);
     
-    let client = create_sip_client_with_proxy(
-        &server, 
-        &user, 
-        &password, 
-        args.outbound_proxy.as_deref()
-    ).await?;
+    let client = connect(args, &server, &user, &password).await?;
     
     // Register with SIP server
     match client.register().await {
@@ -360,41 +367,31 @@ async fn run_media_mode(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
     
     println!("Local SDP for media mode:");
     println!("{}", local_sdp);
+    write_local_sdp(args.local_sdp_out.as_deref(), &local_sdp)?;
     
     // Make call to target with SDP offer
     info!("Making media call to: {}", target);
-    match client.make_call(&target, &local_sdp).await {
-        Ok((dialog, response)) => {
+    match client.make_call_with_answer(&target, &local_sdp).await {
+        Ok((dialog, answer)) => {
             info!("Call initiated successfully");
             info!("Dialog ID: {:?}", dialog.id());
-            
-            // Process response to get remote SDP
-            let remote_sdp = if let Some(resp) = response {
-                info!("Received response: {}", resp.status_code);
-                
-                // Extract SDP answer from response
-                let sdp_answer = extract_sdp_from_response(&resp)?;
-                if !sdp_answer.is_empty() {
-                    info!("Received SDP answer in response");
-                    Some(sdp_answer)
-                } else {
-                    // We'll need to wait for a re-INVITE with SDP or for SDP in ACK
-                    info!("No SDP in 200 OK, waiting for subsequent messages");
-                    None
-                }
-            } else {
-                info!("No response received");
-                None
-            };
+            info!("Received response: {}", answer.status);
+            if answer.status.kind() != rsip::StatusCodeKind::Successful {
+                return Err(format!("Call failed with status: {}", answer.status).into());
+            }
+
+            // Falls back to the 183 early-media SDP when the 200 OK carries none
+            let remote_sdp = answer.sdp_text();
+            match &remote_sdp {
+                Some(_) => info!("Received SDP answer (early: {})", answer.early),
+                None => info!("No SDP in response, waiting for subsequent messages"),
+            }
             
             // If we got SDP, parse it to get the remote RTP address, otherwise wait for it
             let final_remote_sdp = if let Some(sdp) = remote_sdp {
                 sdp
             } else {
-                print!("Enter remote SDP for media: ");
-                io::stdout().flush()?;
-                let mut input = String::new();
-                io::stdin().read_line(&mut input)?;
+                let input = read_remote_sdp(args.remote_sdp_file.as_deref(), "Enter remote SDP for media: ")?;
                 if input.trim().is_empty() {
                     error!("No SDP provided");
                     return Ok(());
@@ -411,7 +408,10 @@ async fn run_media_mode(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
             
             // Start media playback
             info!("Starting media playback with SDP");
-            rtp_player.set_remote_sdp_and_play(&final_remote_sdp, media_player).await?;
+            rtp_player
+                .set_remote_sdp_and_play(&final_remote_sdp, media_player)
+                .instrument(call_span(&dialog.id()))
+                .await?;
             
             info!("Media playback active");
             info!("Press Ctrl+C to exit");
@@ -425,24 +425,193 @@ async fn run_media_mode(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
         Err(e) => {
             error!("Failed to make media call: {}", e);
             error!("Error code: {}", e.error_code());
-            return Err(format!("Media call failed: {}", e).into());
+            Err(format!("Media call failed: {}", e).into())
         }
     }
 }
-// Helper function to detect media type
+
+/// Result of a `check` mode run
+struct CheckReport {
+    server: String,
+    registered: bool,
+    auth_scheme: Option<String>,
+    expires: Option<u32>,
+    reachable: bool,
+    rtt: Option<Duration>,
+    errors: Vec<String>,
+}
+
+impl CheckReport {
+    fn ok(&self) -> bool {
+        self.registered && self.reachable
+    }
+
+    fn print_human(&self) {
+        let status = if self.ok() { "OK" } else { "FAILED" };
+        println!("SIP health check: {} ({})", status, self.server);
+        println!("  registered:  {}", self.registered);
+        println!("  auth method: {}", self.auth_scheme.as_deref().unwrap_or("none"));
+        match self.expires {
+            Some(expires) => println!("  expires:     {}s", expires),
+            None => println!("  expires:     -"),
+        }
+        println!("  reachable:   {}", self.reachable);
+        match self.rtt {
+            Some(rtt) => println!("  rtt:         {:.1}ms", rtt.as_secs_f64() * 1000.0),
+            None => println!("  rtt:         -"),
+        }
+        for error in &self.errors {
+            println!("  error:       {}", error);
+        }
+    }
+
+    fn print_json(&self) {
+        let auth_scheme = self.auth_scheme.as_deref().map_or("null".to_string(), json_string);
+        let expires = self.expires.map_or("null".to_string(), |e| e.to_string());
+        let rtt = self.rtt.map_or("null".to_string(), |rtt| format!("{:.3}", rtt.as_secs_f64() * 1000.0));
+        let errors = self.errors.iter().map(|e| json_string(e)).collect::<Vec<_>>().join(",");
+        println!(
+            "{{\"ok\":{},\"server\":{},\"registered\":{},\"auth_scheme\":{},\"expires\":{},\"reachable\":{},\"rtt_ms\":{},\"errors\":[{}]}}",
+            self.ok(),
+            json_string(&self.server),
+            self.registered,
+            auth_scheme,
+            expires,
+            self.reachable,
+            rtt,
+            errors
+        );
+    }
+}
+
+/// Quote and escape `value` as a JSON string
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Register, send an OPTIONS ping and unregister, then report the outcome; exits non-zero on failure
+async fn run_check_mode(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let server = args.server.clone()
+        .or_else(|| std::env::var("SIP_SERVER").ok())
+        .ok_or("SIP server address is required")?;
+
+    let user = args.user.clone()
+        .or_else(|| std::env::var("SIP_USER").ok())
+        .ok_or("SIP user is required")?;
+
+    let password = args.password.clone()
+        .or_else(|| std::env::var("SIP_PASSWORD").ok())
+        .unwrap_or_else(|| "password".to_string());
+
+    info!("Creating SIP client for health check: {}@{}", user, server);
+
+    let client = connect(args, &server, &user, &password).await?;
+
+    let mut report = CheckReport {
+        server,
+        registered: false,
+        auth_scheme: None,
+        expires: None,
+        reachable: false,
+        rtt: None,
+        errors: Vec::new(),
+    };
+
+    match client.register().await {
+        Ok(response) => {
+            info!("Registration response: {}", response.status_code);
+            let status = client.status();
+            report.registered = status.registered;
+            report.expires = status.registration_expires;
+            report.auth_scheme = status.auth_scheme;
+        }
+        Err(e) => {
+            error!("SIP registration failed: {}", e);
+            report.errors.push(format!("register: {}", e));
+            report.auth_scheme = client.status().auth_scheme;
+        }
+    }
+
+    let started = std::time::Instant::now();
+    match client.send_options(None).await {
+        Ok(response) => {
+            info!("OPTIONS response: {}", response.status_code);
+            report.reachable = true;
+            report.rtt = Some(started.elapsed());
+        }
+        Err(e) => {
+            error!("OPTIONS failed: {}", e);
+            report.errors.push(format!("options: {}", e));
+        }
+    }
+
+    if report.registered {
+        if let Err(e) = client.unregister().await {
+            error!("SIP unregistration failed: {}", e);
+            report.errors.push(format!("unregister: {}", e));
+        }
+    }
+    client.shutdown().await;
+
+    if args.json {
+        report.print_json();
+    } else {
+        report.print_human();
+    }
+    if !report.ok() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Span carrying the call's Call-ID and Dialog-ID for media tasks started within it
+fn call_span(dialog_id: &rsipstack::dialog::DialogId) -> tracing::Span {
+    info_span!("call", call_id = %dialog_id.call_id, dialog_id = %dialog_id)
+}
+
+/// Write the local SDP to `path` when `--local-sdp-out` is given
+fn write_local_sdp(path: Option<&Path>, local_sdp: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(path) = path {
+        std::fs::write(path, local_sdp)
+            .map_err(|e| format!("Failed to write local SDP to {}: {}", path.display(), e))?;
+        info!("Local SDP written to {}", path.display());
+    }
+    Ok(())
+}
+
+/// Read the remote SDP from `path` when `--remote-sdp-file` is given, otherwise prompt on stdin
+fn read_remote_sdp(path: Option<&Path>, prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+    if let Some(path) = path {
+        let sdp = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read remote SDP from {}: {}", path.display(), e))?;
+        info!("Remote SDP read from {}", path.display());
+        return Ok(sdp);
+    }
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input)
+}
+
+// Helper function to detect media type by content (WAV/MP3 audio, IVF video; MP3 needs the `mp3` feature)
 fn detect_media_type(file_path: &str, media_type: &str) -> Result<MediaKind, Box<dyn std::error::Error>> {
     if media_type == "auto" {
-        let path = std::path::Path::new(file_path);
-        let ext = path.extension()
-            .and_then(|s| s.to_str())
-            .unwrap_or("")
-            .to_lowercase();
-        
-        match ext.as_str() {
-            "wav" => Ok(MediaKind::Audio),
-            "ivf" => Ok(MediaKind::Video),
-            _ => Err(Box::from("Unsupported media format")),
-        }
+        Ok(utils::sniff_media_kind(file_path)?)
     } else {
         match media_type {
             "audio" => Ok(MediaKind::Audio),
@@ -0,0 +1,252 @@
+/// Prometheus 指标模块
+///
+/// 以 Prometheus 文本格式（0.0.4）导出呼叫、注册与 RTP 收发计数，
+/// 失败呼叫按 `CallError::error_code` 分标签统计。启用 `metrics-http` 特性后
+/// 可用 [`serve`] 通过 HTTP 提供 `/metrics` 抓取端点
+use crate::error::CallError;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// 共享的指标注册表句柄
+pub type MetricsHandle = Arc<Metrics>;
+
+/// 活跃通话数的来源，在导出时读取
+type ActiveCallsSource = Box<dyn Fn() -> usize + Send + Sync>;
+
+/// 指标注册表
+#[derive(Default)]
+pub struct Metrics {
+    calls_placed: AtomicU64,
+    calls_answered: AtomicU64,
+    calls_failed: Mutex<BTreeMap<&'static str, u64>>,
+    registered: AtomicBool,
+    rtp_packets_sent: AtomicU64,
+    rtp_packets_received: AtomicU64,
+    active_calls: Mutex<Option<ActiveCallsSource>>,
+}
+
+impl std::fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Metrics")
+            .field("calls_placed", &self.calls_placed)
+            .field("calls_answered", &self.calls_answered)
+            .field("calls_failed", &self.calls_failed)
+            .field("registered", &self.registered)
+            .field("rtp_packets_sent", &self.rtp_packets_sent)
+            .field("rtp_packets_received", &self.rtp_packets_received)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Metrics {
+    /// 创建空的指标注册表
+    pub fn new() -> MetricsHandle {
+        Arc::new(Self::default())
+    }
+
+    /// 记录一次发起的呼叫
+    pub fn record_call_placed(&self) {
+        self.calls_placed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录一次被应答（2xx）的呼叫
+    pub fn record_call_answered(&self) {
+        self.calls_answered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录一次失败的呼叫，按错误码分类
+    pub fn record_call_failed(&self, error: &CallError) {
+        *self.calls_failed.lock().unwrap().entry(error.error_code()).or_default() += 1;
+    }
+
+    /// 更新注册状态
+    pub fn set_registered(&self, registered: bool) {
+        self.registered.store(registered, Ordering::Relaxed);
+    }
+
+    /// 记录发送的 RTP 包
+    pub fn record_rtp_sent(&self) {
+        self.rtp_packets_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录收到的 RTP 包
+    pub fn record_rtp_received(&self) {
+        self.rtp_packets_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 设置活跃通话数的来源，每次导出时调用
+    pub fn set_active_calls_source<F>(&self, source: F)
+    where
+        F: Fn() -> usize + Send + Sync + 'static,
+    {
+        *self.active_calls.lock().unwrap() = Some(Box::new(source));
+    }
+
+    /// 已发起的呼叫数
+    pub fn calls_placed(&self) -> u64 {
+        self.calls_placed.load(Ordering::Relaxed)
+    }
+
+    /// 已应答的呼叫数
+    pub fn calls_answered(&self) -> u64 {
+        self.calls_answered.load(Ordering::Relaxed)
+    }
+
+    /// 以 `error_code` 分类的失败呼叫数
+    pub fn calls_failed(&self, error_code: &str) -> u64 {
+        self.calls_failed.lock().unwrap().get(error_code).copied().unwrap_or(0)
+    }
+
+    /// 以 Prometheus 文本格式导出全部指标
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, u64)]| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(out, "{}{} {}", name, labels, value);
+            }
+        };
+        let plain = |value: u64| [(String::new(), value)];
+
+        metric("sip_calls_placed_total", "counter", "Calls placed", &plain(self.calls_placed()));
+        metric("sip_calls_answered_total", "counter", "Calls answered with 2xx", &plain(self.calls_answered()));
+        let failed: Vec<(String, u64)> = self
+            .calls_failed
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(code, count)| (format!("{{error_code=\"{}\"}}", code), *count))
+            .collect();
+        metric("sip_calls_failed_total", "counter", "Failed calls by error code", &failed);
+        let active = self.active_calls.lock().unwrap().as_ref().map_or(0, |source| source());
+        metric("sip_active_calls", "gauge", "Active dialogs", &plain(active as u64));
+        let registered = self.registered.load(Ordering::Relaxed);
+        metric("sip_registered", "gauge", "Whether the client is registered (1) or not (0)", &plain(registered.into()));
+        metric(
+            "rtp_packets_sent_total",
+            "counter",
+            "RTP packets sent",
+            &plain(self.rtp_packets_sent.load(Ordering::Relaxed)),
+        );
+        metric(
+            "rtp_packets_received_total",
+            "counter",
+            "RTP packets received",
+            &plain(self.rtp_packets_received.load(Ordering::Relaxed)),
+        );
+        out
+    }
+}
+
+/// 在 `listener` 上提供指标抓取端点，`GET /metrics` 返回 [`Metrics::render`]，其他路径返回 404
+///
+/// 直到 `cancel` 被取消或监听失败才返回
+#[cfg(feature = "metrics-http")]
+pub async fn serve(
+    metrics: MetricsHandle,
+    listener: tokio::net::TcpListener,
+    cancel: tokio_util::sync::CancellationToken,
+) -> std::io::Result<()> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    loop {
+        let (mut stream, _) = tokio::select! {
+            _ = cancel.cancelled() => return Ok(()),
+            accepted = listener.accept() => accepted?,
+        };
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; 1024];
+            let Ok(len) = stream.read(&mut buf).await else {
+                return;
+            };
+            let request = String::from_utf8_lossy(&buf[..len]);
+            let path = request.split_whitespace().nth(1).unwrap_or("");
+            let (status, body) = if path == "/metrics" {
+                ("200 OK", metrics.render())
+            } else {
+                ("404 Not Found", String::new())
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prometheus_text() {
+        let metrics = Metrics::new();
+        metrics.record_call_placed();
+        metrics.record_call_placed();
+        metrics.record_call_answered();
+        metrics.record_call_failed(&CallError::rejected(&rsip::StatusCode::BusyHere));
+        metrics.record_call_failed(&CallError::NotConnected);
+        metrics.record_call_failed(&CallError::NotConnected);
+        metrics.set_registered(true);
+        metrics.record_rtp_sent();
+        metrics.record_rtp_received();
+        metrics.record_rtp_received();
+        metrics.set_active_calls_source(|| 3);
+
+        let text = metrics.render();
+        for line in [
+            "# TYPE sip_calls_placed_total counter",
+            "sip_calls_placed_total 2",
+            "sip_calls_answered_total 1",
+            "sip_calls_failed_total{error_code=\"CALL_REJECTED\"} 1",
+            "sip_calls_failed_total{error_code=\"NOT_CONNECTED\"} 2",
+            "# TYPE sip_active_calls gauge",
+            "sip_active_calls 3",
+            "sip_registered 1",
+            "rtp_packets_sent_total 1",
+            "rtp_packets_received_total 2",
+        ] {
+            assert!(text.lines().any(|l| l == line), "缺少 {}:\n{}", line, text);
+        }
+        assert_eq!(metrics.calls_failed("NOT_CONNECTED"), 2);
+        assert_eq!(metrics.calls_failed("NETWORK_TIMEOUT"), 0);
+    }
+
+    #[cfg(feature = "metrics-http")]
+    #[tokio::test]
+    async fn test_serve_metrics_over_http() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let metrics = Metrics::new();
+        metrics.record_call_placed();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let cancel = tokio_util::sync::CancellationToken::new();
+        let server = tokio::spawn(serve(metrics, listener, cancel.clone()));
+
+        let fetch = |path: &'static str| async move {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            stream
+                .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes())
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+        let response = fetch("/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains("sip_calls_placed_total 1"));
+        assert!(fetch("/").await.starts_with("HTTP/1.1 404"));
+
+        cancel.cancel();
+        server.await.unwrap().unwrap();
+    }
+}
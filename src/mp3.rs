@@ -0,0 +1,130 @@
+/// MP3 解码模块（`mp3` 特性）
+///
+/// 使用 symphonia 将 MP3 文件解码为单声道 16 位 PCM，供播放前重采样与编码
+use crate::codec::downmix_to_mono;
+use crate::wav::{WavAudio, WavFormat};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tracing::warn;
+
+/// 解码 MP3 文件为单声道 PCM（保持原始采样率）
+///
+/// 损坏的帧会被跳过并记录警告；流中途采样率或声道数变化时在该处截断，
+/// 只返回此前解码出的音频；没有任何可用帧时返回 `InvalidData`
+pub fn decode_mp3(path: impl AsRef<Path>) -> io::Result<WavAudio> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let source = MediaSourceStream::new(Box::new(File::open(path)?), Default::default());
+    let mut hint = Hint::new();
+    hint.with_extension("mp3");
+    let probed = symphonia::default::get_probe()
+        .format(&hint, source, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| invalid(format!("无法识别 MP3 流: {}", e)))?;
+    let mut reader = probed.format;
+    let track = reader
+        .default_track()
+        .ok_or_else(|| invalid("MP3 文件中没有音频轨道".to_string()))?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| invalid(format!("无法创建 MP3 解码器: {}", e)))?;
+
+    let mut format: Option<WavFormat> = None;
+    let mut samples = Vec::new();
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => {
+                warn!("读取 MP3 数据失败，停止解码: {}", e);
+                break;
+            }
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(Error::DecodeError(e)) => {
+                warn!("跳过无法解码的 MP3 帧: {}", e);
+                continue;
+            }
+            Err(e) => {
+                warn!("MP3 解码失败，停止解码: {}", e);
+                break;
+            }
+        };
+
+        let spec = *decoded.spec();
+        let frame_format = WavFormat {
+            channels: spec.channels.count() as u16,
+            sample_rate: spec.rate,
+            bits_per_sample: 16,
+        };
+        match format {
+            None => format = Some(frame_format),
+            Some(f) if f != frame_format => {
+                warn!(
+                    "MP3 流中途由 {} Hz {} 声道变为 {} Hz {} 声道，忽略其后的音频",
+                    f.sample_rate, f.channels, frame_format.sample_rate, frame_format.channels
+                );
+                break;
+            }
+            Some(_) => {}
+        }
+
+        let mut buffer = SampleBuffer::<i16>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend(downmix_to_mono(buffer.samples(), frame_format.channels));
+    }
+
+    let format = format.ok_or_else(|| invalid("MP3 文件中没有可解码的音频帧".to_string()))?;
+    Ok(WavAudio {
+        format: WavFormat { channels: 1, ..format },
+        samples,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// 写入 `frames` 个 44.1 kHz、128 kbit/s 的静音 MPEG-1 Layer III 帧
+    pub(crate) fn write_silent_mp3(path: &Path, frames: usize) {
+        let mut data = Vec::new();
+        for _ in 0..frames {
+            data.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+            data.extend(std::iter::repeat_n(0u8, 413));
+        }
+        std::fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn test_decode_silent_mp3() {
+        let path = std::env::temp_dir().join(format!("mp3-{}.mp3", uuid::Uuid::new_v4()));
+        write_silent_mp3(&path, 10);
+        let audio = decode_mp3(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(audio.format.sample_rate, 44100);
+        assert_eq!(audio.format.channels, 1);
+        assert!(audio.samples.len() >= 1152 * 8, "{}", audio.samples.len());
+        assert!(audio.samples.iter().all(|s| *s == 0));
+    }
+
+    #[test]
+    fn test_decode_garbage_fails() {
+        let path = std::env::temp_dir().join(format!("mp3-bad-{}.mp3", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"not an mp3 at all").unwrap();
+        assert!(decode_mp3(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,6 +1,14 @@
 /// RTP 媒体流处理模块
 ///
 /// 提供 RTP 连接建立、音频播放等功能
+use crate::codec::{decode_g711, level_dbov};
+use crate::config::parse_external_addr;
+use crate::error::{CallError, CallResult};
+use crate::rtp_ext::{
+    build_outgoing_extensions, read_audio_level, AudioLevel, AudioLevelMonitor,
+    NegotiatedExtensions, RtpExtension,
+};
+use crate::sip_transport::SdpAttributes;
 use rsipstack::transport::udp::UdpConnection;
 use rsipstack::transport::SipAddr;
 use rsipstack::{Error, Result};
@@ -11,13 +19,33 @@ use tokio::select;
 use tokio_util::sync::CancellationToken;
 use tracing::info;
 
+/// 舒适噪声（RFC 3389）的静态载荷类型
+pub const CN_PAYLOAD_TYPE: u8 = 13;
+
+/// 生成指定 G.711 载荷类型的静音数据
+///
+/// PCMA 静音为 `0xD5`，PCMU（及其他）为 `0xFF`
+pub fn silence_payload(payload_type: u8, len: usize) -> Vec<u8> {
+    let value = if payload_type == 8 { 0xD5 } else { 0xFF };
+    vec![value; len]
+}
+
 /// 媒体会话配置选项
 #[derive(Debug, Clone)]
 pub struct MediaSessionOption {
-    /// 外部 IP 地址（用于 NAT 穿透）
+    /// 外部 IP 地址（用于 NAT 穿透），可为 `IP` 或 `IP:端口`
+    ///
+    /// 只给出 IP 时沿用本地绑定的 RTP 端口，
+    /// 可直接使用 `SipClient::public_ip()` 学习到的公网 IP
     pub external_ip: Option<String>,
     /// 取消令牌
     pub cancel_token: CancellationToken,
+    /// 是否在 SDP 中声明舒适噪声 `CN/8000`（PT 13）
+    pub comfort_noise: bool,
+    /// 额外注入到生成的 SDP 中的属性
+    pub sdp_attributes: SdpAttributes,
+    /// 在 SDP 中通过 `a=extmap` 声明的 RTP 头部扩展
+    pub rtp_extensions: Vec<RtpExtension>,
 }
 
 impl Default for MediaSessionOption {
@@ -25,6 +53,9 @@ impl Default for MediaSessionOption {
         Self {
             external_ip: None,
             cancel_token: CancellationToken::new(),
+            comfort_noise: false,
+            sdp_attributes: SdpAttributes::default(),
+            rtp_extensions: Vec::new(),
         }
     }
 }
@@ -38,25 +69,28 @@ impl Default for MediaSessionOption {
 /// * `payload_type` - 有效载荷类型 (0=PCMU, 8=PCMA)
 ///
 /// # 返回
-/// 返回 UDP 连接和 SDP 字符串
+/// 返回 UDP 连接和 SDP 字符串；`external_ip` 无法解析时返回 `CallError::InvalidConfig`
 pub async fn build_rtp_conn(
     local_ip: IpAddr,
     opt: &MediaSessionOption,
     ssrc: u32,
     payload_type: u8,
-) -> Result<(UdpConnection, String)> {
+) -> CallResult<(UdpConnection, String)> {
+    if let Some(external) = &opt.external_ip {
+        parse_external_addr(external, 0).map_err(|e| CallError::invalid_config(e.to_string()))?;
+    }
     let mut conn = None;
 
     // 尝试绑定 100 个端口
     for p in 0..100 {
         let port = 20000 + p * 2;
-        let addr = format!("{}:{}", local_ip, port).parse()?;
+        let addr = SocketAddr::new(local_ip, port);
 
         if let Ok(c) = UdpConnection::create_connection(
             addr,
             opt.external_ip
                 .as_ref()
-                .map(|ip| ip.parse::<SocketAddr>().expect("Invalid external IP")),
+                .and_then(|ip| parse_external_addr(ip, port).ok()),
             Some(opt.cancel_token.clone()),
         )
         .await
@@ -67,7 +101,7 @@ pub async fn build_rtp_conn(
     }
 
     if conn.is_none() {
-        return Err(Error::Error("无法绑定 RTP 端口".to_string()));
+        return Err(Error::Error("无法绑定 RTP 端口".to_string()).into());
     }
 
     let conn = conn.unwrap();
@@ -79,22 +113,40 @@ pub async fn build_rtp_conn(
     };
 
     let socketaddr: SocketAddr = conn.get_addr().addr.to_owned().try_into()?;
+    let family = if socketaddr.is_ipv6() { "IP6" } else { "IP4" };
+
+    let (cn_format, cn_rtpmap) = if opt.comfort_noise {
+        (
+            format!(" {CN_PAYLOAD_TYPE}"),
+            format!("a=rtpmap:{CN_PAYLOAD_TYPE} CN/8000\r\n"),
+        )
+    } else {
+        (String::new(), String::new())
+    };
+    let extmaps: String = opt
+        .rtp_extensions
+        .iter()
+        .map(|ext| format!("a={}\r\n", ext.extmap_attribute()))
+        .collect();
 
     // 生成 SDP 描述
     let sdp = format!(
         "v=0\r\n\
-        o=- 0 0 IN IP4 {}\r\n\
+        o=- 0 0 IN {family} {}\r\n\
         s=rsipstack\r\n\
-        c=IN IP4 {}\r\n\
+        c=IN {family} {}\r\n\
         t=0 0\r\n\
-        m=audio {} RTP/AVP {codec}\r\n\
+        m=audio {} RTP/AVP {codec}{cn_format}\r\n\
         a=rtpmap:{codec} {codec_name}/8000\r\n\
+        {cn_rtpmap}\
+        {extmaps}\
         a=ssrc:{ssrc}\r\n\
         a=sendrecv\r\n",
         socketaddr.ip(),
         socketaddr.ip(),
         socketaddr.port(),
     );
+    let sdp = opt.sdp_attributes.apply(&sdp);
 
     info!("✓ RTP 连接已建立: {}", conn.get_addr().addr);
     tracing::debug!("SDP 内容:\n{}", sdp);
@@ -113,6 +165,33 @@ pub async fn play_echo(
     token: CancellationToken,
     peer_addr: String,
     ssrc: u32,
+) -> Result<()> {
+    play_echo_with_extensions(
+        conn,
+        token,
+        peer_addr,
+        ssrc,
+        NegotiatedExtensions::default(),
+        None,
+    )
+    .await
+}
+
+/// 播放回声，并处理已协商的 RTP 头部扩展
+///
+/// 收到的音频电平写入 `levels`；回送的包按协商结果写入本端音频电平和 abs-send-time。
+/// 未协商的扩展既不读取也不写入
+///
+/// # 参数
+/// * `extensions` - 通过 [`NegotiatedExtensions::negotiate`] 得到的协商结果
+/// * `levels` - 对端音频电平观察者
+pub async fn play_echo_with_extensions(
+    conn: UdpConnection,
+    token: CancellationToken,
+    peer_addr: String,
+    ssrc: u32,
+    extensions: NegotiatedExtensions,
+    levels: Option<AudioLevelMonitor>,
 ) -> Result<()> {
     use rsipstack::transport::SipAddr;
     use rtp_rs::RtpReader;
@@ -121,6 +200,8 @@ pub async fn play_echo(
     let mut packet_count = 0u64;
     let mut seq = 0u16;
     let mut ts = 0u32;
+    // 最近一次收到的媒体载荷类型，用于将舒适噪声替换为对应编码的静音
+    let mut media_payload_type = 0u8;
 
     // 将对端地址解析为 SipAddr
     let peer_sip_addr = SipAddr {
@@ -187,19 +268,47 @@ pub async fn play_echo(
                     }
                 };
 
+                // 读取对端音频电平
+                if let (Some(levels), Some((profile, data))) = (&levels, rtp_reader.extension()) {
+                    if let Some(level) = read_audio_level(&extensions, profile, data) {
+                        levels.update(level);
+                    }
+                }
+
                 // 提取有效载荷
-                let payload = rtp_reader.payload();
-                let payload_type = rtp_reader.payload_type();
+                let mut payload = rtp_reader.payload();
+                let mut payload_type = rtp_reader.payload_type();
+
+                // 舒适噪声包不是媒体数据，回送一帧静音代替
+                let silence;
+                if payload_type == CN_PAYLOAD_TYPE {
+                    silence = silence_payload(media_payload_type, 160);
+                    payload = &silence;
+                    payload_type = media_payload_type;
+                } else {
+                    media_payload_type = payload_type;
+                }
+
+                // 按协商结果生成头部扩展
+                let local_level = extensions.audio_level.and_then(|_| {
+                    decode_g711(payload_type, payload).map(|samples| {
+                        let level = level_dbov(&samples);
+                        AudioLevel { voice: level < 127, level }
+                    })
+                });
+                let ext_data = build_outgoing_extensions(&extensions, local_level);
 
                 // 用我们自己的 SSRC 重新打包
-                let echo_packet = match RtpPacketBuilder::new()
+                let mut builder = RtpPacketBuilder::new()
                     .payload_type(payload_type)
                     .ssrc(ssrc)
                     .sequence(seq.into())
                     .timestamp(ts)
-                    .payload(payload)
-                    .build()
-                {
+                    .payload(payload);
+                if let Some(data) = &ext_data {
+                    builder = builder.extension(crate::rtp_ext::ONE_BYTE_PROFILE, data);
+                }
+                let echo_packet = match builder.build() {
                     Ok(p) => p,
                     Err(e) => {
                         tracing::error!("构建回声 RTP 包失败: {:?}", e);
@@ -238,7 +347,7 @@ pub async fn play_echo(
 ///
 /// # 返回
 /// 返回最终的时间戳和序列号
-#[allow(dead_code)]
+#[allow(dead_code, clippy::too_many_arguments)]
 pub async fn play_audio_file(
     conn: UdpConnection,
     token: CancellationToken,
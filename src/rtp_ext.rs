@@ -0,0 +1,341 @@
+/// RTP 头部扩展模块
+///
+/// 支持通过 SDP `a=extmap` 协商 RTP 头部扩展（RFC 8285 one-byte 格式），
+/// 目前实现客户端到混音器的音频电平（RFC 6464）与 abs-send-time
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+
+/// RFC 6464 音频电平扩展 URI
+pub const AUDIO_LEVEL_URI: &str = "urn:ietf:params:rtp-hdrext:ssrc-audio-level";
+/// abs-send-time 扩展 URI
+pub const ABS_SEND_TIME_URI: &str = "http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time";
+/// RFC 8285 one-byte 头部扩展的 profile
+pub const ONE_BYTE_PROFILE: u16 = 0xBEDE;
+
+/// 支持的 RTP 头部扩展
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RtpExtension {
+    /// 客户端到混音器的音频电平（RFC 6464）
+    AudioLevel,
+    /// 发送时间戳（abs-send-time）
+    AbsSendTime,
+}
+
+impl RtpExtension {
+    /// 扩展 URI
+    pub fn uri(&self) -> &'static str {
+        match self {
+            RtpExtension::AudioLevel => AUDIO_LEVEL_URI,
+            RtpExtension::AbsSendTime => ABS_SEND_TIME_URI,
+        }
+    }
+
+    /// 作为 offer 方时使用的扩展 ID
+    pub fn default_id(&self) -> u8 {
+        match self {
+            RtpExtension::AudioLevel => 1,
+            RtpExtension::AbsSendTime => 3,
+        }
+    }
+
+    /// 生成 offer 中的 `extmap` 属性（不含 `a=` 前缀）
+    pub fn extmap_attribute(&self) -> String {
+        format!("extmap:{} {}", self.default_id(), self.uri())
+    }
+}
+
+/// 从 SDP 指定媒体段中解析 `a=extmap`，返回 (ID, URI) 列表
+pub fn parse_extmaps(sdp: &str, media: &str) -> Vec<(u8, String)> {
+    let mut in_section = false;
+    let mut extmaps = Vec::new();
+    for line in sdp.lines().map(str::trim) {
+        if let Some(m) = line.strip_prefix("m=") {
+            in_section = m.split_whitespace().next() == Some(media);
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        let Some(value) = line.strip_prefix("a=extmap:") else {
+            continue;
+        };
+        let mut parts = value.split_whitespace();
+        // ID 后可能带方向，如 "1/sendonly"
+        let id = parts
+            .next()
+            .and_then(|id| id.split('/').next())
+            .and_then(|id| id.parse::<u8>().ok());
+        if let (Some(id), Some(uri)) = (id, parts.next()) {
+            if (1..=14).contains(&id) {
+                extmaps.push((id, uri.to_string()));
+            }
+        }
+    }
+    extmaps
+}
+
+/// 协商成功的扩展及其 ID（使用对端 SDP 中的 ID）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NegotiatedExtensions {
+    pub audio_level: Option<u8>,
+    pub abs_send_time: Option<u8>,
+}
+
+impl NegotiatedExtensions {
+    /// 根据本端启用的扩展和对端 SDP 的音频段协商
+    ///
+    /// 仅当本端启用且对端 SDP 中出现同一 URI 时该扩展才生效
+    pub fn negotiate(enabled: &[RtpExtension], remote_sdp: &str) -> Self {
+        let remote = parse_extmaps(remote_sdp, "audio");
+        let find = |ext: RtpExtension| {
+            if !enabled.contains(&ext) {
+                return None;
+            }
+            remote
+                .iter()
+                .find(|(_, uri)| uri == ext.uri())
+                .map(|(id, _)| *id)
+        };
+        Self {
+            audio_level: find(RtpExtension::AudioLevel),
+            abs_send_time: find(RtpExtension::AbsSendTime),
+        }
+    }
+
+    /// 是否没有任何扩展生效
+    pub fn is_empty(&self) -> bool {
+        self.audio_level.is_none() && self.abs_send_time.is_none()
+    }
+}
+
+/// 音频电平（RFC 6464）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioLevel {
+    /// 发送方是否判定为语音
+    pub voice: bool,
+    /// 电平，单位 -dBov（0 最响，127 静音）
+    pub level: u8,
+}
+
+impl AudioLevel {
+    /// 编码为扩展数据字节
+    pub fn to_byte(self) -> u8 {
+        (if self.voice { 0x80 } else { 0 }) | self.level.min(127)
+    }
+
+    /// 从扩展数据字节解码
+    pub fn from_byte(byte: u8) -> Self {
+        Self {
+            voice: byte & 0x80 != 0,
+            level: byte & 0x7F,
+        }
+    }
+}
+
+/// 解析 one-byte 格式的扩展数据，返回 (ID, 数据) 列表
+pub fn parse_one_byte_extensions(data: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut elements = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let byte = data[i];
+        if byte == 0 {
+            // 填充字节
+            i += 1;
+            continue;
+        }
+        let id = byte >> 4;
+        if id == 15 {
+            break;
+        }
+        let len = (byte & 0x0F) as usize + 1;
+        let start = i + 1;
+        if start + len > data.len() {
+            break;
+        }
+        elements.push((id, &data[start..start + len]));
+        i = start + len;
+    }
+    elements
+}
+
+/// 构建 one-byte 格式的扩展数据（已按 4 字节对齐）
+pub fn build_one_byte_extensions(elements: &[(u8, &[u8])]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for (id, value) in elements {
+        if !(1..=14).contains(id) || value.is_empty() || value.len() > 16 {
+            continue;
+        }
+        data.push((id << 4) | (value.len() as u8 - 1));
+        data.extend_from_slice(value);
+    }
+    while !data.len().is_multiple_of(4) {
+        data.push(0);
+    }
+    data
+}
+
+/// 当前时间的 abs-send-time 值（6.18 定点秒数，24 位）
+pub fn abs_send_time_now() -> [u8; 3] {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let fraction = (u64::from(now.subsec_nanos()) << 18) / 1_000_000_000;
+    let value = ((now.as_secs() & 0x3F) << 18) | fraction;
+    [(value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+/// 为待发送的 RTP 包构建头部扩展数据
+///
+/// 只写入已协商的扩展；没有需要写入的扩展时返回 `None`
+pub fn build_outgoing_extensions(
+    negotiated: &NegotiatedExtensions,
+    level: Option<AudioLevel>,
+) -> Option<Vec<u8>> {
+    let level_byte = level.map(|l| [l.to_byte()]);
+    let send_time = abs_send_time_now();
+    let mut elements: Vec<(u8, &[u8])> = Vec::new();
+    if let (Some(id), Some(byte)) = (negotiated.audio_level, level_byte.as_ref()) {
+        elements.push((id, byte));
+    }
+    if let Some(id) = negotiated.abs_send_time {
+        elements.push((id, &send_time));
+    }
+    if elements.is_empty() {
+        None
+    } else {
+        Some(build_one_byte_extensions(&elements))
+    }
+}
+
+/// 从收到的 RTP 扩展中读取音频电平
+pub fn read_audio_level(
+    negotiated: &NegotiatedExtensions,
+    profile: u16,
+    data: &[u8],
+) -> Option<AudioLevel> {
+    let id = negotiated.audio_level?;
+    if profile != ONE_BYTE_PROFILE {
+        return None;
+    }
+    parse_one_byte_extensions(data)
+        .into_iter()
+        .find(|(ext_id, _)| *ext_id == id)
+        .and_then(|(_, value)| value.first().copied())
+        .map(AudioLevel::from_byte)
+}
+
+/// 对端音频电平观察者
+///
+/// 媒体接收路径写入最新电平，应用通过 `subscribe` 订阅变化
+#[derive(Clone)]
+pub struct AudioLevelMonitor {
+    sender: Arc<watch::Sender<Option<AudioLevel>>>,
+}
+
+impl Default for AudioLevelMonitor {
+    fn default() -> Self {
+        let (sender, _) = watch::channel(None);
+        Self {
+            sender: Arc::new(sender),
+        }
+    }
+}
+
+impl AudioLevelMonitor {
+    /// 创建电平观察者
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 订阅电平变化
+    pub fn subscribe(&self) -> watch::Receiver<Option<AudioLevel>> {
+        self.sender.subscribe()
+    }
+
+    /// 最近一次收到的电平
+    pub fn latest(&self) -> Option<AudioLevel> {
+        *self.sender.borrow()
+    }
+
+    /// 写入新的电平
+    pub fn update(&self, level: AudioLevel) {
+        self.sender.send_replace(Some(level));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANSWER: &str = "v=0\r\n\
+        m=audio 4000 RTP/AVP 0\r\n\
+        a=extmap:5 urn:ietf:params:rtp-hdrext:ssrc-audio-level\r\n\
+        a=extmap:7/sendrecv http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time\r\n\
+        m=video 4002 RTP/AVP 96\r\n\
+        a=extmap:2 urn:ietf:params:rtp-hdrext:toffset\r\n";
+
+    #[test]
+    fn test_parse_extmaps() {
+        let audio = parse_extmaps(ANSWER, "audio");
+        assert_eq!(audio.len(), 2);
+        assert_eq!(audio[0], (5, AUDIO_LEVEL_URI.to_string()));
+        assert_eq!(audio[1].0, 7);
+        assert_eq!(parse_extmaps(ANSWER, "video").len(), 1);
+    }
+
+    #[test]
+    fn test_negotiation_gated_on_both_sides() {
+        let both = NegotiatedExtensions::negotiate(
+            &[RtpExtension::AudioLevel, RtpExtension::AbsSendTime],
+            ANSWER,
+        );
+        assert_eq!(both.audio_level, Some(5));
+        assert_eq!(both.abs_send_time, Some(7));
+
+        let local_only = NegotiatedExtensions::negotiate(&[RtpExtension::AudioLevel], ANSWER);
+        assert_eq!(local_only.abs_send_time, None);
+
+        let remote_none =
+            NegotiatedExtensions::negotiate(&[RtpExtension::AudioLevel], "m=audio 4000 RTP/AVP 0\r\n");
+        assert!(remote_none.is_empty());
+    }
+
+    #[test]
+    fn test_one_byte_roundtrip() {
+        let negotiated = NegotiatedExtensions {
+            audio_level: Some(1),
+            abs_send_time: Some(3),
+        };
+        let level = AudioLevel {
+            voice: true,
+            level: 42,
+        };
+        let data = build_outgoing_extensions(&negotiated, Some(level)).unwrap();
+        assert_eq!(data.len() % 4, 0);
+        assert_eq!(read_audio_level(&negotiated, ONE_BYTE_PROFILE, &data), Some(level));
+
+        let elements = parse_one_byte_extensions(&data);
+        assert_eq!(elements.len(), 2);
+        assert_eq!(elements[1].0, 3);
+        assert_eq!(elements[1].1.len(), 3);
+
+        assert_eq!(read_audio_level(&negotiated, 0x1000, &data), None);
+        assert!(build_outgoing_extensions(&NegotiatedExtensions::default(), Some(level)).is_none());
+    }
+
+    #[test]
+    fn test_audio_level_monitor() {
+        let monitor = AudioLevelMonitor::new();
+        let rx = monitor.subscribe();
+        assert_eq!(monitor.latest(), None);
+        monitor.update(AudioLevel::from_byte(0x8A));
+        assert_eq!(
+            *rx.borrow(),
+            Some(AudioLevel {
+                voice: true,
+                level: 10
+            })
+        );
+    }
+}
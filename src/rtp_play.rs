@@ -1,16 +1,41 @@
 use async_trait::async_trait;
 use rustrtc::media::{
-    MediaError, MediaKind, MediaSample,
+    AudioFrame, JitterBuffer, MediaError, MediaKind, MediaSample,
     MediaStreamTrack,
 };
+use rustrtc::config::MediaCapabilities;
+use rustrtc::transports::ice::IceSocketWrapper;
 use rustrtc::{
-    PeerConnection, RtcConfiguration, SdpType, SessionDescription, TransportMode,
-    RtpCodecParameters,
+    AudioCapability, PeerConnection, RtcConfiguration, RtcpMuxPolicy, SdpType, SessionDescription, TransportMode,
+    RtpCodecParameters, VideoCapability,
 };
-use std::path::PathBuf;
-use std::sync::Arc;
+use crate::codec::{
+    apply_gain, clamp_gain_db, decode_g711, downmix_to_mono, encode_g711, resample_linear, VoiceActivityDetector,
+};
+#[cfg(feature = "g722")]
+use crate::codec::G722Encoder;
+use crate::dtmf::{collect_with_barge_in, TelephoneEventDetector};
+use crate::metrics::MetricsHandle;
+use crate::rtp::{silence_payload, CN_PAYLOAD_TYPE};
+use crate::sip_transport::{
+    connection_line_ip, extract_payload_types, find_rtpmap_payload_type, media_direction,
+    media_stream_states, normalize_address_types, remap_payload_types, restrict_payload_types,
+    rtpmap_encoding, rtpmap_format, MediaDirection, SdpAttributes,
+};
+use crate::wav::{read_wav, read_wav_format, WavFormat, WavWriter};
+use socket2::SockRef;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use thiserror::Error;
-use tracing::{info, warn};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn, Instrument};
 
 /// Custom error type for media playback operations
 #[derive(Debug, Error)]
@@ -29,6 +54,18 @@ pub enum MediaPlayError {
     
     #[error("RTP error: {0}")]
     Rtp(String),
+
+    #[error("Codec negotiation failed: offered {offered}, answered {answered}")]
+    CodecNegotiation { offered: String, answered: String },
+
+    #[error("No common codec between offer and answer")]
+    NoCommonCodec,
+
+    #[error("Seek offset {offset:?} is beyond media duration {duration:?}")]
+    SeekOutOfRange { offset: Duration, duration: Duration },
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 impl From<MediaError> for MediaPlayError {
@@ -44,65 +81,398 @@ impl From<MediaError> for MediaPlayError {
     }
 }
 
+/// 接收路径的 SSRC 选择策略
+///
+/// 对端（如 SFU）可能在同一 m 行上复用多个 SSRC，
+/// 回声等消费者只应处理其中一路
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SsrcSelection {
+    /// 锁定收到的第一个 SSRC（默认）
+    #[default]
+    First,
+    /// 只接收指定的 SSRC
+    Exact(u32),
+    /// 不过滤，接收所有 SSRC
+    Any,
+}
+
+/// 接收路径上的 SSRC 过滤器
+#[derive(Debug, Clone, Default)]
+pub struct SsrcFilter {
+    selection: SsrcSelection,
+    selected: Option<u32>,
+}
+
+impl SsrcFilter {
+    /// 创建 SSRC 过滤器
+    pub fn new(selection: SsrcSelection) -> Self {
+        let selected = match selection {
+            SsrcSelection::Exact(ssrc) => Some(ssrc),
+            _ => None,
+        };
+        Self { selection, selected }
+    }
+
+    /// 判断是否接收该 SSRC 的数据包
+    ///
+    /// 无法获知 SSRC（`None`）的样本总是放行
+    pub fn accept(&mut self, ssrc: Option<u32>) -> bool {
+        let Some(ssrc) = ssrc else {
+            return true;
+        };
+        match self.selection {
+            SsrcSelection::Any => true,
+            SsrcSelection::Exact(expected) => ssrc == expected,
+            SsrcSelection::First => match self.selected {
+                Some(selected) => ssrc == selected,
+                None => {
+                    info!("接收路径选定 SSRC: {:#010x}", ssrc);
+                    self.selected = Some(ssrc);
+                    true
+                }
+            },
+        }
+    }
+
+    /// 当前选定的 SSRC
+    pub fn selected(&self) -> Option<u32> {
+        self.selected
+    }
+}
+
+/// 获取媒体样本所属的 SSRC
+pub fn sample_ssrc(sample: &MediaSample) -> Option<u32> {
+    match sample {
+        MediaSample::Audio(f) => f.raw_packet.as_ref().map(|p| p.header.ssrc),
+        MediaSample::Video(f) => f.raw_packet.as_ref().map(|p| p.header.ssrc),
+    }
+}
+
+/// 允许的最小抖动缓冲深度（毫秒）
+pub const MIN_JITTER_BUFFER_MS: u32 = 20;
+/// 允许的最大抖动缓冲深度（毫秒）
+pub const MAX_JITTER_BUFFER_MS: u32 = 500;
+
+/// 允许的最小带宽上限（kbps）
+pub const MIN_BANDWIDTH_KBPS: u32 = 8;
+/// 允许的最大带宽上限（kbps）
+pub const MAX_BANDWIDTH_KBPS: u32 = 20_000;
+
+/// 抖动缓冲最多保留的包数（按 20 ms 一帧可容纳两倍最大深度）
+const JITTER_BUFFER_CAPACITY: usize = 64;
+
+/// 在入站轨道上叠加抖动缓冲的接收器
+///
+/// 未设置深度时直接透传，与不使用缓冲的行为一致。设置深度后每个包至少缓冲
+/// `depth` 再交付，乱序包按序号重排；缺失的包最多等待两倍深度后跳过
+struct JitteredTrack {
+    track: Arc<rustrtc::media::SampleStreamTrack>,
+    buffer: Option<JitterBuffer>,
+}
+
+impl JitteredTrack {
+    fn new(track: Arc<rustrtc::media::SampleStreamTrack>, depth: Option<Duration>) -> Self {
+        let buffer = depth.map(|depth| JitterBuffer::new(depth, depth * 2, JITTER_BUFFER_CAPACITY));
+        Self { track, buffer }
+    }
+
+    async fn recv(&mut self) -> rustrtc::media::MediaResult<MediaSample> {
+        let Some(buffer) = self.buffer.as_mut() else {
+            return self.track.recv().await;
+        };
+        loop {
+            if let Some(sample) = buffer.pop() {
+                return Ok(sample);
+            }
+            let sample = match buffer.next_pop_wait() {
+                Some(wait) => tokio::select! {
+                    sample = self.track.recv() => sample?,
+                    _ = tokio::time::sleep(wait) => continue,
+                },
+                None => self.track.recv().await?,
+            };
+            // 没有序号的样本无法排序，直接交付
+            let sequenced = match &sample {
+                MediaSample::Audio(f) => f.sequence_number.is_some(),
+                MediaSample::Video(f) => f.sequence_number.is_some(),
+            };
+            if !sequenced {
+                return Ok(sample);
+            }
+            buffer.push(sample);
+        }
+    }
+}
+
+/// 音频编解码器
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AudioCodec {
+    /// G.711 μ-law，载荷类型 0
+    #[default]
+    Pcmu,
+    /// G.711 A-law，载荷类型 8
+    Pcma,
+    /// G.722 宽带，载荷类型 9；按 RFC 3551 的历史约定 SDP 中声明 8000 Hz，实际采样率为 16 kHz
+    #[cfg(feature = "g722")]
+    G722,
+}
+
+impl AudioCodec {
+    /// 静态载荷类型
+    pub fn payload_type(self) -> u8 {
+        match self {
+            AudioCodec::Pcmu => 0,
+            AudioCodec::Pcma => 8,
+            #[cfg(feature = "g722")]
+            AudioCodec::G722 => 9,
+        }
+    }
+
+    /// rtpmap 中的编码名称
+    pub fn name(self) -> &'static str {
+        match self {
+            AudioCodec::Pcmu => "PCMU",
+            AudioCodec::Pcma => "PCMA",
+            #[cfg(feature = "g722")]
+            AudioCodec::G722 => "G722",
+        }
+    }
+
+    /// RTP 时钟频率（G.722 为 8000 Hz）
+    pub fn clock_rate(self) -> u32 {
+        8000
+    }
+
+    /// 播放的 WAV 文件需要的采样率
+    pub fn sample_rate(self) -> u32 {
+        match self {
+            #[cfg(feature = "g722")]
+            AudioCodec::G722 => 16000,
+            codec => codec.clock_rate(),
+        }
+    }
+
+    /// 声道数
+    pub fn channels(self) -> u8 {
+        1
+    }
+
+    /// 按载荷类型查找编解码器
+    pub fn from_payload_type(payload_type: u8) -> Option<Self> {
+        match payload_type {
+            0 => Some(AudioCodec::Pcmu),
+            8 => Some(AudioCodec::Pcma),
+            #[cfg(feature = "g722")]
+            9 => Some(AudioCodec::G722),
+            _ => None,
+        }
+    }
+
+    /// 按 SDP 音频段中载荷类型的编码名称、时钟频率与声道数查找本地支持的编解码器
+    ///
+    /// 动态载荷类型（如 `a=rtpmap:96 PCMU/8000`）同样可以识别
+    fn from_rtpmap(sdp: &str, payload_type: u8) -> Option<Self> {
+        let (name, clock_rate, channels) = rtpmap_format(sdp, "audio", payload_type)?;
+        Self::answerable().iter().copied().find(|codec| {
+            codec.name().eq_ignore_ascii_case(&name) && codec.clock_rate() == clock_rate && codec.channels() == channels
+        })
+    }
+
+    /// SDP 音频段中映射到该编解码器的第一个载荷类型
+    fn payload_type_in(self, sdp: &str) -> Option<u8> {
+        extract_payload_types(sdp, "audio")
+            .into_iter()
+            .find(|pt| Self::from_rtpmap(sdp, *pt) == Some(self))
+    }
+
+    fn capability(self) -> AudioCapability {
+        match self {
+            AudioCodec::Pcmu => AudioCapability::pcmu(),
+            AudioCodec::Pcma => AudioCapability::pcma(),
+            #[cfg(feature = "g722")]
+            AudioCodec::G722 => AudioCapability::g722(),
+        }
+    }
+
+    /// 应答方支持的编解码器
+    fn answerable() -> &'static [AudioCodec] {
+        &[
+            AudioCodec::Pcmu,
+            AudioCodec::Pcma,
+            #[cfg(feature = "g722")]
+            AudioCodec::G722,
+        ]
+    }
+
+    /// 将 20 ms 静音编码为该编解码器的载荷
+    fn silence_frame(self) -> Vec<u8> {
+        match self {
+            #[cfg(feature = "g722")]
+            AudioCodec::G722 => G722Encoder::new().encode(&[0; FRAME_SAMPLES * 2]),
+            codec => silence_payload(codec.payload_type(), FRAME_SAMPLES),
+        }
+    }
+}
+
+/// 生成本地初始 offer 的 SDP 文本，不依赖 PeerConnection，也不进行任何网络操作
+///
+/// `addr` 的 IP 写入 `o=`/`c=` 行（其端口不使用），`port` 为 m 行的 RTP 端口。
+/// 音频 m 行包含 `codec` 与 `telephone-event`，视频 m 行为 VP8；
+/// `RtpPlayer` 发出的初始 offer 即由此生成，可用于快照测试
+pub fn build_offer_sdp(media: MediaKind, codec: AudioCodec, addr: SocketAddr, port: u16) -> String {
+    let ip = addr.ip();
+    let family = if ip.is_ipv6() { "IP6" } else { "IP4" };
+    let mut sdp = format!("v=0\r\no=- 0 0 IN {family} {ip}\r\ns=-\r\nc=IN {family} {ip}\r\nt=0 0\r\n");
+
+    // (载荷类型, rtpmap 编码, fmtp, rtcp-fb)
+    let formats: Vec<(u8, String, Option<String>, Vec<String>)> = match media {
+        MediaKind::Audio => std::iter::once(codec.capability())
+            .chain(std::iter::once(AudioCapability::telephone_event()))
+            .map(|cap| {
+                let encoding = format!("{}/{}/{}", cap.codec_name, cap.clock_rate, cap.channels);
+                (cap.payload_type, encoding, cap.fmtp, cap.rtcp_fbs)
+            })
+            .collect(),
+        MediaKind::Video => {
+            let cap = VideoCapability::default();
+            let encoding = format!("{}/{}", cap.codec_name, cap.clock_rate);
+            vec![(cap.payload_type, encoding, None, cap.rtcp_fbs)]
+        }
+    };
+    let kind = match media {
+        MediaKind::Audio => "audio",
+        MediaKind::Video => "video",
+    };
+    let payload_types: Vec<String> = formats.iter().map(|(pt, ..)| pt.to_string()).collect();
+    let _ = write!(
+        sdp,
+        "m={kind} {port} RTP/AVP {}\r\na=mid:0\r\na=sendrecv\r\na=rtcp-mux\r\n",
+        payload_types.join(" ")
+    );
+    for (pt, encoding, fmtp, rtcp_fbs) in formats {
+        let _ = write!(sdp, "a=rtpmap:{pt} {encoding}\r\n");
+        if let Some(fmtp) = fmtp {
+            let _ = write!(sdp, "a=fmtp:{pt} {fmtp}\r\n");
+        }
+        for fb in rtcp_fbs {
+            let _ = write!(sdp, "a=rtcp-fb:{pt} {fb}\r\n");
+        }
+    }
+    sdp
+}
+
+/// 音频播放器选项，由 [`MediaPlayerFactory::create_audio_player_with_options`] 使用
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PlayerOptions {
+    /// 发送使用的编解码器，默认 PCMU
+    pub codec: AudioCodec,
+    /// 播放增益（dB），限制在 -30 ~ +30
+    pub gain_db: f32,
+    /// 自动转换格式：采样率或声道数不符的 WAV（如 44.1 kHz 立体声）
+    /// 在播放前混合为单声道并重采样到编解码器的采样率
+    pub auto_convert: bool,
+}
+
+impl PlayerOptions {
+    pub fn with_codec(mut self, codec: AudioCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    pub fn with_gain_db(mut self, gain_db: f32) -> Self {
+        self.gain_db = gain_db;
+        self
+    }
+
+    pub fn with_auto_convert(mut self, auto_convert: bool) -> Self {
+        self.auto_convert = auto_convert;
+        self
+    }
+}
+
 /// 媒体播放器工厂，用于创建不同类型的媒体播放器
 pub struct MediaPlayerFactory;
 
 impl MediaPlayerFactory {
-    /// 创建音频播放器
+    /// 创建音频播放器（PCMU）
     pub async fn create_audio_player(file_path: &str) -> Result<Box<dyn MediaPlayer>, MediaPlayError> {
+        Self::create_audio_player_with_options(file_path, &PlayerOptions::default()).await
+    }
+
+    /// 按 `options` 创建音频播放器
+    ///
+    /// 未开启 `auto_convert` 时创建阶段读取 WAV 头部，采样率或声道数不符合编解码器要求时返回
+    /// `MediaPlayError::UnsupportedFormat`
+    pub async fn create_audio_player_with_options(
+        file_path: &str,
+        options: &PlayerOptions,
+    ) -> Result<Box<dyn MediaPlayer>, MediaPlayError> {
+        let PlayerOptions {
+            codec,
+            gain_db,
+            auto_convert,
+        } = *options;
         let path = PathBuf::from(file_path);
-        Self::validate_file_exists(&path, file_path)?;
-        
-        let ext = Self::get_file_extension(&path);
-        match ext.as_str() {
-            "wav" => {
-                let player = RtpPlayer::new(MediaKind::Audio).await?;
+        match crate::utils::sniff_media_kind(&path)? {
+            MediaKind::Audio => {
+                // MP3 解码后总会重采样，只校验 WAV
+                if !auto_convert && !crate::utils::is_mp3_file(&path) {
+                    check_wav_format(&read_wav_format(&path)?, codec)?;
+                }
+                let player = RtpPlayer::new_with_codec(MediaKind::Audio, codec)
+                    .await?
+                    .with_media_file(path)
+                    .with_gain_db(gain_db)
+                    .with_auto_convert(auto_convert);
                 Ok(Box::new(player))
             }
-            _ => Err(MediaPlayError::UnsupportedFormat("不支持的音频格式".to_string())),
+            MediaKind::Video => Err(MediaPlayError::UnsupportedFormat(
+                "文件内容为视频，不能作为音频播放".to_string(),
+            )),
         }
     }
     
     /// 创建视频播放器
     pub async fn create_video_player(file_path: &str) -> Result<Box<dyn MediaPlayer>, MediaPlayError> {
-        let path = PathBuf::from(file_path);
-        Self::validate_file_exists(&path, file_path)?;
-        
-        let ext = Self::get_file_extension(&path);
-        match ext.as_str() {
-            "ivf" => {
+        match crate::utils::sniff_media_kind(file_path)? {
+            MediaKind::Video => {
                 let player = RtpPlayer::new(MediaKind::Video).await?;
                 Ok(Box::new(player))
             }
-            _ => Err(MediaPlayError::UnsupportedFormat("不支持的视频格式".to_string())),
+            MediaKind::Audio => Err(MediaPlayError::UnsupportedFormat(
+                "文件内容为音频，不能作为视频播放".to_string(),
+            )),
         }
     }
     
+    /// 创建播放列表播放器
+    ///
+    /// 不存在或内容不是 WAV 的文件会被跳过并记录警告，全部不可用时返回错误
+    pub fn create_playlist_player(files: &[&str]) -> Result<PlaylistPlayer, MediaPlayError> {
+        let mut playable = Vec::new();
+        for file_path in files {
+            match crate::utils::sniff_media_kind(file_path) {
+                Ok(MediaKind::Audio) => playable.push(PathBuf::from(file_path)),
+                Ok(MediaKind::Video) => warn!("跳过播放列表文件 {}: 不支持的音频格式", file_path),
+                Err(e) => warn!("跳过播放列表文件 {}: {}", file_path, e),
+            }
+        }
+        if playable.is_empty() {
+            return Err(MediaPlayError::FileNotFound("播放列表中没有可用的音频文件".to_string()));
+        }
+        Ok(PlaylistPlayer::new(playable))
+    }
+
     /// 创建音频回声播放器
     pub async fn create_echo_player() -> Result<Box<dyn MediaPlayer>, MediaPlayError> {
         let (player, _sdp) = AudioEchoPlayer::new().await?;
         Ok(Box::new(player))
     }
-    
-    // 私有辅助方法
-    fn validate_file_exists(path: &PathBuf, _file_path: &str) -> Result<(), MediaPlayError> {
-        if !path.exists() {
-            return Err(MediaPlayError::FileNotFound("File not found".to_string()));
-        }
-        Ok(())
-    }
-    
-    fn get_file_extension(path: &PathBuf) -> String {
-        path.extension()
-            .and_then(|s| s.to_str())
-            .unwrap_or("")
-            .to_lowercase()
-    }
 }
 
 /// 媒体播放器通用接口
 #[async_trait]
-pub trait MediaPlayer {
+pub trait MediaPlayer: Send {
     /// 获取媒体类型
     fn media_kind(&self) -> MediaKind;
     
@@ -115,6 +485,17 @@ pub trait MediaPlayer {
     /// 播放媒体到指定的远程地址
     async fn play_to_remote(&mut self, peer_connection: Arc<PeerConnection>) -> Result<(), MediaPlayError>;
     
+    /// 停止播放（如 IVR 提示音被按键打断）
+    fn stop(&mut self) {}
+
+    /// 在后台任务中播放，返回可中途停止并等待结束的句柄
+    fn spawn_playback(self: Box<Self>, peer_connection: Arc<PeerConnection>) -> PlaybackHandle
+    where
+        Self: 'static,
+    {
+        PlaybackHandle::spawn(self, peer_connection)
+    }
+
     /// 启动回声模式
     async fn start_echo(&mut self) -> Result<(), MediaPlayError> {
         Err(MediaPlayError::Sdp("此播放器不支持回声模式".to_string()))
@@ -126,167 +507,1927 @@ pub trait MediaPlayer {
     }
 }
 
-/// RTP播放器，用于生成SDP并播放媒体
-pub struct RtpPlayer {
-    peer_connection: Arc<PeerConnection>,
-    running: Option<Arc<std::sync::atomic::AtomicBool>>,
-    is_active: bool,
+/// 本地支持的音频载荷类型
+#[cfg(not(feature = "g722"))]
+pub const SUPPORTED_AUDIO_PAYLOAD_TYPES: &[u8] = &[0, 8]; // PCMU, PCMA
+/// 本地支持的音频载荷类型
+#[cfg(feature = "g722")]
+pub const SUPPORTED_AUDIO_PAYLOAD_TYPES: &[u8] = &[0, 8, 9]; // PCMU, PCMA, G722
+
+/// SDP 中被接受（端口非 0）的音视频媒体类型
+fn accepted_kinds(sdp: &str) -> Vec<MediaKind> {
+    media_stream_states(sdp)
+        .into_iter()
+        .filter(|(_, accepted)| *accepted)
+        .filter_map(|(media, _)| match media.as_str() {
+            "audio" => Some(MediaKind::Audio),
+            "video" => Some(MediaKind::Video),
+            _ => None,
+        })
+        .collect()
 }
 
-impl RtpPlayer {
-    /// 创建新的RTP播放器
-    pub async fn new(media_type: MediaKind) -> Result<Self, MediaPlayError> {
-        let config = Self::create_rtc_config();
-        let pc = Arc::new(PeerConnection::new(config));
-        
-        // 创建媒体轨道
-        let (_sample_source, track, _) = rustrtc::media::sample_track(media_type.clone(), 100);
-        
-        // 设置编解码器参数
-        let params = Self::create_codec_params(media_type.clone());
-        
-        pc.add_track(track, params)
-            .map_err(|e| MediaPlayError::Rtp(format!("添加轨道失败: {}", e)))?;
-        
-        // 创建并设置offer
-        let local_sdp = pc.create_offer()
-            .await
-            .map_err(|e| MediaPlayError::Sdp(format!("创建offer失败: {}", e)))?;
-            
-        pc.set_local_description(local_sdp.clone())
-            .map_err(|e| MediaPlayError::Sdp(format!("设置本地描述失败: {}", e)))?;
-        
-        // 等待收集完成
-        let pc_clone = pc.clone();
-        tokio::spawn(async move {
-            let _ = pc_clone.wait_for_gathering_complete().await;
-        });
-        
-        Ok(Self {
-            peer_connection: pc,
-            running: None,
-            is_active: false,
+/// SDP 的音频 m 行是否包含舒适噪声载荷类型
+/// 音频段中时钟频率为 8000 Hz 的 telephone-event 载荷类型（DTMF 时间戳按 8 kHz 计算）
+fn telephone_event_payload_type(sdp: &str) -> Option<u8> {
+    extract_payload_types(sdp, "audio").into_iter().find(|pt| {
+        rtpmap_format(sdp, "audio", *pt)
+            .is_some_and(|(name, clock_rate, _)| name.eq_ignore_ascii_case("telephone-event") && clock_rate == 8000)
+    })
+}
+
+fn offers_comfort_noise(sdp: &str) -> bool {
+    extract_payload_types(sdp, "audio").contains(&CN_PAYLOAD_TYPE)
+}
+
+/// 对端的远程描述中是否协商了舒适噪声
+fn negotiated_comfort_noise(peer_connection: &PeerConnection) -> bool {
+    peer_connection
+        .remote_description()
+        .is_some_and(|remote| offers_comfort_noise(&remote.to_sdp_string()))
+}
+
+/// 在第一个音频 m 行追加 CN 载荷类型，并在该媒体段末尾加入 `a=rtpmap:13 CN/8000`
+///
+/// 已包含 CN 或没有音频段时原样返回
+fn add_comfort_noise(sdp: &str) -> String {
+    if offers_comfort_noise(sdp) {
+        return sdp.to_string();
+    }
+    let mut lines: Vec<String> = sdp.lines().map(|line| line.trim_end().to_string()).collect();
+    let Some(start) = lines.iter().position(|line| line.starts_with("m=audio ")) else {
+        return sdp.to_string();
+    };
+    lines[start].push_str(&format!(" {CN_PAYLOAD_TYPE}"));
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| line.starts_with("m=") || line.is_empty())
+        .map_or(lines.len(), |offset| start + 1 + offset);
+    lines.insert(end, format!("a=rtpmap:{CN_PAYLOAD_TYPE} CN/8000"));
+    let mut result = lines.join("\r\n");
+    result.push_str("\r\n");
+    result
+}
+
+/// 在每个未被拒绝的媒体段中通告带宽上限 `b=AS:<kbps>`，替换已有的 `b=AS` 行
+///
+/// 按 RFC 4566 的行序插入在 m 行及其 `i=`/`c=` 行之后、属性行之前
+fn with_bandwidth_line(sdp: &str, kbps: u32) -> String {
+    let mut result = String::with_capacity(sdp.len() + 16);
+    let mut in_media = false;
+    let mut pending = false;
+    for line in sdp.lines().map(str::trim_end).filter(|line| !line.is_empty()) {
+        if pending && !line.starts_with("i=") && !line.starts_with("c=") {
+            let _ = write!(result, "b=AS:{kbps}\r\n");
+            pending = false;
+        }
+        if let Some(media) = line.strip_prefix("m=") {
+            in_media = true;
+            pending = media.split_whitespace().nth(1).is_some_and(|port| port != "0");
+        } else if in_media && line.starts_with("b=AS:") {
+            continue;
+        }
+        result.push_str(line);
+        result.push_str("\r\n");
+    }
+    if pending {
+        let _ = write!(result, "b=AS:{kbps}\r\n");
+    }
+    result
+}
+
+/// 去掉各媒体段的 `a=rtcp-mux`，并在 m 行后通告独立的 RTCP 端口 `a=rtcp:<RTP 端口 + 1>`（RFC 3605）
+///
+/// 端口为 0 的（被拒绝的）媒体段不通告 RTCP 端口
+fn without_rtcp_mux(sdp: &str) -> String {
+    let mut lines = Vec::new();
+    for line in sdp.lines().map(str::trim_end).filter(|line| !line.is_empty()) {
+        if line == "a=rtcp-mux" {
+            continue;
+        }
+        lines.push(line.to_string());
+        let port = line
+            .strip_prefix("m=")
+            .and_then(|m| m.split_whitespace().nth(1))
+            .and_then(|port| port.parse::<u16>().ok());
+        if let Some(rtcp_port) = port.filter(|port| *port != 0).and_then(|port| port.checked_add(1)) {
+            lines.push(format!("a=rtcp:{}", rtcp_port));
+        }
+    }
+    let mut result = lines.join("\r\n");
+    result.push_str("\r\n");
+    result
+}
+
+/// 对端首个媒体段的 RTCP 地址：`a=rtcp:<端口> [IN IP4|IP6 <地址>]`（RFC 3605），缺省为 RTP 端口 + 1
+///
+/// 媒体段被拒绝或对端仍声明 `a=rtcp-mux` 时返回 `None`
+fn remote_rtcp_addr(sdp: &str) -> Option<SocketAddr> {
+    let ip = sdp_connection_ip(sdp)?;
+    let mut rtp_port = None;
+    let mut rtcp = None;
+    for line in sdp.lines().map(str::trim_end) {
+        if let Some(media) = line.strip_prefix("m=") {
+            if rtp_port.is_some() {
+                break;
+            }
+            rtp_port = Some(media.split_whitespace().nth(1)?.parse::<u16>().ok()?);
+        } else if rtp_port.is_none() {
+            continue;
+        } else if line == "a=rtcp-mux" {
+            return None;
+        } else if let Some(value) = line.strip_prefix("a=rtcp:") {
+            let mut parts = value.split_whitespace();
+            let port = parts.next()?.parse().ok()?;
+            let addr = parts.nth(2).and_then(|addr| addr.trim_matches(['[', ']']).parse().ok());
+            rtcp = Some(SocketAddr::new(addr.unwrap_or(ip), port));
+        }
+    }
+    let port = rtp_port.filter(|port| *port != 0)?;
+    rtcp.or_else(|| Some(SocketAddr::new(ip, port.checked_add(1)?)))
+}
+
+/// 不含报告块的 RR 与携带 CNAME 的 SDES 组成的复合 RTCP 包
+fn receiver_report(ssrc: u32, cname: &str) -> Option<Vec<u8>> {
+    use rustrtc::rtp::{ReceiverReport, RtcpPacket, SdesChunk, SdesItem, SourceDescription};
+    rustrtc::rtp::marshal_rtcp_packets(&[
+        RtcpPacket::ReceiverReport(ReceiverReport {
+            sender_ssrc: ssrc,
+            report_blocks: vec![],
+        }),
+        RtcpPacket::SourceDescription(SourceDescription {
+            chunks: vec![SdesChunk {
+                ssrc,
+                items: vec![SdesItem {
+                    ty: 1,
+                    text: cname.to_string(),
+                }],
+            }],
+        }),
+    ])
+    .ok()
+}
+
+/// 将 IPv6 的 `c=` 行改写为 rustrtc 能识别的形式
+///
+/// rustrtc 只从 `c=IN IP4` 行读取对端 RTP 地址，`IN IP6` 会被静默忽略而不发送媒体；
+/// 地址本身按 `IpAddr` 解析，因此只改写地址类型并去掉方括号
+fn rtc_connection_lines(sdp: &str) -> String {
+    let mut lines = Vec::new();
+    for line in sdp.lines().map(str::trim_end).filter(|line| !line.is_empty()) {
+        match connection_line_ip(line) {
+            Some(ip) if ip.is_ipv6() => lines.push(format!("c=IN IP4 {}", ip)),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    let mut result = lines.join("\r\n");
+    result.push_str("\r\n");
+    result
+}
+
+/// SDP 中最后一个 `c=` 行的连接地址
+fn sdp_connection_ip(sdp: &str) -> Option<std::net::IpAddr> {
+    sdp.lines().rev().find_map(connection_line_ip)
+}
+
+/// 将舒适噪声帧替换为所选编解码器的静音帧，其他样本原样返回
+///
+/// CN 载荷只携带噪声电平，直接转发会被对端当作 G.711 音频解码
+fn comfort_noise_to_silence(sample: MediaSample, codec: AudioCodec) -> MediaSample {
+    match sample {
+        MediaSample::Audio(frame) if frame.payload_type == Some(CN_PAYLOAD_TYPE) => {
+            MediaSample::Audio(AudioFrame {
+                data: codec.silence_frame().into(),
+                payload_type: Some(codec.payload_type()),
+                raw_packet: None,
+                ..frame
+            })
+        }
+        other => other,
+    }
+}
+
+/// 对 G.711 音频帧应用增益，其他载荷原样返回
+fn amplify_sample(sample: MediaSample, gain_db: f32, codec: AudioCodec) -> MediaSample {
+    match sample {
+        MediaSample::Audio(frame) if gain_db != 0.0 => {
+            let payload_type = frame.payload_type.unwrap_or(codec.payload_type());
+            let Some(mut samples) = decode_g711(payload_type, &frame.data) else {
+                return MediaSample::Audio(frame);
+            };
+            apply_gain(&mut samples, gain_db);
+            match encode_g711(payload_type, &samples) {
+                Some(data) => MediaSample::Audio(AudioFrame {
+                    data: data.into(),
+                    raw_packet: None,
+                    ..frame
+                }),
+                None => MediaSample::Audio(frame),
+            }
+        }
+        other => other,
+    }
+}
+
+/// 语音活动检测判定是否转发音频帧，无法解码的载荷总是转发
+fn vad_accepts(vad: &mut VoiceActivityDetector, frame: &AudioFrame, codec: AudioCodec) -> bool {
+    let payload_type = frame.payload_type.unwrap_or(codec.payload_type());
+    match decode_g711(payload_type, &frame.data) {
+        Some(samples) => vad.process(&samples),
+        None => true,
+    }
+}
+
+/// 让新 offer 沿用上一次本地描述的会话 ID，内容变化时版本号加一
+fn carry_origin(previous: &SessionDescription, offer: &mut SessionDescription) {
+    offer.session.origin.session_id = previous.session.origin.session_id;
+    offer.session.origin.session_version = previous.session.origin.session_version;
+    if offer.to_sdp_string() != previous.to_sdp_string() {
+        offer.session.origin.session_version += 1;
+    }
+}
+
+/// 两份 SDP 是否描述同一会话版本：都带 `o=` 行时比较 `o=` 行，否则比较全文
+fn same_session(a: &str, b: &str) -> bool {
+    let origin = |sdp: &str| sdp.lines().map(str::trim).find(|l| l.starts_with("o=")).map(str::to_string);
+    match (origin(a), origin(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.trim() == b.trim(),
+    }
+}
+
+/// 校验 answer 的音频编解码器与 offer 是否兼容
+///
+/// 音频流被拒绝时不校验；answer 中没有 offer 列出的音频载荷类型（不计 telephone-event 与 CN）时返回
+/// [`MediaPlayError::NoCommonCodec`]，共同载荷类型都映射到不同编码时返回 [`MediaPlayError::CodecNegotiation`]
+fn check_negotiated_codecs(offer: &str, answer: &str) -> Result<(), MediaPlayError> {
+    if !media_stream_states(answer).contains(&("audio".to_string(), true)) {
+        return Ok(());
+    }
+    let is_media_codec = |sdp: &str, pt: u8| {
+        !rtpmap_encoding(sdp, "audio", pt).is_some_and(|name| {
+            name.eq_ignore_ascii_case("telephone-event") || name.eq_ignore_ascii_case("CN")
         })
+    };
+    let offered: Vec<u8> = extract_payload_types(offer, "audio")
+        .into_iter()
+        .filter(|pt| is_media_codec(offer, *pt))
+        .collect();
+    let answered: Vec<u8> = extract_payload_types(answer, "audio")
+        .into_iter()
+        .filter(|pt| is_media_codec(answer, *pt))
+        .collect();
+
+    let common: Vec<u8> = answered.iter().copied().filter(|pt| offered.contains(pt)).collect();
+    if common.is_empty() {
+        return Err(MediaPlayError::NoCommonCodec);
     }
-    
-    fn create_codec_params(media_type: MediaKind) -> RtpCodecParameters {
-        match media_type {
-            MediaKind::Audio => RtpCodecParameters {
-                payload_type: 0, // PCMU
-                clock_rate: 8000,
-                channels: 1,
+    let encoding = |sdp: &str, pt: u8| rtpmap_encoding(sdp, "audio", pt).unwrap_or_else(|| pt.to_string());
+    let compatible = common
+        .iter()
+        .any(|pt| encoding(offer, *pt).eq_ignore_ascii_case(&encoding(answer, *pt)));
+    if !compatible {
+        let describe = |sdp: &str| {
+            common.iter().map(|pt| format!("{}={}", pt, encoding(sdp, *pt))).collect::<Vec<_>>().join(",")
+        };
+        return Err(MediaPlayError::CodecNegotiation {
+            offered: describe(offer),
+            answered: describe(answer),
+        });
+    }
+    Ok(())
+}
+
+/// 协商得到的音频编解码器：按对端 SDP 的优先顺序，取本端 SDP 同样列出的第一个编解码器
+///
+/// 与发送方向的选择一致：作为应答方时按 offer 顺序，作为发起方时按 answer 顺序。
+/// 音频流被拒绝或没有共同编解码器时返回 `None`，telephone-event 与 CN 不计入
+fn negotiated_audio_codec(local: &str, remote: &str) -> Option<AudioCodec> {
+    let audio_active = |sdp: &str| media_stream_states(sdp).contains(&("audio".to_string(), true));
+    if !audio_active(local) || !audio_active(remote) {
+        return None;
+    }
+    let local_types = extract_payload_types(local, "audio");
+    extract_payload_types(remote, "audio")
+        .into_iter()
+        .filter(|pt| local_types.contains(pt))
+        .find_map(|pt| AudioCodec::from_rtpmap(remote, pt))
+}
+
+/// 发送 `codec` 使用的 RTP 参数
+///
+/// 载荷类型取对端描述中映射到该编解码器的载荷类型（对端 offer 可能使用动态载荷类型），
+/// 尚未设置远程描述时使用静态载荷类型
+fn send_codec_params(peer_connection: &PeerConnection, codec: AudioCodec) -> RtpCodecParameters {
+    let mut params = RtpPlayer::create_codec_params(MediaKind::Audio, codec);
+    if let Some(pt) = peer_connection
+        .remote_description()
+        .and_then(|remote| codec.payload_type_in(&remote.to_sdp_string()))
+    {
+        params.payload_type = pt;
+    }
+    params
+}
+
+/// 将以协商载荷类型 `wire_pt` 收到的音频帧改写为编解码器的静态载荷类型，便于按 G.711 解码
+fn with_static_payload_type(sample: MediaSample, wire_pt: u8, codec: AudioCodec) -> MediaSample {
+    match sample {
+        MediaSample::Audio(mut frame) if wire_pt != codec.payload_type() && frame.payload_type == Some(wire_pt) => {
+            frame.payload_type = Some(codec.payload_type());
+            MediaSample::Audio(frame)
+        }
+        other => other,
+    }
+}
+
+impl Clone for MediaPlayError {
+    fn clone(&self) -> Self {
+        match self {
+            Self::UnsupportedFormat(s) => Self::UnsupportedFormat(s.clone()),
+            Self::FileNotFound(s) => Self::FileNotFound(s.clone()),
+            Self::EchoNotInitialized => Self::EchoNotInitialized,
+            Self::Sdp(s) => Self::Sdp(s.clone()),
+            Self::Rtp(s) => Self::Rtp(s.clone()),
+            Self::CodecNegotiation { offered, answered } => Self::CodecNegotiation {
+                offered: offered.clone(),
+                answered: answered.clone(),
             },
-            MediaKind::Video => RtpCodecParameters {
-                payload_type: 96, // VP8
-                clock_rate: 90000,
-                channels: 0,
+            Self::NoCommonCodec => Self::NoCommonCodec,
+            Self::SeekOutOfRange { offset, duration } => Self::SeekOutOfRange {
+                offset: *offset,
+                duration: *duration,
             },
+            // io::Error 不可克隆，保留错误类型与描述
+            Self::Io(e) => Self::Io(std::io::Error::new(e.kind(), e.to_string())),
         }
     }
-    
-    /// 获取本地SDP
-    pub fn get_local_sdp(&self) -> Result<String, MediaPlayError> {
-        // 从 PeerConnection 获取当前本地描述
-        let local_desc = self.peer_connection.local_description()
-            .ok_or_else(|| MediaPlayError::Sdp("本地描述未设置".to_string()))?;
-            
-        Ok(local_desc.to_sdp_string())
+}
+
+/// 媒体会话状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MediaSessionState {
+    /// 尚未生成本地 SDP
+    #[default]
+    Idle,
+    /// 已发出本地 offer，等待对端 answer
+    Offering,
+    /// SDP 协商完成，尚未开始收发媒体
+    Negotiated,
+    /// 回声或文件播放正在进行
+    Streaming,
+    /// 媒体已停止
+    Stopped,
+    /// 协商或媒体处理失败，原因见 [`RtpPlayer::last_error`]
+    Failed,
+}
+
+/// 文件播放的暂停控制句柄，可克隆到其他任务（如按键处理）中使用
+#[derive(Debug, Clone, Default)]
+pub struct PlaybackControl {
+    paused: Arc<AtomicBool>,
+}
+
+impl PlaybackControl {
+    /// 暂停发送，播放位置保持不变
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
     }
-    
-    /// 设置远程SDP并开始播放
-    pub async fn set_remote_sdp_and_play(
-        &mut self,
-        remote_sdp: &str,
-        mut media_player: Box<dyn MediaPlayer>,
-    ) -> Result<(), MediaPlayError> {
-        // 解析并设置远程SDP
-        let remote_sdp = SessionDescription::parse(SdpType::Answer, remote_sdp)
-            .map_err(|e| MediaPlayError::Sdp(format!("解析远程SDP失败: {}", e)))?;
-        
-        self.peer_connection.set_remote_description(remote_sdp)
-            .await
-            .map_err(|e| MediaPlayError::Sdp(format!("设置远程描述失败: {}", e)))?;
-        
-        // 开始播放媒体
-        media_player.play_to_remote(self.peer_connection.clone()).await?;
-        
-        Ok(())
+
+    /// 从暂停的位置继续发送
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
     }
-    
-    /// 启动音频回声
-    pub async fn start_audio_echo(&mut self) -> Result<(), MediaPlayError> {
-        info!("启动音频回声功能");
-        
-        // 检查是否已经运行
-        if self.running.is_some() {
-            warn!("音频回声处理器已在运行");
-            return Ok(());
-        }
+
+    /// 是否处于暂停状态
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+}
+
+/// [`RtpPlayer::rtcp_events`] 通道的容量，订阅者落后超过该数量时丢弃最旧的事件
+const RTCP_EVENT_CAPACITY: usize = 64;
+
+/// 关闭 rtcp-mux 时从 RTCP 端口发送接收报告的间隔（RFC 3550 建议的最小间隔）
+const RTCP_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 会话中收到的 RTCP 反馈，见 [`RtpPlayer::rtcp_events`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RtcpEvent {
+    /// 画面丢失指示（PLI），对端请求关键帧
+    PictureLoss { media_ssrc: u32 },
+    /// 完整帧内请求（FIR），列出被请求的 SSRC
+    FullIntraRequest { ssrcs: Vec<u32> },
+    /// 通用 NACK，列出对端未收到的序列号
+    Nack { media_ssrc: u32, lost_packets: Vec<u16> },
+    /// 接收端估计的最大码率（REMB）
+    Remb { bitrate_bps: u64, ssrcs: Vec<u32> },
+}
+
+impl RtcpEvent {
+    /// 转换反馈类 RTCP 包，报告（SR/RR）、SDES、BYE 等返回 `None`
+    fn from_packet(packet: &rustrtc::rtp::RtcpPacket) -> Option<Self> {
+        use rustrtc::rtp::RtcpPacket;
+        match packet {
+            RtcpPacket::PictureLossIndication(pli) => Some(Self::PictureLoss {
+                media_ssrc: pli.media_ssrc,
+            }),
+            RtcpPacket::FullIntraRequest(fir) => Some(Self::FullIntraRequest {
+                ssrcs: fir.requests.iter().map(|r| r.ssrc).collect(),
+            }),
+            RtcpPacket::GenericNack(nack) => Some(Self::Nack {
+                media_ssrc: nack.media_ssrc,
+                lost_packets: nack.lost_packets.clone(),
+            }),
+            RtcpPacket::RemoteBitrateEstimate(remb) => Some(Self::Remb {
+                bitrate_bps: remb.bitrate_bps,
+                ssrcs: remb.ssrcs.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// 把发送器收到的 RTCP 包中的反馈转发给 [`RtpPlayer::rtcp_events`] 的订阅者
+fn publish_rtcp_event(events: &broadcast::Sender<RtcpEvent>, packet: &rustrtc::rtp::RtcpPacket) {
+    if let Some(event) = RtcpEvent::from_packet(packet) {
+        let _ = events.send(event);
+    }
+}
+
+/// RTP 传输选项，创建 [`RtpPlayer`] 时指定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtpTransportOptions {
+    /// RTCP 与 RTP 复用同一端口（`a=rtcp-mux`，RFC 5761），默认开启
+    ///
+    /// 关闭后本地 SDP 改为通告 `a=rtcp:<RTP 端口 + 1>`，并在该端口与对端 `a=rtcp` 地址收发 RTCP，
+    /// 用于不支持 rtcp-mux 的老旧网关
+    pub rtcp_mux: bool,
+    /// 对称 RTP（latching）：按对端实际发送的源地址回送媒体，默认关闭
+    pub symmetric_rtp: bool,
+}
+
+impl Default for RtpTransportOptions {
+    fn default() -> Self {
+        Self {
+            rtcp_mux: true,
+            symmetric_rtp: false,
+        }
+    }
+}
+
+/// 后台播放句柄，由 [`MediaPlayer::spawn_playback`] 返回
+///
+/// 停止后播放任务在当前等待点放弃发送并调用 [`MediaPlayer::stop`]；句柄被丢弃时播放继续，直到自然结束
+#[derive(Debug)]
+pub struct PlaybackHandle {
+    stop: CancellationToken,
+    finished: CancellationToken,
+    task: JoinHandle<Result<(), MediaPlayError>>,
+}
+
+impl PlaybackHandle {
+    fn spawn<P>(mut player: Box<P>, peer_connection: Arc<PeerConnection>) -> Self
+    where
+        P: MediaPlayer + ?Sized + 'static,
+    {
+        let stop = CancellationToken::new();
+        let stopped = stop.clone();
+        let finished = CancellationToken::new();
+        let done = finished.clone().drop_guard();
+        let task = async move {
+            let _done = done;
+            // 播放已经结束时以播放结果为准
+            let finished = tokio::select! {
+                biased;
+                result = player.play_to_remote(peer_connection) => Some(result),
+                _ = stopped.cancelled() => None,
+            };
+            match finished {
+                Some(result) => result,
+                None => {
+                    player.stop();
+                    info!("后台播放已停止");
+                    Ok(())
+                }
+            }
+        };
+        Self {
+            stop,
+            finished,
+            task: tokio::spawn(task.in_current_span()),
+        }
+    }
+
+    /// 请求停止播放，不等待播放任务结束
+    pub fn stop(&self) {
+        self.stop.cancel();
+    }
+
+    /// 播放任务是否已结束
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// 等待播放任务结束但不取走结果，之后仍可调用 [`await_completion`](Self::await_completion)
+    pub async fn finished(&self) {
+        self.finished.cancelled().await;
+    }
+
+    /// 等待播放结束（自然播完或被 [`stop`](Self::stop) 打断），返回播放结果
+    pub async fn await_completion(self) -> Result<(), MediaPlayError> {
+        self.task
+            .await
+            .map_err(|e| MediaPlayError::Rtp(format!("播放任务异常退出: {}", e)))?
+    }
+}
+
+/// RTP 收发统计
+///
+/// 本端收发计数在发送/接收媒体时累加，抖动与丢包来自对端 RTCP 报告中针对本端 SSRC 的报告块
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RtpStats {
+    pub packets_sent: u64,
+    pub bytes_sent: u64,
+    pub packets_received: u64,
+    pub bytes_received: u64,
+    /// 对端报告的到达间隔抖动（RTP 时间戳单位）
+    pub jitter: u32,
+    /// 对端报告的丢包率，以 1/256 为单位
+    pub fraction_lost: u8,
+    /// 对端报告的累计丢包数
+    pub packets_lost: i32,
+    /// 对端 SR 中声明的已发送包数
+    pub remote_packets_sent: u32,
+    /// 对端 SR 中声明的已发送字节数
+    pub remote_bytes_sent: u32,
+    /// 已收到的 SR/RR 数量
+    pub reports_received: u64,
+}
+
+impl RtpStats {
+    /// 对端报告的丢包率（0.0 ~ 1.0）
+    pub fn loss_ratio(&self) -> f64 {
+        self.fraction_lost as f64 / 256.0
+    }
+
+    fn record_sent(&mut self, bytes: usize) {
+        self.packets_sent += 1;
+        self.bytes_sent += bytes as u64;
+    }
+
+    fn record_received(&mut self, bytes: usize) {
+        self.packets_received += 1;
+        self.bytes_received += bytes as u64;
+    }
+
+    /// 累加 SR/RR 报告，只采用针对 `local_ssrc` 的报告块
+    fn apply_rtcp(&mut self, packet: &rustrtc::rtp::RtcpPacket, local_ssrc: u32) {
+        use rustrtc::rtp::RtcpPacket;
+        let blocks = match packet {
+            RtcpPacket::SenderReport(sr) => {
+                self.remote_packets_sent = sr.packet_count;
+                self.remote_bytes_sent = sr.octet_count;
+                &sr.report_blocks
+            }
+            RtcpPacket::ReceiverReport(rr) => &rr.report_blocks,
+            _ => return,
+        };
+        self.reports_received += 1;
+        if let Some(block) = blocks.iter().find(|b| b.ssrc == local_ssrc) {
+            self.jitter = block.jitter;
+            self.fraction_lost = block.fraction_lost;
+            self.packets_lost = block.packets_lost;
+        }
+    }
+}
+
+/// 按码率上限为转发的媒体定速，超出预算的帧延后发送而不丢弃
+struct BandwidthPacer {
+    bits_per_second: f64,
+    next_send: tokio::time::Instant,
+}
+
+impl BandwidthPacer {
+    fn new(kbps: u32) -> Self {
+        Self {
+            bits_per_second: kbps as f64 * 1000.0,
+            next_send: tokio::time::Instant::now(),
+        }
+    }
+
+    /// 等到可以发送 `bytes` 字节时返回，并预留其按上限发送所需的时长；空闲期不累积额度
+    async fn pace(&mut self, bytes: usize) {
+        let now = tokio::time::Instant::now();
+        if self.next_send > now {
+            tokio::time::sleep_until(self.next_send).await;
+        }
+        let start = self.next_send.max(now);
+        self.next_send = start + Duration::from_secs_f64(bytes as f64 * 8.0 / self.bits_per_second);
+    }
+}
+
+/// 判断 VP8 RTP 载荷（RFC 7741）是否为关键帧的首个分片
+///
+/// 只有分区起始（S=1、PID=0）的包携带 VP8 载荷头，其 P 位为 0 表示关键帧
+fn is_vp8_keyframe(payload: &[u8]) -> bool {
+    let Some(&descriptor) = payload.first() else {
+        return false;
+    };
+    if descriptor & 0x10 == 0 || descriptor & 0x07 != 0 {
+        return false;
+    }
+    let mut offset = 1;
+    if descriptor & 0x80 != 0 {
+        let Some(&extension) = payload.get(1) else {
+            return false;
+        };
+        offset += 1;
+        if extension & 0x80 != 0 {
+            // PictureID：M 位置位时为 15 位
+            let long = payload.get(offset).is_some_and(|b| b & 0x80 != 0);
+            offset += if long { 2 } else { 1 };
+        }
+        if extension & 0x40 != 0 {
+            offset += 1;
+        }
+        if extension & 0x30 != 0 {
+            offset += 1;
+        }
+    }
+    payload.get(offset).is_some_and(|header| header & 0x01 == 0)
+}
+
+/// 将音频帧解码后写入录音文件，非 G.711 载荷忽略
+fn record_frame(recorder: &Mutex<Option<WavWriter>>, frame: &AudioFrame, codec: AudioCodec) {
+    let mut guard = recorder.lock().unwrap();
+    let Some(writer) = guard.as_mut() else {
+        return;
+    };
+    let payload_type = frame.payload_type.unwrap_or(codec.payload_type());
+    let Some(samples) = decode_g711(payload_type, &frame.data) else {
+        return;
+    };
+    if let Err(e) = writer.write_samples(&samples) {
+        warn!("写入录音失败: {}", e);
+        *guard = None;
+    }
+}
+
+/// 可在多个任务间共享的媒体会话（如附加到对话供会话刷新使用）
+pub type SharedRtpPlayer = Arc<tokio::sync::Mutex<RtpPlayer>>;
+
+/// RTP播放器，用于生成SDP并播放媒体
+pub struct RtpPlayer {
+    peer_connection: Arc<PeerConnection>,
+    running: Option<Arc<std::sync::atomic::AtomicBool>>,
+    is_active: bool,
+    video_echo: bool,
+    echo_tasks: Vec<JoinHandle<()>>,
+    ssrc_selection: SsrcSelection,
+    sdp_attributes: SdpAttributes,
+    accepted_media: Vec<MediaKind>,
+    local_direction: MediaDirection,
+    negotiated_direction: MediaDirection,
+    audio_codec: AudioCodec,
+    record_path: Option<PathBuf>,
+    recorder: Arc<Mutex<Option<WavWriter>>>,
+    media_file: Option<PathBuf>,
+    loop_count: Option<u32>,
+    gain_db: f32,
+    auto_convert: bool,
+    vad: Option<VoiceActivityDetector>,
+    playback: PlaybackControl,
+    pause_silence: bool,
+    comfort_noise: bool,
+    jitter_buffer: Option<Duration>,
+    bandwidth_kbps: Option<u32>,
+    state: watch::Sender<MediaSessionState>,
+    last_error: Option<MediaPlayError>,
+    early_answer: Option<String>,
+    cancel: CancellationToken,
+    telephone_event: Option<u8>,
+    dtmf_tx: UnboundedSender<char>,
+    dtmf_rx: Option<UnboundedReceiver<char>>,
+    stats: Arc<Mutex<RtpStats>>,
+    metrics: Option<MetricsHandle>,
+    dscp: Option<u8>,
+    transport: RtpTransportOptions,
+    rtcp_events: broadcast::Sender<RtcpEvent>,
+    /// 关闭 rtcp-mux 时对端的 RTCP 地址，每次协商后按远程描述更新，供 RTCP 转发任务使用
+    rtcp_peer: watch::Sender<Option<SocketAddr>>,
+}
+
+impl RtpPlayer {
+    /// 创建新的RTP播放器（音频使用 PCMU）
+    pub async fn new(media_type: MediaKind) -> Result<Self, MediaPlayError> {
+        Self::new_with_codec(media_type, AudioCodec::default()).await
+    }
+
+    /// 使用指定音频编解码器创建RTP播放器
+    ///
+    /// 本地 offer 的音频 m 行只包含该编解码器（及 `telephone-event`）
+    pub async fn new_with_codec(
+        media_type: MediaKind,
+        codec: AudioCodec,
+    ) -> Result<Self, MediaPlayError> {
+        Self::new_with_transport(media_type, codec, RtpTransportOptions::default()).await
+    }
+
+    /// 使用指定的 RTP 传输选项（rtcp-mux、对称 RTP）创建RTP播放器
+    pub async fn new_with_transport(
+        media_type: MediaKind,
+        codec: AudioCodec,
+        transport: RtpTransportOptions,
+    ) -> Result<Self, MediaPlayError> {
+        let config = Self::create_rtc_config(&[codec], transport);
+        Self::with_rtc_config(media_type, codec, config, transport).await
+    }
+
+    /// 离线创建RTP播放器，不依赖 SIP 服务器与网络
+    ///
+    /// RTP 套接字直接绑定到 `local_addr`（端口为 0 时由系统分配），offer 中通告该地址，
+    /// 不探测本机出口地址也不访问 STUN 服务器；创建后 `get_local_sdp` 立即可用，适合在测试中生成 offer
+    pub async fn new_offline(
+        media_type: MediaKind,
+        codec: AudioCodec,
+        local_addr: SocketAddr,
+    ) -> Result<Self, MediaPlayError> {
+        let transport = RtpTransportOptions::default();
+        let mut config = Self::create_rtc_config(&[codec], transport);
+        config.bind_ip = Some(local_addr.ip().to_string());
+        config.external_ip = Some(local_addr.ip().to_string());
+        if local_addr.port() != 0 {
+            config.rtp_start_port = Some(local_addr.port());
+            config.rtp_end_port = Some(local_addr.port());
+        }
+        Self::with_rtc_config(media_type, codec, config, transport).await
+    }
+
+    async fn with_rtc_config(
+        media_type: MediaKind,
+        codec: AudioCodec,
+        config: RtcConfiguration,
+        transport: RtpTransportOptions,
+    ) -> Result<Self, MediaPlayError> {
+        let (dtmf_tx, dtmf_rx) = mpsc::unbounded_channel();
+        let pc = Arc::new(PeerConnection::new(config));
         
-        // 创建运行标志
-        let running = Arc::new(std::sync::atomic::AtomicBool::new(true));
-        self.running = Some(running.clone());
+        // 创建媒体轨道
+        let (_sample_source, track, _) = rustrtc::media::sample_track(media_type, 100);
         
-        let transceivers = self.peer_connection.get_transceivers();
+        // 设置编解码器参数
+        let params = Self::create_codec_params(media_type, codec);
         
-        for transceiver in transceivers {
-            if transceiver.kind() != rustrtc::MediaKind::Audio {
-                continue;
+        pc.add_track(track, params)
+            .map_err(|e| MediaPlayError::Rtp(format!("添加轨道失败: {}", e)))?;
+        
+        // 由 PeerConnection 分配 RTP 地址与端口，offer 文本按 build_offer_sdp 生成
+        let gathered = pc.create_offer()
+            .await
+            .map_err(|e| MediaPlayError::Sdp(format!("创建offer失败: {}", e)))?;
+        let addr = gathered.session.origin.unicast_address.parse::<std::net::IpAddr>()
+            .map_err(|e| MediaPlayError::Sdp(format!("无效的本地地址: {}", e)))?;
+        let port = gathered.media_sections.first().map_or(0, |section| section.port);
+        let mut offer = build_offer_sdp(media_type, codec, SocketAddr::new(addr, 0), port);
+        if !transport.rtcp_mux {
+            offer = without_rtcp_mux(&offer);
+        }
+        let local_sdp = SessionDescription::parse(SdpType::Offer, &offer)
+            .map_err(|e| MediaPlayError::Sdp(format!("生成offer失败: {}", e)))?;
+
+        pc.set_local_description(local_sdp)
+            .map_err(|e| MediaPlayError::Sdp(format!("设置本地描述失败: {}", e)))?;
+        
+        // 等待收集完成
+        let pc_clone = pc.clone();
+        tokio::spawn(async move {
+            let _ = pc_clone.wait_for_gathering_complete().await;
+        });
+        
+        let player = Self {
+            peer_connection: pc,
+            running: None,
+            is_active: false,
+            video_echo: false,
+            echo_tasks: Vec::new(),
+            ssrc_selection: SsrcSelection::default(),
+            sdp_attributes: SdpAttributes::default(),
+            accepted_media: Vec::new(),
+            local_direction: MediaDirection::SendRecv,
+            negotiated_direction: MediaDirection::SendRecv,
+            audio_codec: codec,
+            record_path: None,
+            recorder: Arc::default(),
+            media_file: None,
+            loop_count: Some(1),
+            gain_db: 0.0,
+            auto_convert: false,
+            vad: None,
+            playback: PlaybackControl::default(),
+            pause_silence: false,
+            comfort_noise: false,
+            jitter_buffer: None,
+            bandwidth_kbps: None,
+            state: watch::channel(MediaSessionState::Offering).0,
+            last_error: None,
+            early_answer: None,
+            cancel: CancellationToken::new(),
+            telephone_event: None,
+            dtmf_tx,
+            dtmf_rx: Some(dtmf_rx),
+            stats: Arc::default(),
+            metrics: None,
+            dscp: None,
+            transport,
+            rtcp_events: broadcast::channel(RTCP_EVENT_CAPACITY).0,
+            rtcp_peer: watch::channel(None).0,
+        };
+        player.spawn_rtcp_relay()?;
+        Ok(player)
+    }
+    
+    /// 作为应答方创建RTP播放器
+    ///
+    /// 根据对端 offer 生成 answer，answer 的音频编解码器为 offer 与本地支持的交集
+    ///
+    /// # 返回
+    /// 返回播放器和本地 SDP answer
+    pub async fn new_answerer(remote_offer: &str) -> Result<(Self, String), MediaPlayError> {
+        let (codec, payload_map, telephone_event) = Self::negotiate_offer(remote_offer)?;
+        let pc = Self::create_answerer_connection(codec, &payload_map, RtpTransportOptions::default())?;
+        let answer_sdp = Self::answer_remote_offer(&pc, remote_offer, &payload_map, true).await?;
+
+        let accepted_media = accepted_kinds(&answer_sdp);
+        let telephone_event = telephone_event
+            .filter(|_| find_rtpmap_payload_type(&answer_sdp, "audio", "telephone-event").is_some());
+        let (dtmf_tx, dtmf_rx) = mpsc::unbounded_channel();
+        Ok((
+            Self {
+                peer_connection: pc,
+                running: None,
+                is_active: false,
+                video_echo: false,
+                echo_tasks: Vec::new(),
+                ssrc_selection: SsrcSelection::default(),
+                sdp_attributes: SdpAttributes::default(),
+                accepted_media,
+                local_direction: MediaDirection::SendRecv,
+                negotiated_direction: media_direction(remote_offer, "audio"),
+                audio_codec: codec,
+                record_path: None,
+                recorder: Arc::default(),
+                media_file: None,
+                loop_count: Some(1),
+                gain_db: 0.0,
+                auto_convert: false,
+                vad: None,
+                playback: PlaybackControl::default(),
+                pause_silence: false,
+                comfort_noise: false,
+                jitter_buffer: None,
+                bandwidth_kbps: None,
+                state: watch::channel(MediaSessionState::Negotiated).0,
+                last_error: None,
+                early_answer: None,
+                cancel: CancellationToken::new(),
+                telephone_event,
+                dtmf_tx,
+                dtmf_rx: Some(dtmf_rx),
+                stats: Arc::default(),
+                metrics: None,
+                dscp: None,
+                transport: RtpTransportOptions::default(),
+                rtcp_events: broadcast::channel(RTCP_EVENT_CAPACITY).0,
+                rtcp_peer: watch::channel(None).0,
+            },
+            answer_sdp,
+        ))
+    }
+
+    /// 作为应答方处理对端 offer（如呼入 INVITE 或对端发起的 re-INVITE）
+    ///
+    /// 以 `SdpType::Offer` 解析并设置远程描述，生成 answer 并设为本地描述。
+    /// 本地 offer 尚未被应答时（如由 `new` 创建后直接收到呼入），rustrtc 不支持 rollback，
+    /// 此时丢弃本地 offer 并重建 PeerConnection
+    ///
+    /// # 返回
+    /// 返回已注入额外属性的本地 SDP answer
+    pub async fn set_remote_offer(&mut self, offer: &str) -> Result<String, MediaPlayError> {
+        let (codec, payload_map, telephone_event) = Self::negotiate_offer(offer).map_err(|e| self.fail(e))?;
+        let mut rebuilt = false;
+        if self.peer_connection.signaling_state() != rustrtc::SignalingState::Stable {
+            info!("本地 offer 未被应答，重建 PeerConnection 以接受对端 offer");
+            self.peer_connection =
+                Self::create_answerer_connection(codec, &payload_map, self.transport).map_err(|e| self.fail(e))?;
+            self.spawn_dscp_marking();
+            rebuilt = true;
+        }
+        let answer_sdp = Self::answer_remote_offer(&self.peer_connection, offer, &payload_map, self.transport.rtcp_mux)
+            .await
+            .map_err(|e| self.fail(e))?;
+        if rebuilt {
+            self.spawn_rtcp_relay().map_err(|e| self.fail(e))?;
+        }
+        self.mark_negotiated();
+
+        self.accepted_media = accepted_kinds(&answer_sdp);
+        self.negotiated_direction = media_direction(offer, "audio");
+        self.telephone_event = telephone_event
+            .filter(|_| find_rtpmap_payload_type(&answer_sdp, "audio", "telephone-event").is_some());
+        if codec != self.audio_codec {
+            info!("对端 offer 首选 {}，音频编解码器由 {} 切换", codec.name(), self.audio_codec.name());
+            self.audio_codec = codec;
+        }
+        Ok(self.sdp_attributes.apply(&answer_sdp))
+    }
+
+    /// 从对端 offer 中选出音频编解码器
+    ///
+    /// 按 rtpmap 的编码名称、时钟频率与声道数（无 rtpmap 时按静态载荷类型）匹配本地编解码器，
+    /// telephone-event 只接受 8000 Hz
+    ///
+    /// # 返回
+    /// 选中的编解码器（offer 中排在最前的共同编解码器）、answer 保留的 `(本地载荷类型, offer 载荷类型)`
+    /// 映射，以及 offer 中 telephone-event 的负载类型
+    #[allow(clippy::type_complexity)]
+    fn negotiate_offer(remote_offer: &str) -> Result<(AudioCodec, Vec<(u8, u8)>, Option<u8>), MediaPlayError> {
+        let mut payload_map: Vec<(u8, u8)> = Vec::new();
+        let mut codecs = Vec::new();
+        for pt in extract_payload_types(remote_offer, "audio") {
+            if let Some(codec) = AudioCodec::from_rtpmap(remote_offer, pt).filter(|codec| !codecs.contains(codec)) {
+                codecs.push(codec);
+                payload_map.push((codec.payload_type(), pt));
             }
+        }
+        // 回声发送使用 offer 中排在最前的共同编解码器
+        let Some(codec) = codecs.first().copied() else {
+            return Err(MediaPlayError::NoCommonCodec);
+        };
+
+        let telephone_event = telephone_event_payload_type(remote_offer);
+        if let Some(pt) = telephone_event {
+            payload_map.push((AudioCapability::telephone_event().payload_type, pt));
+        }
+        Ok((codec, payload_map, telephone_event))
+    }
+
+    /// 创建应答方使用的 PeerConnection（支持 PCMU/PCMA 及启用的 G.722）
+    ///
+    /// 发送轨道使用 `codec`，载荷类型取 `payload_map` 中 offer 为其分配的载荷类型
+    fn create_answerer_connection(
+        codec: AudioCodec,
+        payload_map: &[(u8, u8)],
+        transport: RtpTransportOptions,
+    ) -> Result<Arc<PeerConnection>, MediaPlayError> {
+        let config = Self::create_rtc_config(AudioCodec::answerable(), transport);
+        let pc = Arc::new(PeerConnection::new(config));
+
+        let mut params = Self::create_codec_params(MediaKind::Audio, codec);
+        if let Some((_, pt)) = payload_map.iter().find(|(local, _)| *local == codec.payload_type()) {
+            params.payload_type = *pt;
+        }
+        let (_sample_source, track, _) = rustrtc::media::sample_track(MediaKind::Audio, 100);
+        pc.add_track(track, params)
+            .map_err(|e| MediaPlayError::Rtp(format!("添加轨道失败: {}", e)))?;
+        Ok(pc)
+    }
+
+    /// 设置对端 offer 为远程描述，生成 answer 并设为本地描述
+    ///
+    /// answer 的音频段只保留 `payload_map` 中的编解码器，并沿用 offer 为其分配的载荷类型。
+    /// `rtcp_mux` 为假时 answer 不接受 rtcp-mux，改为通告独立的 RTCP 端口
+    async fn answer_remote_offer(
+        pc: &Arc<PeerConnection>,
+        remote_offer: &str,
+        payload_map: &[(u8, u8)],
+        rtcp_mux: bool,
+    ) -> Result<String, MediaPlayError> {
+        let offer = SessionDescription::parse(SdpType::Offer, &rtc_connection_lines(remote_offer))
+            .map_err(|e| MediaPlayError::Sdp(format!("解析远程SDP失败: {}", e)))?;
+        pc.set_remote_description(offer)
+            .await
+            .map_err(|e| MediaPlayError::Sdp(format!("设置远程描述失败: {}", e)))?;
+
+        let answer = pc.create_answer()
+            .await
+            .map_err(|e| MediaPlayError::Sdp(format!("创建answer失败: {}", e)))?;
+        let answer_sdp = normalize_address_types(&answer.to_sdp_string());
+        let local: Vec<u8> = payload_map.iter().map(|(local, _)| *local).collect();
+        let answer_sdp = restrict_payload_types(&answer_sdp, "audio", &local);
+        let mut answer_sdp = remap_payload_types(&answer_sdp, "audio", payload_map);
+        if !rtcp_mux {
+            answer_sdp = without_rtcp_mux(&answer_sdp);
+        }
+        let answer = SessionDescription::parse(SdpType::Answer, &answer_sdp)
+            .map_err(|e| MediaPlayError::Sdp(format!("解析本地answer失败: {}", e)))?;
+        pc.set_local_description(answer)
+            .map_err(|e| MediaPlayError::Sdp(format!("设置本地描述失败: {}", e)))?;
+
+        let pc_clone = pc.clone();
+        tokio::spawn(async move {
+            let _ = pc_clone.wait_for_gathering_complete().await;
+        });
+        Ok(answer_sdp)
+    }
+
+    fn create_codec_params(media_type: MediaKind, codec: AudioCodec) -> RtpCodecParameters {
+        match media_type {
+            MediaKind::Audio => RtpCodecParameters {
+                payload_type: codec.payload_type(),
+                clock_rate: codec.clock_rate(),
+                channels: codec.channels(),
+            },
+            MediaKind::Video => RtpCodecParameters {
+                payload_type: 96, // VP8
+                clock_rate: 90000,
+                channels: 0,
+            },
+        }
+    }
+    
+    /// 获取本地SDP（已注入额外属性）
+    pub fn get_local_sdp(&self) -> Result<String, MediaPlayError> {
+        // 从 PeerConnection 获取当前本地描述
+        let local_desc = self.peer_connection.local_description()
+            .ok_or_else(|| MediaPlayError::Sdp("本地描述未设置".to_string()))?;
             
-            // 设置为双向通信
-            transceiver.set_direction(rustrtc::TransceiverDirection::SendRecv);
-            
-            // 获取接收器
-            let receiver = transceiver.receiver();
-            let Some(receiver) = receiver else {
-                warn!("音频收发器缺少接收器");
-                continue;
-            };
-            
-            let incoming_track = receiver.track();
-            let (sample_source, outgoing_track, _) = rustrtc::media::sample_track(MediaKind::Audio, 100);
-            
-            // 创建发送器
-            let ssrc = 5000 + transceiver.id() as u32;
-            let sender = rustrtc::peer_connection::RtpSender::builder(outgoing_track, ssrc)
-                .stream_id("echo-stream".to_string())
-                .params(rustrtc::RtpCodecParameters {
-                    payload_type: 0, // PCMU
-                    clock_rate: 8000,
-                    channels: 1,
-                })
-                .build();
-                
-            // 订阅RTCP以处理PLI/FIR请求
-            let mut rtcp_rx = sender.subscribe_rtcp();
-            let incoming_track_clone = incoming_track.clone();
-            tokio::spawn(async move {
-                while let Ok(packet) = rtcp_rx.recv().await {
-                    match packet {
-                        rustrtc::rtp::RtcpPacket::PictureLossIndication(_)
-                        | rustrtc::rtp::RtcpPacket::FullIntraRequest(_) => {
-                            if let Err(e) = incoming_track_clone.request_key_frame().await {
-                                warn!("请求关键帧失败: {}", e);
-                            } else {
-                                info!("转发PLI/FIR到入站轨道");
-                            }
-                        }
-                        _ => {}
+        let mut sdp = normalize_address_types(&local_desc.to_sdp_string());
+        // 作为应答方时只在对端提供了 CN 的情况下才通告
+        let remote_cn = self.peer_connection.remote_description()
+            .is_none_or(|remote| offers_comfort_noise(&remote.to_sdp_string()));
+        if self.comfort_noise && remote_cn {
+            sdp = add_comfort_noise(&sdp);
+        }
+        Ok(self.apply_bandwidth(self.sdp_attributes.apply(&sdp)))
+    }
+
+    /// 设置需要注入到本地 SDP 的额外属性
+    ///
+    /// 仅影响对外发送的 SDP 文本，不影响 PeerConnection 内部协商
+    pub fn set_sdp_attributes(&mut self, attributes: SdpAttributes) {
+        self.sdp_attributes = attributes;
+    }
+
+    /// 在本地 SDP 的 `o=`/`c=` 行中通告公网 IP（如 `SipClient::public_ip()` 返回的 STUN 映射地址）
+    ///
+    /// 只改写地址，RTP 端口保持不变，依赖 NAT 保留端口映射
+    pub fn set_public_ip(&mut self, ip: Option<std::net::IpAddr>) {
+        self.sdp_attributes.set_connection_address(ip);
+    }
+    
+    /// 设置远程SDP并开始播放
+    pub async fn set_remote_sdp_and_play(
+        &mut self,
+        remote_sdp: &str,
+        mut media_player: Box<dyn MediaPlayer>,
+    ) -> Result<(), MediaPlayError> {
+        // 解析并设置远程SDP
+        crate::utils::validate_sdp(remote_sdp)
+            .map_err(|e| self.fail(MediaPlayError::Sdp(e.to_string())))?;
+        self.apply_answer(remote_sdp).await?;
+        
+        // 对端拒绝了该媒体流时不发送
+        if !self.accepted_media.contains(&media_player.media_kind()) {
+            warn!("对端拒绝了 {:?} 媒体流，跳过播放", media_player.media_kind());
+            return Ok(());
+        }
+
+        // 开始播放媒体
+        self.set_state(MediaSessionState::Streaming);
+        media_player.play_to_remote(self.peer_connection.clone()).await.map_err(|e| self.fail(e))?;
+        self.finish_playback();
+        
+        Ok(())
+    }
+    
+    /// 设置接收路径的 SSRC 选择策略（需在启动回声前设置）
+    pub fn set_ssrc_selection(&mut self, selection: SsrcSelection) {
+        self.ssrc_selection = selection;
+    }
+
+    /// 获取 RFC 4733 按键事件通道，只能获取一次，再次调用返回 `None`
+    ///
+    /// 协商了 `telephone-event` 时，回声期间收到的按键在结束包到达后写入该通道，
+    /// 重发的结束包不会重复上报
+    pub fn dtmf_events(&mut self) -> Option<UnboundedReceiver<char>> {
+        self.dtmf_rx.take()
+    }
+
+    /// 按键通道的输入端
+    ///
+    /// SIP INFO 等带外 DTMF 来源写入后，与 RFC 4733 按键一起从 [`dtmf_events`](Self::dtmf_events) 读出
+    pub fn dtmf_sender(&self) -> UnboundedSender<char> {
+        self.dtmf_tx.clone()
+    }
+
+    /// 借出按键通道用于收集按键，未运行回声且已协商 `telephone-event` 时临时启动按键检测
+    ///
+    /// 收集结束后须以 [`return_dtmf_events`](Self::return_dtmf_events) 归还
+    pub(crate) fn lend_dtmf_events(&mut self) -> Result<(UnboundedReceiver<char>, CancellationToken), MediaPlayError> {
+        let Some(mut rx) = self.dtmf_rx.take() else {
+            return Err(MediaPlayError::Rtp("按键通道已被 dtmf_events() 取走".to_string()));
+        };
+        // 丢弃收集开始前残留的按键
+        while rx.try_recv().is_ok() {}
+        let listener = CancellationToken::new();
+        if let (Some(telephone_event), false) = (self.telephone_event, self.is_active) {
+            self.spawn_dtmf_listener(telephone_event, listener.clone());
+        }
+        Ok((rx, listener))
+    }
+
+    /// 归还借出的按键通道并停止临时的按键检测
+    pub(crate) fn return_dtmf_events(&mut self, rx: UnboundedReceiver<char>, listener: CancellationToken) {
+        listener.cancel();
+        self.dtmf_rx = Some(rx);
+    }
+
+    /// 订阅会话中收到的 RTCP 反馈，可用于按拥塞信号调整码率
+    ///
+    /// 只包含 rustrtc 转交给本播放器发送器（文件播放与回声）的反馈：目前为指向本地发送
+    /// SSRC 的 PLI 与通用 NACK。FIR 与 REMB 由 rustrtc 在内部处理、不会转交发送器，
+    /// 对应事件保留给后续版本；SR/RR 报告体现在 [`RtpPlayer::stats`] 中
+    pub fn rtcp_events(&self) -> broadcast::Receiver<RtcpEvent> {
+        self.rtcp_events.subscribe()
+    }
+
+    /// 当前 RTP 收发统计快照，只复制计数，可在通话期间频繁轮询
+    pub fn stats(&self) -> RtpStats {
+        *self.stats.lock().unwrap()
+    }
+
+    /// 已协商的 `telephone-event` 载荷类型
+    pub fn telephone_event_payload_type(&self) -> Option<u8> {
+        self.telephone_event
+    }
+
+    /// 播放提示音并收集 RFC 4733 按键（IVR 基本操作）
+    ///
+    /// 提示音以当前编解码器、增益与格式转换设置在后台播放，收到首个按键时立即停止（barge-in）。
+    /// 提示音播完（或被打断）后 `timeout` 作为等待首个按键以及两次按键之间的超时；达到 `max_digits`
+    /// （为 0 时不限制）、按下结束键（不计入结果）或超时时返回已收集的按键。
+    /// 回声运行时复用回声循环的按键检测，否则在收集期间临时接收对端音频做按键检测。
+    /// 需已协商 `telephone-event`，且按键通道未被 [`dtmf_events`](Self::dtmf_events) 取走
+    pub async fn play_and_collect(
+        &mut self,
+        prompt: &str,
+        max_digits: u8,
+        timeout: Duration,
+        terminator: Option<char>,
+    ) -> Result<String, MediaPlayError> {
+        if self.telephone_event.is_none() {
+            return Err(MediaPlayError::Sdp("对端未协商 telephone-event，无法收集按键".to_string()));
+        }
+        let mut prompt_player = RtpPlayer::new_with_codec(MediaKind::Audio, self.audio_codec)
+            .await?
+            .with_media_file(prompt)
+            .with_gain_db(self.gain_db)
+            .with_auto_convert(self.auto_convert);
+        // 提示音的发送计入本会话的统计
+        prompt_player.stats = self.stats.clone();
+        prompt_player.metrics = self.metrics.clone();
+
+        let (mut rx, listener) = self.lend_dtmf_events()?;
+        let playback = Box::new(prompt_player).spawn_playback(self.peer_connection.clone());
+        let result = collect_with_barge_in(&mut rx, playback, max_digits.into(), timeout, terminator).await;
+        self.return_dtmf_events(rx, listener);
+
+        let collected = result?;
+        info!("DTMF 收集结束: {:?} ({:?})", collected.digits, collected.reason);
+        Ok(collected.digits)
+    }
+
+    /// 在未运行回声时接收对端音频，只检测 RFC 4733 按键并写入按键通道，`cancel` 后停止
+    fn spawn_dtmf_listener(&self, telephone_event: u8, cancel: CancellationToken) {
+        let Some(receiver) = self
+            .peer_connection
+            .get_transceivers()
+            .into_iter()
+            .find(|t| t.kind() == rustrtc::MediaKind::Audio)
+            .and_then(|t| t.receiver())
+        else {
+            warn!("音频收发器缺少接收器，无法检测按键");
+            return;
+        };
+        let mut track = JitteredTrack::new(receiver.track(), self.jitter_buffer);
+        let dtmf_tx = self.dtmf_tx.clone();
+        let stats = self.stats.clone();
+        let metrics = self.metrics.clone();
+        let mut detector = TelephoneEventDetector::new();
+        let listener = async move {
+            loop {
+                let sample = tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    sample = track.recv() => sample,
+                };
+                let Ok(MediaSample::Audio(frame)) = sample else {
+                    if sample.is_err() {
+                        break;
                     }
+                    continue;
+                };
+                stats.lock().unwrap().record_received(frame.data.len());
+                if let Some(metrics) = &metrics {
+                    metrics.record_rtp_received();
                 }
-            });
-            
+                if frame.payload_type != Some(telephone_event) {
+                    continue;
+                }
+                if let Some(digit) = detector.process(frame.rtp_timestamp, &frame.data) {
+                    info!("收到 RFC 4733 按键: {}", digit);
+                    let _ = dtmf_tx.send(digit);
+                }
+            }
+        };
+        tokio::spawn(listener.in_current_span());
+    }
+
+    /// 从媒体文件的 `offset` 位置开始播放，偏移向下取整到 20 ms 帧边界
+    ///
+    /// 偏移超出文件时长时返回 [`MediaPlayError::SeekOutOfRange`]；RTP 时间戳仍从 0 开始，
+    /// 循环播放时第二遍起从文件开头播放
+    pub async fn play_from(
+        &mut self,
+        offset: Duration,
+        peer_connection: Arc<PeerConnection>,
+    ) -> Result<(), MediaPlayError> {
+        self.set_state(MediaSessionState::Streaming);
+        self.stream_file(offset, peer_connection).await.map_err(|e| self.fail(e))?;
+        self.finish_playback();
+        Ok(())
+    }
+
+    async fn stream_file(
+        &mut self,
+        offset: Duration,
+        peer_connection: Arc<PeerConnection>,
+    ) -> Result<(), MediaPlayError> {
+        let Some(path) = self.media_file.clone() else {
+            return Err(MediaPlayError::Sdp("RtpPlayer不支持此操作".to_string()));
+        };
+        let frames = load_wav_frames(&path, self.audio_codec, self.gain_db, self.auto_convert)?;
+        if frames.is_empty() {
+            warn!("媒体文件 {} 不包含音频数据", path.display());
+            return Ok(());
+        }
+        let skip = seek_frame_index(offset, frames.len())?;
+
+        let mut sender =
+            AudioFrameSender::attach(
+                &peer_connection,
+                self.audio_codec,
+                7000,
+                "file-stream",
+                self.stats.clone(),
+                Some(self.rtcp_events.clone()),
+            )?
+                .with_pause(self.playback.clone(), self.pause_silence)
+                .with_metrics(self.metrics.clone());
+        if self.comfort_noise {
+            sender = sender.with_comfort_noise(negotiated_comfort_noise(&peer_connection));
+        }
+        let mut iteration = 0u32;
+        while self.loop_count.is_none_or(|count| iteration < count) {
+            let start = if iteration == 0 { skip } else { 0 };
+            if !sender.send_all(&frames[start..], &self.cancel).await? {
+                info!("媒体文件播放被取消");
+                break;
+            }
+            iteration += 1;
+        }
+        Ok(())
+    }
+
+    /// 暂停文件播放，恢复后从暂停位置继续
+    pub fn pause(&self) {
+        self.playback.pause();
+    }
+
+    /// 恢复文件播放
+    pub fn resume(&self) {
+        self.playback.resume();
+    }
+
+    /// 文件播放是否处于暂停状态
+    pub fn is_paused(&self) -> bool {
+        self.playback.is_paused()
+    }
+
+    /// 暂停控制句柄，用于在播放期间从其他任务暂停/恢复
+    pub fn playback_control(&self) -> PlaybackControl {
+        self.playback.clone()
+    }
+
+    /// 设置暂停期间是否继续发送静音帧，默认停止发送 RTP
+    ///
+    /// 两种方式下 RTP 时间戳都按实际经过的时间递增
+    pub fn with_pause_silence(mut self, enabled: bool) -> Self {
+        self.pause_silence = enabled;
+        self
+    }
+
+    /// 启用舒适噪声（RFC 3389），暂停期间周期性发送 CN 包而不是让 RTP 流中断
+    ///
+    /// 本地 SDP 会通告 `CN/8000`（载荷类型 13）；对端未协商 CN 时改为周期性发送
+    /// 静音帧。恢复播放后的第一帧设置 marker 位，时间戳在静默期间持续推进。
+    /// 优先于 [`with_pause_silence`](Self::with_pause_silence)
+    pub fn with_comfort_noise(mut self, enabled: bool) -> Self {
+        self.comfort_noise = enabled;
+        self
+    }
+
+    /// 设置接收路径的抖动缓冲深度（毫秒），超出 20 ~ 500 的值会被限制到边界
+    ///
+    /// 缓冲越深，高延迟或抖动大的链路上断续越少，但回声与按键检测的延迟也随之增加
+    /// （每个包至少延迟 `ms`，缺失的包最多等待两倍深度）。默认不缓冲，收到即处理
+    pub fn with_jitter_buffer(mut self, ms: u32) -> Self {
+        let clamped = ms.clamp(MIN_JITTER_BUFFER_MS, MAX_JITTER_BUFFER_MS);
+        if clamped != ms {
+            warn!("抖动缓冲 {} ms 超出范围，已限制为 {} ms", ms, clamped);
+        }
+        self.jitter_buffer = Some(Duration::from_millis(clamped.into()));
+        self
+    }
+
+    /// 接收路径的抖动缓冲深度，`None` 表示不缓冲
+    pub fn jitter_buffer(&self) -> Option<Duration> {
+        self.jitter_buffer
+    }
+
+    /// 设置本路通话的带宽上限（kbps），超出 8 ~ 20000 的值会被限制到边界
+    ///
+    /// 对外发送的 SDP（[`RtpPlayer::get_local_sdp`]、[`RtpPlayer::create_reoffer`]）在每个
+    /// 媒体段通告 `b=AS:<kbps>`；视频回声按该码率定速转发。默认不通告带宽
+    pub fn with_bandwidth(mut self, kbps: u32) -> Self {
+        let clamped = kbps.clamp(MIN_BANDWIDTH_KBPS, MAX_BANDWIDTH_KBPS);
+        if clamped != kbps {
+            warn!("带宽 {} kbps 超出范围，已限制为 {} kbps", kbps, clamped);
+        }
+        self.bandwidth_kbps = Some(clamped);
+        self
+    }
+
+    /// 带宽上限（kbps），`None` 表示不限制
+    pub fn bandwidth(&self) -> Option<u32> {
+        self.bandwidth_kbps
+    }
+
+    /// 按带宽上限补充 `b=AS` 行
+    fn apply_bandwidth(&self, sdp: String) -> String {
+        match self.bandwidth_kbps {
+            Some(kbps) => with_bandwidth_line(&sdp, kbps),
+            None => sdp,
+        }
+    }
+
+    /// 将 RTP 收发包数同时计入指标注册表（如 `SipClient::metrics_handle()`）
+    pub fn with_metrics(mut self, metrics: MetricsHandle) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// 为 RTP 套接字设置 DSCP 标记（如 [`DSCP_EF`](crate::utils::DSCP_EF)）
+    ///
+    /// ICE 选定媒体套接字后生效，重建 PeerConnection 时重新应用；
+    /// 无权限或平台不支持时只记录警告，报文按默认优先级发送
+    pub fn with_dscp(mut self, dscp: u8) -> Self {
+        self.dscp = Some(dscp);
+        self.spawn_dscp_marking();
+        self
+    }
+
+    /// 等待 ICE 选定套接字后为其设置 DSCP，PeerConnection 释放后任务结束
+    fn spawn_dscp_marking(&self) {
+        let Some(dscp) = self.dscp else {
+            return;
+        };
+        let mut selected = self.peer_connection.ice_transport().subscribe_selected_socket();
+        tokio::spawn(async move {
+            loop {
+                if let Some(IceSocketWrapper::Udp(socket)) = selected.borrow_and_update().as_ref() {
+                    let ipv6 = socket.local_addr().is_ok_and(|addr| addr.is_ipv6());
+                    crate::utils::mark_dscp(SockRef::from(socket.as_ref()), ipv6, dscp, "RTP 套接字");
+                }
+                if selected.changed().await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// 关闭 rtcp-mux 时在 RTP 端口 + 1 上收发 RTCP
+    ///
+    /// 收到的 RTCP 转发到 RTP 套接字交给 PeerConnection 处理；只转发 RTCP 包，STUN 等其他数据丢弃，
+    /// 使转发的包（源地址为本端 RTCP 端口）不会触发对称 RTP 的地址切换。rustrtc 不生成周期性报告，
+    /// 这里从 RTCP 端口向对端 `a=rtcp` 地址（缺省为 RTP 端口 + 1）发送 RR 与 SDES；开启对称 RTP 时
+    /// 改为发往对端 RTCP 的实际源地址。PeerConnection 被替换或释放后转发任务随之结束
+    fn spawn_rtcp_relay(&self) -> Result<(), MediaPlayError> {
+        if self.transport.rtcp_mux {
+            return Ok(());
+        }
+        let Some(local) = self.peer_connection.local_description() else {
+            return Ok(());
+        };
+        let ip: std::net::IpAddr = local.session.origin.unicast_address.parse()
+            .map_err(|e| MediaPlayError::Sdp(format!("无效的本地地址: {}", e)))?;
+        let port = local.media_sections.first().map_or(0, |section| section.port);
+        let Some(rtcp_port) = port.checked_add(1).filter(|_| port != 0) else {
+            return Ok(());
+        };
+        let rtp_addr = SocketAddr::new(ip, port);
+
+        let socket = std::net::UdpSocket::bind(SocketAddr::new(ip, rtcp_port))?;
+        socket.set_nonblocking(true)?;
+        let socket = tokio::net::UdpSocket::from_std(socket)?;
+        info!("rtcp-mux 已关闭，在 {}:{} 收发 RTCP", ip, rtcp_port);
+
+        let report = self.peer_connection.get_transceivers().iter()
+            .find_map(|transceiver| transceiver.sender())
+            .and_then(|sender| receiver_report(sender.ssrc(), sender.cname()));
+        let latching = self.transport.symmetric_rtp;
+        let mut peer_rx = self.rtcp_peer.subscribe();
+        let mut selected = self.peer_connection.ice_transport().subscribe_selected_socket();
+        let relay = async move {
+            let mut buf = vec![0u8; 1500];
+            let mut peer = *peer_rx.borrow_and_update();
+            let mut latched = None;
+            let mut reports = tokio::time::interval(RTCP_REPORT_INTERVAL);
+            loop {
+                let send_report = tokio::select! {
+                    received = socket.recv_from(&mut buf) => match received {
+                        Ok((len, from)) => {
+                            if !rustrtc::rtp::is_rtcp(&buf[..len]) {
+                                continue;
+                            }
+                            if latching && latched != Some(from) {
+                                info!("对称 RTP: RTCP 发往 {}", from);
+                                latched = Some(from);
+                            }
+                            if let Err(e) = socket.send_to(&buf[..len], rtp_addr).await {
+                                warn!("转发 RTCP 失败: {}", e);
+                            }
+                            false
+                        }
+                        Err(e) => {
+                            warn!("RTCP 套接字接收失败: {}", e);
+                            break;
+                        }
+                    },
+                    _ = reports.tick() => true,
+                    changed = peer_rx.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                        let changed = *peer_rx.borrow_and_update() != peer;
+                        peer = *peer_rx.borrow();
+                        changed
+                    }
+                    changed = selected.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                        false
+                    }
+                };
+                if let (true, Some(report), Some(dest)) = (send_report, &report, latched.or(peer)) {
+                    if let Err(e) = socket.send_to(report, dest).await {
+                        warn!("发送 RTCP 报告到 {} 失败: {}", dest, e);
+                    }
+                }
+            }
+        };
+        tokio::spawn(relay.in_current_span());
+        Ok(())
+    }
+
+    /// 设置 `play_to_remote` 播放的媒体文件（8 kHz 单声道 WAV）
+    pub fn with_media_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.media_file = Some(path.into());
+        self
+    }
+
+    /// 设置媒体文件的播放次数，`None` 表示无限循环，默认播放一次
+    ///
+    /// 循环时 RTP 时间戳连续递增，不会回绕到文件开头的时间戳
+    pub fn with_loop(mut self, count: Option<u32>) -> Self {
+        self.loop_count = count;
+        self
+    }
+
+    /// 设置播放与回声转发音频的增益（dB），超出 -30 ~ +30 的值会被限制到边界
+    ///
+    /// 放大后超出 16 位范围的样本削波处理；回声路径只对 G.711 载荷生效
+    pub fn with_gain_db(mut self, gain_db: f32) -> Self {
+        let clamped = clamp_gain_db(gain_db);
+        if clamped != gain_db {
+            warn!("增益 {} dB 超出范围，已限制为 {} dB", gain_db, clamped);
+        }
+        self.gain_db = clamped;
+        self
+    }
+
+    /// 当前增益（dB）
+    pub fn gain_db(&self) -> f32 {
+        self.gain_db
+    }
+
+    /// 采样率或声道数与编解码器不符的 WAV 在播放前自动混合为单声道并重采样
+    ///
+    /// 关闭（默认）时此类文件返回 `MediaPlayError::UnsupportedFormat`
+    pub fn with_auto_convert(mut self, enabled: bool) -> Self {
+        self.auto_convert = enabled;
+        self
+    }
+
+    /// 启用回声路径的语音活动检测（需在启动回声前设置）
+    ///
+    /// 电平低于 `threshold_dbfs`（如 -45.0）的 G.711 帧不转发，语音结束后保留
+    /// [`VAD_HANGOVER_FRAMES`](crate::codec::VAD_HANGOVER_FRAMES) 帧挂起窗口；录音不受影响
+    pub fn with_vad(mut self, threshold_dbfs: f32) -> Self {
+        self.vad = Some(VoiceActivityDetector::new(threshold_dbfs));
+        self
+    }
+
+    /// 设置回声期间对端音频的录音文件（需在启动回声前设置）
+    ///
+    /// 收到的 G.711 音频解码为 8 kHz 单声道 PCM16 写入 WAV，停止回声时关闭文件
+    pub fn set_record_path(&mut self, path: Option<PathBuf>) {
+        self.record_path = path;
+    }
+
+    /// 获取底层 PeerConnection
+    pub fn peer_connection(&self) -> Arc<PeerConnection> {
+        self.peer_connection.clone()
+    }
+
+    /// 基于当前收发器生成新的 offer（用于 re-INVITE）
+    ///
+    /// 沿用上一次本地描述的 `o=` 会话 ID；描述内容未变化时版本号不变（如会话刷新），
+    /// 变化时版本号加一（RFC 3264 §8）
+    pub async fn create_reoffer(&self) -> Result<String, MediaPlayError> {
+        let mut offer = self.peer_connection.create_offer()
+            .await
+            .map_err(|e| MediaPlayError::Sdp(format!("创建offer失败: {}", e)))?;
+        if let Some(previous) = self.peer_connection.local_description() {
+            carry_origin(&previous, &mut offer);
+        }
+        let sdp = normalize_address_types(&offer.to_sdp_string());
+        let sdp = self.apply_bandwidth(self.sdp_attributes.apply(&sdp));
+        self.peer_connection.set_local_description(offer)
+            .map_err(|e| MediaPlayError::Sdp(format!("设置本地描述失败: {}", e)))?;
+        // 媒体进行中的重协商不打断 Streaming 状态
+        if self.state() != MediaSessionState::Streaming {
+            self.set_state(MediaSessionState::Offering);
+        }
+        Ok(sdp)
+    }
+
+    /// 添加视频轨道并生成包含视频 m 行的新 offer
+    ///
+    /// 之前移除过视频时复用已停用的视频收发器（沿用其 m 行），反复升级/降级不会累积收发器
+    pub async fn add_video_track(&mut self) -> Result<String, MediaPlayError> {
+        let (_sample_source, track, _) = rustrtc::media::sample_track(MediaKind::Video, 100);
+        let params = Self::create_codec_params(MediaKind::Video, self.audio_codec);
+        let existing = self
+            .peer_connection
+            .get_transceivers()
+            .into_iter()
+            .find(|t| t.kind() == rustrtc::MediaKind::Video);
+        match existing {
+            Some(transceiver) => {
+                let ssrc = transceiver.sender_ssrc().unwrap_or(6000 + transceiver.id() as u32);
+                let sender = rustrtc::peer_connection::RtpSender::builder(track, ssrc)
+                    .stream_id(format!("video-{}", transceiver.id()))
+                    .params(params)
+                    .build();
+                transceiver.set_sender(Some(sender));
+                transceiver.set_direction(rustrtc::TransceiverDirection::SendRecv);
+            }
+            None => {
+                self.peer_connection
+                    .add_track(track, params)
+                    .map_err(|e| MediaPlayError::Rtp(format!("添加视频轨道失败: {}", e)))?;
+            }
+        }
+        self.create_reoffer().await
+    }
+
+    /// 停用视频轨道并生成新的 offer
+    pub async fn remove_video_track(&mut self) -> Result<String, MediaPlayError> {
+        self.deactivate_kind(rustrtc::MediaKind::Video);
+        self.create_reoffer().await
+    }
+
+    /// 应用对端对 re-INVITE 的 SDP answer
+    pub async fn apply_answer(&mut self, remote_sdp: &str) -> Result<(), MediaPlayError> {
+        self.negotiate_answer(remote_sdp).await.map_err(|e| self.fail(e))?;
+        self.mark_negotiated();
+        Ok(())
+    }
+
+    async fn negotiate_answer(&mut self, remote_sdp: &str) -> Result<(), MediaPlayError> {
+        let answer = SessionDescription::parse(SdpType::Answer, &rtc_connection_lines(remote_sdp))
+            .map_err(|e| MediaPlayError::Sdp(format!("解析远程SDP失败: {}", e)))?;
+        let local_ip = self.peer_connection.local_description()
+            .and_then(|local| sdp_connection_ip(&local.to_sdp_string()));
+        if let (Some(local_ip), Some(remote_ip)) = (local_ip, sdp_connection_ip(remote_sdp)) {
+            if local_ip.is_ipv6() != remote_ip.is_ipv6() {
+                warn!("本地 RTP 地址 {} 与对端媒体地址 {} 地址族不同，媒体可能无法互通", local_ip, remote_ip);
+            }
+        }
+        self.peer_connection.set_remote_description(answer)
+            .await
+            .map_err(|e| MediaPlayError::Sdp(format!("设置远程描述失败: {}", e)))?;
+        self.check_answer_codecs(remote_sdp)?;
+        self.apply_stream_states(remote_sdp);
+        Ok(())
+    }
+
+    /// 应用早期媒体（183 Session Progress）中的 SDP answer，之后即可开始收放媒体
+    ///
+    /// 再次收到不同的早期 SDP 时按新 SDP 重新协商
+    pub async fn apply_early_answer(&mut self, remote_sdp: &str) -> Result<(), MediaPlayError> {
+        self.supersede_answer(remote_sdp).await?;
+        info!("已应用早期媒体 SDP");
+        self.early_answer = Some(remote_sdp.to_string());
+        Ok(())
+    }
+
+    /// 应用 200 OK 中的最终 SDP answer
+    ///
+    /// 与早期媒体 SDP 相同（`o=` 行一致）时保持现有会话，不同时以最终 SDP 为准重新协商；
+    /// 没有早期媒体时等同于首次应用 answer
+    pub async fn apply_final_answer(&mut self, remote_sdp: &str) -> Result<(), MediaPlayError> {
+        if let Some(early) = self.early_answer.as_deref() {
+            if same_session(early, remote_sdp) {
+                self.early_answer = None;
+                return Ok(());
+            }
+            info!("200 OK 的 SDP 与早期媒体不同，以最终 SDP 为准");
+        }
+        self.supersede_answer(remote_sdp).await?;
+        self.early_answer = None;
+        Ok(())
+    }
+
+    /// 当前是否处于早期媒体阶段
+    pub fn is_early_media(&self) -> bool {
+        self.early_answer.is_some()
+    }
+
+    /// 应用 answer；已有远程描述时先生成新的本地 offer 回到 have-local-offer 状态
+    async fn supersede_answer(&mut self, remote_sdp: &str) -> Result<(), MediaPlayError> {
+        if self.peer_connection.remote_description().is_some() {
+            self.create_reoffer().await?;
+        }
+        self.apply_answer(remote_sdp).await
+    }
+
+    /// 按当前本地 offer 校验对端 answer 的音频编解码器
+    fn check_answer_codecs(&self, answer: &str) -> Result<(), MediaPlayError> {
+        let Some(local) = self.peer_connection.local_description() else {
+            return Ok(());
+        };
+        check_negotiated_codecs(&local.to_sdp_string(), answer).inspect_err(|e| {
+            warn!("对端 answer 的音频编解码器不可用: {}", e);
+        })
+    }
+
+    /// 将未被拒绝的媒体流设置为指定方向，并生成新的 offer
+    ///
+    /// 沿用当前 PeerConnection 的本地描述，m 行端口保持不变；用于保持/恢复通话
+    pub async fn create_direction_offer(
+        &mut self,
+        direction: MediaDirection,
+    ) -> Result<String, MediaPlayError> {
+        self.set_transceiver_direction(direction);
+        self.local_direction = direction;
+        self.create_reoffer().await
+    }
+
+    /// 方向 offer 被拒绝时恢复之前的方向与远程描述
+    pub async fn restore_direction(
+        &mut self,
+        previous: MediaDirection,
+    ) -> Result<(), MediaPlayError> {
+        self.set_transceiver_direction(previous);
+        self.local_direction = previous;
+        let Some(remote) = self.peer_connection.remote_description() else {
+            return Ok(());
+        };
+        self.peer_connection.set_remote_description(remote)
+            .await
+            .map_err(|e| MediaPlayError::Sdp(format!("恢复远程描述失败: {}", e)))
+    }
+
+    /// 本端最近一次 offer 声明的方向
+    pub fn local_direction(&self) -> MediaDirection {
+        self.local_direction
+    }
+
+    /// 对端在最近一次 answer 中声明的音频方向
+    pub fn negotiated_direction(&self) -> MediaDirection {
+        self.negotiated_direction
+    }
+
+    fn set_transceiver_direction(&self, direction: MediaDirection) {
+        let direction = match direction {
+            MediaDirection::SendRecv => rustrtc::TransceiverDirection::SendRecv,
+            MediaDirection::SendOnly => rustrtc::TransceiverDirection::SendOnly,
+            MediaDirection::RecvOnly => rustrtc::TransceiverDirection::RecvOnly,
+            MediaDirection::Inactive => rustrtc::TransceiverDirection::Inactive,
+        };
+        for transceiver in self.peer_connection.get_transceivers() {
+            // 跳过已被拒绝（停用且无发送器）的媒体流
+            if transceiver.direction() == rustrtc::TransceiverDirection::Inactive
+                && transceiver.sender().is_none()
+            {
+                continue;
+            }
+            transceiver.set_direction(direction);
+        }
+    }
+
+    /// 对端在最近一次 answer 中接受的媒体流
+    pub fn accepted_media(&self) -> &[MediaKind] {
+        &self.accepted_media
+    }
+
+    /// 记录 answer 接受的媒体流，并停用被拒绝（端口为 0）的轨道
+    fn apply_stream_states(&mut self, remote_sdp: &str) {
+        for (media, accepted) in media_stream_states(remote_sdp) {
+            if accepted {
+                continue;
+            }
+            let kind = match media.as_str() {
+                "audio" => rustrtc::MediaKind::Audio,
+                "video" => rustrtc::MediaKind::Video,
+                _ => continue,
+            };
+            info!("对端拒绝了 {} 媒体流，停用对应轨道", media);
+            self.deactivate_kind(kind);
+        }
+        self.accepted_media = accepted_kinds(remote_sdp);
+        self.negotiated_direction = media_direction(remote_sdp, "audio");
+        self.telephone_event = telephone_event_payload_type(remote_sdp);
+
+        // 对端 answer 未选用本地首选编解码器时回退到其选中的编解码器
+        let negotiated = extract_payload_types(remote_sdp, "audio")
+            .into_iter()
+            .find_map(|pt| AudioCodec::from_rtpmap(remote_sdp, pt));
+        if let Some(codec) = negotiated.filter(|codec| *codec != self.audio_codec) {
+            info!("对端选用 {}，音频编解码器由 {} 回退", codec.name(), self.audio_codec.name());
+            self.audio_codec = codec;
+        }
+    }
+
+    /// 对端拒绝 re-INVITE 时恢复到上一次协商结果
+    ///
+    /// rustrtc 不支持 rollback，这里重新应用上一次的远端 answer 回到 stable 状态
+    pub async fn rollback_offer(&mut self, kind: rustrtc::MediaKind) -> Result<(), MediaPlayError> {
+        self.deactivate_kind(kind);
+        let previous = self.peer_connection.remote_description()
+            .ok_or_else(|| MediaPlayError::Sdp("远程描述未设置".to_string()))?;
+        self.peer_connection.set_remote_description(previous)
+            .await
+            .map_err(|e| MediaPlayError::Sdp(format!("恢复远程描述失败: {}", e)))
+    }
+
+    fn deactivate_kind(&self, kind: rustrtc::MediaKind) {
+        for transceiver in self.peer_connection.get_transceivers() {
+            if transceiver.kind() == kind {
+                transceiver.set_direction(rustrtc::TransceiverDirection::Inactive);
+                transceiver.set_sender(None);
+            }
+        }
+    }
+
+    /// 启动音频回声
+    pub async fn start_audio_echo(&mut self) -> Result<(), MediaPlayError> {
+        info!("启动音频回声功能");
+        
+        // 检查是否已经运行
+        if self.running.is_some() {
+            warn!("音频回声处理器已在运行");
+            return Ok(());
+        }
+        
+        if let Some(path) = self.record_path.clone() {
+            let writer = WavWriter::create(&path, self.audio_codec.clock_rate())
+                .map_err(|e| self.fail(e.into()))?;
+            info!("录制对端音频到 {}", path.display());
+            *self.recorder.lock().unwrap() = Some(writer);
+        }
+
+        // 创建运行标志
+        let running = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        self.running = Some(running.clone());
+        
+        let transceivers = self.peer_connection.get_transceivers();
+        
+        for transceiver in transceivers {
+            if transceiver.kind() != rustrtc::MediaKind::Audio {
+                continue;
+            }
+            
+            // 设置为双向通信
+            transceiver.set_direction(rustrtc::TransceiverDirection::SendRecv);
+            
+            // 获取接收器
+            let receiver = transceiver.receiver();
+            let Some(receiver) = receiver else {
+                warn!("音频收发器缺少接收器");
+                continue;
+            };
+            
+            let incoming_track = receiver.track();
+            let (sample_source, outgoing_track, _) = rustrtc::media::sample_track(MediaKind::Audio, 100);
+            
+            // 创建发送器
+            let ssrc = 5000 + transceiver.id() as u32;
+            let params = send_codec_params(&self.peer_connection, self.audio_codec);
+            let wire_pt = params.payload_type;
+            let sender = rustrtc::peer_connection::RtpSender::builder(outgoing_track, ssrc)
+                .stream_id("echo-stream".to_string())
+                .params(params)
+                .build();
+                
+            // 订阅RTCP以处理PLI/FIR请求并累计 SR/RR 统计
+            let mut rtcp_rx = sender.subscribe_rtcp();
+            let incoming_track_clone = incoming_track.clone();
+            let rtcp_stats = self.stats.clone();
+            let rtcp_running = running.clone();
+            let rtcp_events = self.rtcp_events.clone();
+            let rtcp_task = async move {
+                while let Ok(packet) = rtcp_rx.recv().await {
+                    if !rtcp_running.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    rtcp_stats.lock().unwrap().apply_rtcp(&packet, ssrc);
+                    publish_rtcp_event(&rtcp_events, &packet);
+                    match packet {
+                        rustrtc::rtp::RtcpPacket::PictureLossIndication(_)
+                        | rustrtc::rtp::RtcpPacket::FullIntraRequest(_) => {
+                            if let Err(e) = incoming_track_clone.request_key_frame().await {
+                                warn!("请求关键帧失败: {}", e);
+                            } else {
+                                info!("转发PLI/FIR到入站轨道");
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            };
+            self.echo_tasks.push(tokio::spawn(rtcp_task.in_current_span()));
+            
             transceiver.set_sender(Some(sender));
             
             // 启动回声循环
             let _pc_clone = self.peer_connection.clone();
-            tokio::spawn(async move {
+            let mut ssrc_filter = SsrcFilter::new(self.ssrc_selection);
+            let codec = self.audio_codec;
+            let recorder = self.recorder.clone();
+            let gain_db = self.gain_db;
+            let mut vad = self.vad.clone();
+            let telephone_event = self.telephone_event;
+            let dtmf_tx = self.dtmf_tx.clone();
+            let mut dtmf_detector = TelephoneEventDetector::new();
+            let stats = self.stats.clone();
+            let metrics = self.metrics.clone();
+            let mut incoming = JitteredTrack::new(incoming_track, self.jitter_buffer);
+            let running = running.clone();
+            let echo_loop = async move {
                 info!("音频回声循环已启动");
                 
-                loop {
-                    match incoming_track.recv().await {
+                while running.load(Ordering::Relaxed) {
+                    match incoming.recv().await {
                         Ok(sample) => {
+                            if !ssrc_filter.accept(sample_ssrc(&sample)) {
+                                continue;
+                            }
+
+                            if let MediaSample::Audio(frame) = &sample {
+                                stats.lock().unwrap().record_received(frame.data.len());
+                                if let Some(metrics) = &metrics {
+                                    metrics.record_rtp_received();
+                                }
+                            }
+
+                            // 电话事件包只用于按键检测，不回送也不录音
+                            if let MediaSample::Audio(frame) = &sample {
+                                if telephone_event.is_some() && frame.payload_type == telephone_event {
+                                    if let Some(digit) = dtmf_detector.process(frame.rtp_timestamp, &frame.data) {
+                                        info!("收到 RFC 4733 按键: {}", digit);
+                                        let _ = dtmf_tx.send(digit);
+                                    }
+                                    continue;
+                                }
+                            }
+
+                            let sample = with_static_payload_type(sample, wire_pt, codec);
+                            let sample = comfort_noise_to_silence(sample, codec);
+
                             // 检查样本是否为空
                             let is_empty = match &sample {
                                 MediaSample::Audio(f) => f.data.is_empty(),
@@ -296,12 +2437,31 @@ impl RtpPlayer {
                             if is_empty {
                                 continue;
                             }
+
+                            if let MediaSample::Audio(frame) = &sample {
+                                record_frame(&recorder, frame, codec);
+                                // 静音与底噪帧不回送
+                                if let Some(vad) = vad.as_mut() {
+                                    if !vad_accepts(vad, frame, codec) {
+                                        continue;
+                                    }
+                                }
+                            }
+                            let sample = amplify_sample(sample, gain_db, codec);
                             
                             // 直接转发收到的音频样本
+                            let sent_bytes = match &sample {
+                                MediaSample::Audio(f) => f.data.len(),
+                                MediaSample::Video(_) => 0,
+                            };
                             if let Err(e) = sample_source.send(sample).await {
                                 warn!("音频回声转发失败: {}", e);
                                 break;
                             }
+                            stats.lock().unwrap().record_sent(sent_bytes);
+                            if let Some(metrics) = &metrics {
+                                metrics.record_rtp_sent();
+                            }
                         }
                         Err(e) => {
                             warn!("音频入站轨道结束: {}", e);
@@ -311,156 +2471,2045 @@ impl RtpPlayer {
                 }
                 
                 info!("音频回声循环已停止");
-            });
+            };
+            self.echo_tasks.push(tokio::spawn(echo_loop.in_current_span()));
+        }
+        
+        self.is_active = true;
+        self.set_state(MediaSessionState::Streaming);
+        info!("音频回声处理器已启动");
+        Ok(())
+    }
+    
+    /// 启动视频回声：将对端的 VP8 帧原样回送
+    ///
+    /// 启动时先向对端请求一次关键帧，使回送的画面可以立即解码；回送方向收到对端的
+    /// PLI/FIR 时同样转为向对端请求关键帧。空载荷被丢弃，关键帧总是转发
+    pub async fn start_video_echo(&mut self) -> Result<(), MediaPlayError> {
+        if self.video_echo {
+            warn!("视频回声处理器已在运行");
+            return Ok(());
+        }
+
+        let transceivers: Vec<_> = self
+            .peer_connection
+            .get_transceivers()
+            .into_iter()
+            .filter(|t| t.kind() == rustrtc::MediaKind::Video)
+            .collect();
+        if transceivers.is_empty() {
+            return Err(self.fail(MediaPlayError::Sdp("没有可用的视频媒体流".to_string())));
+        }
+
+        for transceiver in transceivers {
+            transceiver.set_direction(rustrtc::TransceiverDirection::SendRecv);
+            let Some(receiver) = transceiver.receiver() else {
+                warn!("视频收发器缺少接收器");
+                continue;
+            };
+
+            let incoming_track = receiver.track();
+            let (sample_source, outgoing_track, _) = rustrtc::media::sample_track(MediaKind::Video, 100);
+            let ssrc = 5000 + transceiver.id() as u32;
+            let sender = rustrtc::peer_connection::RtpSender::builder(outgoing_track, ssrc)
+                .stream_id("video-echo-stream".to_string())
+                .params(Self::create_codec_params(MediaKind::Video, self.audio_codec))
+                .build();
+
+            // 对端请求回送画面的关键帧时，向对端请求关键帧，下一个关键帧会原样回送
+            let mut rtcp_rx = sender.subscribe_rtcp();
+            let keyframe_track = incoming_track.clone();
+            let rtcp_events = self.rtcp_events.clone();
+            let rtcp_task = async move {
+                while let Ok(packet) = rtcp_rx.recv().await {
+                    publish_rtcp_event(&rtcp_events, &packet);
+                    if matches!(
+                        packet,
+                        rustrtc::rtp::RtcpPacket::PictureLossIndication(_)
+                            | rustrtc::rtp::RtcpPacket::FullIntraRequest(_)
+                    ) {
+                        match keyframe_track.request_key_frame().await {
+                            Ok(()) => info!("对端请求关键帧，已向对端转发 PLI/FIR"),
+                            Err(e) => warn!("请求关键帧失败: {}", e),
+                        }
+                    }
+                }
+            };
+            self.echo_tasks.push(tokio::spawn(rtcp_task.in_current_span()));
+            transceiver.set_sender(Some(sender));
+
+            if let Err(e) = incoming_track.request_key_frame().await {
+                warn!("请求初始关键帧失败: {}", e);
+            }
+
+            let mut ssrc_filter = SsrcFilter::new(self.ssrc_selection);
+            let stats = self.stats.clone();
+            let metrics = self.metrics.clone();
+            let mut incoming = JitteredTrack::new(incoming_track, self.jitter_buffer);
+            let mut pacer = self.bandwidth_kbps.map(BandwidthPacer::new);
+            let echo_loop = async move {
+                info!("视频回声循环已启动");
+                loop {
+                    let frame = match incoming.recv().await {
+                        Ok(MediaSample::Video(frame)) => frame,
+                        Ok(MediaSample::Audio(_)) => continue,
+                        Err(e) => {
+                            warn!("视频入站轨道结束: {}", e);
+                            break;
+                        }
+                    };
+                    if !ssrc_filter.accept(frame.raw_packet.as_ref().map(|p| p.header.ssrc)) {
+                        continue;
+                    }
+                    stats.lock().unwrap().record_received(frame.data.len());
+                    if let Some(metrics) = &metrics {
+                        metrics.record_rtp_received();
+                    }
+                    if frame.data.is_empty() {
+                        continue;
+                    }
+                    if is_vp8_keyframe(&frame.data) {
+                        info!("回送视频关键帧 (ts={})", frame.rtp_timestamp);
+                    }
+
+                    let sent_bytes = frame.data.len();
+                    if let Some(pacer) = pacer.as_mut() {
+                        pacer.pace(sent_bytes).await;
+                    }
+                    if let Err(e) = sample_source.send(MediaSample::Video(frame)).await {
+                        warn!("视频回声转发失败: {}", e);
+                        break;
+                    }
+                    stats.lock().unwrap().record_sent(sent_bytes);
+                    if let Some(metrics) = &metrics {
+                        metrics.record_rtp_sent();
+                    }
+                }
+                info!("视频回声循环已停止");
+            };
+            self.echo_tasks.push(tokio::spawn(echo_loop.in_current_span()));
+        }
+
+        self.video_echo = true;
+        self.is_active = true;
+        self.set_state(MediaSessionState::Streaming);
+        info!("视频回声处理器已启动");
+        Ok(())
+    }
+    
+    /// 停止回声处理
+    ///
+    /// 清除运行标志并中止回声循环与 RTCP 转发任务，之后可以重新启动回声
+    pub fn stop_echo(&mut self) {
+        if !self.is_active {
+            warn!("回声处理器未运行");
+            return;
+        }
+        
+        self.is_active = false;
+        self.video_echo = false;
+        if let Some(running) = self.running.take() {
+            running.store(false, Ordering::Relaxed);
+        }
+        // 循环阻塞在接收上时不会看到运行标志，直接中止
+        for task in self.echo_tasks.drain(..) {
+            task.abort();
+        }
+        self.set_state(MediaSessionState::Stopped);
+
+        if let Some(mut writer) = self.recorder.lock().unwrap().take() {
+            match writer.finalize() {
+                Ok(()) => info!("录音已保存，共 {} 字节音频数据", writer.data_len()),
+                Err(e) => warn!("关闭录音文件失败: {}", e),
+            }
+        }
+        
+        info!("音频回声处理器已停止");
+    }
+    
+    /// 设置远程SDP
+    pub async fn set_remote_sdp(&mut self, remote_sdp: &str) -> Result<(), MediaPlayError> {
+        self.ensure_initialized()?;
+        self.apply_answer(remote_sdp).await?;
+        
+        info!("远程SDP设置成功");
+        
+        // 启动回声
+        self.start_audio_echo().await?;
+        if self.accepted_media.contains(&MediaKind::Video) {
+            self.start_video_echo().await?;
+        }
+        Ok(())
+    }
+    
+    /// 检查是否已初始化
+    pub fn is_initialized(&self) -> bool {
+        true // RtpPlayer is always initialized after new()
+    }
+    
+    /// 检查回声是否正在运行
+    pub fn is_echo_running(&self) -> bool {
+        self.is_active
+    }
+
+    /// 当前媒体会话状态
+    pub fn state(&self) -> MediaSessionState {
+        *self.state.borrow()
+    }
+
+    /// 订阅媒体会话状态变化
+    pub fn state_changes(&self) -> watch::Receiver<MediaSessionState> {
+        self.state.subscribe()
+    }
+
+    /// 最近一次进入 [`MediaSessionState::Failed`] 的原因
+    pub fn last_error(&self) -> Option<&MediaPlayError> {
+        self.last_error.as_ref()
+    }
+
+    fn set_state(&self, state: MediaSessionState) {
+        let previous = self.state.send_replace(state);
+        if previous != state {
+            info!("媒体会话状态: {:?} -> {:?}", previous, state);
+        }
+    }
+
+    /// 协商完成；媒体进行中的重协商保持 Streaming
+    fn mark_negotiated(&self) {
+        if self.state() != MediaSessionState::Streaming {
+            self.set_state(MediaSessionState::Negotiated);
+        }
+        if !self.transport.rtcp_mux {
+            let remote = self.peer_connection.remote_description().map(|remote| remote.to_sdp_string());
+            self.rtcp_peer.send_replace(remote.as_deref().and_then(remote_rtcp_addr));
+        }
+    }
+
+    /// 文件播放结束；回声仍在运行时保持 Streaming
+    fn finish_playback(&self) {
+        if !self.is_active {
+            self.set_state(MediaSessionState::Stopped);
+        }
+    }
+
+    /// 记录失败原因并切换到 Failed，返回原错误
+    fn fail(&mut self, error: MediaPlayError) -> MediaPlayError {
+        warn!("媒体会话失败: {}", error);
+        self.last_error = Some(error.clone());
+        self.set_state(MediaSessionState::Failed);
+        error
+    }
+    
+    /// 当前使用的音频编解码器
+    pub fn audio_codec(&self) -> AudioCodec {
+        self.audio_codec
+    }
+
+    /// 与对端实际协商出的音频编解码器参数（本端与对端 SDP 的交集中对端最优先的一个）
+    ///
+    /// 可用于让播放列表等按对端接受的编码（如 PCMU 或 PCMA）编码文件；
+    /// 协商完成前或音频流被拒绝时返回 `None`
+    pub fn negotiated_codec(&self) -> Option<RtpCodecParameters> {
+        let local = self.peer_connection.local_description()?;
+        let remote = self.peer_connection.remote_description()?;
+        let codec = negotiated_audio_codec(&local.to_sdp_string(), &remote.to_sdp_string())?;
+        Some(send_codec_params(&self.peer_connection, codec))
+    }
+
+    // 私有辅助方法
+    fn create_rtc_config(codecs: &[AudioCodec], transport: RtpTransportOptions) -> RtcConfiguration {
+        let mut audio: Vec<AudioCapability> = codecs.iter().map(|codec| codec.capability()).collect();
+        audio.push(AudioCapability::telephone_event());
+        let capabilities = MediaCapabilities {
+            audio,
+            ..Default::default()
+        };
+        RtcConfiguration {
+            transport_mode: TransportMode::Rtp,
+            media_capabilities: Some(capabilities),
+            rtcp_mux_policy: if transport.rtcp_mux { RtcpMuxPolicy::Require } else { RtcpMuxPolicy::Negotiate },
+            enable_latching: transport.symmetric_rtp,
+            ..Default::default()
+        }
+    }
+    
+    fn ensure_initialized(&self) -> Result<(), MediaPlayError> {
+        if !self.is_initialized() {
+            return Err(MediaPlayError::EchoNotInitialized);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MediaPlayer for RtpPlayer {
+    fn media_kind(&self) -> MediaKind {
+        MediaKind::Audio
+    }
+    
+    fn payload_type(&self) -> u8 {
+        send_codec_params(&self.peer_connection, self.audio_codec).payload_type
+    }
+    
+    fn clock_rate(&self) -> u32 {
+        self.audio_codec.clock_rate()
+    }
+    
+    async fn play_to_remote(&mut self, peer_connection: Arc<PeerConnection>) -> Result<(), MediaPlayError> {
+        self.play_from(Duration::ZERO, peer_connection).await
+    }
+
+    fn stop(&mut self) {
+        self.cancel.cancel();
+        self.set_state(MediaSessionState::Stopped);
+    }
+    
+    async fn start_echo(&mut self) -> Result<(), MediaPlayError> {
+        self.start_audio_echo().await
+    }
+    
+    async fn get_local_sdp(&self) -> Result<String, MediaPlayError> {
+        self.get_local_sdp()
+    }
+}
+
+/// 音频回声播放器
+pub struct AudioEchoPlayer {
+    rtp_player: RtpPlayer,
+}
+
+impl AudioEchoPlayer {
+    /// 创建新的音频回声播放器
+    pub async fn new() -> Result<(Self, String), MediaPlayError> {
+        let rtp_player = RtpPlayer::new(MediaKind::Audio).await?;
+        let sdp = rtp_player.get_local_sdp()?;
+        
+        Ok((
+            Self { rtp_player },
+            sdp
+        ))
+    }
+    
+    /// 初始化播放器
+    pub async fn initialize(&mut self) -> Result<(), MediaPlayError> {
+        // 已经在new()中初始化了
+        Ok(())
+    }
+    
+    /// 设置远程SDP
+    pub async fn set_remote_sdp(&mut self, remote_sdp: &str) -> Result<(), MediaPlayError> {
+        self.rtp_player.set_remote_sdp(remote_sdp).await
+    }
+    
+    /// 设置接收路径的 SSRC 选择策略
+    pub fn set_ssrc_selection(&mut self, selection: SsrcSelection) {
+        self.rtp_player.set_ssrc_selection(selection)
+    }
+
+    /// 设置回声期间对端音频的录音文件
+    pub fn set_record_path(&mut self, path: Option<PathBuf>) {
+        self.rtp_player.set_record_path(path)
+    }
+
+    /// 获取 RFC 4733 按键事件通道
+    pub fn dtmf_events(&mut self) -> Option<UnboundedReceiver<char>> {
+        self.rtp_player.dtmf_events()
+    }
+
+    /// 停止回声
+    pub fn stop_echo(&mut self) {
+        self.rtp_player.stop_echo()
+    }
+}
+
+#[async_trait]
+impl MediaPlayer for AudioEchoPlayer {
+    fn media_kind(&self) -> MediaKind {
+        MediaKind::Audio
+    }
+    
+    fn payload_type(&self) -> u8 {
+        self.rtp_player.audio_codec().payload_type()
+    }
+    
+    fn clock_rate(&self) -> u32 {
+        self.rtp_player.audio_codec().clock_rate()
+    }
+    
+    async fn play_to_remote(&mut self, _peer_connection: Arc<PeerConnection>) -> Result<(), MediaPlayError> {
+        Err(MediaPlayError::Sdp("AudioEchoPlayer不支持此操作".to_string()))
+    }
+    
+    async fn start_echo(&mut self) -> Result<(), MediaPlayError> {
+        self.rtp_player.start_audio_echo().await
+    }
+    
+    async fn get_local_sdp(&self) -> Result<String, MediaPlayError> {
+        self.rtp_player.get_local_sdp()
+    }
+}
+
+/// 每帧 20 ms 的样本数（8 kHz）
+const FRAME_SAMPLES: usize = 160;
+
+/// 每帧时长
+const FRAME_DURATION: Duration = Duration::from_millis(20);
+
+/// 播放列表中音轨之间的静音间隔
+const PLAYLIST_GAP: Duration = Duration::from_millis(200);
+
+/// 舒适噪声包携带的噪声电平（-dBov，RFC 3389）
+const CN_NOISE_LEVEL: u8 = 70;
+
+/// 静默期间发送舒适噪声或静音帧的间隔（帧数，即 200 ms）
+const GAP_FILL_INTERVAL_FRAMES: u32 = 10;
+
+/// 未在播放时的音轨索引
+const NOT_PLAYING: usize = usize::MAX;
+
+/// 将播放偏移换算为起始帧序号，超出 `frame_count` 帧的时长时返回错误
+fn seek_frame_index(offset: Duration, frame_count: usize) -> Result<usize, MediaPlayError> {
+    let index = (offset.as_millis() / FRAME_DURATION.as_millis()) as usize;
+    if index >= frame_count {
+        return Err(MediaPlayError::SeekOutOfRange {
+            offset,
+            duration: FRAME_DURATION * frame_count as u32,
+        });
+    }
+    Ok(index)
+}
+
+/// 读取单声道 WAV，按 `gain_db` 调整音量后编码，返回按 20 ms 切分的载荷
+///
+/// G.711 需要 8 kHz 输入，G.722 需要 16 kHz 输入；`auto_convert` 时其他格式混合为单声道并
+/// 重采样。启用 `mp3` 特性时 MP3 文件（按内容识别）解码后总是重采样到所需采样率
+fn load_wav_frames(
+    path: &Path,
+    codec: AudioCodec,
+    gain_db: f32,
+    auto_convert: bool,
+) -> Result<Vec<Vec<u8>>, MediaPlayError> {
+    let sample_rate = codec.sample_rate();
+    #[cfg(feature = "mp3")]
+    let mut audio = if crate::utils::is_mp3_file(path) {
+        // MP3 已混合为单声道，重采样到编解码器的采样率
+        let mut audio = crate::mp3::decode_mp3(path)
+            .map_err(|e| MediaPlayError::UnsupportedFormat(format!("MP3 解码失败: {}", e)))?;
+        audio.samples = resample_linear(&audio.samples, audio.format.sample_rate, sample_rate);
+        audio.format.sample_rate = sample_rate;
+        audio
+    } else {
+        read_wav(path)?
+    };
+    #[cfg(not(feature = "mp3"))]
+    let mut audio = read_wav(path)?;
+    if let Err(e) = check_wav_format(&audio.format, codec) {
+        if !auto_convert {
+            return Err(e);
+        }
+        info!(
+            "转换 {}: {} Hz {} 声道 -> {} Hz 单声道",
+            path.display(),
+            audio.format.sample_rate,
+            audio.format.channels,
+            sample_rate
+        );
+        let mono = downmix_to_mono(&audio.samples, audio.format.channels);
+        audio.samples = resample_linear(&mono, audio.format.sample_rate, sample_rate);
+        audio.format.channels = 1;
+        audio.format.sample_rate = sample_rate;
+    }
+    apply_gain(&mut audio.samples, gain_db);
+    #[cfg(feature = "g722")]
+    if codec == AudioCodec::G722 {
+        let mut encoder = G722Encoder::new();
+        return Ok(audio
+            .samples
+            .chunks(FRAME_SAMPLES * 2)
+            .map(|chunk| encoder.encode(chunk))
+            .collect());
+    }
+    audio
+        .samples
+        .chunks(FRAME_SAMPLES)
+        .map(|chunk| {
+            encode_g711(codec.payload_type(), chunk).ok_or_else(|| {
+                MediaPlayError::UnsupportedFormat(format!("无法编码为 {}", codec.name()))
+            })
+        })
+        .collect()
+}
+
+/// 校验 WAV 的采样率与声道数是否可直接编码为 `codec`
+fn check_wav_format(format: &WavFormat, codec: AudioCodec) -> Result<(), MediaPlayError> {
+    let sample_rate = codec.sample_rate();
+    if format.sample_rate != sample_rate || format.channels != 1 {
+        return Err(MediaPlayError::UnsupportedFormat(format!(
+            "{} 需要 {} Hz 单声道，实际为 {} Hz {} 声道",
+            codec.name(),
+            sample_rate,
+            format.sample_rate,
+            format.channels
+        )));
+    }
+    Ok(())
+}
+
+/// 按 20 ms 节奏向音频发送轨道推送载荷，RTP 时间戳在多次推送间保持单调递增
+struct AudioFrameSender {
+    source: rustrtc::media::SampleStreamSource,
+    codec: AudioCodec,
+    ticker: tokio::time::Interval,
+    timestamp: u32,
+    stats: Arc<Mutex<RtpStats>>,
+    metrics: Option<MetricsHandle>,
+    playback: PlaybackControl,
+    gap_fill: GapFill,
+    /// 当前静默期已经过的帧数
+    gap_frames: u32,
+    /// 静默期间 RTP 流中断过，下一帧音频需设置 marker 位
+    talkspurt: bool,
+}
+
+/// 静默期间（暂停、音轨间隔）的填充方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GapFill {
+    /// 不发送 RTP，仅推进时间戳
+    Skip,
+    /// 每帧发送静音帧
+    Silence,
+    /// 周期性发送 RFC 3389 舒适噪声包
+    ComfortNoise,
+    /// 对端不支持 CN 时周期性发送静音帧
+    PeriodicSilence,
+}
+
+impl AudioFrameSender {
+    /// 在第一个音频收发器上挂载新的发送轨道，发送计数与 RTCP 报告累计到 `stats`，
+    /// 收到的 RTCP 反馈转发到 `rtcp_events`
+    fn attach(
+        peer_connection: &PeerConnection,
+        codec: AudioCodec,
+        ssrc_base: u32,
+        stream_id: &str,
+        stats: Arc<Mutex<RtpStats>>,
+        rtcp_events: Option<broadcast::Sender<RtcpEvent>>,
+    ) -> Result<Self, MediaPlayError> {
+        let transceiver = peer_connection
+            .get_transceivers()
+            .into_iter()
+            .find(|t| t.kind() == rustrtc::MediaKind::Audio)
+            .ok_or_else(|| MediaPlayError::Rtp("缺少音频收发器".to_string()))?;
+
+        let (source, track, _) = rustrtc::media::sample_track(MediaKind::Audio, 100);
+        let ssrc = ssrc_base + transceiver.id() as u32;
+        let sender = rustrtc::peer_connection::RtpSender::builder(track, ssrc)
+            .stream_id(stream_id.to_string())
+            .params(send_codec_params(peer_connection, codec))
+            .build();
+
+        let mut rtcp_rx = sender.subscribe_rtcp();
+        let rtcp_stats = stats.clone();
+        tokio::spawn(async move {
+            while let Ok(packet) = rtcp_rx.recv().await {
+                rtcp_stats.lock().unwrap().apply_rtcp(&packet, ssrc);
+                if let Some(events) = &rtcp_events {
+                    publish_rtcp_event(events, &packet);
+                }
+            }
+        });
+        transceiver.set_sender(Some(sender));
+
+        Ok(Self {
+            source,
+            codec,
+            ticker: tokio::time::interval(FRAME_DURATION),
+            timestamp: 0,
+            stats,
+            metrics: None,
+            playback: PlaybackControl::default(),
+            gap_fill: GapFill::Skip,
+            gap_frames: 0,
+            talkspurt: false,
+        })
+    }
+
+    /// 使用 `playback` 控制暂停，`silence` 为真时暂停期间发送静音帧
+    fn with_pause(mut self, playback: PlaybackControl, silence: bool) -> Self {
+        self.playback = playback;
+        self.gap_fill = if silence { GapFill::Silence } else { GapFill::Skip };
+        self
+    }
+
+    /// 同时将发送的包计入全局指标
+    fn with_metrics(mut self, metrics: Option<MetricsHandle>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// 静默期间改用舒适噪声；`negotiated` 为假时退化为周期性发送静音帧
+    fn with_comfort_noise(mut self, negotiated: bool) -> Self {
+        self.gap_fill = if negotiated { GapFill::ComfortNoise } else { GapFill::PeriodicSilence };
+        self
+    }
+
+    /// 发送单帧音频载荷并推进 RTP 时间戳，静默后的第一帧设置 marker 位
+    async fn send_frame(&mut self, payload: &[u8]) -> Result<(), MediaPlayError> {
+        let marker = std::mem::take(&mut self.talkspurt);
+        self.send_packet(self.codec.payload_type(), payload, marker).await
+    }
+
+    /// 以指定载荷类型发送一个包并推进 RTP 时间戳
+    async fn send_packet(&mut self, payload_type: u8, payload: &[u8], marker: bool) -> Result<(), MediaPlayError> {
+        let frame = AudioFrame {
+            rtp_timestamp: self.timestamp,
+            clock_rate: self.codec.clock_rate(),
+            data: payload.to_vec().into(),
+            payload_type: Some(payload_type),
+            marker,
+            ..Default::default()
+        };
+        self.timestamp = self.timestamp.wrapping_add(FRAME_SAMPLES as u32);
+        self.source.send(MediaSample::Audio(frame)).await?;
+        self.stats.lock().unwrap().record_sent(payload.len());
+        if let Some(metrics) = &self.metrics {
+            metrics.record_rtp_sent();
+        }
+        Ok(())
+    }
+
+    /// 依次发送载荷，被取消时返回 `Ok(false)`
+    async fn send_all(
+        &mut self,
+        payloads: &[Vec<u8>],
+        cancel: &CancellationToken,
+    ) -> Result<bool, MediaPlayError> {
+        for payload in payloads {
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => return Ok(false),
+                    _ = self.ticker.tick() => {}
+                }
+                if !self.playback.is_paused() {
+                    break;
+                }
+                self.fill_gap().await?;
+            }
+            self.gap_frames = 0;
+            self.send_frame(payload).await?;
+        }
+        Ok(true)
+    }
+
+    /// 按填充方式度过 `frames` 帧静默期，被取消时返回 `Ok(false)`
+    async fn send_gap(&mut self, frames: usize, cancel: &CancellationToken) -> Result<bool, MediaPlayError> {
+        for _ in 0..frames {
+            tokio::select! {
+                _ = cancel.cancelled() => return Ok(false),
+                _ = self.ticker.tick() => {}
+            }
+            self.fill_gap().await?;
+        }
+        self.gap_frames = 0;
+        Ok(true)
+    }
+
+    /// 填充一帧静默期
+    ///
+    /// 时间戳始终随时间推进，恢复后接收端不会把后续帧当作过期数据
+    async fn fill_gap(&mut self) -> Result<(), MediaPlayError> {
+        let due = self.gap_frames.is_multiple_of(GAP_FILL_INTERVAL_FRAMES);
+        self.gap_frames = self.gap_frames.wrapping_add(1);
+        match self.gap_fill {
+            GapFill::Silence => return self.send_frame(&self.codec.silence_frame()).await,
+            GapFill::ComfortNoise if due => {
+                self.send_packet(CN_PAYLOAD_TYPE, &[CN_NOISE_LEVEL], false).await?
+            }
+            GapFill::PeriodicSilence if due => {
+                let silence = self.codec.silence_frame();
+                self.send_packet(self.codec.payload_type(), &silence, false).await?
+            }
+            _ => self.timestamp = self.timestamp.wrapping_add(FRAME_SAMPLES as u32),
+        }
+        self.talkspurt = true;
+        Ok(())
+    }
+}
+
+/// 播放列表播放器，在同一个 PeerConnection 上按顺序播放多个 WAV 文件
+///
+/// WAV 需为 8 kHz 单声道 16 位 PCM，不符合的文件跳过并记录警告
+pub struct PlaylistPlayer {
+    files: Vec<PathBuf>,
+    codec: AudioCodec,
+    current: Arc<AtomicUsize>,
+    cancel: CancellationToken,
+    comfort_noise: bool,
+}
+
+impl PlaylistPlayer {
+    /// 创建播放列表播放器（PCMU）
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        Self {
+            files,
+            codec: AudioCodec::default(),
+            current: Arc::new(AtomicUsize::new(NOT_PLAYING)),
+            cancel: CancellationToken::new(),
+            comfort_noise: false,
+        }
+    }
+
+    /// 设置发送使用的音频编解码器
+    pub fn with_codec(mut self, codec: AudioCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// 音轨间隔期间发送舒适噪声（RFC 3389）代替连续静音帧
+    ///
+    /// 对端未协商 CN 时周期性发送静音帧
+    pub fn with_comfort_noise(mut self, enabled: bool) -> Self {
+        self.comfort_noise = enabled;
+        self
+    }
+
+    /// 播放列表中的文件
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// 当前正在播放的音轨索引，未在播放时返回 `None`
+    pub fn current_index(&self) -> Option<usize> {
+        match self.current.load(Ordering::Relaxed) {
+            NOT_PLAYING => None,
+            index => Some(index),
+        }
+    }
+
+    /// 取消令牌，可在其他任务中中途停止播放
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+}
+
+#[async_trait]
+impl MediaPlayer for PlaylistPlayer {
+    fn media_kind(&self) -> MediaKind {
+        MediaKind::Audio
+    }
+
+    fn payload_type(&self) -> u8 {
+        self.codec.payload_type()
+    }
+
+    fn clock_rate(&self) -> u32 {
+        self.codec.clock_rate()
+    }
+
+    async fn play_to_remote(&mut self, peer_connection: Arc<PeerConnection>) -> Result<(), MediaPlayError> {
+        let mut sender =
+            AudioFrameSender::attach(&peer_connection, self.codec, 6000, "playlist-stream", Arc::default(), None)?
+                .with_pause(PlaybackControl::default(), true);
+        if self.comfort_noise {
+            sender = sender.with_comfort_noise(negotiated_comfort_noise(&peer_connection));
+        }
+        let gap_frames = (PLAYLIST_GAP.as_millis() / 20) as usize;
+        let mut played_any = false;
+
+        for (index, path) in self.files.iter().enumerate() {
+            let frames = match load_wav_frames(path, self.codec, 0.0, false) {
+                Ok(frames) => frames,
+                Err(e) => {
+                    warn!("跳过播放列表文件 {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            let gap = if played_any { gap_frames } else { 0 };
+            played_any = true;
+            self.current.store(index, Ordering::Relaxed);
+            info!("播放列表第 {} 首: {}", index + 1, path.display());
+
+            let completed = sender.send_gap(gap, &self.cancel).await?
+                && sender.send_all(&frames, &self.cancel).await?;
+            if !completed {
+                info!("播放列表在第 {} 首时被取消", index + 1);
+                break;
+            }
+        }
+
+        self.current.store(NOT_PLAYING, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sip_transport::extract_media_port;
+
+    #[tokio::test]
+    async fn test_build_offer_sdp() {
+        let addr: SocketAddr = "192.0.2.10:0".parse().unwrap();
+        let sdp = build_offer_sdp(MediaKind::Audio, AudioCodec::Pcmu, addr, 4000);
+        assert!(sdp.contains("c=IN IP4 192.0.2.10\r\n"));
+        assert!(sdp.contains("m=audio 4000 RTP/AVP 0 101\r\n"));
+        assert!(sdp.contains("a=rtpmap:0 PCMU/8000/1\r\n"));
+        assert!(sdp.contains("a=rtpmap:101 telephone-event/8000/1\r\na=fmtp:101 0-16\r\n"));
+        crate::utils::validate_sdp(&sdp).unwrap();
+
+        let v6 = build_offer_sdp(MediaKind::Video, AudioCodec::Pcmu, "[2001:db8::1]:0".parse().unwrap(), 5000);
+        assert!(v6.contains("c=IN IP6 2001:db8::1\r\nt=0 0\r\nm=video 5000 RTP/AVP 96\r\n"));
+        assert!(v6.contains("a=rtpmap:96 VP8/90000\r\na=rtcp-fb:96 nack\r\n"));
+
+        // RtpPlayer 发出的初始 offer 与纯函数生成的一致
+        let cases = [
+            (MediaKind::Audio, AudioCodec::Pcmu),
+            (MediaKind::Audio, AudioCodec::Pcma),
+            (MediaKind::Video, AudioCodec::Pcmu),
+        ];
+        for (kind, codec) in cases {
+            let player = RtpPlayer::new_with_codec(kind, codec).await.unwrap();
+            let local = player.get_local_sdp().unwrap();
+            let desc = SessionDescription::parse(SdpType::Offer, &local).unwrap();
+            let ip = desc.session.origin.unicast_address.parse().unwrap();
+            let port = desc.media_sections[0].port;
+            assert_eq!(local, build_offer_sdp(kind, codec, SocketAddr::new(ip, 0), port));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_offline_offer_uses_supplied_address() {
+        let player = RtpPlayer::new_offline(MediaKind::Audio, AudioCodec::Pcma, "127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        let local = player.get_local_sdp().unwrap();
+        assert!(local.contains("c=IN IP4 127.0.0.1\r\n"));
+        let port = extract_media_port(&local, "audio").unwrap();
+        assert_ne!(port, 0);
+        assert_eq!(
+            local,
+            build_offer_sdp(MediaKind::Audio, AudioCodec::Pcma, "127.0.0.1:0".parse().unwrap(), port)
+        );
+
+        // 指定端口时 RTP 套接字绑定到该端口
+        let port = std::net::UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+        let player = RtpPlayer::new_offline(MediaKind::Audio, AudioCodec::Pcmu, addr).await.unwrap();
+        let local = player.get_local_sdp().unwrap();
+        assert_eq!(crate::sip_transport::extract_peer_rtp_addr(&local).unwrap(), addr.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_rtcp_mux_disabled_uses_separate_port() {
+        let transport = RtpTransportOptions {
+            rtcp_mux: false,
+            symmetric_rtp: true,
+        };
+        let mut player = RtpPlayer::new_with_transport(MediaKind::Audio, AudioCodec::Pcmu, transport)
+            .await
+            .unwrap();
+        assert!(player.peer_connection().config().enable_latching);
+        let local = player.get_local_sdp().unwrap();
+        let port = extract_media_port(&local, "audio").unwrap();
+        assert!(!local.contains("a=rtcp-mux"));
+        assert!(local.contains(&format!("a=rtcp:{}\r\n", port + 1)));
+        let ip = local.lines().find_map(|l| l.strip_prefix("c=IN IP4 ")).unwrap().trim().to_string();
+
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let peer_rtcp = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let answer = format!(
+            "v=0\r\no=- 1 1 IN IP4 127.0.0.1\r\ns=-\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\n\
+             m=audio {} RTP/AVP 0\r\na=rtcp:{}\r\na=rtpmap:0 PCMU/8000\r\na=sendrecv\r\n",
+            socket.local_addr().unwrap().port(),
+            peer_rtcp.local_addr().unwrap().port()
+        );
+        player.set_remote_sdp(&answer).await.unwrap();
+
+        // 本端报告从 RTCP 端口发往对端 a=rtcp 声明的地址
+        let mut buf = [0u8; 1500];
+        let (len, from) = tokio::time::timeout(Duration::from_secs(3), peer_rtcp.recv_from(&mut buf))
+            .await
+            .expect("对端 RTCP 端口未收到报告")
+            .unwrap();
+        assert_eq!(from.port(), port + 1);
+        let packets = rustrtc::rtp::parse_rtcp_packets(&buf[..len]).unwrap();
+        assert!(matches!(packets[0], rustrtc::rtp::RtcpPacket::ReceiverReport(_)), "{:?}", packets);
+
+        // 发往 RTCP 端口的发送报告由 PeerConnection 处理
+        use rustrtc::rtp::{marshal_rtcp_packets, RtcpPacket, SenderReport};
+        let sr = marshal_rtcp_packets(&[RtcpPacket::SenderReport(SenderReport {
+            sender_ssrc: 4321,
+            ntp_most: 0,
+            ntp_least: 0,
+            rtp_timestamp: 0,
+            packet_count: 10,
+            octet_count: 1600,
+            report_blocks: vec![],
+        })])
+        .unwrap();
+        let pc = player.peer_connection();
+        let received = tokio::time::timeout(Duration::from_secs(3), async {
+            loop {
+                socket.send_to(&sr, format!("{}:{}", ip, port + 1)).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                let stats = pc.get_stats().await.unwrap();
+                if stats.entries.iter().any(|e| e.kind == rustrtc::StatsKind::RemoteOutboundRtp) {
+                    break;
+                }
+            }
+        })
+        .await;
+        assert!(received.is_ok(), "RTCP 端口未收到报告");
+
+        // 对称 RTP：之后的报告发往对端 RTCP 的实际源地址（该套接字同时收到本端 RTP 端口发出的媒体）
+        let latched = tokio::time::timeout(RTCP_REPORT_INTERVAL * 2, async {
+            loop {
+                let (_, from) = socket.recv_from(&mut buf).await.unwrap();
+                if from.port() == port + 1 {
+                    break;
+                }
+            }
+        })
+        .await;
+        assert!(latched.is_ok(), "未按实际源地址发送报告");
+
+        // 默认开启 rtcp-mux
+        let player = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        assert!(player.get_local_sdp().unwrap().contains("a=rtcp-mux\r\n"));
+        assert!(!player.peer_connection().config().enable_latching);
+    }
+
+    #[tokio::test]
+    async fn test_negotiated_codec() {
+        let mut player = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        assert!(player.negotiated_codec().is_none());
+        let answer = "v=0\r\no=- 1 1 IN IP4 127.0.0.1\r\ns=-\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\n\
+                      m=audio 40000 RTP/AVP 101 0\r\na=rtpmap:101 telephone-event/8000\r\n\
+                      a=rtpmap:0 PCMU/8000\r\na=sendrecv\r\n";
+        player.apply_answer(answer).await.unwrap();
+        let codec = player.negotiated_codec().unwrap();
+        assert_eq!((codec.payload_type, codec.clock_rate, codec.channels), (0, 8000, 1));
+
+        // 作为应答方时按 offer 的顺序选择
+        let offer = answer.replace("RTP/AVP 101 0", "RTP/AVP 8 0 101").replace("0 PCMU", "8 PCMA");
+        let (answerer, _) = RtpPlayer::new_answerer(&offer).await.unwrap();
+        assert_eq!(answerer.negotiated_codec().unwrap().payload_type, 8);
+        assert_eq!(answerer.audio_codec(), AudioCodec::Pcma);
+
+        assert_eq!(
+            negotiated_audio_codec("m=audio 4000 RTP/AVP 0 8 101\r\n", "m=audio 5000 RTP/AVP 8 0\r\n"),
+            Some(AudioCodec::Pcma)
+        );
+        assert_eq!(negotiated_audio_codec("m=audio 4000 RTP/AVP 0\r\n", "m=audio 0 RTP/AVP 0\r\n"), None);
+        assert_eq!(negotiated_audio_codec("m=audio 4000 RTP/AVP 0\r\n", "m=audio 5000 RTP/AVP 8\r\n"), None);
+    }
+
+    #[tokio::test]
+    async fn test_answer_telephone_event_requires_8khz() {
+        let answer = "v=0\r\no=- 1 1 IN IP4 127.0.0.1\r\ns=-\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\n\
+                      m=audio 40000 RTP/AVP 0 101\r\na=rtpmap:0 PCMU/8000\r\n\
+                      a=rtpmap:101 telephone-event/48000\r\na=sendrecv\r\n";
+        let mut player = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        player.apply_answer(answer).await.unwrap();
+        assert_eq!(player.telephone_event, None);
+
+        let mut player = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        player.apply_answer(&answer.replace("/48000", "/8000")).await.unwrap();
+        assert_eq!(player.telephone_event, Some(101));
+
+        let both = "m=audio 5000 RTP/AVP 0 100 101\r\na=rtpmap:100 telephone-event/48000\r\n\
+                    a=rtpmap:101 telephone-event/8000\r\n";
+        assert_eq!(telephone_event_payload_type(both), Some(101));
+    }
+
+    #[test]
+    fn test_remote_rtcp_addr() {
+        let sdp = "v=0\r\nc=IN IP4 10.0.0.1\r\nm=audio 4000 RTP/AVP 0\r\na=sendrecv\r\n";
+        assert_eq!(remote_rtcp_addr(sdp), Some("10.0.0.1:4001".parse().unwrap()));
+        let sdp = "v=0\r\nc=IN IP4 10.0.0.1\r\nm=audio 4000 RTP/AVP 0\r\na=rtcp:5003\r\n";
+        assert_eq!(remote_rtcp_addr(sdp), Some("10.0.0.1:5003".parse().unwrap()));
+        let sdp = "v=0\r\nc=IN IP4 10.0.0.1\r\nm=audio 4000 RTP/AVP 0\r\na=rtcp:5003 IN IP4 10.0.0.2\r\n";
+        assert_eq!(remote_rtcp_addr(sdp), Some("10.0.0.2:5003".parse().unwrap()));
+        let sdp = "v=0\r\nc=IN IP4 10.0.0.1\r\nm=audio 4000 RTP/AVP 0\r\na=rtcp-mux\r\n";
+        assert_eq!(remote_rtcp_addr(sdp), None);
+        assert_eq!(remote_rtcp_addr("v=0\r\nc=IN IP4 10.0.0.1\r\nm=audio 0 RTP/AVP 0\r\n"), None);
+    }
+
+    #[test]
+    fn test_without_rtcp_mux() {
+        let sdp = "v=0\r\nc=IN IP4 10.0.0.1\r\nm=audio 4000 RTP/AVP 0\r\na=rtcp-mux\r\na=sendrecv\r\n\
+                   m=video 0 RTP/AVP 96\r\na=rtcp-mux\r\n";
+        assert_eq!(
+            without_rtcp_mux(sdp),
+            "v=0\r\nc=IN IP4 10.0.0.1\r\nm=audio 4000 RTP/AVP 0\r\na=rtcp:4001\r\na=sendrecv\r\n\
+             m=video 0 RTP/AVP 96\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bandwidth_line_and_pacing() {
+        let sdp = "v=0\r\nc=IN IP4 10.0.0.1\r\nm=audio 4000 RTP/AVP 0\r\nc=IN IP4 10.0.0.2\r\nb=AS:30\r\n\
+                   a=sendrecv\r\nm=video 0 RTP/AVP 96\r\na=inactive\r\nm=video 5000 RTP/AVP 96\r\n";
+        assert_eq!(
+            with_bandwidth_line(sdp, 64),
+            "v=0\r\nc=IN IP4 10.0.0.1\r\nm=audio 4000 RTP/AVP 0\r\nc=IN IP4 10.0.0.2\r\nb=AS:64\r\n\
+             a=sendrecv\r\nm=video 0 RTP/AVP 96\r\na=inactive\r\nm=video 5000 RTP/AVP 96\r\nb=AS:64\r\n"
+        );
+
+        // 默认不通告带宽，超出范围的值被限制
+        let player = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        assert_eq!(player.bandwidth(), None);
+        assert!(!player.get_local_sdp().unwrap().contains("b="));
+        let player = player.with_bandwidth(1);
+        assert_eq!(player.bandwidth(), Some(MIN_BANDWIDTH_KBPS));
+        let player = player.with_bandwidth(128);
+        let local = player.get_local_sdp().unwrap();
+        assert!(local.contains("b=AS:128\r\n"), "{}", local);
+        assert!(SessionDescription::parse(SdpType::Offer, &local).is_ok());
+        assert_eq!(RtpPlayer::new(MediaKind::Audio).await.unwrap().with_bandwidth(u32::MAX).bandwidth(), Some(MAX_BANDWIDTH_KBPS));
+
+        // 8 kbps 下 100 字节需要 100 ms，首个包立即发送
+        let mut pacer = BandwidthPacer::new(8);
+        let started = std::time::Instant::now();
+        pacer.pace(100).await;
+        assert!(started.elapsed() < Duration::from_millis(50));
+        pacer.pace(100).await;
+        pacer.pace(0).await;
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(200) && elapsed < Duration::from_millis(400), "{:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn test_direction_offer_keeps_port() {
+        let mut player = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        let original = player.get_local_sdp().unwrap();
+        // 以本端 offer 作为对端 answer，使信令状态回到 stable
+        player.apply_answer(&original).await.unwrap();
+        assert_eq!(player.negotiated_direction(), MediaDirection::SendRecv);
+
+        let held = player.create_direction_offer(MediaDirection::SendOnly).await.unwrap();
+        assert_eq!(media_direction(&held, "audio"), MediaDirection::SendOnly);
+        assert_eq!(
+            extract_media_port(&held, "audio"),
+            extract_media_port(&original, "audio")
+        );
+        assert_eq!(player.local_direction(), MediaDirection::SendOnly);
+
+        // 对端拒绝后恢复原方向，可再次发起 offer
+        player.restore_direction(MediaDirection::SendRecv).await.unwrap();
+        let resumed = player.create_direction_offer(MediaDirection::SendRecv).await.unwrap();
+        assert_eq!(media_direction(&resumed, "audio"), MediaDirection::SendRecv);
+    }
+
+    #[tokio::test]
+    async fn test_pcma_offer_and_answer() {
+        let player = RtpPlayer::new_with_codec(MediaKind::Audio, AudioCodec::Pcma).await.unwrap();
+        let sdp = player.get_local_sdp().unwrap();
+        assert_eq!(extract_payload_types(&sdp, "audio"), vec![8, 101]);
+        assert!(sdp.contains("a=rtpmap:8 PCMA/8000"));
+        assert_eq!(MediaPlayer::payload_type(&player), 8);
+
+        let default = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        let sdp = default.get_local_sdp().unwrap();
+        assert_eq!(extract_payload_types(&sdp, "audio"), vec![0, 101]);
+
+        // 应答方沿用 offer 中排在最前的共同编解码器
+        let offer = "v=0\r\no=- 1 1 IN IP4 127.0.0.1\r\ns=-\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\n\
+                     m=audio 4000 RTP/AVP 8 0\r\na=rtpmap:8 PCMA/8000\r\na=rtpmap:0 PCMU/8000\r\na=sendrecv\r\n";
+        let (answerer, answer) = RtpPlayer::new_answerer(offer).await.unwrap();
+        assert_eq!(answerer.audio_codec(), AudioCodec::Pcma);
+        assert!(extract_payload_types(&answer, "audio").contains(&8));
+    }
+
+    #[tokio::test]
+    async fn test_answer_echoes_dynamic_payload_types() {
+        // offer 以动态载荷类型承载 PCMU，answer 沿用 offer 的载荷类型
+        let offer = "v=0\r\no=- 1 1 IN IP4 127.0.0.1\r\ns=-\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\n\
+                     m=audio 4000 RTP/AVP 96 100\r\na=rtpmap:96 PCMU/8000\r\n\
+                     a=rtpmap:100 telephone-event/8000\r\na=sendrecv\r\n";
+        let (answerer, answer) = RtpPlayer::new_answerer(offer).await.unwrap();
+        assert_eq!(answerer.audio_codec(), AudioCodec::Pcmu);
+        assert_eq!(extract_payload_types(&answer, "audio"), vec![96, 100]);
+        assert!(answer.contains("a=rtpmap:96 PCMU/8000"));
+        assert!(answer.contains("a=rtpmap:100 telephone-event/8000"));
+        assert_eq!(MediaPlayer::payload_type(&answerer), 96);
+        assert_eq!(answerer.negotiated_codec().unwrap().payload_type, 96);
+
+        // 时钟频率或声道数不符的编码不匹配
+        for rtpmap in ["PCMU/16000", "PCMU/8000/2"] {
+            let offer = offer.replace("PCMU/8000", rtpmap);
+            assert!(matches!(
+                RtpPlayer::new_answerer(&offer).await,
+                Err(MediaPlayError::NoCommonCodec)
+            ));
         }
-        
-        self.is_active = true;
-        info!("音频回声处理器已启动");
-        Ok(())
     }
-    
-    /// 停止回声处理
-    pub fn stop_echo(&mut self) {
-        if !self.is_active {
-            warn!("回声处理器未运行");
-            return;
+
+    #[tokio::test]
+    async fn test_set_remote_offer_answers_inbound_call() {
+        let caller = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        let offer = caller.get_local_sdp().unwrap();
+
+        // 由 new 创建（已有本地 offer）的播放器也能改为应答方
+        let mut callee = RtpPlayer::new_with_codec(MediaKind::Audio, AudioCodec::Pcma)
+            .await
+            .unwrap();
+        let answer = callee.set_remote_offer(&offer).await.unwrap();
+        assert_eq!(extract_payload_types(&answer, "audio"), vec![0, 101]);
+        assert_eq!(callee.audio_codec(), AudioCodec::Pcmu);
+        assert_eq!(callee.telephone_event_payload_type(), Some(101));
+        assert_eq!(callee.accepted_media(), &[MediaKind::Audio]);
+        assert_eq!(callee.get_local_sdp().unwrap(), answer);
+
+        let mut caller = caller;
+        caller.apply_answer(&answer).await.unwrap();
+        assert_eq!(caller.audio_codec(), AudioCodec::Pcmu);
+
+        #[cfg(not(feature = "g722"))]
+        {
+            let g722_only = "v=0\r\no=- 1 1 IN IP4 127.0.0.1\r\ns=-\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\n\
+                              m=audio 4000 RTP/AVP 9\r\na=rtpmap:9 G722/8000\r\n";
+            assert!(callee.set_remote_offer(g722_only).await.is_err());
         }
-        
-        self.is_active = false;
-        
-        // 关闭PeerConnection
-        info!("正在关闭音频回声处理器");
-        // PeerConnection没有直接的关闭方法，但我们可以在析构时处理
     }
-    
-    /// 设置远程SDP
-    pub async fn set_remote_sdp(&mut self, remote_sdp: &str) -> Result<(), MediaPlayError> {
-        self.ensure_initialized()?;
-        
-        let remote_sdp = SessionDescription::parse(SdpType::Answer, remote_sdp)
-            .map_err(|e| MediaPlayError::Sdp(format!("解析远程SDP失败: {}", e)))?;
-        
-        let pc = &self.peer_connection;
-        pc.set_remote_description(remote_sdp)
+
+    #[cfg(feature = "g722")]
+    #[tokio::test]
+    async fn test_g722_offer_and_wav_frames() {
+        let player = RtpPlayer::new_with_codec(MediaKind::Audio, AudioCodec::G722).await.unwrap();
+        let offer = player.get_local_sdp().unwrap();
+        assert_eq!(extract_payload_types(&offer, "audio"), vec![9, 101]);
+        assert!(offer.contains("a=rtpmap:9 G722/8000"));
+
+        let dir = std::env::temp_dir();
+        let narrow = dir.join(format!("g722-narrow-{}.wav", uuid::Uuid::new_v4()));
+        let wide = dir.join(format!("g722-wide-{}.wav", uuid::Uuid::new_v4()));
+        let mut writer = WavWriter::create(&narrow, 8000).unwrap();
+        writer.write_samples(&[0; 160]).unwrap();
+        drop(writer);
+        let mut writer = WavWriter::create(&wide, 16000).unwrap();
+        writer.write_samples(&[100; 640]).unwrap();
+        drop(writer);
+
+        let rejected = load_wav_frames(&narrow, AudioCodec::G722, 0.0, false);
+        let frames = load_wav_frames(&wide, AudioCodec::G722, 0.0, false).unwrap();
+        std::fs::remove_file(&narrow).unwrap();
+        std::fs::remove_file(&wide).unwrap();
+        assert!(matches!(rejected, Err(MediaPlayError::UnsupportedFormat(msg)) if msg.contains("16000")));
+        assert_eq!(frames.len(), 2);
+        assert!(frames.iter().all(|frame| frame.len() == 160));
+        assert_eq!(AudioCodec::G722.silence_frame().len(), 160);
+    }
+
+    #[tokio::test]
+    async fn test_stereo_44k_wav_converted_on_request() {
+        // 0.1 秒 44.1 kHz 立体声，左右声道分别为 2000 与 0
+        let frames = 4410;
+        let mut data = Vec::new();
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + frames * 4u32).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&44100u32.to_le_bytes());
+        data.extend_from_slice(&(44100u32 * 4).to_le_bytes());
+        data.extend_from_slice(&4u16.to_le_bytes());
+        data.extend_from_slice(&16u16.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&(frames * 4).to_le_bytes());
+        for _ in 0..frames {
+            data.extend_from_slice(&2000i16.to_le_bytes());
+            data.extend_from_slice(&0i16.to_le_bytes());
+        }
+        let path = std::env::temp_dir().join(format!("stereo-44k-{}.wav", uuid::Uuid::new_v4()));
+        std::fs::write(&path, data).unwrap();
+        let file = path.to_str().unwrap();
+
+        let rejected = MediaPlayerFactory::create_audio_player(file).await;
+        assert!(
+            matches!(&rejected, Err(MediaPlayError::UnsupportedFormat(msg)) if msg.contains("44100 Hz 2 声道")),
+            "{:?}",
+            rejected.err()
+        );
+        let options = PlayerOptions::default().with_auto_convert(true);
+        assert!(MediaPlayerFactory::create_audio_player_with_options(file, &options)
             .await
-            .map_err(|e| MediaPlayError::Sdp(format!("设置远程描述失败: {}", e)))?;
-        
-        info!("远程SDP设置成功");
-        
-        // 启动回声
-        self.start_audio_echo().await?;
-        Ok(())
+            .is_ok());
+
+        let converted = load_wav_frames(&path, AudioCodec::Pcmu, 0.0, true);
+        let unconverted = load_wav_frames(&path, AudioCodec::Pcmu, 0.0, false);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(unconverted, Err(MediaPlayError::UnsupportedFormat(_))));
+        // 800 个 8 kHz 样本切为 5 帧，取两声道平均值
+        let converted = converted.unwrap();
+        assert_eq!(converted.len(), 5);
+        assert!(converted.iter().all(|frame| frame.len() == FRAME_SAMPLES));
+        let decoded = decode_g711(0, &converted[0]).unwrap();
+        assert!(decoded.iter().all(|s| (950..=1050).contains(s)), "{:?}", &decoded[..4]);
     }
-    
-    /// 检查是否已初始化
-    pub fn is_initialized(&self) -> bool {
-        true // RtpPlayer is always initialized after new()
+
+    #[tokio::test]
+    async fn test_gain_applied_to_file_and_echo() {
+        let player = RtpPlayer::new(MediaKind::Audio).await.unwrap().with_gain_db(50.0);
+        assert_eq!(player.gain_db(), 30.0);
+
+        let path = std::env::temp_dir().join(format!("gain-{}.wav", uuid::Uuid::new_v4()));
+        let mut writer = WavWriter::create(&path, 8000).unwrap();
+        writer.write_samples(&[1000; 160]).unwrap();
+        drop(writer);
+        let plain = load_wav_frames(&path, AudioCodec::Pcmu, 0.0, false).unwrap();
+        let boosted = load_wav_frames(&path, AudioCodec::Pcmu, 6.0, false).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(decode_g711(0, &plain[0]).unwrap()[0], 988);
+        assert_eq!(decode_g711(0, &boosted[0]).unwrap()[0], 1980);
+
+        let frame = AudioFrame {
+            data: encode_g711(8, &[1000; 160]).unwrap().into(),
+            payload_type: Some(8),
+            ..Default::default()
+        };
+        let MediaSample::Audio(amplified) = amplify_sample(MediaSample::Audio(frame), 6.0, AudioCodec::Pcmu) else {
+            panic!("应为音频样本");
+        };
+        assert_eq!(decode_g711(8, &amplified.data).unwrap()[0], 2016);
     }
-    
-    /// 检查回声是否正在运行
-    pub fn is_echo_running(&self) -> bool {
-        self.is_active
+
+    #[test]
+    fn test_vad_drops_background_noise() {
+        let mut vad = VoiceActivityDetector::new(-45.0).with_hangover(1);
+        let frame = |level: i16| AudioFrame {
+            data: encode_g711(0, &[level; 160]).unwrap().into(),
+            payload_type: Some(0),
+            ..Default::default()
+        };
+        assert!(!vad_accepts(&mut vad, &frame(10), AudioCodec::Pcmu));
+        assert!(vad_accepts(&mut vad, &frame(4000), AudioCodec::Pcmu));
+        assert!(vad_accepts(&mut vad, &frame(10), AudioCodec::Pcmu));
+        assert!(!vad_accepts(&mut vad, &frame(10), AudioCodec::Pcmu));
+        // 无法解码的载荷总是转发
+        let opaque = AudioFrame {
+            data: vec![0; 10].into(),
+            payload_type: Some(111),
+            ..Default::default()
+        };
+        assert!(vad_accepts(&mut vad, &opaque, AudioCodec::Pcmu));
     }
-    
-    // 私有辅助方法
-    fn create_rtc_config() -> RtcConfiguration {
-        let mut config = RtcConfiguration::default();
-        config.transport_mode = TransportMode::Rtp;
-        config
+
+    #[test]
+    fn test_check_negotiated_codecs() {
+        let offer = "v=0\r\nm=audio 4000 RTP/AVP 0 8 101\r\na=rtpmap:0 PCMU/8000\r\na=rtpmap:8 PCMA/8000\r\n\
+                     a=rtpmap:101 telephone-event/8000\r\n";
+        let ok = "v=0\r\nm=audio 5000 RTP/AVP 8 101\r\na=rtpmap:101 telephone-event/8000\r\n";
+        assert!(check_negotiated_codecs(offer, ok).is_ok());
+
+        let only_events = "v=0\r\nm=audio 5000 RTP/AVP 18 101\r\na=rtpmap:101 telephone-event/8000\r\n";
+        assert!(matches!(check_negotiated_codecs(offer, only_events), Err(MediaPlayError::NoCommonCodec)));
+
+        let remapped = "v=0\r\nm=audio 5000 RTP/AVP 0\r\na=rtpmap:0 G729/8000\r\n";
+        assert!(matches!(
+            check_negotiated_codecs(offer, remapped),
+            Err(MediaPlayError::CodecNegotiation { offered, answered }) if offered == "0=PCMU" && answered == "0=G729"
+        ));
+
+        // 音频被拒绝时不校验
+        let rejected = "v=0\r\nm=audio 0 RTP/AVP 18\r\n";
+        assert!(check_negotiated_codecs(offer, rejected).is_ok());
     }
-    
-    fn ensure_initialized(&self) -> Result<(), MediaPlayError> {
-        if !self.is_initialized() {
-            return Err(MediaPlayError::EchoNotInitialized);
+
+    #[tokio::test]
+    async fn test_final_answer_supersedes_early_media() {
+        let mut caller = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        let offer = caller.get_local_sdp().unwrap();
+        let (_callee, early) = RtpPlayer::new_answerer(&offer).await.unwrap();
+
+        caller.apply_early_answer(&early).await.unwrap();
+        assert!(caller.is_early_media());
+        // 相同 SDP 的 200 OK 保持早期会话
+        caller.apply_final_answer(&early).await.unwrap();
+        assert!(!caller.is_early_media());
+
+        let final_sdp = early.replace("RTP/AVP 0 101", "RTP/AVP 0").replacen("o=- ", "o=- 9", 1);
+        caller.apply_early_answer(&early).await.unwrap();
+        caller.apply_final_answer(&final_sdp).await.unwrap();
+        assert!(!caller.is_early_media());
+        let remote = caller.peer_connection().remote_description().unwrap().to_sdp_string();
+        assert_eq!(extract_payload_types(&remote, "audio"), vec![0]);
+        assert!(same_session(&early, &early) && !same_session(&early, &final_sdp));
+    }
+
+    #[test]
+    fn test_record_frame_decodes_g711() {
+        let path = std::env::temp_dir().join(format!("echo-record-{}.wav", uuid::Uuid::new_v4()));
+        let recorder = Mutex::new(Some(WavWriter::create(&path, 8000).unwrap()));
+        let frame = AudioFrame {
+            data: silence_payload(8, 160).into(),
+            payload_type: Some(8),
+            ..Default::default()
+        };
+        record_frame(&recorder, &frame, AudioCodec::Pcmu);
+        // 非 G.711 载荷不写入
+        let other = AudioFrame {
+            data: vec![1, 2, 3].into(),
+            payload_type: Some(111),
+            ..Default::default()
+        };
+        record_frame(&recorder, &other, AudioCodec::Pcmu);
+        recorder.lock().unwrap().take().unwrap().finalize().unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(u32::from_le_bytes(data[40..44].try_into().unwrap()), 320);
+        assert_eq!(data.len(), 44 + 320);
+    }
+
+    #[cfg(feature = "mp3")]
+    #[tokio::test]
+    async fn test_mp3_resampled_for_pcmu() {
+        let path = std::env::temp_dir().join(format!("prompt-{}.mp3", uuid::Uuid::new_v4()));
+        crate::mp3::tests::write_silent_mp3(&path, 10);
+        let frames = load_wav_frames(&path, AudioCodec::Pcmu, 0.0, false).unwrap();
+        assert!(MediaPlayerFactory::create_audio_player(path.to_str().unwrap()).await.is_ok());
+        std::fs::remove_file(&path).unwrap();
+
+        // 约 10 * 1152 个 44.1 kHz 样本，重采样到 8 kHz 约 2090 个样本，即 13~14 帧
+        assert!((12..=14).contains(&frames.len()), "{}", frames.len());
+        assert!(frames.iter().all(|f| f.len() == 160 || f == frames.last().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_playlist_skips_invalid_files() {
+        let dir = std::env::temp_dir();
+        let good = dir.join(format!("playlist-good-{}.wav", uuid::Uuid::new_v4()));
+        let wide = dir.join(format!("playlist-wide-{}.wav", uuid::Uuid::new_v4()));
+        let mut writer = WavWriter::create(&good, 8000).unwrap();
+        writer.write_samples(&[0; 320]).unwrap();
+        drop(writer);
+        WavWriter::create(&wide, 44100).unwrap();
+
+        let files = [good.to_str().unwrap(), "/nonexistent/track.wav", wide.to_str().unwrap()];
+        let mut playlist = MediaPlayerFactory::create_playlist_player(&files).unwrap();
+        assert_eq!(playlist.files(), &[good.clone(), wide.clone()]);
+        assert!(matches!(
+            load_wav_frames(&wide, AudioCodec::Pcmu, 0.0, false),
+            Err(MediaPlayError::UnsupportedFormat(_))
+        ));
+        assert_eq!(load_wav_frames(&good, AudioCodec::Pcmu, 0.0, false).unwrap().len(), 2);
+
+        let player = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        playlist.play_to_remote(player.peer_connection()).await.unwrap();
+        assert_eq!(playlist.current_index(), None);
+
+        // 取消后不再播放
+        playlist.stop();
+        let started = std::time::Instant::now();
+        playlist.play_to_remote(player.peer_connection()).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(30));
+
+        std::fs::remove_file(&good).unwrap();
+        std::fs::remove_file(&wide).unwrap();
+        assert!(MediaPlayerFactory::create_playlist_player(&["/nonexistent/a.wav"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_play_from_offset() {
+        assert_eq!(seek_frame_index(Duration::from_millis(45), 5).unwrap(), 2);
+        assert!(matches!(
+            seek_frame_index(Duration::from_millis(100), 5),
+            Err(MediaPlayError::SeekOutOfRange { duration, .. }) if duration == Duration::from_millis(100)
+        ));
+
+        let path = std::env::temp_dir().join(format!("seek-{}.wav", uuid::Uuid::new_v4()));
+        let mut writer = WavWriter::create(&path, 8000).unwrap();
+        writer.write_samples(&[0; 160 * 3]).unwrap();
+        drop(writer);
+
+        let mut player = RtpPlayer::new(MediaKind::Audio).await.unwrap().with_media_file(&path);
+        let pc = player.peer_connection();
+        player.play_from(Duration::from_millis(40), pc.clone()).await.unwrap();
+        assert_eq!(player.stats().packets_sent, 1);
+        let past_end = player.play_from(Duration::from_secs(1), pc).await;
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(past_end, Err(MediaPlayError::SeekOutOfRange { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume_playback() {
+        let path = std::env::temp_dir().join(format!("pause-{}.wav", uuid::Uuid::new_v4()));
+        let mut writer = WavWriter::create(&path, 8000).unwrap();
+        writer.write_samples(&[0; 160 * 4]).unwrap();
+        drop(writer);
+
+        let mut player = RtpPlayer::new(MediaKind::Audio).await.unwrap().with_media_file(&path);
+        let pc = player.peer_connection();
+        let control = player.playback_control();
+        player.pause();
+        assert!(control.is_paused());
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            control.resume();
+        });
+        let started = std::time::Instant::now();
+        player.play_to_remote(pc.clone()).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert!(!player.is_paused());
+        // 暂停期间不发送，恢复后 4 帧全部发出
+        assert_eq!(player.stats().packets_sent, 4);
+
+        let mut sender = AudioFrameSender::attach(&pc, AudioCodec::Pcmu, 7000, "test", Arc::default(), None)
+            .unwrap()
+            .with_pause(PlaybackControl::default(), true);
+        sender.playback.pause();
+        let cancel = CancellationToken::new();
+        let stopper = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            stopper.cancel();
+        });
+        assert!(!sender.send_all(&[vec![0xFF; 160]], &cancel).await.unwrap());
+        // 暂停时发送静音帧，时间戳持续推进
+        assert!(sender.stats.lock().unwrap().packets_sent >= 2);
+        assert!(sender.timestamp >= 320);
+    }
+
+    #[tokio::test]
+    async fn test_comfort_noise_fills_pause() {
+        let player = RtpPlayer::new(MediaKind::Audio).await.unwrap().with_comfort_noise(true);
+        let local = player.get_local_sdp().unwrap();
+        assert!(offers_comfort_noise(&local));
+        assert!(local.contains("a=rtpmap:13 CN/8000\r\n"));
+        assert_eq!(add_comfort_noise(&local), local);
+
+        let pc = player.peer_connection();
+        for (negotiated, fill) in [(true, GapFill::ComfortNoise), (false, GapFill::PeriodicSilence)] {
+            let mut sender = AudioFrameSender::attach(&pc, AudioCodec::Pcmu, 7000, "test", Arc::default(), None)
+                .unwrap()
+                .with_pause(PlaybackControl::default(), true)
+                .with_comfort_noise(negotiated);
+            assert_eq!(sender.gap_fill, fill);
+            sender.playback.pause();
+            let cancel = CancellationToken::new();
+            let stopper = cancel.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(70)).await;
+                stopper.cancel();
+            });
+            assert!(!sender.send_all(&[vec![0xFF; 160]], &cancel).await.unwrap());
+            // 静默开始时只发一个填充包，之后每 10 帧一个，时间戳持续推进
+            assert_eq!(sender.stats.lock().unwrap().packets_sent, 1);
+            assert!(sender.timestamp >= 480);
+            assert!(sender.talkspurt);
+
+            sender.playback.resume();
+            assert!(sender.send_all(&[vec![0xFF; 160]], &CancellationToken::new()).await.unwrap());
+            assert_eq!(sender.stats.lock().unwrap().packets_sent, 2);
+            assert!(!sender.talkspurt);
         }
-        Ok(())
     }
-}
 
-#[async_trait]
-impl MediaPlayer for RtpPlayer {
-    fn media_kind(&self) -> MediaKind {
-        MediaKind::Audio
+    #[tokio::test]
+    async fn test_loop_keeps_timestamps_monotonic() {
+        let path = std::env::temp_dir().join(format!("loop-{}.wav", uuid::Uuid::new_v4()));
+        let mut writer = WavWriter::create(&path, 8000).unwrap();
+        writer.write_samples(&[0; 160]).unwrap();
+        drop(writer);
+
+        let mut player = RtpPlayer::new(MediaKind::Audio)
+            .await
+            .unwrap()
+            .with_media_file(&path)
+            .with_loop(Some(3));
+        let pc = player.peer_connection();
+        let started = std::time::Instant::now();
+        player.play_to_remote(pc.clone()).await.unwrap();
+        // 3 次循环各 1 帧，前两帧后各等待 20 ms
+        assert!(started.elapsed() >= Duration::from_millis(40));
+
+        assert_eq!(player.stats().packets_sent, 3);
+        assert_eq!(player.stats().bytes_sent, 480);
+
+        let mut sender =
+            AudioFrameSender::attach(&pc, AudioCodec::Pcmu, 7000, "test", Arc::default(), None).unwrap();
+        let frames = load_wav_frames(&path, AudioCodec::Pcmu, 0.0, false).unwrap();
+        for _ in 0..3 {
+            sender.send_all(&frames, &CancellationToken::new()).await.unwrap();
+        }
+        assert_eq!(sender.timestamp, 480);
+
+        // 无限循环可被取消
+        let mut endless = RtpPlayer::new(MediaKind::Audio)
+            .await
+            .unwrap()
+            .with_media_file(&path)
+            .with_loop(None);
+        endless.stop();
+        endless.play_to_remote(pc).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
     }
-    
-    fn payload_type(&self) -> u8 {
-        0 // PCMU
+
+    #[tokio::test]
+    async fn test_playback_handle_stops_and_completes() {
+        let path = std::env::temp_dir().join(format!("handle-{}.wav", uuid::Uuid::new_v4()));
+        let mut writer = WavWriter::create(&path, 8000).unwrap();
+        writer.write_samples(&[0; 160]).unwrap();
+        drop(writer);
+
+        // 自然播完
+        let player = RtpPlayer::new(MediaKind::Audio).await.unwrap().with_media_file(&path);
+        let pc = player.peer_connection();
+        let playback = Box::new(player).spawn_playback(pc);
+        playback.await_completion().await.unwrap();
+
+        // 无限循环在中途被打断
+        let player = RtpPlayer::new(MediaKind::Audio)
+            .await
+            .unwrap()
+            .with_media_file(&path)
+            .with_loop(None);
+        let pc = player.peer_connection();
+        let mut state = player.state_changes();
+        let playback = Box::new(player).spawn_playback(pc);
+        state.wait_for(|s| *s == MediaSessionState::Streaming).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!playback.is_finished());
+        playback.stop();
+        tokio::time::timeout(Duration::from_secs(1), playback.await_completion())
+            .await
+            .expect("播放未停止")
+            .unwrap();
+        assert_eq!(*state.borrow(), MediaSessionState::Stopped);
+        std::fs::remove_file(&path).unwrap();
     }
-    
-    fn clock_rate(&self) -> u32 {
-        8000
+
+    #[tokio::test]
+    async fn test_echo_reports_rfc4733_digits_once() {
+        let mut player = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        let local = player.get_local_sdp().unwrap();
+        let player_addr = crate::sip_transport::extract_peer_rtp_addr(&local).unwrap();
+
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let answer = format!(
+            "v=0\r\no=- 1 1 IN IP4 127.0.0.1\r\ns=-\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\n\
+             m=audio {port} RTP/AVP 0 101\r\na=rtpmap:0 PCMU/8000\r\n\
+             a=rtpmap:101 telephone-event/8000\r\na=sendrecv\r\n"
+        );
+        let mut events = player.dtmf_events().unwrap();
+        assert!(player.dtmf_events().is_none());
+        player.set_remote_sdp(&answer).await.unwrap();
+        assert_eq!(player.telephone_event_payload_type(), Some(101));
+
+        let payloads: [&[u8]; 4] = [&[9, 0x0A, 0, 160], &[9, 0x8A, 3, 32], &[9, 0x8A, 3, 32], &[9, 0x8A, 3, 32]];
+        for (seq, payload) in payloads.iter().enumerate() {
+            let packet = rtp_rs::RtpPacketBuilder::new()
+                .payload_type(101)
+                .ssrc(4321)
+                .sequence((seq as u16 + 1).into())
+                .timestamp(1000)
+                .marked(seq == 0)
+                .payload(payload)
+                .build()
+                .unwrap();
+            socket.send_to(&packet, &player_addr).await.unwrap();
+        }
+
+        let digit = tokio::time::timeout(Duration::from_secs(3), events.recv()).await.unwrap();
+        assert_eq!(digit, Some('9'));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(events.try_recv().is_err());
+        let stats = player.stats();
+        assert_eq!(stats.packets_received, 4);
+        assert_eq!(stats.bytes_received, 16);
+        assert_eq!(stats.packets_sent, 0);
     }
-    
-    async fn play_to_remote(&mut self, _peer_connection: Arc<PeerConnection>) -> Result<(), MediaPlayError> {
-        Err(MediaPlayError::Sdp("RtpPlayer不支持此操作".to_string()))
+
+    #[tokio::test]
+    async fn test_ipv6_media_echo() {
+        // 信令仍可走 IPv4，媒体单独使用 IPv6 回环地址
+        let Ok(socket) = tokio::net::UdpSocket::bind("[::1]:0").await else {
+            return;
+        };
+        let mut player = RtpPlayer::new_offline(MediaKind::Audio, AudioCodec::Pcmu, "[::1]:0".parse().unwrap())
+            .await
+            .unwrap();
+        let local = player.get_local_sdp().unwrap();
+        assert!(local.contains("c=IN IP6 ::1\r\n"));
+        let player_addr = crate::sip_transport::extract_peer_rtp_addr(&local).unwrap();
+        assert!(player_addr.starts_with("[::1]:"));
+
+        let answer = format!(
+            "v=0\r\no=- 1 1 IN IP6 ::1\r\ns=-\r\nc=IN IP6 [::1]\r\nt=0 0\r\n\
+             m=audio {} RTP/AVP 0\r\na=rtpmap:0 PCMU/8000\r\na=sendrecv\r\n",
+            socket.local_addr().unwrap().port()
+        );
+        player.set_remote_sdp(&answer).await.unwrap();
+
+        let packet = rtp_rs::RtpPacketBuilder::new()
+            .payload_type(0)
+            .ssrc(4321)
+            .sequence(1.into())
+            .timestamp(160)
+            .payload(&[0xFF; 160])
+            .build()
+            .unwrap();
+        let mut buf = vec![0u8; 1500];
+        let echoed = tokio::time::timeout(Duration::from_secs(3), async {
+            loop {
+                socket.send_to(&packet, &player_addr).await.unwrap();
+                if let Ok(Ok(_)) = tokio::time::timeout(Duration::from_millis(100), socket.recv_from(&mut buf)).await {
+                    break;
+                }
+            }
+        })
+        .await;
+        assert!(echoed.is_ok(), "IPv6 对端未收到回声");
+
+        // rustrtc 生成的 re-offer 同样通告 IPv6 地址类型
+        let reoffer = player.create_reoffer().await.unwrap();
+        assert!(!reoffer.contains("IN IP4"));
+        assert!(reoffer.contains("c=IN IP6 ::1\r\n"));
     }
-    
-    async fn start_echo(&mut self) -> Result<(), MediaPlayError> {
-        self.start_audio_echo().await
+
+    #[test]
+    fn test_is_vp8_keyframe() {
+        // 最简描述符 + 关键帧载荷头（P=0）
+        assert!(is_vp8_keyframe(&[0x10, 0x00, 0x9D, 0x01, 0x2A]));
+        assert!(!is_vp8_keyframe(&[0x10, 0x01, 0x00]));
+        // 非分区起始的分片不携带载荷头
+        assert!(!is_vp8_keyframe(&[0x00, 0x00]));
+        // X=1，I=1 且 15 位 PictureID，L=1，T=1
+        assert!(is_vp8_keyframe(&[0x90, 0xE0, 0x80, 0x01, 0x05, 0x20, 0x00]));
+        assert!(!is_vp8_keyframe(&[0x90, 0xE0, 0x80, 0x01, 0x05, 0x20, 0x01]));
+        assert!(!is_vp8_keyframe(&[0x90, 0x80]));
+        assert!(!is_vp8_keyframe(&[]));
     }
-    
-    async fn get_local_sdp(&self) -> Result<String, MediaPlayError> {
-        self.get_local_sdp()
+
+    #[tokio::test]
+    async fn test_video_echo_loops_frames_and_keyframe_requests() {
+        let mut player = RtpPlayer::new(MediaKind::Video).await.unwrap();
+        let local = player.get_local_sdp().unwrap();
+        let video_port: u16 = local
+            .lines()
+            .find_map(|l| l.strip_prefix("m=video "))
+            .and_then(|l| l.split_whitespace().next())
+            .and_then(|p| p.parse().ok())
+            .unwrap();
+        let ip = local.lines().find_map(|l| l.strip_prefix("c=IN IP4 ")).unwrap().trim();
+        let player_addr = format!("{}:{}", ip, video_port);
+
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let answer = format!(
+            "v=0\r\no=- 1 1 IN IP4 127.0.0.1\r\ns=-\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\n\
+             m=video {port} RTP/AVP 96\r\na=rtpmap:96 VP8/90000\r\na=rtcp-fb:96 nack pli\r\n\
+             a=rtcp-mux\r\na=sendrecv\r\n"
+        );
+        player.set_remote_sdp(&answer).await.unwrap();
+        assert!(player.is_echo_running());
+
+        let keyframe: &[u8] = &[0x10, 0x00, 0x9D, 0x01, 0x2A];
+        let empty: &[u8] = &[];
+        for (seq, payload) in [keyframe, empty, &[0x10, 0x01, 0x02]].into_iter().enumerate() {
+            let packet = rtp_rs::RtpPacketBuilder::new()
+                .payload_type(96)
+                .ssrc(1234)
+                .sequence((seq as u16 + 1).into())
+                .timestamp(3000 * (seq as u32 + 1))
+                .marked(true)
+                .payload(payload)
+                .build()
+                .unwrap();
+            socket.send_to(&packet, &player_addr).await.unwrap();
+        }
+
+        // 回送的 RTP 包（跳过 RTCP），空载荷不回送
+        let mut echoed = Vec::new();
+        let mut buf = [0u8; 1500];
+        while echoed.len() < 2 {
+            let (len, _) = tokio::time::timeout(Duration::from_secs(3), socket.recv_from(&mut buf))
+                .await
+                .expect("未收到回送的视频帧")
+                .unwrap();
+            let Ok(packet) = rtp_rs::RtpReader::new(&buf[..len]) else {
+                continue;
+            };
+            if (200..=206).contains(&buf[1]) {
+                continue;
+            }
+            assert_eq!(packet.payload_type(), 96);
+            echoed.push(packet.payload().to_vec());
+        }
+        assert_eq!(echoed[0], keyframe);
+        assert_eq!(echoed[1], vec![0x10, 0x01, 0x02]);
+
+        // 对端对回送流发出 PLI，播放器转为向对端请求关键帧
+        let echo_ssrc = u32::from_be_bytes(buf[8..12].try_into().unwrap());
+        let mut pli = vec![0x81, 206, 0, 2];
+        pli.extend_from_slice(&1234u32.to_be_bytes());
+        pli.extend_from_slice(&echo_ssrc.to_be_bytes());
+        socket.send_to(&pli, &player_addr).await.unwrap();
+        let requested = tokio::time::timeout(Duration::from_secs(3), async {
+            loop {
+                let (len, _) = socket.recv_from(&mut buf).await.unwrap();
+                // PSFB（206）PLI 或 FIR，媒体源为对端 SSRC
+                let feedback = len >= 12 && matches!(buf[1], 192 | 206);
+                if feedback && u32::from_be_bytes(buf[8..12].try_into().unwrap()) == 1234 {
+                    break;
+                }
+            }
+        })
+        .await;
+        assert!(requested.is_ok(), "未向对端请求关键帧");
     }
-}
 
-/// 音频回声播放器
-pub struct AudioEchoPlayer {
-    rtp_player: RtpPlayer,
-}
+    #[tokio::test]
+    async fn test_play_and_collect_digits() {
+        // 3 秒的提示音，按键应打断播放
+        let path = std::env::temp_dir().join(format!("prompt-{}.wav", uuid::Uuid::new_v4()));
+        let mut writer = WavWriter::create(&path, 8000).unwrap();
+        writer.write_samples(&[0; 24000]).unwrap();
+        drop(writer);
+        let prompt = path.to_str().unwrap();
 
-impl AudioEchoPlayer {
-    /// 创建新的音频回声播放器
-    pub async fn new() -> Result<(Self, String), MediaPlayError> {
-        let rtp_player = RtpPlayer::new(MediaKind::Audio).await?;
-        let sdp = rtp_player.get_local_sdp()?;
-        
-        Ok((
-            Self { rtp_player },
-            sdp
-        ))
+        let mut unnegotiated = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        assert!(matches!(
+            unnegotiated.play_and_collect(prompt, 4, Duration::from_millis(10), None).await,
+            Err(MediaPlayError::Sdp(_))
+        ));
+
+        // 回声运行时与仅检测按键两种接收方式
+        for echo in [true, false] {
+            let metrics = crate::metrics::Metrics::new();
+            let mut player = RtpPlayer::new(MediaKind::Audio).await.unwrap().with_metrics(metrics.clone());
+            let player_addr =
+                crate::sip_transport::extract_peer_rtp_addr(&player.get_local_sdp().unwrap()).unwrap();
+            let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let port = socket.local_addr().unwrap().port();
+            let answer = format!(
+                "v=0\r\no=- 1 1 IN IP4 127.0.0.1\r\ns=-\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\n\
+                 m=audio {port} RTP/AVP 0 101\r\na=rtpmap:0 PCMU/8000\r\n\
+                 a=rtpmap:101 telephone-event/8000\r\na=sendrecv\r\n"
+            );
+            if echo {
+                player.set_remote_sdp(&answer).await.unwrap();
+            } else {
+                let desc = SessionDescription::parse(SdpType::Answer, &answer).unwrap();
+                player.peer_connection().set_remote_description(desc).await.unwrap();
+                player.telephone_event = Some(101);
+            }
+
+            // 提示音播放期间按下 1，之后按 2 与结束键 #
+            tokio::spawn(async move {
+                let mut seq = 0u16;
+                for (index, event) in [1u8, 2, 11].into_iter().enumerate() {
+                    tokio::time::sleep(Duration::from_millis(30)).await;
+                    for payload in [[event, 0x0A, 0, 160], [event, 0x8A, 3, 32]] {
+                        seq += 1;
+                        let packet = rtp_rs::RtpPacketBuilder::new()
+                            .payload_type(101)
+                            .ssrc(4321)
+                            .sequence(seq.into())
+                            .timestamp(1000 * (index as u32 + 1))
+                            .marked(payload[1] & 0x80 == 0)
+                            .payload(&payload)
+                            .build()
+                            .unwrap();
+                        socket.send_to(&packet, &player_addr).await.unwrap();
+                    }
+                }
+            });
+            let started = std::time::Instant::now();
+            let digits = player
+                .play_and_collect(prompt, 4, Duration::from_secs(2), Some('#'))
+                .await
+                .unwrap();
+            assert_eq!(digits, "12", "echo={}", echo);
+            assert!(started.elapsed() < Duration::from_secs(2), "提示音未被按键打断");
+            // 按键通道归还，之后仍可取得
+            assert!(player.dtmf_events().is_some());
+            let text = metrics.render();
+            assert!(!text.contains("rtp_packets_received_total 0"), "{}", text);
+            assert!(!text.contains("rtp_packets_sent_total 0"), "{}", text);
+        }
+        std::fs::remove_file(&path).unwrap();
     }
-    
-    /// 初始化播放器
-    pub async fn initialize(&mut self) -> Result<(), MediaPlayError> {
-        // 已经在new()中初始化了
-        Ok(())
+
+    #[tokio::test]
+    async fn test_media_session_state_transitions() {
+        let mut player = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        let mut changes = player.state_changes();
+        assert_eq!(player.state(), MediaSessionState::Offering);
+        assert!(player.last_error().is_none());
+
+        let answer = "v=0\r\no=- 1 1 IN IP4 127.0.0.1\r\ns=-\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\n\
+                      m=audio 40000 RTP/AVP 0\r\na=rtpmap:0 PCMU/8000\r\na=sendrecv\r\n";
+        player.set_remote_sdp(answer).await.unwrap();
+        assert_eq!(player.state(), MediaSessionState::Streaming);
+        assert!(changes.has_changed().unwrap());
+        assert_eq!(*changes.borrow_and_update(), MediaSessionState::Streaming);
+
+        player.stop_echo();
+        assert_eq!(player.state(), MediaSessionState::Stopped);
+
+        // answer 中没有可用的编解码器时协商失败
+        let mut player = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        let bad = answer.replace("RTP/AVP 0", "RTP/AVP 18").replace("0 PCMU", "18 G729");
+        let err = player.set_remote_sdp(&bad).await.unwrap_err();
+        assert_eq!(player.state(), MediaSessionState::Failed);
+        assert_eq!(player.last_error().unwrap().to_string(), err.to_string());
+
+        let (answerer, _) = RtpPlayer::new_answerer(answer).await.unwrap();
+        assert_eq!(answerer.state(), MediaSessionState::Negotiated);
     }
-    
-    /// 设置远程SDP
-    pub async fn set_remote_sdp(&mut self, remote_sdp: &str) -> Result<(), MediaPlayError> {
-        self.rtp_player.set_remote_sdp(remote_sdp).await
+
+    #[tokio::test]
+    async fn test_stop_echo_terminates_tasks() {
+        let mut player = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        let answer = "v=0\r\no=- 1 1 IN IP4 127.0.0.1\r\ns=-\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\n\
+                      m=audio 40000 RTP/AVP 0\r\na=rtpmap:0 PCMU/8000\r\na=sendrecv\r\n";
+        player.set_remote_sdp(answer).await.unwrap();
+        assert!(player.is_echo_running());
+        assert_eq!(player.echo_tasks.len(), 2);
+
+        // 回声循环与 RTCP 任务都持有运行标志，全部退出后标志随之释放
+        let running = Arc::downgrade(player.running.as_ref().unwrap());
+        player.stop_echo();
+        assert!(!player.is_echo_running());
+        assert!(player.echo_tasks.is_empty());
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while running.upgrade().is_some() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("回声任务未退出");
+
+        // 停止后可以重新启动
+        player.start_audio_echo().await.unwrap();
+        assert!(player.is_echo_running());
+        assert_eq!(player.echo_tasks.len(), 2);
     }
-    
-    /// 停止回声
-    pub fn stop_echo(&mut self) {
-        self.rtp_player.stop_echo()
+
+    #[tokio::test]
+    async fn test_rtcp_events_surface_feedback() {
+        use rustrtc::rtp::{marshal_rtcp_packets, GenericNack, PictureLossIndication, RtcpPacket};
+
+        let mut player = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        let local = player.get_local_sdp().unwrap();
+        let port = extract_media_port(&local, "audio").unwrap();
+        let ip = local.lines().find_map(|l| l.strip_prefix("c=IN IP4 ")).unwrap().trim().to_string();
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let answer = format!(
+            "v=0\r\no=- 1 1 IN IP4 127.0.0.1\r\ns=-\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\n\
+             m=audio {} RTP/AVP 0\r\na=rtpmap:0 PCMU/8000\r\na=rtcp-mux\r\na=sendrecv\r\n",
+            socket.local_addr().unwrap().port()
+        );
+        let mut events = player.rtcp_events();
+        player.set_remote_sdp(&answer).await.unwrap();
+        assert!(player.is_echo_running());
+
+        // 反馈只转交给 SSRC 匹配的发送器
+        let ssrc = player
+            .peer_connection()
+            .get_transceivers()
+            .into_iter()
+            .find_map(|t| t.sender())
+            .unwrap()
+            .ssrc();
+        let feedback = marshal_rtcp_packets(&[
+            RtcpPacket::PictureLossIndication(PictureLossIndication { sender_ssrc: 4321, media_ssrc: ssrc }),
+            RtcpPacket::GenericNack(GenericNack {
+                sender_ssrc: 4321,
+                media_ssrc: ssrc,
+                lost_packets: vec![7, 9],
+            }),
+        ])
+        .unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(3), async {
+            loop {
+                socket.send_to(&feedback, format!("{}:{}", ip, port)).await.unwrap();
+                if let Ok(Ok(event)) = tokio::time::timeout(Duration::from_millis(100), events.recv()).await {
+                    let next = events.recv().await.unwrap();
+                    return (event, next);
+                }
+            }
+        })
+        .await
+        .expect("未收到 RTCP 反馈事件");
+        assert_eq!(received.0, RtcpEvent::PictureLoss { media_ssrc: ssrc });
+        assert_eq!(
+            received.1,
+            RtcpEvent::Nack {
+                media_ssrc: ssrc,
+                lost_packets: vec![7, 9]
+            }
+        );
+        assert_eq!(
+            RtcpEvent::from_packet(&RtcpPacket::Goodbye(rustrtc::rtp::Goodbye {
+                sources: vec![1],
+                reason: None
+            })),
+            None
+        );
     }
-}
 
-#[async_trait]
-impl MediaPlayer for AudioEchoPlayer {
-    fn media_kind(&self) -> MediaKind {
-        MediaKind::Audio
+    #[tokio::test]
+    async fn test_jitter_buffer_reorders_packets() {
+        let player = RtpPlayer::new(MediaKind::Audio).await.unwrap();
+        assert_eq!(player.jitter_buffer(), None);
+        let player = player.with_jitter_buffer(5);
+        assert_eq!(player.jitter_buffer(), Some(Duration::from_millis(20)));
+        let player = player.with_jitter_buffer(2000);
+        assert_eq!(player.jitter_buffer(), Some(Duration::from_millis(500)));
+
+        let frame = |seq: u16| {
+            MediaSample::Audio(AudioFrame {
+                sequence_number: Some(seq),
+                rtp_timestamp: seq as u32 * 160,
+                data: vec![0xFF; 160].into(),
+                ..Default::default()
+            })
+        };
+        let sequence = |sample: MediaSample| match sample {
+            MediaSample::Audio(f) => f.sequence_number.unwrap(),
+            MediaSample::Video(_) => unreachable!(),
+        };
+
+        // 不缓冲时按到达顺序交付
+        let (source, track, _) = rustrtc::media::sample_track(MediaKind::Audio, 10);
+        let mut direct = JitteredTrack::new(track, None);
+        for seq in [2, 1, 3] {
+            source.send(frame(seq)).await.unwrap();
+        }
+        let mut order = Vec::new();
+        for _ in 0..3 {
+            order.push(sequence(direct.recv().await.unwrap()));
+        }
+        assert_eq!(order, vec![2, 1, 3]);
+
+        // 缓冲时乱序包按序号重排
+        let (source, track, _) = rustrtc::media::sample_track(MediaKind::Audio, 10);
+        let mut buffered = JitteredTrack::new(track, Some(Duration::from_millis(20)));
+        for seq in [2, 1, 3] {
+            source.send(frame(seq)).await.unwrap();
+        }
+        let mut order = Vec::new();
+        for _ in 0..3 {
+            let sample = tokio::time::timeout(Duration::from_secs(1), buffered.recv()).await.unwrap();
+            order.push(sequence(sample.unwrap()));
+        }
+        assert_eq!(order, vec![1, 2, 3]);
     }
-    
-    fn payload_type(&self) -> u8 {
-        0 // PCMU
+
+    #[test]
+    fn test_stats_apply_rtcp_reports() {
+        use rustrtc::rtp::{ReceiverReport, ReportBlock, RtcpPacket, SenderReport};
+        let block = |ssrc, fraction_lost, jitter| ReportBlock {
+            ssrc,
+            fraction_lost,
+            packets_lost: 3,
+            highest_sequence: 100,
+            jitter,
+            last_sender_report: 0,
+            delay_since_last_sender_report: 0,
+        };
+        let mut stats = RtpStats::default();
+        stats.apply_rtcp(
+            &RtcpPacket::ReceiverReport(ReceiverReport {
+                sender_ssrc: 1,
+                report_blocks: vec![block(9999, 200, 1), block(5000, 64, 40)],
+            }),
+            5000,
+        );
+        assert_eq!((stats.jitter, stats.fraction_lost, stats.packets_lost), (40, 64, 3));
+        assert_eq!(stats.loss_ratio(), 0.25);
+
+        // 不含本端报告块的 SR 只更新对端发送计数
+        stats.apply_rtcp(
+            &RtcpPacket::SenderReport(SenderReport {
+                sender_ssrc: 1,
+                ntp_most: 0,
+                ntp_least: 0,
+                rtp_timestamp: 0,
+                packet_count: 50,
+                octet_count: 8000,
+                report_blocks: vec![],
+            }),
+            5000,
+        );
+        assert_eq!((stats.remote_packets_sent, stats.remote_bytes_sent), (50, 8000));
+        assert_eq!(stats.jitter, 40);
+        assert_eq!(stats.reports_received, 2);
     }
-    
-    fn clock_rate(&self) -> u32 {
-        8000
+
+    #[test]
+    fn test_ssrc_filter_locks_first() {
+        let mut filter = SsrcFilter::new(SsrcSelection::First);
+        assert!(filter.accept(Some(1111)));
+        assert!(!filter.accept(Some(2222)));
+        assert!(filter.accept(Some(1111)));
+        assert!(filter.accept(None));
+        assert_eq!(filter.selected(), Some(1111));
     }
-    
-    async fn play_to_remote(&mut self, _peer_connection: Arc<PeerConnection>) -> Result<(), MediaPlayError> {
-        Err(MediaPlayError::Sdp("AudioEchoPlayer不支持此操作".to_string()))
+
+    #[test]
+    fn test_ssrc_filter_exact_and_any() {
+        let mut exact = SsrcFilter::new(SsrcSelection::Exact(2222));
+        assert!(!exact.accept(Some(1111)));
+        assert!(exact.accept(Some(2222)));
+
+        let mut any = SsrcFilter::new(SsrcSelection::Any);
+        assert!(any.accept(Some(1111)) && any.accept(Some(2222)));
+        assert_eq!(any.selected(), None);
     }
-    
-    async fn start_echo(&mut self) -> Result<(), MediaPlayError> {
-        self.rtp_player.start_audio_echo().await
+
+    #[test]
+    fn test_accepted_kinds_skips_declined() {
+        let sdp = "v=0\r\nm=audio 4000 RTP/AVP 0\r\nm=video 0 RTP/AVP 96\r\n";
+        assert_eq!(accepted_kinds(sdp), vec![MediaKind::Audio]);
     }
-    
-    async fn get_local_sdp(&self) -> Result<String, MediaPlayError> {
-        self.rtp_player.get_local_sdp()
+
+    #[test]
+    fn test_comfort_noise_replaced_with_silence() {
+        let cn = MediaSample::Audio(AudioFrame {
+            rtp_timestamp: 320,
+            data: vec![40u8].into(),
+            payload_type: Some(CN_PAYLOAD_TYPE),
+            ..Default::default()
+        });
+        match comfort_noise_to_silence(cn.clone(), AudioCodec::Pcmu) {
+            MediaSample::Audio(frame) => {
+                assert_eq!(frame.payload_type, Some(0));
+                assert_eq!(frame.rtp_timestamp, 320);
+                assert_eq!(frame.data.len(), 160);
+                assert!(frame.data.iter().all(|b| *b == 0xFF));
+            }
+            _ => panic!("应为音频样本"),
+        }
+
+        let media = MediaSample::Audio(AudioFrame {
+            data: vec![1, 2, 3].into(),
+            payload_type: Some(0),
+            ..Default::default()
+        });
+        match comfort_noise_to_silence(cn, AudioCodec::Pcma) {
+            MediaSample::Audio(frame) => {
+                assert_eq!(frame.payload_type, Some(8));
+                assert!(frame.data.iter().all(|b| *b == 0xD5));
+            }
+            _ => panic!("应为音频样本"),
+        }
+
+        match comfort_noise_to_silence(media, AudioCodec::Pcmu) {
+            MediaSample::Audio(frame) => assert_eq!(&frame.data[..], &[1, 2, 3]),
+            _ => panic!("应为音频样本"),
+        }
     }
-}
\ No newline at end of file
+}
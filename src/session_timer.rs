@@ -0,0 +1,246 @@
+/// SIP 会话定时器模块（RFC 4028）
+///
+/// 解析/生成 `Session-Expires` 与 `Min-SE` 头部，并在本端为刷新方时
+/// 按协商间隔的一半周期性发送 UPDATE（对端不支持时为 re-INVITE）保持会话
+use crate::error::{CallError, CallResult};
+use crate::rtp_play::SharedRtpPlayer;
+use crate::sip_body::{response_sdp, SDP_CONTENT_TYPE};
+use rsip::Header;
+use rsipstack::dialog::client_dialog::ClientInviteDialog;
+use rsipstack::dialog::DialogId;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn, Instrument};
+
+/// RFC 4028 规定的最小会话间隔（秒）
+pub const MIN_SESSION_EXPIRES: u32 = 90;
+
+/// 会话刷新方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refresher {
+    /// 主叫（本端）刷新
+    Uac,
+    /// 被叫刷新
+    Uas,
+}
+
+/// `Session-Expires` 头部内容
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionExpires {
+    /// 会话间隔（秒）
+    pub interval: u32,
+    /// 刷新方，未指定时为 `None`
+    pub refresher: Option<Refresher>,
+}
+
+impl SessionExpires {
+    /// 由本端刷新的会话间隔，低于 [`MIN_SESSION_EXPIRES`] 时取最小值
+    pub fn uac(interval: u32) -> Self {
+        Self {
+            interval: interval.max(MIN_SESSION_EXPIRES),
+            refresher: Some(Refresher::Uac),
+        }
+    }
+
+    /// 从头部列表中解析 `Session-Expires`（含紧凑形式 `x`）
+    pub fn parse(headers: &rsip::Headers) -> Option<Self> {
+        let value = find_header(headers, &["Session-Expires", "x"])?;
+        let mut parts = value.split(';').map(str::trim);
+        let interval = parts.next()?.parse().ok()?;
+        let refresher = parts
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("refresher"))
+            .and_then(|(_, value)| match value.trim().to_ascii_lowercase().as_str() {
+                "uac" => Some(Refresher::Uac),
+                "uas" => Some(Refresher::Uas),
+                _ => None,
+            });
+        Some(Self { interval, refresher })
+    }
+
+    /// 生成 `Session-Expires` 头部
+    pub fn to_header(self) -> Header {
+        let value = match self.refresher {
+            Some(Refresher::Uac) => format!("{};refresher=uac", self.interval),
+            Some(Refresher::Uas) => format!("{};refresher=uas", self.interval),
+            None => self.interval.to_string(),
+        };
+        Header::Other("Session-Expires".into(), value)
+    }
+
+    /// 本端是否负责刷新（对端未指定刷新方时由本端刷新）
+    pub fn local_refresh(self) -> bool {
+        self.refresher != Some(Refresher::Uas)
+    }
+}
+
+/// 从 422 响应等头部中解析 `Min-SE`（秒）
+pub fn parse_min_se(headers: &rsip::Headers) -> Option<u32> {
+    find_header(headers, &["Min-SE"])?
+        .split(';')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// INVITE 中声明会话定时器支持所需的头部
+pub fn invite_headers(session: SessionExpires) -> Vec<Header> {
+    vec![Header::Supported("timer".into()), session.to_header()]
+}
+
+/// 对话内的媒体状态，供会话刷新的 re-INVITE 生成 offer
+#[derive(Default)]
+pub(crate) struct SessionMedia {
+    /// 最近一次在对话内发出的 SDP offer，未附加媒体会话时原样重发（`o=` 版本不变）
+    pub(crate) offer: Option<Vec<u8>>,
+    /// `SipClient::attach_media` 附加的媒体会话
+    pub(crate) player: Option<SharedRtpPlayer>,
+}
+
+/// 各对话的媒体状态，键为对话 ID
+pub(crate) type MediaSessions = Arc<Mutex<HashMap<DialogId, SessionMedia>>>;
+
+/// 启动会话刷新任务：每隔半个会话间隔发送携带 `Session-Expires` 的 UPDATE
+///
+/// `use_update` 为 `false` 时（对端未在 `Allow` 中声明 UPDATE）改用 re-INVITE 刷新，
+/// offer 见 [`refresh_session`]。刷新 2xx 中的 `Session-Expires` 会更新后续的刷新间隔，
+/// 对端接管刷新（`refresher=uas`）、对话终止、刷新请求被以 481 等非 2xx 拒绝、
+/// 或 `cancel` 被取消时退出
+pub(crate) fn spawn_refresher(
+    dialog: ClientInviteDialog,
+    session: SessionExpires,
+    use_update: bool,
+    media: MediaSessions,
+    cancel: CancellationToken,
+) -> JoinHandle<()> {
+    let refresher = async move {
+        let mut session = session;
+        loop {
+            let period = Duration::from_secs(session.interval.max(MIN_SESSION_EXPIRES) as u64 / 2);
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = tokio::time::sleep(period) => {}
+            }
+            if dialog.state().is_terminated() {
+                break;
+            }
+            match refresh_session(&dialog, session, use_update, &media).await {
+                Ok(Some(next)) => {
+                    info!("⏲️ 会话刷新成功 ({}s)", next.interval);
+                    session = next;
+                }
+                Ok(None) => {
+                    info!("⏲️ 对端接管会话刷新，停止本端刷新");
+                    break;
+                }
+                Err(e @ (CallError::CallRejected { .. } | CallError::NotConnected)) => {
+                    warn!("会话刷新被拒绝: {}，停止刷新", e);
+                    break;
+                }
+                Err(e) => warn!("会话刷新失败: {}", e),
+            }
+        }
+    };
+    tokio::spawn(refresher.in_current_span())
+}
+
+/// 发送一次会话刷新，返回 2xx 协商出的会话间隔；对端接管刷新时返回 `None`
+///
+/// re-INVITE 刷新时优先由附加的媒体会话按当前本地描述生成 offer（内容未变时 `o=` 版本不变），
+/// 并将 2xx 中的 answer 应用到该媒体会话；未附加时原样重发最近一次发出的 offer
+pub(crate) async fn refresh_session(
+    dialog: &ClientInviteDialog,
+    session: SessionExpires,
+    use_update: bool,
+    media: &MediaSessions,
+) -> CallResult<Option<SessionExpires>> {
+    let mut headers = vec![Header::Supported("timer".into()), session.to_header()];
+    let response = if use_update {
+        dialog.update(Some(headers), None).await?
+    } else {
+        let (offer, player) = media
+            .lock()
+            .unwrap()
+            .get(&dialog.id())
+            .map(|media| (media.offer.clone(), media.player.clone()))
+            .unwrap_or_default();
+        headers.push(Header::ContentType(SDP_CONTENT_TYPE.into()));
+        match player {
+            Some(player) => {
+                let mut player = player.lock().await;
+                let offer = player.create_reoffer().await?;
+                let response = dialog.reinvite(Some(headers), Some(offer.clone().into_bytes())).await?;
+                match response.as_ref().filter(|resp| resp.status_code.kind() == rsip::StatusCodeKind::Successful) {
+                    Some(resp) => {
+                        let answer = response_sdp(resp).ok_or_else(|| CallError::invalid_sdp("刷新应答缺少 SDP answer"))?;
+                        player.apply_answer(&answer).await?;
+                        if let Some(media) = media.lock().unwrap().get_mut(&dialog.id()) {
+                            media.offer = Some(offer.into_bytes());
+                        }
+                    }
+                    None => {
+                        let direction = player.local_direction();
+                        if let Err(e) = player.restore_direction(direction).await {
+                            warn!("恢复媒体会话失败: {}", e);
+                        }
+                    }
+                }
+                response
+            }
+            None => {
+                let offer = offer.ok_or_else(|| CallError::invalid_sdp("没有可用于 re-INVITE 刷新的 SDP offer"))?;
+                dialog.reinvite(Some(headers), Some(offer)).await?
+            }
+        }
+    };
+
+    match response {
+        Some(resp) if resp.status_code.kind() == rsip::StatusCodeKind::Successful => {
+            let granted = SessionExpires::parse(&resp.headers).unwrap_or(session);
+            Ok(granted.local_refresh().then(|| SessionExpires::uac(granted.interval)))
+        }
+        Some(resp) => Err(CallError::rejected(&resp.status_code)),
+        None => Err(CallError::NotConnected),
+    }
+}
+
+fn find_header<'a>(headers: &'a rsip::Headers, names: &[&str]) -> Option<&'a str> {
+    headers.iter().find_map(|header| match header {
+        Header::Other(name, value) if names.iter().any(|n| name.trim().eq_ignore_ascii_case(n)) => {
+            Some(value.as_str())
+        }
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_session_expires_and_min_se() {
+        let headers: rsip::Headers = vec![
+            Header::Other("Session-Expires".into(), "1800;refresher=uas".into()),
+            Header::Other("Min-SE".into(), "600".into()),
+        ]
+        .into();
+        let session = SessionExpires::parse(&headers).unwrap();
+        assert_eq!(session, SessionExpires { interval: 1800, refresher: Some(Refresher::Uas) });
+        assert!(!session.local_refresh());
+        assert_eq!(parse_min_se(&headers), Some(600));
+
+        let compact: rsip::Headers = vec![Header::Other("x".into(), "300".into())].into();
+        assert!(SessionExpires::parse(&compact).unwrap().local_refresh());
+
+        // 低于 90 秒时取最小值
+        assert_eq!(SessionExpires::uac(30).interval, MIN_SESSION_EXPIRES);
+        assert_eq!(
+            SessionExpires::uac(1800).to_header(),
+            Header::Other("Session-Expires".into(), "1800;refresher=uac".into())
+        );
+    }
+}
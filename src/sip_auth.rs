@@ -0,0 +1,976 @@
+/// SIP Digest 认证辅助模块
+///
+/// 解析 WWW-Authenticate / Proxy-Authenticate 挑战参数，
+/// 并决定收到挑战后是否需要（再次）发送认证
+#[cfg(feature = "aka")]
+use crate::sip_aka::AkaKey;
+use rsip::headers::auth::{Algorithm, AuthQop, Qop};
+use rsip::prelude::{ToTypedHeader, UntypedHeader};
+use rsip::Response;
+use rsipstack::dialog::authenticate::Credential;
+use rsipstack::transaction::{random_text, CNONCE_LEN};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// 动态凭证回调
+///
+/// 以挑战的 realm 与 nonce 调用，返回本次应答使用的密码（如轮换的令牌），
+/// 用户名仍取自静态凭证
+pub type CredentialProvider =
+    Arc<dyn Fn(&str, &str) -> Pin<Box<dyn Future<Output = String> + Send>> + Send + Sync>;
+
+/// 按挑战解析实际使用的凭证：配置了回调时以其结果替换静态密码
+pub async fn provide_credential(
+    credential: &Credential,
+    provider: Option<&CredentialProvider>,
+    realm: &str,
+    nonce: &str,
+) -> Credential {
+    let mut credential = credential.clone();
+    if let Some(provider) = provider {
+        credential.password = provider(realm, nonce).await;
+    }
+    credential
+}
+
+/// 从认证头中提取指定参数的值（去掉引号）
+///
+/// 参数名不区分大小写，例如 `extract_param(h, "nonce")`；
+/// 引号内的逗号、空格和 `\"` 转义不会截断参数值
+pub fn extract_param(header: &str, name: &str) -> Option<String> {
+    // 跳过认证方案（如 "Digest "）
+    let params = match header.trim_start().split_once(char::is_whitespace) {
+        Some((scheme, rest)) if !scheme.contains('=') => rest,
+        _ => header,
+    };
+
+    split_params(params)
+        .into_iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+/// 将 `k1=v1, k2="v,2"` 形式的参数列表切分为 (名称, 值) 对
+fn split_params(params: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut chars = params.chars().peekable();
+    loop {
+        // 跳过分隔符
+        while chars.peek().is_some_and(|c| *c == ',' || c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=' && *c != ',') {
+            key.push(c);
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'=').is_some() {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.next_if_eq(&'"').is_some() {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => value.extend(chars.next()),
+                        _ => value.push(c),
+                    }
+                }
+                // 丢弃右引号之后到下一个逗号之间的内容
+                while chars.next_if(|c| *c != ',').is_some() {}
+            } else {
+                while let Some(c) = chars.next_if(|c| *c != ',') {
+                    value.push(c);
+                }
+                value = value.trim_end().to_string();
+            }
+        }
+        pairs.push((key.trim().to_string(), value));
+    }
+    pairs
+}
+
+/// Digest 认证挑战
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DigestChallenge {
+    pub realm: String,
+    pub nonce: String,
+    pub opaque: Option<String>,
+    pub algorithm: Option<String>,
+    pub qop: Option<String>,
+    /// `stale=true` 表示仅 nonce 过期，凭证本身是正确的
+    pub stale: bool,
+}
+
+impl DigestChallenge {
+    /// 解析认证头的值
+    pub fn parse(header: &str) -> Option<Self> {
+        Some(Self {
+            realm: extract_param(header, "realm")?,
+            nonce: extract_param(header, "nonce")?,
+            opaque: extract_param(header, "opaque"),
+            algorithm: extract_param(header, "algorithm"),
+            qop: extract_param(header, "qop"),
+            stale: extract_param(header, "stale")
+                .map(|v| v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+        })
+    }
+
+    /// 从挑战提供的 qop 列表（如 `"auth,auth-int"`）中选择使用的 qop，优先 `auth`
+    pub fn preferred_qop(&self) -> Option<&str> {
+        let options: Vec<&str> = self.qop.as_deref()?.split(',').map(str::trim).collect();
+        ["auth", "auth-int"]
+            .into_iter()
+            .find(|q| options.iter().any(|o| o.eq_ignore_ascii_case(q)))
+    }
+
+    /// 转换为 rsip 的类型化挑战
+    ///
+    /// rsip 无法解析 `qop="auth,auth-int"` 这类 qop 列表，此时按 `preferred_qop` 选定单个 qop
+    pub fn to_typed(&self) -> rsip::typed::WwwAuthenticate {
+        rsip::typed::WwwAuthenticate {
+            scheme: rsip::headers::auth::Scheme::Digest,
+            realm: self.realm.clone(),
+            domain: None,
+            nonce: self.nonce.clone(),
+            opaque: self.opaque.clone(),
+            stale: self.stale.then(|| "true".to_string()),
+            algorithm: self.algorithm.as_deref().and_then(parse_algorithm),
+            qop: self.preferred_qop().and_then(|q| q.parse().ok()),
+            charset: None,
+        }
+    }
+
+    /// 从 401/407 响应中解析挑战（优先 WWW-Authenticate）
+    pub fn from_response(resp: &Response) -> Option<Self> {
+        let www = resp.headers.iter().find_map(|h| match h {
+            rsip::Header::WwwAuthenticate(h) => Some(h.value().to_string()),
+            _ => None,
+        });
+        let proxy = || {
+            resp.headers.iter().find_map(|h| match h {
+                rsip::Header::ProxyAuthenticate(h) => Some(h.value().to_string()),
+                _ => None,
+            })
+        };
+        www.or_else(proxy).and_then(|value| Self::parse(&value))
+    }
+}
+
+/// 认证重试状态
+///
+/// 首次挑战总是应答；认证发送后再次收到挑战时，
+/// 仅当挑战带 `stale=true` 且允许 stale 重试时再应答一次
+#[derive(Debug, Clone, Default)]
+pub struct AuthRetryState {
+    allow_stale_retry: bool,
+    auth_sent: bool,
+    stale_retry_used: bool,
+}
+
+impl AuthRetryState {
+    /// 创建认证重试状态
+    pub fn new(allow_stale_retry: bool) -> Self {
+        Self {
+            allow_stale_retry,
+            ..Default::default()
+        }
+    }
+
+    /// 收到挑战时调用，返回是否应该发送认证
+    pub fn on_challenge(&mut self, stale: bool) -> bool {
+        if !self.auth_sent {
+            self.auth_sent = true;
+            return true;
+        }
+        if stale && self.allow_stale_retry && !self.stale_retry_used {
+            self.stale_retry_used = true;
+            return true;
+        }
+        false
+    }
+}
+
+/// nonce-count 跟踪器
+///
+/// 同一 nonce 每使用一次 `nc` 递增（服务器据此拒绝重放），nonce 变化时从 1 重新计数
+#[derive(Debug, Clone, Default)]
+pub struct NonceCounter {
+    nonce: Option<String>,
+    count: u8,
+}
+
+impl NonceCounter {
+    /// 返回本次使用 `nonce` 应携带的 nonce-count
+    ///
+    /// rsip 的 nc 为 8 位，计数用尽时返回 `None`，此时应等待服务器下发新 nonce
+    pub fn next(&mut self, nonce: &str) -> Option<u8> {
+        if self.nonce.as_deref() != Some(nonce) {
+            self.nonce = Some(nonce.to_string());
+            self.count = 0;
+        }
+        self.count = self.count.checked_add(1)?;
+        Some(self.count)
+    }
+}
+
+/// Digest 摘要计算的输入（RFC 7616 §3.4.1）
+#[derive(Debug, Clone)]
+pub struct DigestInput<'a> {
+    pub algorithm: Algorithm,
+    pub username: &'a str,
+    pub realm: &'a str,
+    pub password: &'a str,
+    pub method: &'a str,
+    pub uri: &'a str,
+    pub nonce: &'a str,
+    pub qop: Option<&'a AuthQop>,
+    /// 请求消息体，仅 `qop=auth-int` 时参与 HA2 计算
+    pub body: &'a [u8],
+}
+
+impl DigestInput<'_> {
+    /// 计算 `response` 参数的值
+    ///
+    /// 按 `algorithm` 选择 MD5 / SHA-256 / SHA-512-256，`-sess` 变体的 HA1 为
+    /// `H(H(username:realm:password):nonce:cnonce)`
+    pub fn response(&self) -> String {
+        self.response_with_password(self.password.as_bytes())
+    }
+
+    /// 以原始字节作为密码计算 `response`（AKA 的 RES 不一定是合法 UTF-8）
+    fn response_with_password(&self, password: &[u8]) -> String {
+        let hash = |value: String| digest_hash(self.algorithm, value.as_bytes());
+        let (cnonce, nc, qop) = match self.qop {
+            Some(AuthQop::Auth { cnonce, nc }) => (cnonce.as_str(), *nc, "auth"),
+            Some(AuthQop::AuthInt { cnonce, nc }) => (cnonce.as_str(), *nc, "auth-int"),
+            None => ("", 0, ""),
+        };
+
+        let mut a1 = format!("{}:{}:", self.username, self.realm).into_bytes();
+        a1.extend_from_slice(password);
+        let mut ha1 = digest_hash(self.algorithm, &a1);
+        if is_sess(self.algorithm) {
+            ha1 = hash(format!("{}:{}:{}", ha1, self.nonce, cnonce));
+        }
+        let ha2 = match self.qop {
+            // auth-int 的 HA2 为 H(method:uri:H(entity-body))，无消息体时为空串的摘要
+            Some(AuthQop::AuthInt { .. }) => hash(format!(
+                "{}:{}:{}",
+                self.method,
+                self.uri,
+                digest_hash(self.algorithm, self.body)
+            )),
+            _ => hash(format!("{}:{}", self.method, self.uri)),
+        };
+
+        match self.qop {
+            // nc 的格式与 rsip 生成的 Authorization 头保持一致
+            Some(_) => hash(format!("{}:{}:{:08}:{}:{}:{}", ha1, self.nonce, nc, cnonce, qop, ha2)),
+            None => hash(format!("{}:{}:{}", ha1, self.nonce, ha2)),
+        }
+    }
+}
+
+fn is_sess(algorithm: Algorithm) -> bool {
+    matches!(
+        algorithm,
+        Algorithm::Md5Sess | Algorithm::Sha256Sess | Algorithm::Sha512Sess
+    )
+}
+
+/// 按算法计算十六进制摘要，MD5 为默认算法
+///
+/// rsip 的 `Sha512` / `Sha512Sess` 对应 RFC 7616 的 `SHA-512-256`，即截断为 256 位的 SHA-512/256
+fn digest_hash(algorithm: Algorithm, data: &[u8]) -> String {
+    use sha2::Digest;
+
+    let bytes: Vec<u8> = match algorithm {
+        Algorithm::Sha256 | Algorithm::Sha256Sess => sha2::Sha256::digest(data).to_vec(),
+        Algorithm::Sha512 | Algorithm::Sha512Sess => sha2::Sha512_256::digest(data).to_vec(),
+        Algorithm::Md5 | Algorithm::Md5Sess => md5::Md5::digest(data).to_vec(),
+    };
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// AKAv1-MD5 在 `algorithm` 参数中的写法
+#[cfg(feature = "aka")]
+const AKA_V1_MD5: &str = "AKAv1-MD5";
+
+/// 认证方案
+///
+/// 决定 Digest 计算使用的哈希算法与密码来源：普通 Digest 直接使用凭证密码，
+/// AKAv1-MD5 以 nonce 经 Milenage 推导出的 RES 作为密码
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthScheme {
+    /// 普通 Digest（MD5 / SHA-256 / SHA-512-256 及 `-sess` 变体）
+    Digest(Algorithm),
+    /// AKAv1-MD5（RFC 3310），携带 ISIM 密钥
+    #[cfg(feature = "aka")]
+    AkaV1Md5(AkaKey),
+}
+
+impl Default for AuthScheme {
+    fn default() -> Self {
+        Self::Digest(Algorithm::Md5)
+    }
+}
+
+impl AuthScheme {
+    /// 摘要计算使用的哈希算法
+    pub fn algorithm(&self) -> Algorithm {
+        match self {
+            Self::Digest(algorithm) => *algorithm,
+            #[cfg(feature = "aka")]
+            Self::AkaV1Md5(_) => Algorithm::Md5,
+        }
+    }
+
+    /// 用于展示的方案名称，如 `Digest MD5`、`Digest SHA-256`、`AKAv1-MD5`
+    pub fn name(&self) -> String {
+        match self {
+            Self::Digest(algorithm) => format!("Digest {}", rfc7616_algorithm_name(*algorithm)),
+            #[cfg(feature = "aka")]
+            Self::AkaV1Md5(_) => AKA_V1_MD5.to_string(),
+        }
+    }
+
+    /// 按方案得到本次摘要计算使用的密码，AKA 校验网络失败时返回 `None`
+    #[cfg_attr(not(feature = "aka"), allow(unused_variables))]
+    fn password(&self, credential: &Credential, nonce: &str) -> Option<Vec<u8>> {
+        match self {
+            Self::Digest(_) => Some(credential.password.clone().into_bytes()),
+            #[cfg(feature = "aka")]
+            Self::AkaV1Md5(key) => match key.respond(nonce) {
+                Ok(vector) => Some(vector.res.to_vec()),
+                Err(e) => {
+                    tracing::warn!("无法应答 AKA 挑战: {}", e);
+                    None
+                }
+            },
+        }
+    }
+}
+
+/// 判断挑战是否要求 AKA 认证（`algorithm=AKAv1-MD5` 等）
+fn is_aka_challenge(challenge: &str) -> bool {
+    extract_param(challenge, "algorithm")
+        .is_some_and(|algorithm| algorithm.to_ascii_uppercase().starts_with("AKA"))
+}
+
+/// 计算 Digest 认证的 Authorization
+///
+/// 算法取自挑战的 `algorithm` 参数（缺省为 MD5），`nc` 仅在挑战带 qop 时使用，
+/// `body` 为请求消息体，仅 `qop=auth-int` 时参与计算
+pub fn compute_authorization(
+    challenge: &rsip::typed::WwwAuthenticate,
+    credential: &Credential,
+    method: &rsip::Method,
+    uri: &rsip::Uri,
+    body: &[u8],
+    nc: u8,
+) -> rsip::typed::Authorization {
+    let scheme = AuthScheme::Digest(challenge.algorithm.unwrap_or(Algorithm::Md5));
+    compute_authorization_with(&scheme, challenge, credential, method, uri, body, nc)
+        .expect("Digest 方案总是使用凭证密码")
+}
+
+/// 按指定认证方案计算 Authorization
+///
+/// 哈希算法与密码由 `scheme` 决定，其余同 [`compute_authorization`]；
+/// 方案无法得到密码（如 AKA 网络认证失败）时返回 `None`
+pub fn compute_authorization_with(
+    scheme: &AuthScheme,
+    challenge: &rsip::typed::WwwAuthenticate,
+    credential: &Credential,
+    method: &rsip::Method,
+    uri: &rsip::Uri,
+    body: &[u8],
+    nc: u8,
+) -> Option<rsip::typed::Authorization> {
+    let password = scheme.password(credential, &challenge.nonce)?;
+    let cnonce = random_text(CNONCE_LEN);
+    let qop = match challenge.qop {
+        Some(Qop::Auth) => Some(AuthQop::Auth { cnonce, nc }),
+        Some(Qop::AuthInt) => Some(AuthQop::AuthInt { cnonce, nc }),
+        _ => None,
+    };
+    let algorithm = scheme.algorithm();
+    let realm = credential.realm.as_deref().unwrap_or(&challenge.realm);
+
+    let response = DigestInput {
+        algorithm,
+        username: &credential.username,
+        realm,
+        password: "",
+        method: &method.to_string(),
+        uri: &uri.to_string(),
+        nonce: &challenge.nonce,
+        qop: qop.as_ref(),
+        body,
+    }
+    .response_with_password(&password);
+
+    Some(rsip::typed::Authorization {
+        scheme: challenge.scheme.clone(),
+        username: credential.username.clone(),
+        realm: realm.to_string(),
+        nonce: challenge.nonce.clone(),
+        uri: uri.clone(),
+        response,
+        algorithm: Some(algorithm),
+        opaque: challenge.opaque.clone(),
+        qop,
+    })
+}
+
+/// 解析 `algorithm` 参数
+///
+/// 接受 RFC 7616 的写法（`SHA-256`、`SHA-512-256` 及 `-sess` 变体，不区分大小写），
+/// 以及 rsip 输出的 `SHA256` / `SHA256-sess`；其他取值（如非标准的 `SHA512`）返回 `None`
+pub fn parse_algorithm(value: &str) -> Option<Algorithm> {
+    match value.trim().to_ascii_uppercase().as_str() {
+        "MD5" => Some(Algorithm::Md5),
+        "MD5-SESS" => Some(Algorithm::Md5Sess),
+        "SHA-256" | "SHA256" => Some(Algorithm::Sha256),
+        "SHA-256-SESS" | "SHA256-SESS" => Some(Algorithm::Sha256Sess),
+        "SHA-512-256" => Some(Algorithm::Sha512),
+        "SHA-512-256-SESS" => Some(Algorithm::Sha512Sess),
+        _ => None,
+    }
+}
+
+/// 算法在 RFC 7616 `algorithm` 参数中的写法
+pub fn rfc7616_algorithm_name(algorithm: Algorithm) -> &'static str {
+    match algorithm {
+        Algorithm::Md5 => "MD5",
+        Algorithm::Md5Sess => "MD5-sess",
+        Algorithm::Sha256 => "SHA-256",
+        Algorithm::Sha256Sess => "SHA-256-sess",
+        Algorithm::Sha512 => "SHA-512-256",
+        Algorithm::Sha512Sess => "SHA-512-256-sess",
+    }
+}
+
+/// 将认证头中 `algorithm` 参数的值整体替换为 `name`
+fn replace_algorithm_param(value: &str, name: &str) -> String {
+    let Some(start) = value.find("algorithm=").map(|i| i + "algorithm=".len()) else {
+        return value.to_string();
+    };
+    let end = value[start..].find(',').map_or(value.len(), |i| start + i);
+    format!("{}{}{}", &value[..start], name, &value[end..])
+}
+
+/// 改写 rsip 输出的 `algorithm` 参数：按 RFC 7616 写出算法名（如 `SHA-256-sess`），
+/// AKA 方案写为 `AKAv1-MD5`（rsip 只能表示其哈希算法 MD5）
+fn with_rfc7616_algorithm(header: rsip::Header, scheme: &AuthScheme) -> rsip::Header {
+    let fix = |value: &str| match scheme {
+        AuthScheme::Digest(algorithm) => replace_algorithm_param(value, rfc7616_algorithm_name(*algorithm)),
+        #[cfg(feature = "aka")]
+        AuthScheme::AkaV1Md5(_) => replace_algorithm_param(value, AKA_V1_MD5),
+    };
+    match header {
+        rsip::Header::Authorization(h) => {
+            rsip::headers::Authorization::new(fix(h.value())).into()
+        }
+        rsip::Header::ProxyAuthorization(h) => {
+            rsip::headers::ProxyAuthorization::new(fix(h.value())).into()
+        }
+        other => other,
+    }
+}
+
+/// 解析挑战头的值，rsip 解析失败时（如 qop 列表、`SHA-256`）回退到 `DigestChallenge`
+///
+/// `algorithm` 始终按 [`parse_algorithm`] 解释，不采用 rsip 对 `SHA512` 等非标准写法的映射
+fn parse_challenge(value: &str) -> Option<rsip::typed::WwwAuthenticate> {
+    let mut challenge = rsip::headers::WwwAuthenticate::new(value)
+        .typed()
+        .ok()
+        .or_else(|| DigestChallenge::parse(value).map(|c| c.to_typed()))?;
+    challenge.algorithm = extract_param(value, "algorithm").as_deref().and_then(parse_algorithm);
+    Some(challenge)
+}
+
+/// Digest 认证会话
+///
+/// 记录最近一次接受的挑战，后续请求（如注册刷新）复用其 nonce 预先携带认证，
+/// 并按 nonce 递增 nonce-count
+#[derive(Debug, Clone, Default)]
+pub struct DigestSession {
+    challenge: rsip::typed::WwwAuthenticate,
+    proxy: bool,
+    counter: NonceCounter,
+    scheme: AuthScheme,
+    /// 配置后优先应答 AKAv1-MD5 挑战
+    #[cfg(feature = "aka")]
+    aka_key: Option<AkaKey>,
+}
+
+impl DigestSession {
+    /// 从 401/407 响应中解析挑战（优先 WWW-Authenticate）
+    pub fn from_response(resp: &Response) -> Option<Self> {
+        let mut session = Self::default();
+        session.update(resp).then_some(session)
+    }
+
+    /// 设置 ISIM 密钥，之后的挑战中有 AKAv1-MD5 时优先按 AKA 应答
+    #[cfg(feature = "aka")]
+    pub fn with_aka_key(mut self, key: Option<AkaKey>) -> Self {
+        self.aka_key = key;
+        self
+    }
+
+    /// 使用新挑战更新会话，返回是否解析成功
+    ///
+    /// 响应携带多个挑战时，优先选择可应答的方案：配置了 AKA 密钥时为 AKAv1-MD5，
+    /// 否则为普通 MD5/SHA Digest；都不满足时退回第一个挑战。
+    /// nonce 不变时 nonce-count 继续递增，否则重新计数
+    pub fn update(&mut self, resp: &Response) -> bool {
+        let challenges: Vec<(bool, &str, rsip::typed::WwwAuthenticate)> = resp
+            .headers
+            .iter()
+            .filter_map(|header| match header {
+                rsip::Header::WwwAuthenticate(h) => Some((false, h.value())),
+                rsip::Header::ProxyAuthenticate(h) => Some((true, h.value())),
+                _ => None,
+            })
+            .filter_map(|(proxy, value)| parse_challenge(value).map(|c| (proxy, value, c)))
+            .collect();
+
+        let chosen = challenges
+            .iter()
+            .find_map(|(proxy, value, challenge)| {
+                self.scheme_for(value, challenge).map(|scheme| (*proxy, challenge, scheme))
+            })
+            .or_else(|| {
+                challenges.first().map(|(proxy, _, challenge)| {
+                    let scheme = AuthScheme::Digest(challenge.algorithm.unwrap_or(Algorithm::Md5));
+                    (*proxy, challenge, scheme)
+                })
+            });
+        match chosen {
+            Some((proxy, challenge, scheme)) => {
+                self.challenge = challenge.clone();
+                self.proxy = proxy;
+                self.scheme = scheme;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 本会话能够应答该挑战时返回对应的认证方案
+    fn scheme_for(
+        &self,
+        value: &str,
+        challenge: &rsip::typed::WwwAuthenticate,
+    ) -> Option<AuthScheme> {
+        if !is_aka_challenge(value) {
+            return Some(AuthScheme::Digest(challenge.algorithm.unwrap_or(Algorithm::Md5)));
+        }
+        #[cfg(feature = "aka")]
+        if extract_param(value, "algorithm").is_some_and(|a| a.eq_ignore_ascii_case(AKA_V1_MD5)) {
+            return self.aka_key.clone().map(AuthScheme::AkaV1Md5);
+        }
+        None
+    }
+
+    /// 当前使用的认证方案
+    pub fn scheme(&self) -> &AuthScheme {
+        &self.scheme
+    }
+
+    /// 当前挑战的 nonce
+    pub fn nonce(&self) -> &str {
+        &self.challenge.nonce
+    }
+
+    /// 当前挑战的 realm
+    pub fn realm(&self) -> &str {
+        &self.challenge.realm
+    }
+
+    /// 为请求生成认证头（Authorization 或 Proxy-Authorization），nonce-count 自动递增
+    ///
+    /// `body` 为请求消息体（`qop=auth-int` 时参与计算）；当前 nonce 的计数已用尽
+    /// 或 AKA 网络认证失败时返回 `None`
+    pub fn authorization_header(
+        &mut self,
+        credential: &Credential,
+        method: &rsip::Method,
+        uri: &rsip::Uri,
+        body: &[u8],
+    ) -> Option<rsip::Header> {
+        let nc = self.counter.next(&self.challenge.nonce)?;
+        let auth = compute_authorization_with(
+            &self.scheme,
+            &self.challenge,
+            credential,
+            method,
+            uri,
+            body,
+            nc,
+        )?;
+        let header = if self.proxy {
+            rsip::typed::ProxyAuthorization(auth).into()
+        } else {
+            auth.into()
+        };
+        Some(with_rfc7616_algorithm(header, &self.scheme))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHALLENGE: &str =
+        r#"Digest realm="example.com", nonce="abc123", opaque="xyz", algorithm=MD5, qop="auth""#;
+
+    #[test]
+    fn test_extract_param() {
+        assert_eq!(extract_param(CHALLENGE, "realm").as_deref(), Some("example.com"));
+        assert_eq!(extract_param(CHALLENGE, "NONCE").as_deref(), Some("abc123"));
+        assert_eq!(extract_param(CHALLENGE, "algorithm").as_deref(), Some("MD5"));
+        assert_eq!(extract_param(CHALLENGE, "stale"), None);
+    }
+
+    #[test]
+    fn test_extract_param_quoted_commas() {
+        let header = r#"Digest realm="sip.example.com", domain="a,b", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", qop="auth,auth-int", opaque="5c,c\"d= e", algorithm=SHA-256"#;
+        assert_eq!(extract_param(header, "domain").as_deref(), Some("a,b"));
+        assert_eq!(
+            extract_param(header, "nonce").as_deref(),
+            Some("dcd98b7102dd2f0e8b11d0f600bfb0c093")
+        );
+        assert_eq!(extract_param(header, "qop").as_deref(), Some("auth,auth-int"));
+        assert_eq!(extract_param(header, "opaque").as_deref(), Some(r#"5c,c"d= e"#));
+        assert_eq!(extract_param(header, "algorithm").as_deref(), Some("SHA-256"));
+
+        let challenge = DigestChallenge::parse(header).unwrap();
+        assert_eq!(challenge.preferred_qop(), Some("auth"));
+        let int_only = DigestChallenge::parse(r#"Digest realm="r", nonce="n", qop="auth-int""#).unwrap();
+        assert_eq!(int_only.preferred_qop(), Some("auth-int"));
+
+        // rsip 无法解析 qop 列表，会话仍能据此生成 qop=auth 的认证
+        let typed = parse_challenge(header).unwrap();
+        assert_eq!(typed.qop, Some(Qop::Auth));
+        assert_eq!(typed.algorithm, Some(Algorithm::Sha256));
+        assert_eq!(typed.opaque.as_deref(), Some(r#"5c,c"d= e"#));
+        assert_eq!(parse_algorithm("sha-256-sess"), Some(Algorithm::Sha256Sess));
+        assert_eq!(parse_algorithm("MD5"), Some(Algorithm::Md5));
+        assert_eq!(parse_algorithm("SHA-512-256-sess"), Some(Algorithm::Sha512Sess));
+        assert_eq!(parse_algorithm("SHA512"), None);
+        for algorithm in [Algorithm::Md5, Algorithm::Sha256Sess, Algorithm::Sha512] {
+            assert_eq!(parse_algorithm(rfc7616_algorithm_name(algorithm)), Some(algorithm));
+        }
+        assert_eq!(AuthScheme::Digest(Algorithm::Sha512Sess).name(), "Digest SHA-512-256-sess");
+        // SHA-512-256 为 SHA-512/256，而非截断的 SHA-512
+        assert_eq!(
+            digest_hash(Algorithm::Sha512, b""),
+            "c672b8d1ef56ed28ab87c3622c5114069bdd3ad7b8f9737498d0c01ecef0967a"
+        );
+    }
+
+    #[test]
+    fn test_parse_stale_challenge() {
+        let challenge = DigestChallenge::parse(&format!("{}, stale=TRUE", CHALLENGE)).unwrap();
+        assert!(challenge.stale);
+        assert_eq!(challenge.nonce, "abc123");
+
+        let challenge = DigestChallenge::parse(CHALLENGE).unwrap();
+        assert!(!challenge.stale);
+        assert!(DigestChallenge::parse("Digest realm=\"a\"").is_none());
+    }
+
+    #[test]
+    fn test_stale_nonce_sequence() {
+        // 401 -> 认证 -> 401 stale=true -> 再次认证 -> 401 stale=true -> 放弃
+        let mut state = AuthRetryState::new(true);
+        assert!(state.on_challenge(false));
+        assert!(state.on_challenge(true));
+        assert!(!state.on_challenge(true));
+    }
+
+    #[test]
+    fn test_non_stale_second_challenge_fails() {
+        let mut state = AuthRetryState::new(true);
+        assert!(state.on_challenge(false));
+        assert!(!state.on_challenge(false));
+    }
+
+    fn challenge_response(nonce: &str) -> Response {
+        Response {
+            status_code: rsip::StatusCode::Unauthorized,
+            version: rsip::Version::V2,
+            headers: vec![rsip::headers::WwwAuthenticate::new(format!(
+                r#"Digest realm="example.com", nonce="{nonce}", qop="auth", algorithm=MD5"#
+            ))
+            .into()]
+            .into(),
+            body: vec![],
+        }
+    }
+
+    /// RFC 7616 §3.9.1 的示例
+    fn rfc7616_input(algorithm: Algorithm, qop: &AuthQop) -> DigestInput<'_> {
+        DigestInput {
+            algorithm,
+            username: "Mufasa",
+            realm: "http-auth@example.org",
+            password: "Circle of Life",
+            method: "GET",
+            uri: "/dir/index.html",
+            nonce: "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v",
+            qop: Some(qop),
+            body: &[],
+        }
+    }
+
+    /// RFC 2617 §3.5 的示例（auth-int 变体沿用相同输入）
+    fn rfc2617_input<'a>(qop: &'a AuthQop, body: &'a [u8]) -> DigestInput<'a> {
+        DigestInput {
+            algorithm: Algorithm::Md5,
+            username: "Mufasa",
+            realm: "testrealm@host.com",
+            password: "Circle Of Life",
+            method: "GET",
+            uri: "/dir/index.html",
+            nonce: "dcd98b7102dd2f0e8b11d0f600bfb0c093",
+            qop: Some(qop),
+            body,
+        }
+    }
+
+    #[test]
+    fn test_digest_auth_int_hashes_body() {
+        let cnonce = "0a4f113b".to_string();
+        let auth = AuthQop::Auth { cnonce: cnonce.clone(), nc: 1 };
+        assert_eq!(
+            rfc2617_input(&auth, b"ignored").response(),
+            "6629fae49393a05397450978507c4ef1"
+        );
+
+        // HA2 = MD5(method:uri:MD5(entity-body))
+        let auth_int = AuthQop::AuthInt { cnonce, nc: 1 };
+        assert_eq!(
+            rfc2617_input(&auth_int, b"").response(),
+            "5e6610ecf9ba3017a4870ad48e3ad30b"
+        );
+        assert_eq!(
+            rfc2617_input(&auth_int, b"v=0\r\n").response(),
+            "151b6cabb7e59e0ac757207a039ff3d0"
+        );
+    }
+
+    #[test]
+    fn test_digest_rfc7616_vectors() {
+        let qop = AuthQop::Auth {
+            cnonce: "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ".to_string(),
+            nc: 1,
+        };
+        // RFC 原文中的 MD5 值有误，此处为勘误 4897 修正后的值
+        assert_eq!(
+            rfc7616_input(Algorithm::Md5, &qop).response(),
+            "8ca523f5e9506fed4657c9700eebdbec"
+        );
+        assert_eq!(
+            rfc7616_input(Algorithm::Sha256, &qop).response(),
+            "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1"
+        );
+    }
+
+    #[test]
+    fn test_digest_sess_hashes_nonce_and_cnonce() {
+        let qop = AuthQop::Auth {
+            cnonce: "0a4f113b".to_string(),
+            nc: 1,
+        };
+        let input = rfc7616_input(Algorithm::Sha256Sess, &qop);
+        let ha1 = digest_hash(
+            Algorithm::Sha256,
+            format!("{}:{}:{}", input.username, input.realm, input.password).as_bytes(),
+        );
+        let sess_ha1 = digest_hash(
+            Algorithm::Sha256,
+            format!("{}:{}:{}", ha1, input.nonce, "0a4f113b").as_bytes(),
+        );
+        let ha2 = digest_hash(Algorithm::Sha256, b"GET:/dir/index.html");
+        let expected = digest_hash(
+            Algorithm::Sha256,
+            format!("{}:{}:00000001:0a4f113b:auth:{}", sess_ha1, input.nonce, ha2).as_bytes(),
+        );
+        assert_eq!(input.response(), expected);
+        assert_ne!(input.response(), rfc7616_input(Algorithm::Sha256, &qop).response());
+    }
+
+    #[test]
+    fn test_rfc7616_algorithm_in_header() {
+        let credential = Credential {
+            username: "alice".to_string(),
+            password: "secret".to_string(),
+            realm: None,
+        };
+        let uri: rsip::Uri = "sip:example.com".try_into().unwrap();
+        for (token, algorithm) in [
+            ("SHA-256", Algorithm::Sha256),
+            ("SHA-256-sess", Algorithm::Sha256Sess),
+            ("SHA-512-256", Algorithm::Sha512),
+            ("MD5-sess", Algorithm::Md5Sess),
+        ] {
+            let resp = Response {
+                status_code: rsip::StatusCode::Unauthorized,
+                version: rsip::Version::V2,
+                headers: vec![rsip::headers::WwwAuthenticate::new(format!(
+                    r#"Digest realm="example.com", nonce="n1", qop="auth,auth-int", algorithm={token}"#
+                ))
+                .into()]
+                .into(),
+                body: vec![],
+            };
+            let mut session = DigestSession::from_response(&resp).unwrap();
+            assert_eq!(session.scheme(), &AuthScheme::Digest(algorithm));
+            let header = session
+                .authorization_header(&credential, &rsip::Method::Register, &uri, &[])
+                .unwrap()
+                .to_string();
+            assert_eq!(extract_param(&header, "algorithm").as_deref(), Some(token), "{}", header);
+            assert!(header.contains(r#"qop="auth""#), "{}", header);
+        }
+    }
+
+    #[test]
+    fn test_nonce_count_increments_per_nonce() {
+        let credential = Credential {
+            username: "alice".to_string(),
+            password: "secret".to_string(),
+            realm: None,
+        };
+        let uri: rsip::Uri = "sip:example.com".try_into().unwrap();
+        let mut session = DigestSession::from_response(&challenge_response("n1")).unwrap();
+
+        // 同一 nonce 下的两次请求：nc 依次为 1、2
+        let first = session.authorization_header(&credential, &rsip::Method::Register, &uri, &[]).unwrap();
+        let second = session.authorization_header(&credential, &rsip::Method::Register, &uri, &[]).unwrap();
+        assert!(first.to_string().contains("nc=00000001"));
+        assert!(second.to_string().contains("nc=00000002"));
+        assert!(second.to_string().contains(r#"nonce="n1""#));
+
+        // 同一 nonce 的重复挑战不重置计数
+        assert!(session.update(&challenge_response("n1")));
+        let third = session.authorization_header(&credential, &rsip::Method::Register, &uri, &[]).unwrap();
+        assert!(third.to_string().contains("nc=00000003"));
+
+        // nonce 变化后重新计数
+        assert!(session.update(&challenge_response("n2")));
+        let fresh = session.authorization_header(&credential, &rsip::Method::Register, &uri, &[]).unwrap();
+        assert!(fresh.to_string().contains("nc=00000001"));
+        assert_eq!(session.nonce(), "n2");
+
+        let mut counter = NonceCounter::default();
+        for _ in 0..255 {
+            assert!(counter.next("n").is_some());
+        }
+        assert_eq!(counter.next("n"), None);
+        assert_eq!(counter.next("m"), Some(1));
+    }
+
+    fn aka_challenge_response() -> Response {
+        Response {
+            status_code: rsip::StatusCode::Unauthorized,
+            version: rsip::Version::V2,
+            headers: vec![
+                rsip::headers::WwwAuthenticate::new(
+                    r#"Digest realm="ims.example.com", nonce="I1U8vpY3qJ0hiuZNrke/NVXzKLQ1d7m5Sp/6w1Tfr7M=", qop="auth", algorithm=AKAv1-MD5"#,
+                )
+                .into(),
+                rsip::headers::WwwAuthenticate::new(
+                    r#"Digest realm="ims.example.com", nonce="plain", algorithm=MD5"#,
+                )
+                .into(),
+            ]
+            .into(),
+            body: vec![],
+        }
+    }
+
+    #[test]
+    fn test_aka_challenge_falls_back_to_digest() {
+        // 未配置 AKA 密钥时选择普通 Digest 挑战
+        let session = DigestSession::from_response(&aka_challenge_response()).unwrap();
+        assert_eq!(session.nonce(), "plain");
+        assert_eq!(session.scheme(), &AuthScheme::Digest(Algorithm::Md5));
+    }
+
+    #[cfg(feature = "aka")]
+    #[test]
+    fn test_aka_v1_md5_authorization() {
+        use crate::sip_aka::AkaKey;
+
+        let hex = |value: &str| -> [u8; 16] {
+            std::array::from_fn(|i| u8::from_str_radix(&value[2 * i..2 * i + 2], 16).unwrap())
+        };
+        // 3GPP TS 35.207 测试集 1 的 K/OP，nonce 为其 RAND || AUTN
+        let key = AkaKey::with_op(
+            hex("465b5ce8b199b49faa5f0a2ee238a6bc"),
+            hex("cdc202d5123e20f62b6d676ac72cb318"),
+        );
+        let credential = Credential {
+            username: "001010000000001@ims.example.com".to_string(),
+            password: "unused".to_string(),
+            realm: None,
+        };
+        let uri: rsip::Uri = "sip:ims.example.com".try_into().unwrap();
+
+        let mut session = DigestSession::default().with_aka_key(Some(key.clone()));
+        assert!(session.update(&aka_challenge_response()));
+        assert!(matches!(session.scheme(), AuthScheme::AkaV1Md5(_)));
+        let header = session
+            .authorization_header(&credential, &rsip::Method::Register, &uri, &[])
+            .unwrap()
+            .to_string();
+        assert!(header.contains("algorithm=AKAv1-MD5"), "{}", header);
+
+        // response 以 RES 作为密码按 MD5 计算
+        let res = key.respond(session.nonce()).unwrap().res;
+        let cnonce = extract_param(&header, "cnonce").unwrap();
+        let qop = AuthQop::Auth { cnonce, nc: 1 };
+        let expected = DigestInput {
+            algorithm: Algorithm::Md5,
+            username: &credential.username,
+            realm: "ims.example.com",
+            password: "",
+            method: "REGISTER",
+            uri: "sip:ims.example.com",
+            nonce: session.nonce(),
+            qop: Some(&qop),
+            body: &[],
+        }
+        .response_with_password(&res);
+        assert_eq!(extract_param(&header, "response").as_deref(), Some(expected.as_str()));
+
+        // 网络认证失败时不生成认证头
+        let mut forged = aka_challenge_response();
+        forged.headers = vec![rsip::headers::WwwAuthenticate::new(
+            r#"Digest realm="ims.example.com", nonce="I1U8vpY3qJ0hiuZNrke/NVXzKLQ1d7m5Sp/6w1Tfr7I=", algorithm=AKAv1-MD5"#,
+        )
+        .into()]
+        .into();
+        assert!(session.update(&forged));
+        assert!(session
+            .authorization_header(&credential, &rsip::Method::Register, &uri, &[])
+            .is_none());
+    }
+
+    #[test]
+    fn test_stale_retry_disabled() {
+        let mut state = AuthRetryState::new(false);
+        assert!(state.on_challenge(true));
+        assert!(!state.on_challenge(true));
+    }
+}
@@ -0,0 +1,343 @@
+/// SIP 消息体模块
+///
+/// 支持 `multipart/mixed` 消息体（RFC 5621），用于在 INVITE 中同时携带 SDP 与其他内容
+/// （如紧急呼叫的 PIDF-LO 位置信息、ISUP），并从 multipart 应答中取出 SDP
+use crate::error::CallError;
+use rsip::headers::UntypedHeader;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// SDP 的内容类型
+pub const SDP_CONTENT_TYPE: &str = "application/sdp";
+
+/// 纯文本的内容类型，MESSAGE 未指定内容类型时使用
+pub const TEXT_CONTENT_TYPE: &str = "text/plain";
+
+/// RFC 2046 允许的边界最大长度
+const MAX_BOUNDARY_LEN: usize = 70;
+
+/// multipart 消息体错误
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BodyError {
+    #[error("无效的 multipart 边界: {0:?}")]
+    InvalidBoundary(String),
+
+    #[error("multipart 消息体格式错误: {0}")]
+    Malformed(String),
+}
+
+impl From<BodyError> for CallError {
+    fn from(err: BodyError) -> Self {
+        CallError::Serialization(err.to_string())
+    }
+}
+
+/// multipart 消息体中的单个部分
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyPart {
+    /// 部分的 Content-Type
+    pub content_type: String,
+    /// 部分的 Content-Disposition（如 `session`、`render;handling=optional`）
+    pub disposition: Option<String>,
+    /// 部分的内容
+    pub body: Vec<u8>,
+}
+
+impl BodyPart {
+    /// 创建消息体部分
+    pub fn new(content_type: impl Into<String>, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            content_type: content_type.into(),
+            disposition: None,
+            body: body.into(),
+        }
+    }
+
+    /// 创建 SDP 部分（Content-Disposition 为 `session`）
+    pub fn sdp(sdp: &str) -> Self {
+        Self::new(SDP_CONTENT_TYPE, sdp).with_disposition("session")
+    }
+
+    /// 设置 Content-Disposition
+    pub fn with_disposition(mut self, disposition: impl Into<String>) -> Self {
+        self.disposition = Some(disposition.into());
+        self
+    }
+
+    /// 内容类型（不含参数）是否与给定类型一致
+    pub fn is_type(&self, content_type: &str) -> bool {
+        media_type(&self.content_type).eq_ignore_ascii_case(content_type)
+    }
+}
+
+/// `multipart/mixed` 消息体
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultipartBody {
+    boundary: String,
+    parts: Vec<BodyPart>,
+}
+
+impl Default for MultipartBody {
+    fn default() -> Self {
+        Self {
+            boundary: format!("boundary-{}", Uuid::new_v4().simple()),
+            parts: Vec::new(),
+        }
+    }
+}
+
+impl MultipartBody {
+    /// 创建使用随机边界的空消息体
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 指定边界
+    pub fn with_boundary(mut self, boundary: impl Into<String>) -> Self {
+        self.boundary = boundary.into();
+        self
+    }
+
+    /// 追加一个部分
+    pub fn with_part(mut self, part: BodyPart) -> Self {
+        self.parts.push(part);
+        self
+    }
+
+    /// 边界
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// 所有部分
+    pub fn parts(&self) -> &[BodyPart] {
+        &self.parts
+    }
+
+    /// 第一个指定内容类型的部分
+    pub fn find(&self, content_type: &str) -> Option<&BodyPart> {
+        self.parts.iter().find(|p| p.is_type(content_type))
+    }
+
+    /// SDP 部分的内容
+    pub fn sdp(&self) -> Option<String> {
+        self.find(SDP_CONTENT_TYPE)
+            .map(|p| String::from_utf8_lossy(&p.body).to_string())
+    }
+
+    /// 消息的 Content-Type 头部值
+    pub fn content_type(&self) -> String {
+        format!("multipart/mixed;boundary={}", self.boundary)
+    }
+
+    /// 校验后编码为消息体
+    ///
+    /// 边界必须合法且不能出现在任何部分的内容中，且至少包含一个部分
+    pub fn encode(&self) -> Result<Vec<u8>, BodyError> {
+        validate_boundary(&self.boundary)?;
+        if self.parts.is_empty() {
+            return Err(BodyError::Malformed("multipart 消息体没有任何部分".into()));
+        }
+        let delimiter = format!("--{}", self.boundary);
+        let mut body = Vec::new();
+        for part in &self.parts {
+            if part.content_type.trim().is_empty() {
+                return Err(BodyError::Malformed("multipart 部分缺少 Content-Type".into()));
+            }
+            if contains(&part.body, delimiter.as_bytes()) {
+                return Err(BodyError::Malformed(format!(
+                    "边界 {} 出现在 {} 部分的内容中",
+                    self.boundary, part.content_type
+                )));
+            }
+            body.extend_from_slice(format!("{}\r\n", delimiter).as_bytes());
+            body.extend_from_slice(format!("Content-Type: {}\r\n", part.content_type).as_bytes());
+            if let Some(disposition) = &part.disposition {
+                body.extend_from_slice(format!("Content-Disposition: {}\r\n", disposition).as_bytes());
+            }
+            body.extend_from_slice(b"\r\n");
+            body.extend_from_slice(&part.body);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("{}--\r\n", delimiter).as_bytes());
+        Ok(body)
+    }
+
+    /// 按 Content-Type 中的边界解析 multipart 消息体
+    pub fn parse(content_type: &str, body: &[u8]) -> Result<Self, BodyError> {
+        if !media_type(content_type).to_ascii_lowercase().starts_with("multipart/") {
+            return Err(BodyError::Malformed(format!("不是 multipart 内容类型: {}", content_type)));
+        }
+        let boundary = boundary_param(content_type)
+            .ok_or_else(|| BodyError::Malformed("multipart 缺少 boundary 参数".into()))?;
+        validate_boundary(&boundary)?;
+
+        let text = String::from_utf8_lossy(body);
+        let delimiter = format!("--{}", boundary);
+        let mut segments = text.split(delimiter.as_str());
+        // 第一个分隔符之前为前言，忽略
+        segments.next();
+
+        let mut parts = Vec::new();
+        let mut closed = false;
+        for segment in segments {
+            if segment.starts_with("--") {
+                closed = true;
+                break;
+            }
+            let segment = segment.strip_prefix("\r\n").unwrap_or(segment);
+            let (head, content) = segment
+                .split_once("\r\n\r\n")
+                .ok_or_else(|| BodyError::Malformed("multipart 部分缺少头部结束标记".into()))?;
+            let content = content.strip_suffix("\r\n").unwrap_or(content);
+
+            let mut part = BodyPart::new(String::new(), content.as_bytes());
+            for line in head.lines() {
+                let Some((name, value)) = line.split_once(':') else {
+                    continue;
+                };
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "content-type" | "c" => part.content_type = value.trim().to_string(),
+                    "content-disposition" => part.disposition = Some(value.trim().to_string()),
+                    _ => {}
+                }
+            }
+            if part.content_type.is_empty() {
+                // RFC 2046：缺省内容类型为 text/plain
+                part.content_type = "text/plain".to_string();
+            }
+            parts.push(part);
+        }
+
+        if !closed {
+            return Err(BodyError::Malformed("multipart 消息体缺少结束边界".into()));
+        }
+        if parts.is_empty() {
+            return Err(BodyError::Malformed("multipart 消息体没有任何部分".into()));
+        }
+        Ok(Self { boundary, parts })
+    }
+}
+
+/// 从消息体中取出 SDP，支持 `application/sdp` 与 multipart 消息体
+///
+/// 未携带 Content-Type 的非空消息体按 SDP 处理
+pub fn extract_sdp(content_type: Option<&str>, body: &[u8]) -> Option<String> {
+    if body.is_empty() {
+        return None;
+    }
+    match content_type {
+        Some(ct) if media_type(ct).to_ascii_lowercase().starts_with("multipart/") => {
+            MultipartBody::parse(ct, body).ok()?.sdp()
+        }
+        Some(ct) if !media_type(ct).eq_ignore_ascii_case(SDP_CONTENT_TYPE) => None,
+        _ => Some(String::from_utf8_lossy(body).to_string()),
+    }
+}
+
+/// 从响应中取出 SDP answer
+pub fn response_sdp(resp: &rsip::Response) -> Option<String> {
+    let content_type = resp.headers.iter().find_map(|h| match h {
+        rsip::Header::ContentType(ct) => Some(ct.value().to_string()),
+        _ => None,
+    });
+    extract_sdp(content_type.as_deref(), &resp.body)
+}
+
+/// 去掉参数后的媒体类型
+fn media_type(content_type: &str) -> &str {
+    content_type.split(';').next().unwrap_or_default().trim()
+}
+
+/// 解析 Content-Type 中的 boundary 参数（可带引号）
+fn boundary_param(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("boundary") {
+            return None;
+        }
+        Some(value.trim().trim_matches('"').to_string())
+    })
+}
+
+fn validate_boundary(boundary: &str) -> Result<(), BodyError> {
+    let valid_char = |c: char| c.is_ascii_alphanumeric() || "'()+_,-./:=?".contains(c);
+    if boundary.is_empty()
+        || boundary.len() > MAX_BOUNDARY_LEN
+        || !boundary.chars().all(valid_char)
+    {
+        return Err(BodyError::InvalidBoundary(boundary.to_string()));
+    }
+    Ok(())
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SDP: &str = "v=0\r\nm=audio 4000 RTP/AVP 0\r\n";
+    const PIDF: &str = "<?xml version=\"1.0\"?><presence/>";
+
+    #[test]
+    fn test_multipart_roundtrip() {
+        let body = MultipartBody::new()
+            .with_boundary("unique-boundary-1")
+            .with_part(BodyPart::sdp(SDP))
+            .with_part(
+                BodyPart::new("application/pidf+xml", PIDF).with_disposition("render;handling=optional"),
+            );
+        assert_eq!(body.content_type(), "multipart/mixed;boundary=unique-boundary-1");
+
+        let encoded = body.encode().unwrap();
+        let text = String::from_utf8_lossy(&encoded);
+        assert!(text.starts_with("--unique-boundary-1\r\nContent-Type: application/sdp\r\n"));
+        assert!(text.contains("Content-Disposition: session\r\n"));
+        assert!(text.ends_with("--unique-boundary-1--\r\n"));
+
+        let parsed = MultipartBody::parse(&body.content_type(), &encoded).unwrap();
+        assert_eq!(parsed, body);
+        assert_eq!(parsed.sdp().as_deref(), Some(SDP));
+        assert_eq!(
+            parsed.find("application/pidf+xml").unwrap().disposition.as_deref(),
+            Some("render;handling=optional")
+        );
+    }
+
+    #[test]
+    fn test_multipart_validation() {
+        assert!(MultipartBody::new().encode().is_err());
+        let bad_boundary = MultipartBody::new()
+            .with_boundary("has space")
+            .with_part(BodyPart::sdp(SDP));
+        assert!(bad_boundary.encode().is_err());
+        let collides = MultipartBody::new()
+            .with_boundary("b1")
+            .with_part(BodyPart::new("text/plain", "--b1 inside"));
+        assert!(collides.encode().is_err());
+
+        let unterminated = "--b1\r\nContent-Type: application/sdp\r\n\r\nv=0\r\n";
+        assert!(MultipartBody::parse("multipart/mixed;boundary=b1", unterminated.as_bytes()).is_err());
+        assert!(MultipartBody::parse("multipart/mixed", b"").is_err());
+    }
+
+    #[test]
+    fn test_extract_sdp() {
+        let body = MultipartBody::new()
+            .with_boundary("b1")
+            .with_part(BodyPart::new("application/isup", "isup"))
+            .with_part(BodyPart::sdp(SDP));
+        let encoded = body.encode().unwrap();
+        assert_eq!(
+            extract_sdp(Some("multipart/mixed; boundary=\"b1\""), &encoded).as_deref(),
+            Some(SDP)
+        );
+        assert_eq!(extract_sdp(Some("application/sdp"), SDP.as_bytes()).as_deref(), Some(SDP));
+        assert_eq!(extract_sdp(None, SDP.as_bytes()).as_deref(), Some(SDP));
+        assert_eq!(extract_sdp(Some("application/isup"), b"isup"), None);
+        assert_eq!(extract_sdp(Some("application/sdp"), b""), None);
+    }
+}
@@ -1,23 +1,62 @@
 /// SIP 客户端核心模块
 ///
 /// 提供高层次的SIP客户端功能封装
+use crate::backoff::{Backoff, BackoffStrategy, RetryPolicy};
+use crate::call::IncomingCall;
+use crate::dtmf::{format_dtmf_relay, is_valid_dtmf};
+use crate::config::{
+    validate_contact_uri, validate_register_expires, AuthMode, ExpiresMode, Protocol, QValue,
+};
 use crate::error::CallError;
-use crate::sip_transport::create_transport_connection;
+use crate::metrics::{Metrics, MetricsHandle};
+use crate::rtp_play::{RtpPlayer, SharedRtpPlayer};
+#[cfg(feature = "aka")]
+use crate::sip_aka::AkaKey;
+use crate::sip_auth::{CredentialProvider, DigestSession};
+use crate::sip_fork::{ForkTracker, ForkedDialog};
+use crate::sip_prack::PrackTracker;
+use crate::sip_body::{response_sdp, MultipartBody, SDP_CONTENT_TYPE, TEXT_CONTENT_TYPE};
+use crate::sip_dialog;
+use crate::sip_registration::{ContactBinding, SipRegistration};
+use crate::sip_subscription::{accept_for_event, granted_expires, Subscription};
+use crate::sip_dialog::{EarlyMedia, ReferProgress};
+use crate::session_timer::{self, MediaSessions, SessionExpires};
+use crate::sip_headers::{
+    allows_method, find_reserved_header, quote_display_name, strip_rport, OutgoingHeaders,
+    RejectHeaders, ALLOWED_METHODS,
+};
+use crate::sip_transport::{
+    bind_error, connection_local_addr, create_transport_connection, MediaDirection, TcpLink, TlsOptions,
+};
+use crate::utils::STUN_TIMEOUT;
+use rustrtc::{SdpType, SessionDescription};
 use rsipstack::{
     dialog::{
-        authenticate::Credential, dialog_layer::DialogLayer, invitation::InviteOption,
-        registration::Registration,
+        authenticate::{handle_client_authenticate, Credential},
+        dialog_layer::DialogLayer,
+        invitation::InviteOption,
     },
-    transaction::Endpoint,
+    transaction::key::{TransactionKey, TransactionRole},
+    transaction::transaction::Transaction,
+    transaction::{make_tag, Endpoint},
     transport::{SipAddr, TransportLayer},
     EndpointBuilder,
 };
-use std::sync::Arc;
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use rsip::prelude::{HeadersExt, ToTypedHeader, UntypedHeader};
 use rsip::Response;
 use rsipstack::dialog::client_dialog::ClientInviteDialog;
+use rsipstack::dialog::dialog::{Dialog, DialogState, TerminatedReason};
+use rsipstack::dialog::DialogId;
+use tokio::sync::{broadcast, mpsc, watch};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, info, warn};
+use tracing::field::{display, Empty};
+use tracing::{debug, error, info, info_span, warn, Instrument};
 use uuid::Uuid;
 use crate::error::CallResult;
 
@@ -30,36 +69,418 @@ pub struct SipClientConfig {
     /// 完整URI格式，如 "sip:proxy.example.com:5060;transport=udp;lr"
     pub outbound_proxy: Option<rsip::Uri>,
 
-    /// SIP 用户名
+    /// SIP 用户名（AOR 与 Contact 的用户部分）
     pub username: String,
 
-    /// SIP 密码
+    /// SIP 域（AOR、From 与 REGISTER To 的主机部分），`None` 时使用服务器地址
+    pub domain: Option<String>,
+
+    /// Digest 认证用户名，`None` 时使用 `username`
+    pub auth_username: Option<String>,
+
+    /// SIP 密码（`AuthMode::Ip` 下可为空）
     pub password: String,
 
     /// User-Agent字符串
     pub user_agent: String,
+
+    /// 注册时 expires 的携带方式（Expires 头 / Contact 参数 / 两者）
+    pub expires_mode: ExpiresMode,
+
+    /// `register()` 请求的注册时长（秒），须在 1 ~ `MAX_REGISTER_EXPIRES` 之间
+    pub register_expires: u32,
+
+    /// 自动注册失败后的重试退避策略（呼叫重试使用 `RetryPolicy::backoff`）
+    pub register_backoff: Backoff,
+
+    /// 认证模式：Digest 或基于源 IP（未受挑战的 200 OK 即视为成功）
+    pub auth_mode: AuthMode,
+
+    /// 注册认证后收到 `stale=true` 挑战时是否允许额外重试一次
+    pub stale_nonce_retry: bool,
+
+    /// 是否启用对称响应路由（RFC 3581 rport）
+    ///
+    /// 启用时 REGISTER 的 Via 携带 `rport`，并根据响应 Via 的 `received`/`rport`
+    /// 学习公网地址，用于后续 Contact 与媒体 SDP 地址；
+    /// 关闭时 REGISTER 不携带 `rport`，始终使用本地地址。
+    /// INVITE 等对话内请求的 Via 由 rsipstack 生成，始终携带 `rport`
+    pub rport: bool,
+
+    /// REGISTER 的 Contact `q` 参数，表示本设备在同一 AOR 多个绑定中的优先级
+    /// （如备用设备使用较低的 q 值）；`None` 时不携带
+    pub contact_q: Option<QValue>,
+
+    /// REGISTER 与 INVITE（含呼入应答）使用的固定 Contact URI（如 NAT 后的固定公网地址）；
+    /// `None` 时按公网地址（rport/STUN）或本地地址生成。创建客户端时校验
+    pub contact_uri: Option<rsip::Uri>,
+
+    /// 盲转（REFER）后等待最终转接结果的最长时间
+    pub transfer_timeout: Duration,
+
+    /// 单次注册（含认证重试）等待最终响应的最长时间，默认 32 秒（RFC 3261 Timer B）
+    pub register_timeout: Duration,
+
+    /// TLS 传输（`sips:` 或 `transport=tls`）的证书校验选项
+    pub tls: TlsOptions,
+
+    /// 本地 SIP 绑定地址（网卡与端口），`None` 时自动探测出口网卡并使用临时端口。
+    /// UDP 与 TCP 生效；WS/WSS/TLS 只能使用临时端口
+    pub local_bind_addr: Option<SocketAddr>,
+
+    /// STUN 服务器（如 `stun.example.com:3478`），仅 UDP 传输使用。
+    /// 创建客户端时在 SIP 端口上查询 NAT 映射地址，用于 Contact 与媒体 SDP；
+    /// 查询失败时回退到本地地址
+    pub stun_server: Option<String>,
+
+    /// 会话定时器间隔（秒，RFC 4028），`None` 时不启用。
+    /// 启用时 INVITE 携带 `Supported: timer` 与 `Session-Expires`，接通后由本端按半个间隔发送 UPDATE 刷新；
+    /// 低于 90 秒时按 90 秒处理
+    pub session_expires: Option<u32>,
+
+    /// `shutdown` 时是否先对已接通的通话发送 BYE（默认开启）；
+    /// 关闭后直接取消，对端需等待超时才能发现通话结束
+    pub bye_on_shutdown: bool,
+
+    /// SIP 信令套接字的 DSCP 标记（如 [`DSCP_CS3`](crate::utils::DSCP_CS3)），`None` 时不标记。
+    /// 仅 UDP 与 TCP 生效；无权限或平台不支持时只记录警告
+    pub dscp_sip: Option<u8>,
+
+    /// 是否支持可靠临时响应（RFC 3262 100rel）。
+    /// 启用时 INVITE 携带 `Supported: 100rel`，对携带 `Require: 100rel` 与 `RSeq` 的 1xx
+    /// 发送 PRACK（`RAck` 按 RSeq/CSeq 生成），用于要求可靠早期媒体协商的运营商
+    pub enable_100rel: bool,
+
+    /// TCP 传输时每个新事务前关闭已有连接并重新建立，仅用于排查连接问题；
+    /// 默认关闭，注册、呼叫与对话内请求复用同一连接，连接被对端关闭或写入失败时自动重连
+    pub tcp_fresh_connection: bool,
+}
+
+/// 单次呼叫的附加选项
+///
+/// 自定义头部原样追加到 INVITE；Via/From/To/Call-ID/CSeq 由协议栈生成，不允许覆盖
+#[derive(Debug, Clone, Default)]
+pub struct CallOptions {
+    /// 追加到 INVITE 的自定义头部（如 `X-Call-Reason`、`P-Asserted-Identity`）
+    pub headers: Vec<rsip::Header>,
+    /// 覆盖全局配置的 User-Agent
+    pub user_agent: Option<String>,
+    /// 接收早期媒体（携带 SDP 的 183 等临时响应）的通道，可在 200 OK 之前开始收放媒体
+    pub early_media: Option<mpsc::UnboundedSender<EarlyMedia>>,
+    /// From 头部的显示名（被叫端显示的主叫名称），发送时自动加引号并转义
+    pub display_name: Option<String>,
+    /// 追加到 From 头部的参数（`tag` 由协议栈生成，不允许设置）
+    pub from_params: Vec<rsip::Param>,
+    /// 接收分叉通知的通道：INVITE 被分叉且收到多个 2xx 时，每释放一个落选对话发送一次
+    pub fork_events: Option<mpsc::UnboundedSender<ForkedDialog>>,
+    /// 等待 INVITE 最终响应的时长，`None` 表示一直等待；启用重试时对每次尝试生效
+    ///
+    /// 超时后放弃 INVITE 事务：rsipstack 在未确认对话被丢弃时会自动发送 CANCEL 并终止对话
+    pub timeout: Option<Duration>,
+    /// 非 2xx 最终响应（3xx~6xx）转换为 `CallError::CallRejected { code, phrase }`
+    pub strict: bool,
+    /// 遇到可恢复错误（超时、网络异常）时按策略退避重试，不可恢复错误立即返回
+    pub retry: Option<RetryPolicy>,
+    /// multipart/mixed 消息体（如 SDP + PIDF-LO 位置信息），设置后代替 SDP offer 发送
+    pub multipart: Option<MultipartBody>,
+}
+
+impl CallOptions {
+    /// 追加一个自定义头部
+    pub fn with_header(mut self, header: impl Into<rsip::Header>) -> Self {
+        self.headers.push(header.into());
+        self
+    }
+
+    /// 覆盖本次呼叫的 User-Agent
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// 设置 From 头部的显示名
+    pub fn with_display_name(mut self, name: impl Into<String>) -> Self {
+        self.display_name = Some(name.into());
+        self
+    }
+
+    /// 追加一个 From 头部参数
+    pub fn with_from_param(mut self, param: rsip::Param) -> Self {
+        self.from_params.push(param);
+        self
+    }
+
+    /// 设置早期媒体通道
+    ///
+    /// 收到的 SDP 可交给 [`RtpPlayer::apply_early_answer`]；之后的 200 OK 通过
+    /// [`RtpPlayer::apply_final_answer`] 应用，SDP 不同时以 200 OK 为准
+    pub fn with_early_media(mut self, sender: mpsc::UnboundedSender<EarlyMedia>) -> Self {
+        self.early_media = Some(sender);
+        self
+    }
+
+    /// 设置分叉通知通道
+    ///
+    /// `make_call` 返回的对话即第一个 2xx 建立的胜出对话；之后到达的其他 2xx 会被 ACK 并立即 BYE，
+    /// 每个落选对话通过该通道报告一次
+    pub fn with_fork_events(mut self, sender: mpsc::UnboundedSender<ForkedDialog>) -> Self {
+        self.fork_events = Some(sender);
+        self
+    }
+
+    /// 设置等待最终响应的超时
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// 非 2xx 最终响应作为错误返回
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// 设置重试策略
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// 以 multipart/mixed 消息体发送 INVITE
+    ///
+    /// 各部分的 Content-Type 与 Content-Disposition 由 `body` 决定，发送前校验边界；
+    /// 对端 answer 可通过 [`crate::sip_body::response_sdp`] 取出 SDP
+    pub fn with_multipart(mut self, body: MultipartBody) -> Self {
+        self.multipart = Some(body);
+        self
+    }
+
+    /// 生成 INVITE 的附加头部，User-Agent 排在自定义头部之后
+    fn invite_headers(&self) -> Option<Vec<rsip::Header>> {
+        let mut headers = self.headers.clone();
+        headers.extend(
+            self.user_agent
+                .as_ref()
+                .map(|ua| rsip::Header::UserAgent(ua.clone().into())),
+        );
+        (!headers.is_empty()).then_some(headers)
+    }
+}
+
+/// 呼叫结果及解析后的远端媒体描述，由 [`SipClient::make_call_with_answer`] 返回
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallAnswer {
+    /// 最终响应状态码
+    pub status: rsip::StatusCode,
+    /// 对端的 SDP answer；2xx 未携带 SDP 时取此前 183 等临时响应中的 SDP
+    pub sdp: Option<SessionDescription>,
+    /// `sdp` 是否来自临时响应（早期媒体）
+    pub early: bool,
+}
+
+impl CallAnswer {
+    /// 由最终响应与此前收到的早期媒体组合呼叫结果
+    ///
+    /// 3xx~6xx 最终响应不使用早期媒体 SDP；SDP 无法解析时返回错误原因
+    fn new(response: &Response, early: Option<EarlyMedia>) -> Result<Self, String> {
+        let status = response.status_code.clone();
+        let answer_allowed = !matches!(
+            status.kind(),
+            rsip::StatusCodeKind::Redirection
+                | rsip::StatusCodeKind::RequestFailure
+                | rsip::StatusCodeKind::ServerFailure
+                | rsip::StatusCodeKind::GlobalFailure
+        );
+        let (text, early) = match response_sdp(response) {
+            Some(text) if answer_allowed => (Some(text), false),
+            _ if answer_allowed => (early.map(|e| e.sdp), true),
+            _ => (None, false),
+        };
+        let sdp = text
+            .map(|text| {
+                SessionDescription::parse(SdpType::Answer, &text)
+                    .map_err(|e| format!("无法解析对端 SDP: {}", e))
+            })
+            .transpose()?;
+        Ok(Self {
+            status,
+            early: early && sdp.is_some(),
+            sdp,
+        })
+    }
+
+    /// 对端 SDP 的文本形式
+    pub fn sdp_text(&self) -> Option<String> {
+        self.sdp.as_ref().map(SessionDescription::to_sdp_string)
+    }
+}
+
+/// 客户端状态快照
+///
+/// 由 `SipClient::status()` 返回，只组合内存中的已有状态，不产生网络 I/O
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ClientStatus {
+    /// 当前是否处于已注册状态（授予时长未过期）
+    pub registered: bool,
+    /// 服务器授予的注册时长（秒）
+    pub registration_expires: Option<u32>,
+    /// 注册剩余有效时长（秒）
+    pub registration_remaining: Option<u64>,
+    /// 传输层是否仍在运行
+    pub transport_alive: bool,
+    /// 当前活跃的对话数
+    pub active_calls: usize,
+    /// 最近一次错误
+    pub last_error: Option<String>,
+    /// 本地绑定地址
+    pub local_address: Option<String>,
+    /// 服务器回报的公网地址（Via received/rport）
+    pub public_address: Option<String>,
+    /// OPTIONS 保活探测结果，未启用保活时为 `None`
+    pub reachable: Option<bool>,
+    /// 最近一次应答认证挑战使用的方案（见 `AuthScheme::name`），未被挑战时为 `None`
+    pub auth_scheme: Option<String>,
 }
 
+/// 客户端运行期状态
+#[derive(Default)]
+struct ClientState {
+    registered_at: Option<Instant>,
+    registration_expires: Option<u32>,
+    last_error: Option<String>,
+    public_address: Option<rsip::HostWithPort>,
+    /// 注册认证会话，跨刷新复用 nonce 并递增 nonce-count
+    digest: Option<DigestSession>,
+    /// 最近一次注册成功时服务器返回的绑定列表
+    bindings: Vec<ContactBinding>,
+    /// OPTIONS 保活探测结果，未启用保活时为 `None`
+    reachable: Option<bool>,
+    /// 当前注册绑定，刷新与注销沿用其 Call-ID、From tag 与 CSeq
+    binding: Option<RegisterBinding>,
+}
+
+/// 最近一次成功注册的会话标识与参数（RFC 3261 §10.2.4：刷新与注销使用同一 Call-ID，CSeq 递增）
+#[derive(Debug, Clone)]
+struct RegisterBinding {
+    call_id: rsip::headers::CallId,
+    from_tag: rsip::param::Tag,
+    last_seq: u32,
+    server_uri: rsip::Uri,
+    expires: u32,
+}
+
+/// 呼入通话回调
+///
+/// 每个新的 INVITE 调用一次，回调中可直接 `answer`/`reject`，
+/// 或将 `IncomingCall` 转交给其他任务处理
+pub type IncomingCallHandler = Arc<dyn Fn(IncomingCall) + Send + Sync>;
+
+/// CANCEL 后等待 INVITE 最终响应的最长时间（Timer B，64*T1）
+const CANCEL_TIMEOUT: Duration = Duration::from_secs(32);
+
+/// 关闭客户端时等待每个 BYE 完成的最长时间
+const SHUTDOWN_BYE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 注册状态，通过 `SipClient::registration_status()` 订阅
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum RegistrationStatus {
+    /// 尚未注册或已注销
+    #[default]
+    Unregistered,
+    /// 注册成功，附带服务器授予的时长（秒）
+    Registered { expires: u32 },
+    /// 自动注册遇到可恢复错误，正在退避重试
+    Retrying { attempt: u32, error: String },
+    /// 最近一次注册失败
+    Failed(String),
+}
+
+/// 主叫呼叫的生命周期事件，通过 `SipClient::events()` 订阅
+#[derive(Debug, Clone)]
+pub enum CallEvent {
+    /// 收到首个 2xx 最终响应；`setup_time` 从发出 INVITE 起计时，
+    /// `remote_sdp` 为 2xx 携带的 SDP（延迟协商时为 `None`）
+    Answered {
+        dialog_id: DialogId,
+        setup_time: Duration,
+        remote_sdp: Option<String>,
+    },
+    /// 呼叫未接通，`error_code` 与 `CallError::error_code` 一致
+    Failed {
+        call_id: String,
+        error_code: &'static str,
+    },
+    /// 已接通的通话结束
+    Terminated {
+        dialog_id: DialogId,
+        reason: TerminatedReason,
+    },
+}
+
+/// `SipClient::events()` 通道的容量，订阅者落后超过该数量时丢弃最旧的事件
+const CALL_EVENT_CAPACITY: usize = 64;
+
+/// 等待转接进度的对话，键为对话 ID
+type ReferWatchers = Arc<Mutex<HashMap<DialogId, mpsc::UnboundedSender<ReferProgress>>>>;
+
+/// 对端在 2xx 的 `Allow` 中声明支持 UPDATE 的对话
+type UpdatePeers = Arc<Mutex<HashSet<DialogId>>>;
+
 /// SIP 客户端
 pub struct SipClient {
     config: SipClientConfig,
     endpoint: Endpoint,
     dialog_layer: Arc<DialogLayer>,
     cancel_token: CancellationToken,
+    state: Mutex<ClientState>,
+    incoming_handler: Arc<Mutex<Option<IncomingCallHandler>>>,
+    credential_provider: Mutex<Option<CredentialProvider>>,
+    forks: ForkTracker,
+    #[cfg(feature = "aka")]
+    aka_key: Mutex<Option<AkaKey>>,
+    refer_watchers: ReferWatchers,
+    update_peers: UpdatePeers,
+    media_sessions: MediaSessions,
+    registration_status: watch::Sender<RegistrationStatus>,
+    auto_register: Mutex<Option<CancellationToken>>,
+    keepalive: Mutex<Option<CancellationToken>>,
+    local_addr: Option<SocketAddr>,
+    stun_address: Option<SocketAddr>,
+    transport: rsip::transport::Transport,
+    connection_target: String,
+    tcp_link: Option<TcpLink>,
+    metrics: MetricsHandle,
+    events: broadcast::Sender<CallEvent>,
 }
 
 impl SipClient {
     /// 创建新的SIP客户端
+    ///
+    /// 配置了 `local_bind_addr` 时绑定该地址，端口被占用时返回 `CallError::AddressInUse`；
+    /// 否则自动探测出口网卡并使用临时端口
     pub async fn new(config: SipClientConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Some(uri) = &config.contact_uri {
+            validate_contact_uri(uri)?;
+        }
         let cancel_token = CancellationToken::new();
 
-        // 获取本地IP
-        let local_ip = crate::utils::get_first_non_loopback_interface()?;
-        info!(
-            "检测到本地出口IP: {} ({})",
-            local_ip,
-            if local_ip.is_ipv6() { "IPv6" } else { "IPv4" }
-        );
+        // 获取本地绑定地址
+        let local_addr = match config.local_bind_addr {
+            Some(addr) => {
+                info!("使用配置的本地绑定地址: {}", addr);
+                addr
+            }
+            None => {
+                let local_ip = crate::utils::get_first_non_loopback_interface()?;
+                info!(
+                    "检测到本地出口IP: {} ({})",
+                    local_ip,
+                    if local_ip.is_ipv6() { "IPv6" } else { "IPv4" }
+                );
+                SocketAddr::new(local_ip, 0)
+            }
+        };
 
         // 创建传输层
         let mut transport_layer = TransportLayer::new(cancel_token.clone());
@@ -97,24 +518,84 @@ impl SipClient {
             );
         }
 
-        // 使用提取出的protocol创建传输连接
-        let local_addr = format!("{}:{}", local_ip, 0).parse()?;
-        let connection = create_transport_connection(
-            protocol,
-            local_addr,
-            &connection_target,
-            cancel_token.clone(),
-        )
-        .await?;
+        // 在 SIP 将使用的端口上做 STUN 查询，随后以同一端口创建传输，使映射地址可直接用于 Contact
+        let mut stun_address = None;
+        let local_addr = match (&config.stun_server, protocol) {
+            (Some(stun_server), Protocol::Udp) => {
+                let socket = tokio::net::UdpSocket::bind(local_addr)
+                    .await
+                    .map_err(|e| bind_error(local_addr, e))?;
+                let bound = socket.local_addr()?;
+                match crate::utils::stun_binding(&socket, stun_server, STUN_TIMEOUT).await {
+                    Ok(mapped) => {
+                        info!("🌐 STUN 映射地址: {} -> {}", bound, mapped);
+                        stun_address = Some(mapped);
+                    }
+                    Err(e) => warn!("STUN 查询失败，使用本地地址: {}", e),
+                }
+                bound
+            }
+            (Some(_), _) => {
+                warn!("STUN 仅用于 UDP 传输，{} 传输忽略 stun_server", protocol);
+                local_addr
+            }
+            (None, _) => local_addr,
+        };
+
+        // 使用提取出的protocol创建传输连接；TCP 使用断线自动重连的出口连接
+        let tcp_link = if protocol == Protocol::Tcp {
+            Some(TcpLink::connect(local_addr, &connection_target, config.dscp_sip, cancel_token.clone()).await?)
+        } else {
+            None
+        };
+        let connection = match &tcp_link {
+            Some(link) => link.connection(),
+            None => {
+                create_transport_connection(
+                    protocol,
+                    local_addr,
+                    &connection_target,
+                    &config.tls,
+                    config.dscp_sip,
+                    cancel_token.clone(),
+                )
+                .await?
+            }
+        };
+        let bound_addr = tcp_link.as_ref().map(TcpLink::local_addr).or_else(|| connection_local_addr(&connection));
+        info!("本地 SIP 传输已绑定: {:?}", bound_addr);
+        info!(
+            "📡 SIP 出站传输: {} -> {}{}",
+            protocol.as_str().to_uppercase(),
+            connection_target,
+            if config.outbound_proxy.is_some() { "（Outbound 代理）" } else { "" }
+        );
 
-        transport_layer.add_transport(connection);
+        transport_layer.add_transport(connection.clone());
+        if tcp_link.is_some() {
+            // rsipstack 只从连接池查找 TCP 连接，未命中时另建新连接；将启动时的连接放入连接池
+            // 并作为所有请求的出口，使注册、呼叫与对话内请求共用同一连接（断线后由 TcpLink 重连）
+            transport_layer.outbound = Some(connection.get_addr().clone());
+            transport_layer.add_connection(connection);
+        }
 
         // 创建端点
+        let reject_headers = RejectHeaders::default();
+        let forks = ForkTracker::default();
+        let (fork_sender, fork_responses) = mpsc::unbounded_channel();
+        let (prack_sender, prack_responses) = mpsc::unbounded_channel();
         let mut endpoint_builder = EndpointBuilder::new();
         endpoint_builder
             .with_cancel_token(cancel_token.clone())
             .with_transport_layer(transport_layer)
-            .with_user_agent(&config.user_agent);
+            .with_user_agent(&config.user_agent)
+            .with_inspector(Box::new(OutgoingHeaders {
+                reject_headers: reject_headers.clone(),
+                forks: forks.clone(),
+                fork_responses: Some(fork_sender),
+                pracks: PrackTracker::default(),
+                prack_responses: Some(prack_sender),
+            }));
 
         let endpoint = endpoint_builder.build();
 
@@ -123,29 +604,94 @@ impl SipClient {
         tokio::spawn(async move {
             endpoint_for_serve.serve().await.ok();
         });
+        forks.spawn_handler(endpoint.inner.clone(), fork_responses, cancel_token.clone());
 
         // 创建对话层
         let dialog_layer = Arc::new(DialogLayer::new(endpoint.inner.clone()));
+        PrackTracker::spawn_handler(dialog_layer.clone(), prack_responses, cancel_token.clone());
 
         // 启动传入请求处理
+        let incoming_handler = Arc::new(Mutex::new(None));
         Self::start_incoming_handler(
             endpoint.incoming_transactions()?,
             dialog_layer.clone(),
+            incoming_handler.clone(),
+            reject_headers,
+            config.username.clone(),
+            config.contact_uri.clone(),
             cancel_token.clone(),
         );
 
+        let metrics = Metrics::new();
+        let active_dialogs = dialog_layer.clone();
+        metrics.set_active_calls_source(move || active_dialogs.len());
+
         Ok(Self {
             config,
             endpoint,
             dialog_layer,
             cancel_token,
+            state: Mutex::new(ClientState::default()),
+            incoming_handler,
+            credential_provider: Mutex::new(None),
+            forks,
+            #[cfg(feature = "aka")]
+            aka_key: Mutex::new(None),
+            refer_watchers: Arc::new(Mutex::new(HashMap::new())),
+            update_peers: Arc::new(Mutex::new(HashSet::new())),
+            media_sessions: Arc::new(Mutex::new(HashMap::new())),
+            registration_status: watch::channel(RegistrationStatus::default()).0,
+            auto_register: Mutex::new(None),
+            keepalive: Mutex::new(None),
+            local_addr: bound_addr,
+            stun_address,
+            transport: protocol.into(),
+            connection_target,
+            tcp_link,
+            metrics,
+            events: broadcast::channel(CALL_EVENT_CAPACITY).0,
         })
     }
 
+    /// 注册呼入通话回调
+    ///
+    /// 未注册回调时，新的 INVITE 以 480 Temporarily Unavailable 拒绝；
+    /// 重复调用会替换之前的回调
+    pub fn on_incoming_call<F>(&self, handler: F)
+    where
+        F: Fn(IncomingCall) + Send + Sync + 'static,
+    {
+        *self.incoming_handler.lock().unwrap() = Some(Arc::new(handler));
+    }
+
+    /// 设置注册认证的动态凭证回调
+    ///
+    /// 收到 401/407 挑战（以及刷新时复用 nonce 预先认证）时以 realm 与 nonce 调用，
+    /// 返回值替代配置中的静态密码；未设置时使用静态密码
+    pub fn on_auth_challenge<F>(&self, provider: F)
+    where
+        F: Fn(&str, &str) -> Pin<Box<dyn Future<Output = String> + Send>> + Send + Sync + 'static,
+    {
+        *self.credential_provider.lock().unwrap() = Some(Arc::new(provider));
+    }
+
+    /// 设置 ISIM 密钥，用于 IMS/VoLTE 注册的 AKAv1-MD5 认证
+    ///
+    /// 注册挑战中有 `algorithm=AKAv1-MD5` 时以 Milenage 推导的 RES 应答，
+    /// 服务器未提供 AKA 挑战时仍使用配置的密码进行 MD5/SHA Digest 认证
+    #[cfg(feature = "aka")]
+    pub fn set_aka_key(&self, key: AkaKey) {
+        *self.aka_key.lock().unwrap() = Some(key);
+    }
+
     /// 启动传入请求处理器
     fn start_incoming_handler(
         mut incoming: rsipstack::transaction::TransactionReceiver,
         dialog_layer: Arc<DialogLayer>,
+        incoming_handler: Arc<Mutex<Option<IncomingCallHandler>>>,
+        reject_headers: RejectHeaders,
+        username: String,
+        contact_uri: Option<rsip::Uri>,
         cancel_token: CancellationToken,
     ) {
         tokio::spawn(async move {
@@ -154,23 +700,270 @@ impl SipClient {
                 _ = cancel_token.cancelled() => None,
             } {
                 let method = transaction.original.method;
-                debug!("收到传入请求: {}", method);
+                let span = info_span!("sip_request", %method, call_id = Empty, dialog_id = Empty);
+                if let Ok(call_id) = transaction.original.call_id_header() {
+                    span.record("call_id", display(call_id.value()));
+                }
+                span.in_scope(|| debug!("收到传入请求: {}", method));
 
                 if let Some(mut dialog) = dialog_layer.match_dialog(&transaction.original) {
-                    tokio::spawn(async move {
-                        if let Err(e) = dialog.handle(&mut transaction).await {
-                            error!("处理 {} 请求失败: {}", method, e);
+                    span.record("dialog_id", display(dialog.id()));
+                    tokio::spawn(
+                        async move {
+                            if let Err(e) = dialog.handle(&mut transaction).await {
+                                error!("处理 {} 请求失败: {}", method, e);
+                            }
                         }
-                    });
+                        .instrument(span),
+                    );
+                } else if method == rsip::Method::Invite {
+                    Self::accept_incoming_invite(
+                        transaction,
+                        &dialog_layer,
+                        &incoming_handler,
+                        &reject_headers,
+                        &username,
+                        contact_uri.as_ref(),
+                    )
+                    .instrument(span)
+                    .await;
+                } else if method == rsip::Method::Notify {
+                    // NOTIFY 可能先于 SUBSCRIBE 的 2xx 到达：不应答，等待对端重传时订阅对话已建立
+                    span.in_scope(|| debug!("NOTIFY 暂无匹配的订阅，等待重传"));
                 } else {
-                    warn!("未找到匹配的对话: {}", method);
+                    span.in_scope(|| warn!("未找到匹配的对话: {}", method));
                 }
             }
         });
     }
 
-    /// 执行注册
+    /// 为新的 INVITE 创建服务端对话，回复 180 Ringing 并交给呼入回调
+    async fn accept_incoming_invite(
+        mut transaction: rsipstack::transaction::transaction::Transaction,
+        dialog_layer: &DialogLayer,
+        incoming_handler: &Mutex<Option<IncomingCallHandler>>,
+        reject_headers: &RejectHeaders,
+        username: &str,
+        contact_uri: Option<&rsip::Uri>,
+    ) {
+        let handler = incoming_handler.lock().unwrap().clone();
+        let Some(handler) = handler else {
+            info!("未注册呼入回调，拒绝呼入通话");
+            if let Err(e) = transaction
+                .reply(rsip::StatusCode::TemporarilyUnavailable)
+                .await
+            {
+                warn!("回复 480 失败: {}", e);
+            }
+            return;
+        };
+
+        let (state_sender, state_receiver) = dialog_layer.new_dialog_state_channel();
+        let contact = contact_uri.cloned().or_else(|| {
+            dialog_layer
+                .build_local_contact(Some(username.to_string()), None)
+                .ok()
+        });
+        let dialog = match dialog_layer.get_or_create_server_invite(
+            &transaction,
+            state_sender,
+            None,
+            contact,
+        ) {
+            Ok(dialog) => dialog,
+            Err(e) => {
+                error!("创建服务端对话失败: {}", e);
+                transaction
+                    .reply(rsip::StatusCode::ServerInternalError)
+                    .await
+                    .ok();
+                return;
+            }
+        };
+        tracing::Span::current().record("dialog_id", display(dialog.id()));
+        info!("📲 收到呼入通话: {}", dialog.id());
+
+        // INVITE 事务（含 ACK 处理，收到 ACK 后对话进入 Confirmed）由对话驱动
+        let mut server_dialog = dialog.clone();
+        tokio::spawn(
+            async move {
+                if let Err(e) = server_dialog.handle(&mut transaction).await {
+                    error!("处理呼入 INVITE 失败: {}", e);
+                }
+            }
+            .in_current_span(),
+        );
+
+        if let Err(e) = dialog.ringing(None, None) {
+            warn!("发送 180 Ringing 失败: {}", e);
+        }
+        handler(
+            IncomingCall::new(dialog)
+                .with_reject_headers(reject_headers.clone())
+                .with_dialog_states(state_receiver),
+        );
+    }
+
+    /// 执行注册，注册时长取配置的 `register_expires`
     pub async fn register(&self) -> CallResult<Response> {
+        self.register_with_expires(self.config.register_expires).await
+    }
+
+    /// 以指定注册时长（秒）执行注册
+    ///
+    /// expires 按配置的 `expires_mode` 携带在 `Expires` 头和/或 Contact 的 `expires` 参数中
+    ///
+    /// # 返回
+    /// - `Err(CallError::InvalidConfig)` - `expires` 为 0 或超过 `MAX_REGISTER_EXPIRES`
+    /// - `Err(CallError::NetworkTimeout)` - `register_timeout` 内未收到最终响应
+    /// - `Err(CallError::NotConnected)` - 等待期间客户端被关闭
+    pub async fn register_with_expires(&self, expires: u32) -> CallResult<Response> {
+        let result = match validate_register_expires(expires) {
+            Ok(expires) => {
+                self.do_register(expires)
+                    .instrument(info_span!("register", call_id = Empty))
+                    .await
+            }
+            Err(e) => Err(CallError::invalid_config(e.to_string())),
+        };
+        self.record_result(&result);
+        match &result {
+            Ok(_) => {
+                let granted = self.state.lock().unwrap().registration_expires.unwrap_or(expires);
+                self.set_registration_status(RegistrationStatus::Registered { expires: granted });
+            }
+            Err(e) => {
+                self.set_registration_status(RegistrationStatus::Failed(e.to_string()));
+            }
+        }
+        result
+    }
+
+    /// 以上次注册的服务器与注册时长重新注册，沿用同一 Call-ID 并递增 CSeq
+    ///
+    /// # 返回
+    /// - `Err(CallError::InvalidConfig)` - 尚未成功注册过
+    pub async fn refresh(&self) -> CallResult<Response> {
+        let binding = self.state.lock().unwrap().binding.clone();
+        let Some(binding) = binding else {
+            return Err(CallError::invalid_config("尚未注册，无法刷新"));
+        };
+        info!("刷新注册: {} (expires={})", binding.server_uri, binding.expires);
+        self.register_with_expires(binding.expires).await
+    }
+
+    /// 让新的注册会话沿用当前绑定的 Call-ID、From tag 与 CSeq；没有绑定时使用新的 Call-ID
+    fn resume_binding(&self, registration: &mut SipRegistration) {
+        match self.state.lock().unwrap().binding.as_ref() {
+            Some(binding) => {
+                registration.call_id = binding.call_id.clone();
+                registration.from_tag = binding.from_tag.clone();
+                registration.last_seq = binding.last_seq;
+            }
+            None => registration.call_id = Uuid::new_v4().to_string().into(),
+        }
+    }
+
+    /// 保存注册会话的认证状态，并同步当前绑定已用到的 CSeq（失败的请求同样消耗序号）
+    fn save_registration_session(&self, registration: &SipRegistration) {
+        let mut state = self.state.lock().unwrap();
+        state.digest = registration.digest_session().cloned();
+        if let Some(binding) = state.binding.as_mut() {
+            if binding.call_id == registration.call_id {
+                binding.last_seq = registration.last_seq;
+            }
+        }
+    }
+
+    /// 订阅注册状态变化
+    pub fn registration_status(&self) -> watch::Receiver<RegistrationStatus> {
+        self.registration_status.subscribe()
+    }
+
+    /// 订阅主叫呼叫的接通、失败与结束事件
+    ///
+    /// 只收到订阅之后发生的事件；订阅者处理过慢时最旧的事件被丢弃
+    pub fn events(&self) -> broadcast::Receiver<CallEvent> {
+        self.events.subscribe()
+    }
+
+    /// 更新注册状态并同步 `sip_registered` 指标
+    fn set_registration_status(&self, status: RegistrationStatus) {
+        self.metrics
+            .set_registered(matches!(status, RegistrationStatus::Registered { .. }));
+        self.registration_status.send_replace(status);
+    }
+
+    /// 本客户端的指标注册表，可用 [`Metrics::render`] 导出 Prometheus 文本格式
+    ///
+    /// 统计发起/应答/失败（按 `error_code` 分类）的呼叫、活跃对话数与注册状态；
+    /// RTP 收发包数需通过 `RtpPlayer::with_metrics` 关联到同一注册表
+    pub fn metrics_handle(&self) -> MetricsHandle {
+        self.metrics.clone()
+    }
+
+    /// 启动后台自动注册任务
+    ///
+    /// 立即注册一次，之后在服务器授予时长的一半时刷新。可恢复的错误（超时、网络、5xx）
+    /// 按配置的 `register_backoff` 退避重试；认证失败或其他不可恢复错误时停止任务。
+    /// 重复调用会先停止之前的任务；`shutdown` 时任务随之退出
+    ///
+    /// # 返回
+    /// 注册状态的订阅端，与 `registration_status()` 相同
+    pub fn start_auto_register(self: &Arc<Self>, expires: u32) -> watch::Receiver<RegistrationStatus> {
+        let token = self.cancel_token.child_token();
+        if let Some(previous) = self.auto_register.lock().unwrap().replace(token.clone()) {
+            previous.cancel();
+        }
+
+        // 只持有弱引用，客户端被释放后任务自动结束
+        let client = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut attempt = 0u32;
+            loop {
+                let Some(this) = client.upgrade() else {
+                    break;
+                };
+                let delay = match this.register_with_expires(expires).await {
+                    Ok(_) => {
+                        attempt = 0;
+                        let granted = this
+                            .state
+                            .lock()
+                            .unwrap()
+                            .registration_expires
+                            .unwrap_or(expires);
+                        Duration::from_secs(u64::from(granted / 2).max(1))
+                    }
+                    Err(e) if e.is_recoverable() => {
+                        let delay = this.config.register_backoff.delay(attempt);
+                        attempt = attempt.saturating_add(1);
+                        warn!("注册失败，{:?} 后重试 (第 {} 次): {}", delay, attempt, e);
+                        this.set_registration_status(RegistrationStatus::Retrying {
+                            attempt,
+                            error: e.to_string(),
+                        });
+                        delay
+                    }
+                    Err(e) => {
+                        error!("注册失败且不可恢复，停止自动注册: {}", e);
+                        break;
+                    }
+                };
+                drop(this);
+
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = token.cancelled() => break,
+                }
+            }
+            debug!("自动注册任务已退出");
+        });
+
+        self.registration_status()
+    }
+
+    async fn do_register(&self, expires: u32) -> CallResult<Response> {
         info!("正在注册到 SIP 服务器...");
 
         let actual_local_addr = self
@@ -193,30 +986,84 @@ impl SipClient {
 
         info!("Register URI: {}", register_uri);
 
-        // 创建认证凭证
-        let credential = Credential {
-            username: self.config.username.clone(),
-            password: self.config.password.clone(),
-            realm: None, // 将从 401 响应自动提取
-        };
+        // 按认证模式创建凭证（IP 认证且无密码时不应答挑战）
+        let credential = self.credential();
 
-        // 创建 Registration 实例（全局 route_set 已在 Endpoint 层面配置）
-        let mut registration = Registration::new(self.endpoint.inner.clone(), Some(credential));
+        // 创建注册会话（全局 route_set 已在 Endpoint 层面配置）
+        let registration =
+            SipRegistration::new(self.endpoint.inner.clone(), credential)
+                .with_expires_mode(self.config.expires_mode)
+                .with_stale_nonce_retry(self.config.stale_nonce_retry)
+                .with_rport(self.config.rport)
+                .with_contact_q(self.config.contact_q)
+                .with_contact_uri(self.config.contact_uri.clone())
+                .with_aor(Some(self.aor_uri().as_str().try_into()?))
+                .with_digest_session(self.state.lock().unwrap().digest.clone())
+                .with_credential_provider(self.credential_provider.lock().unwrap().clone());
+        #[cfg(feature = "aka")]
+        let registration = registration.with_aka_key(self.aka_key.lock().unwrap().clone());
+        let mut registration = registration;
+        // 沿用已知的公网地址（rport 学习或 STUN 映射）作为 Contact
+        registration.public_address = self.public_address();
+        self.resume_binding(&mut registration);
+        tracing::Span::current().record("call_id", display(registration.call_id.value()));
 
-        registration.call_id = Uuid::new_v4().to_string().into();
-        // 执行注册
-        let response = registration.register(register_uri.clone(), Some(3600)).await?;
+        // 执行注册；注册服务器无响应或客户端关闭时不再等待
+        self.reset_tcp_connection();
+        let limit = self.config.register_timeout;
+        let result = tokio::select! {
+            result = tokio::time::timeout(limit, registration.register(register_uri.clone(), Some(expires))) => result,
+            _ = self.cancel_token.cancelled() => {
+                self.save_registration_session(&registration);
+                return Err(CallError::NotConnected);
+            }
+        };
+        self.save_registration_session(&registration);
+        let Ok(result) = result else {
+            warn!("⏱️ 注册在 {:?} 内未收到最终响应", limit);
+            return Err(CallError::NetworkTimeout {
+                duration: limit.as_millis() as u64,
+            });
+        };
+        let response = result?;
         
         if response.status_code == rsip::StatusCode::OK {
-            info!("✔ 注册成功,响应状态: {}", response.status_code);
+            info!(
+                "✔ 注册成功,响应状态: {}, 授予时长: {:?}秒",
+                response.status_code,
+                registration.granted_expires()
+            );
+            let mut state = self.state.lock().unwrap();
+            state.registered_at = Some(Instant::now());
+            state.registration_expires = registration
+                .granted_expires()
+                .or(registration.requested_expires())
+                .or(Some(expires));
+            if registration.public_address.is_some() {
+                state.public_address = registration.public_address.clone();
+            }
+            state.bindings = registration.bindings().to_vec();
+            state.binding = Some(RegisterBinding {
+                call_id: registration.call_id.clone(),
+                from_tag: registration.from_tag.clone(),
+                last_seq: registration.last_seq,
+                server_uri: register_uri,
+                expires,
+            });
         } else {
             warn!("注册响应: {}", response.status_code);
             
             // 根据状态码返回适当的错误
             match response.status_code {
-                rsip::StatusCode::Unauthorized => {
-                    return Err(CallError::AuthenticationFailed { 
-                        reason: "认证失败".to_string() 
+                rsip::StatusCode::Unauthorized
+                | rsip::StatusCode::ProxyAuthenticationRequired => {
+                    let reason = if registration.credential.is_none() {
+                        "服务器要求 Digest 认证，但 IP 认证模式未配置密码"
+                    } else {
+                        "认证失败"
+                    };
+                    return Err(CallError::AuthenticationFailed {
+                        reason: reason.to_string(),
                     });
                 }
                 rsip::StatusCode::NotFound => {
@@ -246,68 +1093,289 @@ impl SipClient {
 
     /// 发起呼叫
     pub async fn make_call(&self, target: &str,sdp_offer: &str) -> CallResult<(ClientInviteDialog, Option<Response>)> {
-        info!("📞发起呼叫到: {}", target);
+        self.make_call_with_options(target, sdp_offer, &CallOptions::default()).await
+    }
 
-        let actual_local_addr = self
-            .endpoint
-            .get_addrs()
-            .first()
-            .ok_or(CallError::NotInitialized)?
-            .addr
-            .clone();
+    /// 按 `options` 发起呼叫
+    ///
+    /// 自定义头部、超时、重试、非 2xx 转错误与 multipart 消息体均由 `options` 指定；
+    /// 设置了 `multipart` 时以其代替 `sdp_offer` 作为 INVITE 消息体
+    ///
+    /// # 返回
+    /// - `Err(CallError::InvalidConfig)` - 自定义头部试图覆盖 Via/From/To/Call-ID/CSeq
+    /// - `Err(CallError::NetworkTimeout)` - 设置了 `timeout` 且期间未收到最终响应
+    /// - `Err(CallError::CallRejected)` - 设置了 `strict` 且 INVITE 以 3xx~6xx 结束
+    pub async fn make_call_with_options(
+        &self,
+        target: &str,
+        sdp_offer: &str,
+        options: &CallOptions,
+    ) -> CallResult<(ClientInviteDialog, Option<Response>)> {
+        let (content_type, offer) = match &options.multipart {
+            Some(body) => match body.encode() {
+                Ok(encoded) => (body.content_type(), encoded),
+                Err(e) => {
+                    let result = Err(e.into());
+                    self.record_result(&result);
+                    return result;
+                }
+            },
+            None => (SDP_CONTENT_TYPE.to_string(), sdp_offer.as_bytes().to_vec()),
+        };
+        let attempt = || self.do_make_call(target, content_type.clone(), offer.clone(), options);
+        let result = match &options.retry {
+            Some(policy) => retry_call(policy, attempt).await,
+            None => attempt().await,
+        };
+        // 严格模式下 3xx~6xx 最终响应转换为错误，未收到最终响应（`None`）仍视为成功
+        let rejected = match &result {
+            Ok((_, Some(resp)))
+                if options.strict
+                    && !matches!(
+                        resp.status_code.kind(),
+                        rsip::StatusCodeKind::Provisional | rsip::StatusCodeKind::Successful
+                    ) =>
+            {
+                Some(resp.status_code.clone())
+            }
+            _ => None,
+        };
+        let result = match rejected {
+            Some(status) => {
+                warn!("呼叫被拒绝: {}", status);
+                Err(CallError::rejected(&status))
+            }
+            None => result,
+        };
+        self.record_result(&result);
+        result
+    }
 
-        let contact_uri_str = format!("sip:{}@{}", self.config.username, actual_local_addr);
+    /// 发起呼叫并返回解析后的对端 SDP answer
+    ///
+    /// 最终响应未携带 SDP 时使用此前 183 等临时响应中的 SDP（`CallAnswer::early` 为 true），
+    /// 调用方无需再从响应消息体中提取
+    ///
+    /// # 返回
+    /// - `Err(CallError::InvalidSdp)` - 对端 SDP 无法解析
+    /// - `Err(CallError::NetworkTimeout)` - INVITE 事务结束时仍未收到最终响应
+    pub async fn make_call_with_answer(
+        &self,
+        target: &str,
+        sdp_offer: &str,
+    ) -> CallResult<(ClientInviteDialog, CallAnswer)> {
+        let (early_tx, mut early_rx) = mpsc::unbounded_channel();
+        let options = CallOptions::default().with_early_media(early_tx);
+        let (dialog, response) = self.make_call_with_options(target, sdp_offer, &options).await?;
+        drop(options);
+        let Some(response) = response else {
+            let result = Err(CallError::NetworkTimeout {
+                duration: self.endpoint.inner.option.t1x64.as_millis() as u64,
+            });
+            self.record_result(&result);
+            return result;
+        };
 
-        // 构造 From/To URI（使用服务器URI的域名部分）
-        let server_domain = self.config.server.host_with_port.to_string();
+        // 早期媒体由对话状态任务按顺序转发，该任务处理到最终响应对应的状态后关闭通道，
+        // 因此读到通道关闭即已收齐最终响应之前的全部早期媒体
+        let mut early = None;
+        if response_sdp(&response).is_none() {
+            while let Some(media) = early_rx.recv().await {
+                early = Some(media);
+            }
+        }
+        let result = CallAnswer::new(&response, early).map_err(CallError::invalid_sdp);
+        self.record_result(&result);
+        Ok((dialog, result?))
+    }
 
-        let from_uri = format!("sip:{}@{}", self.config.username, server_domain);
-        let to_uri = if target.contains('@') {
-            format!("sip:{}", target)
-        } else {
-            format!("sip:{}@{}", target, server_domain)
+    /// 生成呼叫 Call-ID，并在携带 `call_id`（收到响应后补充 `dialog_id`）的 span 中发起呼叫
+    async fn do_make_call(
+        &self,
+        target: &str,
+        content_type: String,
+        offer: Vec<u8>,
+        options: &CallOptions,
+    ) -> CallResult<(ClientInviteDialog, Option<Response>)> {
+        // 生成呼叫 Call-ID（直接使用 UUID 字符串）
+        let call_id = Uuid::new_v4().to_string();
+        let span = info_span!("call", call_id = %call_id, dialog_id = Empty);
+        self.metrics.record_call_placed();
+        self.forks.track(&call_id, options.fork_events.clone());
+        let started = Instant::now();
+        let result = self
+            .send_invite(call_id.clone(), target, content_type, offer, options)
+            .instrument(span)
+            .await;
+        let failed = |error: &CallError| CallEvent::Failed {
+            call_id: call_id.clone(),
+            error_code: error.error_code(),
         };
+        match &result {
+            Ok((dialog, Some(resp))) if resp.status_code.kind() == rsip::StatusCodeKind::Successful => {
+                self.metrics.record_call_answered();
+                // 事务结束后的 32 秒内仍可能收到其他分叉的 2xx
+                let window = self.endpoint.inner.option.t1x64;
+                self.forks.answered(&self.endpoint.inner, &dialog.id(), window);
+                let _ = self.events.send(CallEvent::Answered {
+                    dialog_id: dialog.id(),
+                    setup_time: started.elapsed(),
+                    remote_sdp: response_sdp(resp),
+                });
+            }
+            Ok((_, Some(resp))) => {
+                self.forks.untrack(&call_id);
+                let error = CallError::rejected(&resp.status_code);
+                self.metrics.record_call_failed(&error);
+                let _ = self.events.send(failed(&error));
+            }
+            Ok((_, None)) => self.forks.untrack(&call_id),
+            Err(e) => {
+                self.forks.untrack(&call_id);
+                self.metrics.record_call_failed(e);
+                let _ = self.events.send(failed(e));
+            }
+        }
+        result
+    }
+
+    async fn send_invite(
+        &self,
+        call_id_string: String,
+        target: &str,
+        content_type: String,
+        offer: Vec<u8>,
+        options: &CallOptions,
+    ) -> CallResult<(ClientInviteDialog, Option<Response>)> {
+        info!("📞发起呼叫到: {}", target);
+        if let Some(name) = find_reserved_header(&options.headers) {
+            return Err(CallError::invalid_config(format!("自定义头部不能覆盖 {}", name)));
+        }
+        if options.from_params.iter().any(|p| matches!(p, rsip::Param::Tag(_))) {
+            return Err(CallError::invalid_config("From 参数不能包含 tag"));
+        }
+        // 空 offer 为延迟协商（late offer），不做校验
+        if content_type == SDP_CONTENT_TYPE && !offer.is_empty() {
+            crate::utils::validate_sdp(&String::from_utf8_lossy(&offer))?;
+        }
+
+        // 保活探测已判定服务器不可达（如 NAT 绑定失效）时直接失败，避免 INVITE 长时间无响应
+        if !self.is_reachable() {
+            warn!("OPTIONS 保活探测失败，服务器当前不可达");
+            return Err(CallError::NotConnected);
+        }
+
+        let contact_uri_str = self.local_contact_uri().ok_or(CallError::NotInitialized)?;
+
+        // 构造 From/To URI（From 为本端 AOR）
+        let from_uri = self.aor_uri();
+        let to_uri = self.target_uri(target).ok_or_else(|| CallError::invalid_target(target))?;
 
         info!("Call信息 源：{} -> 目标：{}", from_uri, to_uri);
 
 
-        // 生成呼叫 Call-ID（直接使用 UUID 字符串）
-        let call_id_string = Uuid::new_v4().to_string();
         info!("生成呼叫 Call-ID: {}", call_id_string);
 
-        // 创建认证凭证
-        let credential = Credential {
-            username: self.config.username.clone(),
-            password: self.config.password.clone(),
-            realm: None, // 将从 401/407 响应自动提取
-        };
-
-        // 全局 route_set 已在 Endpoint 层面配置，INVITE 会自动使用
-        let invite_opt = InviteOption {
-            caller: from_uri.as_str().try_into()?,
-            callee: to_uri.as_str().try_into()?,
-            contact: contact_uri_str.as_str().try_into()?,
-            credential: Some(credential),
-            caller_display_name: None,
-            caller_params: vec![],
-            destination: None, // 让 rsipstack 自动从 Route header 解析
-            content_type: Some("application/sdp".to_string()),
-            offer: Some(sdp_offer.as_bytes().to_vec()),
-            headers: None, // 不需要手动添加，rsipstack 自动处理
-            support_prack: false,
-            call_id: Some(call_id_string),
-        };
-
-        // 创建状态通道
-        let (state_sender, _state_receiver) = self.dialog_layer.new_dialog_state_channel();
-
-        // 发送 INVITE
-        let (dialog, response) = self
-            .dialog_layer
-            .do_invite(invite_opt, state_sender)
-            .await?;
+        // 启用会话定时器时声明 timer 支持；收到 422 时按对端 Min-SE 重试一次
+        let mut session = self.config.session_expires.map(SessionExpires::uac);
+        let mut retried_interval = false;
+        let (dialog, response) = loop {
+            let mut headers = options.invite_headers().unwrap_or_default();
+            if !headers.iter().any(|h| matches!(h, rsip::Header::Allow(_))) {
+                headers.push(rsip::headers::Allow::new(ALLOWED_METHODS).into());
+            }
+            if let Some(session) = session {
+                headers.extend(session_timer::invite_headers(session));
+            }
+
+            // 全局 route_set 已在 Endpoint 层面配置，INVITE 会自动使用
+            let invite_opt = InviteOption {
+                caller: from_uri.as_str().try_into()?,
+                callee: to_uri.as_str().try_into()?,
+                contact: contact_uri_str.as_str().try_into()?,
+                // 按认证模式创建凭证（IP 认证且无密码时不应答挑战）
+                credential: self.credential(),
+                caller_display_name: options.display_name.as_deref().map(quote_display_name),
+                caller_params: options.from_params.clone(),
+                destination: None, // 让 rsipstack 自动从 Route header 解析
+                content_type: Some(content_type.clone()),
+                offer: Some(offer.clone()),
+                headers: (!headers.is_empty()).then_some(headers),
+                support_prack: self.config.enable_100rel,
+                call_id: Some(call_id_string.clone()),
+            };
+
+            // 创建状态通道，由后台任务处理对话内的转接通知
+            let (state_sender, state_receiver) = self.dialog_layer.new_dialog_state_channel();
+            Self::watch_dialog_states(
+                state_receiver,
+                self.refer_watchers.clone(),
+                self.update_peers.clone(),
+                self.media_sessions.clone(),
+                options.early_media.clone(),
+                self.events.clone(),
+            );
+
+            // 发送 INVITE；未指定超时时一直等待最终响应
+            self.reset_tcp_connection();
+            let invite = self.dialog_layer.do_invite(invite_opt, state_sender);
+            let (dialog, response) = match options.timeout {
+                None => invite.await?,
+                Some(limit) => match tokio::time::timeout(limit, invite).await {
+                    Ok(result) => result?,
+                    Err(_) => {
+                        // 丢弃 do_invite 时 rsipstack 会为未确认的对话发送 CANCEL
+                        warn!("⏱️ INVITE 在 {:?} 内未收到最终响应，取消呼叫", limit);
+                        return Err(CallError::NetworkTimeout {
+                            duration: limit.as_millis() as u64,
+                        });
+                    }
+                },
+            };
+
+            let min_se = response
+                .as_ref()
+                .filter(|resp| resp.status_code == rsip::StatusCode::SessionIntervalTooSmall)
+                .and_then(|resp| session_timer::parse_min_se(&resp.headers));
+            match (min_se, session) {
+                (Some(min_se), Some(current)) if !retried_interval && min_se > current.interval => {
+                    warn!("会话间隔 {}s 过小，按 Min-SE {}s 重试", current.interval, min_se);
+                    session = Some(SessionExpires::uac(min_se));
+                    retried_interval = true;
+                }
+                _ => break (dialog, response),
+            }
+        };
+
+        // 记录对端是否支持 UPDATE，供 send_update 与会话刷新选择方法
+        let answered = response
+            .as_ref()
+            .filter(|resp| resp.status_code.kind() == rsip::StatusCodeKind::Successful);
+        let peer_allows_update = answered.is_some_and(|resp| allows_method(&resp.headers, rsip::Method::Update));
+        if peer_allows_update {
+            self.update_peers.lock().unwrap().insert(dialog.id());
+        }
+        if answered.is_some() && content_type == SDP_CONTENT_TYPE && !offer.is_empty() {
+            self.media_sessions.lock().unwrap().entry(dialog.id()).or_default().offer = Some(offer.clone());
+        }
+
+        // 接通后由本端负责刷新时启动刷新任务；对端不支持 UPDATE 时以当前媒体描述发送 re-INVITE
+        if let (Some(requested), Some(resp)) = (session, answered) {
+            let negotiated = SessionExpires::parse(&resp.headers).unwrap_or(requested);
+            if negotiated.local_refresh() {
+                info!("⏲️ 会话定时器已协商: {}s，由本端刷新", negotiated.interval);
+                session_timer::spawn_refresher(
+                    dialog.clone(),
+                    negotiated,
+                    peer_allows_update,
+                    self.media_sessions.clone(),
+                    self.cancel_token.clone(),
+                );
+            }
+        }
 
         let dialog_id = dialog.id();
+        tracing::Span::current().record("dialog_id", display(&dialog_id));
         info!(
             "✅ INVITE 请求已发送，Dialog -> Call-ID: {} From-Tag: {} To-Tag: {}",
             dialog_id.call_id, dialog_id.local_tag, dialog_id.remote_tag
@@ -327,45 +1395,827 @@ impl SipClient {
         Ok((dialog, response))
     }
 
-    /// 注销
-    pub async fn unregister(&self) -> CallResult<Response> {
-        info!("正在从SIP服务器注销...");
-        
-        let _actual_local_addr = self
-            .endpoint
-            .get_addrs()
-            .first()
-            .ok_or(CallError::NotInitialized)?
-            .addr
-            .clone();
-        
-        // 构造注册URI（从 config.server 复制并移除 transport 参数）
-        let mut register_uri = self.config.server.clone();
-        
-        // 移除 transport 参数（如果有）registrar 不需要 transport 参数
-        register_uri
-            .params
-            .retain(|p| !matches!(p, rsip::Param::Transport(_)));
-        
-        info!("Unregister URI: {}", register_uri);
-        
-        // 创建认证凭证
-        let credential = Credential {
-            username: self.config.username.clone(),
-            password: self.config.password.clone(),
-            realm: None, // 将从 401 响应自动提取
-        };
-        
-        // 创建 Registration 实例（全局 route_set 已在 Endpoint 层面配置）
-        let mut registration = Registration::new(self.endpoint.inner.clone(), Some(credential));
-        
-        registration.call_id = Uuid::new_v4().to_string().into();
-        
-        // 执行注销（expires=0表示注销）
-        let response = registration.register(register_uri, Some(0)).await?;
+    /// 将呼叫目标补全为 SIP URI（不含域名时使用服务器域名）
+    ///
+    /// 目标为空、含空白或多个 `@` 等格式错误时返回 `None`，调用方以 `CallError::InvalidTarget` 报告
+    pub(crate) fn target_uri(&self, target: &str) -> Option<String> {
+        match crate::config::split_user_domain(target) {
+            Ok((user, Some(domain))) => Some(format!("sip:{}@{}", user, domain)),
+            Ok((user, None)) => Some(format!("sip:{}@{}", user, self.config.server.host_with_port)),
+            Err(_) => None,
+        }
+    }
+
+    /// 本端 AOR：`username@domain`，未配置域名时使用服务器地址
+    fn aor_uri(&self) -> String {
+        match &self.config.domain {
+            Some(domain) => format!("sip:{}@{}", self.config.username, domain),
+            None => format!("sip:{}@{}", self.config.username, self.config.server.host_with_port),
+        }
+    }
+
+    /// 处理主叫对话的状态事件
+    ///
+    /// `Event: refer` 的 NOTIFY 以 200 OK 应答，并转发给等待该对话转接结果的 `transfer`；
+    /// 携带 SDP 的临时响应转发到 `early_media`，收到最终响应（Confirmed/Terminated）后关闭该通道；
+    /// 已接通的对话结束时发布 `CallEvent::Terminated`
+    fn watch_dialog_states(
+        mut state_receiver: rsipstack::dialog::dialog::DialogStateReceiver,
+        refer_watchers: ReferWatchers,
+        update_peers: UpdatePeers,
+        media_sessions: MediaSessions,
+        mut early_media: Option<mpsc::UnboundedSender<EarlyMedia>>,
+        events: broadcast::Sender<CallEvent>,
+    ) {
+        let watcher = async move {
+            let mut confirmed = false;
+            while let Some(state) = state_receiver.recv().await {
+                if let (Some(sender), Some(media)) = (&early_media, sip_dialog::early_media(&state)) {
+                    info!("📲 收到早期媒体 SDP ({})", media.status);
+                    let _ = sender.send(media);
+                }
+                match state {
+                    DialogState::Notify(id, request, handle) => {
+                        let Some(progress) = sip_dialog::parse_refer_notify(&request) else {
+                            continue;
+                        };
+                        info!("🔀 转接进度: {} ({})", progress.status, id);
+                        let _ = handle.reply(rsip::StatusCode::OK).await;
+                        if let Some(watcher) = refer_watchers.lock().unwrap().get(&id) {
+                            let _ = watcher.send(progress);
+                        }
+                    }
+                    DialogState::Confirmed(..) => {
+                        confirmed = true;
+                        // 最终响应之后不再有早期媒体，关闭通道通知等待方
+                        early_media = None;
+                    }
+                    DialogState::Terminated(id, reason) => {
+                        refer_watchers.lock().unwrap().remove(&id);
+                        update_peers.lock().unwrap().remove(&id);
+                        media_sessions.lock().unwrap().remove(&id);
+                        if confirmed {
+                            let _ = events.send(CallEvent::Terminated { dialog_id: id, reason });
+                        }
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        };
+        tokio::spawn(watcher.in_current_span());
+    }
+
+    /// 盲转：在对话内发送 REFER，将对端转接到 `target`
+    ///
+    /// REFER 被接受后等待对端通过 NOTIFY（`message/sipfrag`）报告转接进度，
+    /// 直到收到最终状态。对端以 `Refer-Sub: false`（RFC 4488）应答时不会有通知，
+    /// REFER 被接受即视为成功
+    ///
+    /// # 返回
+    /// - `Ok(())` - 转接目标已应答（sipfrag 2xx）
+    /// - `Err(CallError::CallRejected)` - REFER 被拒绝，或转接目标返回失败状态
+    /// - `Err(CallError::NetworkTimeout)` - `transfer_timeout` 内未收到最终状态
+    pub async fn transfer(&self, dialog: &ClientInviteDialog, target: &str) -> CallResult<()> {
+        if !dialog.state().is_confirmed() {
+            return Err(CallError::NotConnected);
+        }
+        let refer_to = self.target_uri(target).ok_or_else(|| CallError::invalid_target(target))?;
+        let refer_to: rsip::Uri = refer_to.as_str().try_into()?;
+        let dialog_id = dialog.id();
+
+        // 先登记再发送 REFER，避免错过紧随 202 的首个 NOTIFY
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        self.refer_watchers
+            .lock()
+            .unwrap()
+            .insert(dialog_id.clone(), tx);
+        let result = self.do_transfer(dialog, refer_to, &mut rx).await;
+        self.refer_watchers.lock().unwrap().remove(&dialog_id);
+        result
+    }
+
+    async fn do_transfer(
+        &self,
+        dialog: &ClientInviteDialog,
+        refer_to: rsip::Uri,
+        progress: &mut mpsc::UnboundedReceiver<ReferProgress>,
+    ) -> CallResult<()> {
+        info!("🔀 发送 REFER 转接到 {}: {}", refer_to, dialog.id());
+        let response = match dialog.refer(refer_to, None, None).await? {
+            Some(resp) if resp.status_code.kind() == rsip::StatusCodeKind::Successful => resp,
+            Some(resp) => return Err(CallError::rejected(&resp.status_code)),
+            None => return Err(CallError::NotConnected),
+        };
+
+        let no_subscription = response.headers.iter().any(|h| {
+            matches!(h, rsip::Header::Other(name, value)
+                if name.eq_ignore_ascii_case("Refer-Sub") && value.trim().eq_ignore_ascii_case("false"))
+        });
+        if no_subscription {
+            info!("对端未建立转接订阅 (Refer-Sub: false)，REFER 已被接受");
+            return Ok(());
+        }
+
+        let timeout = self.config.transfer_timeout;
+        let last = tokio::time::timeout(timeout, async {
+            while let Some(update) = progress.recv().await {
+                if update.is_final() || update.terminated {
+                    return Some(update);
+                }
+            }
+            None
+        })
+        .await
+        .map_err(|_| CallError::NetworkTimeout {
+            duration: timeout.as_millis() as u64,
+        })?;
+
+        match last {
+            Some(update) if update.status.kind() == rsip::StatusCodeKind::Successful => {
+                info!("✅ 转接成功: {}", update.status);
+                Ok(())
+            }
+            Some(update) => Err(CallError::rejected(&update.status)),
+            None => Err(CallError::NotConnected),
+        }
+    }
+
+    /// 发送 OPTIONS 请求并返回最终响应
+    ///
+    /// `target` 为 `None` 时发往注册服务器。任何最终响应（包括 401/404/405）都说明
+    /// 对端可达，因此不应答认证挑战
+    ///
+    /// # 返回
+    /// - `Err(CallError::NetworkTimeout)` - 事务超时仍未收到最终响应
+    pub async fn send_options(&self, target: Option<&str>) -> CallResult<Response> {
+        let uri: rsip::Uri = match target {
+            Some(target) => self
+                .target_uri(target)
+                .ok_or_else(|| CallError::invalid_target(target))?
+                .as_str()
+                .try_into()?,
+            None => {
+                let mut uri = self.config.server.clone();
+                uri.params.retain(|p| !matches!(p, rsip::Param::Transport(_)));
+                uri
+            }
+        };
+
+        let via = self.endpoint.inner.get_via(None, None)?;
+        let mut request = self.out_of_dialog_request(rsip::Method::Options, uri, via)?;
+        request
+            .headers
+            .push(rsip::headers::Accept::from(SDP_CONTENT_TYPE).into());
+        request
+            .headers
+            .push(rsip::headers::ContentLength::from(0u32).into());
+
+        debug!("发送 OPTIONS: {}", request.uri);
+        let key = TransactionKey::from_request(&request, TransactionRole::Client)?;
+        self.reset_tcp_connection();
+        let mut tx = Transaction::new_client(key, request, self.endpoint.inner.clone(), None);
+        tx.send().await?;
+
+        while let Some(msg) = tx.receive().await {
+            if let rsip::SipMessage::Response(resp) = msg {
+                if resp.status_code.kind() != rsip::StatusCodeKind::Provisional {
+                    debug!("OPTIONS 响应: {}", resp.status_code);
+                    return Ok(resp);
+                }
+            }
+        }
+        Err(CallError::NetworkTimeout {
+            duration: CANCEL_TIMEOUT.as_millis() as u64,
+        })
+    }
+
+    /// 发送对话外的 MESSAGE 即时消息（RFC 3428）并返回最终响应
+    ///
+    /// `target` 的补全规则与 `make_call` 相同；`content_type` 为空时使用 `text/plain`。
+    /// 收到 401/407 时按认证模式的凭证应答一次挑战
+    ///
+    /// # 返回
+    /// - `Ok(response)` - 2xx 最终响应
+    /// - `Err(CallError::CallRejected)` - 非 2xx 最终响应，状态码见 `sip_status_code()`
+    /// - `Err(CallError::NetworkTimeout)` - 事务超时仍未收到最终响应
+    pub async fn send_message(
+        &self,
+        target: &str,
+        body: &str,
+        content_type: &str,
+    ) -> CallResult<Response> {
+        let uri = self.target_uri(target).ok_or_else(|| CallError::invalid_target(target))?;
+        let uri: rsip::Uri = uri.as_str().try_into()?;
+        let via = self.endpoint.inner.get_via(None, None)?;
+        let mut request = self.out_of_dialog_request(rsip::Method::Message, uri, via)?;
+        request
+            .headers
+            .push(rsip::Header::ContentType(message_content_type(content_type).into()));
+        request
+            .headers
+            .push(rsip::headers::ContentLength::from(body.len() as u32).into());
+        request.body = body.as_bytes().to_vec();
+
+        self.send_out_of_dialog(request).await.map(|(_, resp)| resp)
+    }
+
+    /// 在已接通的通话中发送 MESSAGE 即时消息
+    ///
+    /// 沿用对话的 Call-ID、CSeq 与路由集，认证挑战由对话的凭证应答；
+    /// `content_type` 为空时使用 `text/plain`
+    pub async fn send_message_in_dialog(
+        &self,
+        dialog: &ClientInviteDialog,
+        body: &str,
+        content_type: &str,
+    ) -> CallResult<Response> {
+        if !dialog.state().is_confirmed() {
+            return Err(CallError::NotConnected);
+        }
+        debug!("发送对话内 MESSAGE: {}", dialog.id());
+        let headers = vec![rsip::Header::ContentType(message_content_type(content_type).into())];
+        match dialog.message(Some(headers), Some(body.as_bytes().to_vec())).await? {
+            Some(resp) if resp.status_code.kind() == rsip::StatusCodeKind::Successful => Ok(resp),
+            Some(resp) => Err(CallError::rejected(&resp.status_code)),
+            None => Err(CallError::NotConnected),
+        }
+    }
+
+    /// `tcp_fresh_connection` 开启时关闭 TCP 出口连接，使下一个事务重新建立连接
+    fn reset_tcp_connection(&self) {
+        if let (true, Some(link)) = (self.config.tcp_fresh_connection, &self.tcp_link) {
+            link.reset();
+        }
+    }
+
+    /// 发送对话外请求并等待最终响应，收到 401/407 时按认证模式的凭证应答一次挑战
+    ///
+    /// # 返回
+    /// 实际发出的最后一个请求（可能已携带认证头）与 2xx 最终响应；
+    /// 非 2xx 最终响应返回 `CallError::CallRejected`
+    async fn send_out_of_dialog(&self, request: rsip::Request) -> CallResult<(rsip::Request, Response)> {
+        let method = request.method;
+        debug!("发送 {}: {}", method, request.uri);
+        let key = TransactionKey::from_request(&request, TransactionRole::Client)?;
+        self.reset_tcp_connection();
+        let mut tx = Transaction::new_client(key, request, self.endpoint.inner.clone(), None);
+        tx.send().await?;
+
+        let mut seq = 1;
+        let mut authenticated = false;
+        while let Some(msg) = tx.receive().await {
+            let rsip::SipMessage::Response(resp) = msg else {
+                continue;
+            };
+            match resp.status_code.kind() {
+                rsip::StatusCodeKind::Provisional => continue,
+                rsip::StatusCodeKind::Successful => {
+                    debug!("{} 响应: {}", method, resp.status_code);
+                    return Ok((tx.original.clone(), resp));
+                }
+                _ => {}
+            }
+            let challenged = matches!(
+                resp.status_code,
+                rsip::StatusCode::Unauthorized | rsip::StatusCode::ProxyAuthenticationRequired
+            );
+            match self.credential() {
+                Some(credential) if challenged && !authenticated => {
+                    authenticated = true;
+                    seq += 1;
+                    tx = handle_client_authenticate(seq, &tx, resp, &credential).await?;
+                    tx.send().await?;
+                }
+                _ => {
+                    warn!("{} 被拒绝: {}", method, resp.status_code);
+                    return Err(CallError::rejected(&resp.status_code));
+                }
+            }
+        }
+        Err(CallError::NetworkTimeout {
+            duration: CANCEL_TIMEOUT.as_millis() as u64,
+        })
+    }
+
+    /// 本端 Contact URI：优先使用配置的固定 URI，其次为通过 rport/STUN 学习到的公网地址，
+    /// 保证 NAT 后对端能按 Contact 回送请求
+    fn local_contact_uri(&self) -> Option<String> {
+        if let Some(uri) = &self.config.contact_uri {
+            return Some(uri.to_string());
+        }
+        let actual_local_addr = self.endpoint.get_addrs().first()?.addr.clone();
+        let contact_host = self.public_address().unwrap_or(actual_local_addr);
+        Some(format!("sip:{}@{}", self.config.username, contact_host))
+    }
+
+    /// 构造对话外请求：From 为本端 AOR（新 tag），To 为 `uri`，CSeq 为 1
+    fn out_of_dialog_request(
+        &self,
+        method: rsip::Method,
+        uri: rsip::Uri,
+        mut via: rsip::typed::Via,
+    ) -> Result<rsip::Request, rsip::Error> {
+        if !self.config.rport {
+            strip_rport(&mut via);
+        }
+        let from_uri = self.aor_uri();
+        let from = rsip::typed::From {
+            display_name: None,
+            uri: from_uri.as_str().try_into()?,
+            params: vec![],
+        }
+        .with_tag(make_tag());
+        let to = rsip::typed::To {
+            display_name: None,
+            uri: uri.clone(),
+            params: vec![],
+        };
+        Ok(self
+            .endpoint
+            .inner
+            .make_request(method, uri, via, from, to, 1, None))
+    }
+
+    /// 订阅事件（RFC 6665），如 `presence`（在线状态 / BLF）或 `message-summary`（语音信箱 MWI）
+    ///
+    /// `target` 的补全规则与 `make_call` 相同，`expires` 为期望的订阅时长（秒）。
+    /// 订阅建立后对端的 NOTIFY 通过 [`Subscription::notifications`] 上报，
+    /// 到期前自动刷新，[`Subscription::unsubscribe`] 以 `Expires: 0` 结束订阅
+    ///
+    /// # 返回
+    /// - `Err(CallError::CallRejected)` - SUBSCRIBE 被拒绝（如 489 Bad Event）
+    /// - `Err(CallError::NetworkTimeout)` - 事务超时仍未收到最终响应
+    pub async fn subscribe(&self, target: &str, event: &str, expires: u32) -> CallResult<Subscription> {
+        let uri = self.target_uri(target).ok_or_else(|| CallError::invalid_target(target))?;
+        let uri: rsip::Uri = uri.as_str().try_into()?;
+        let contact = self.local_contact_uri().ok_or(CallError::NotInitialized)?;
+        let contact: rsip::Uri = contact.as_str().try_into()?;
+        let via = self.endpoint.inner.get_via(None, None)?;
+        let mut request = self.out_of_dialog_request(rsip::Method::Subscribe, uri, via)?;
+        request.headers.push(rsip::Header::Event(event.into()));
+        request.headers.push(rsip::Header::Expires(expires.into()));
+        request
+            .headers
+            .push(rsip::headers::Contact::new(format!("<{}>", contact)).into());
+        if let Some(accept) = accept_for_event(event) {
+            request.headers.push(rsip::headers::Accept::from(accept).into());
+        }
+        request
+            .headers
+            .push(rsip::headers::ContentLength::from(0u32).into());
+
+        info!("📋 订阅 {} 事件: {}", event, request.uri);
+        let (request, response) = self.send_out_of_dialog(request).await?;
+        let id = DialogId::from_uac_response(&response)?;
+        let (state_sender, state_receiver) = self.dialog_layer.new_dialog_state_channel();
+        let dialog = self.dialog_layer.get_or_create_client_subscription(
+            id.call_id,
+            id.local_tag,
+            id.remote_tag,
+            request,
+            state_sender,
+            self.credential(),
+            Some(contact),
+        )?;
+        if let Some(contact) = rsip::header_opt!(response.headers.iter(), rsip::Header::Contact) {
+            if let Ok(typed) = contact.typed() {
+                Dialog::ClientSubscription(dialog.clone()).set_remote_target(typed.uri, Some(contact.clone()));
+            }
+        }
+
+        let granted = granted_expires(&response).unwrap_or(expires);
+        info!("✅ 订阅已建立 ({}, {}s): {}", event, granted, dialog.id());
+        Ok(Subscription::start(
+            dialog,
+            self.dialog_layer.clone(),
+            state_receiver,
+            event,
+            expires,
+            granted,
+        ))
+    }
+
+    /// 最近一次保活探测是否成功；未启用保活时始终为 `true`
+    pub fn is_reachable(&self) -> bool {
+        self.state.lock().unwrap().reachable != Some(false)
+    }
+
+    /// 启动后台 OPTIONS 保活
+    ///
+    /// 每隔 `interval` 向注册服务器发送一次 OPTIONS，在该间隔内收到任何最终响应即视为可达。
+    /// 不可达期间 `make_call` 直接返回 `CallError::NotConnected`。
+    /// 重复调用会先停止之前的任务；`stop_keepalive` 或 `shutdown` 时任务退出
+    pub fn start_keepalive(self: &Arc<Self>, interval: Duration) {
+        let token = self.cancel_token.child_token();
+        if let Some(previous) = self.keepalive.lock().unwrap().replace(token.clone()) {
+            previous.cancel();
+        }
+        let interval = interval.max(Duration::from_millis(100));
+
+        // 只持有弱引用，客户端被释放后任务自动结束
+        let client = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                let Some(this) = client.upgrade() else {
+                    break;
+                };
+                let reachable = tokio::select! {
+                    result = tokio::time::timeout(interval, this.send_options(None)) => match result {
+                        Ok(Ok(_)) => true,
+                        Ok(Err(e)) => {
+                            warn!("OPTIONS 保活失败: {}", e);
+                            false
+                        }
+                        Err(_) => {
+                            warn!("OPTIONS 保活在 {:?} 内无响应", interval);
+                            false
+                        }
+                    },
+                    _ = token.cancelled() => break,
+                };
+                let previous = this.state.lock().unwrap().reachable.replace(reachable);
+                if previous != Some(reachable) {
+                    info!("服务器可达状态: {}", if reachable { "可达" } else { "不可达" });
+                }
+                drop(this);
+
+                tokio::select! {
+                    _ = tokio::time::sleep(interval) => {}
+                    _ = token.cancelled() => break,
+                }
+            }
+            debug!("OPTIONS 保活任务已退出");
+        });
+    }
+
+    /// 停止后台 OPTIONS 保活，并清除可达状态
+    pub fn stop_keepalive(&self) {
+        if let Some(token) = self.keepalive.lock().unwrap().take() {
+            token.cancel();
+        }
+        self.state.lock().unwrap().reachable = None;
+    }
+
+    /// 正在建立中（尚未收到最终响应）的主叫对话
+    ///
+    /// `make_call` 在收到最终响应前不会返回，可在其他任务中通过此方法取得对话并调用 [`SipClient::cancel`]
+    pub fn pending_calls(&self) -> Vec<ClientInviteDialog> {
+        self.dialog_layer
+            .all_dialog_ids()
+            .iter()
+            .filter_map(|id| match self.dialog_layer.get_dialog_with(id) {
+                Some(Dialog::ClientInvite(dialog)) => Some(dialog),
+                _ => None,
+            })
+            .filter(|dialog| {
+                matches!(
+                    dialog.state(),
+                    DialogState::Calling(_) | DialogState::Trying(_) | DialogState::Early(_, _)
+                )
+            })
+            .collect()
+    }
+
+    /// 取消尚未应答的呼叫
+    ///
+    /// 发送与原 INVITE 事务匹配的 CANCEL，并等待 INVITE 以 487 Request Terminated 结束。
+    /// 若对端的 200 OK 与 CANCEL 交错到达，呼叫仍会建立，此时按 RFC 3261 立即发送 BYE 挂断
+    ///
+    /// # 返回
+    /// - `Ok(())` - 呼叫已取消、已终止，或交错建立后已挂断
+    /// - `Err(CallError::CallInProgress)` - 对话已确认，CANCEL 无效，应使用 `hangup`
+    /// - `Err(CallError::NetworkTimeout)` - 超时未收到 INVITE 的最终响应
+    pub async fn cancel(&self, dialog: &ClientInviteDialog) -> CallResult<()> {
+        match dialog.state() {
+            DialogState::Terminated(_, reason) => {
+                info!("对话已终止 ({:?})，无需取消: {}", reason, dialog.id());
+                return Ok(());
+            }
+            state if state.is_confirmed() => {
+                warn!("对话已建立，拒绝发送 CANCEL: {}", dialog.id());
+                return Err(CallError::CallInProgress);
+            }
+            _ => {}
+        }
+
+        info!("🚫 发送 CANCEL 取消呼叫: {}", dialog.id());
+        dialog.cancel().await?;
+
+        let deadline = Instant::now() + CANCEL_TIMEOUT;
+        loop {
+            match dialog.state() {
+                DialogState::Terminated(id, reason) => {
+                    info!("✅ 呼叫已取消 ({:?}): {}", reason, id);
+                    return Ok(());
+                }
+                state if state.is_confirmed() => {
+                    warn!("200 OK 与 CANCEL 交错到达，发送 BYE 挂断: {}", dialog.id());
+                    return self.hangup(dialog).await;
+                }
+                _ => {}
+            }
+            if Instant::now() >= deadline {
+                return Err(CallError::NetworkTimeout {
+                    duration: CANCEL_TIMEOUT.as_millis() as u64,
+                });
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    /// 挂断已建立的通话
+    ///
+    /// 在对话内发送 BYE 并等待最终响应，随后将对话从对话层移除
+    ///
+    /// # 返回
+    /// - `Ok(())` - BYE 已完成，或对端已先行挂断（对话已终止）
+    /// - `Err(CallError::NotConnected)` - 对话尚未确认（如仍在振铃，应使用 CANCEL）
+    pub async fn hangup(&self, dialog: &ClientInviteDialog) -> CallResult<()> {
+        let dialog_id = dialog.id();
+        match dialog.state() {
+            DialogState::Terminated(_, reason) => {
+                info!("对话已终止 ({:?})，无需发送 BYE: {}", reason, dialog_id);
+                self.dialog_layer.remove_dialog(&dialog_id);
+                return Ok(());
+            }
+            state if !state.is_confirmed() => {
+                warn!("对话尚未建立，拒绝发送 BYE: {}", dialog_id);
+                return Err(CallError::NotConnected);
+            }
+            _ => {}
+        }
+
+        info!("📴 发送 BYE 挂断通话: {}", dialog_id);
+        dialog.bye().await?;
+        self.dialog_layer.remove_dialog(&dialog_id);
+        info!("✅ 通话已挂断: {}", dialog_id);
+        Ok(())
+    }
+
+    /// 向所有已接通的通话发送 BYE 并移除对话
+    async fn hangup_all(&self) {
+        let confirmed: Vec<Dialog> = self
+            .dialog_layer
+            .all_dialog_ids()
+            .iter()
+            .filter_map(|id| self.dialog_layer.get_dialog_with(id))
+            .filter(|dialog| match dialog {
+                Dialog::ClientInvite(d) => d.state().is_confirmed(),
+                Dialog::ServerInvite(d) => d.state().is_confirmed(),
+                _ => false,
+            })
+            .collect();
+        if confirmed.is_empty() {
+            return;
+        }
+
+        info!("📴 关闭前挂断 {} 个通话", confirmed.len());
+        futures_util::future::join_all(confirmed.into_iter().map(|dialog| async move {
+            let dialog_id = dialog.id();
+            match tokio::time::timeout(SHUTDOWN_BYE_TIMEOUT, dialog.hangup()).await {
+                Ok(Ok(())) => debug!("关闭时已挂断: {}", dialog_id),
+                Ok(Err(e)) => warn!("关闭时挂断失败 {}: {}", dialog_id, e),
+                Err(_) => warn!("关闭时等待 BYE 超时: {}", dialog_id),
+            }
+            self.dialog_layer.remove_dialog(&dialog_id);
+        }))
+        .await;
+    }
+
+    /// 通过 SIP INFO 发送 DTMF 按键（`application/dtmf-relay`）
+    ///
+    /// INFO 在对话内发送，沿用对话的 Call-ID、CSeq 与路由集，收到最终响应后返回
+    ///
+    /// # 参数
+    /// - `digit`: 按键，取值 0-9、`*`、`#`、A-D
+    /// - `duration_ms`: 按键时长（毫秒）
+    pub async fn send_dtmf(
+        &self,
+        dialog: &ClientInviteDialog,
+        digit: char,
+        duration_ms: u32,
+    ) -> CallResult<()> {
+        if !is_valid_dtmf(digit) {
+            return Err(CallError::invalid_target(format!("无效的 DTMF 按键: {:?}", digit)));
+        }
+        if !dialog.state().is_confirmed() {
+            return Err(CallError::NotConnected);
+        }
+
+        debug!("发送 DTMF '{}' ({}ms): {}", digit, duration_ms, dialog.id());
+        let headers = vec![rsip::Header::ContentType(
+            "application/dtmf-relay".into(),
+        )];
+        let body = format_dtmf_relay(digit, duration_ms).into_bytes();
+        match dialog.info(Some(headers), Some(body)).await? {
+            Some(resp) if resp.status_code.kind() == rsip::StatusCodeKind::Successful => Ok(()),
+            Some(resp) => Err(CallError::rejected(&resp.status_code)),
+            None => Err(CallError::NotConnected),
+        }
+    }
+
+    /// 在对话内发送 UPDATE（RFC 3311）更新会话，对端未在 `Allow` 中声明 UPDATE 时改用 re-INVITE
+    ///
+    /// `sdp` 为新的 offer（如更换编解码器或保持），`None` 时只刷新会话、不携带消息体，
+    /// 此时对端必须支持 UPDATE
+    ///
+    /// # 返回
+    /// 对端应答中的 SDP answer（未携带时为 `None`），可交给 `RtpPlayer::apply_answer` 应用
+    pub async fn send_update(&self, dialog: &ClientInviteDialog, sdp: Option<&str>) -> CallResult<Option<String>> {
+        if !dialog.state().is_confirmed() {
+            return Err(CallError::NotConnected);
+        }
+        if let Some(sdp) = sdp {
+            crate::utils::validate_sdp(sdp)?;
+        }
+        let headers = sdp.map(|_| vec![rsip::Header::ContentType(SDP_CONTENT_TYPE.into())]);
+        let body = sdp.map(|sdp| sdp.as_bytes().to_vec());
+
+        let use_update = self.update_peers.lock().unwrap().contains(&dialog.id());
+        let response = if use_update {
+            info!("发送 UPDATE: {}", dialog.id());
+            dialog.update(headers, body).await?
+        } else if body.is_some() {
+            info!("对端未声明支持 UPDATE，改用 re-INVITE: {}", dialog.id());
+            dialog.reinvite(headers, body).await?
+        } else {
+            return Err(CallError::invalid_sdp("对端不支持 UPDATE，改用 re-INVITE 时必须提供 SDP offer"));
+        };
+
+        match response {
+            Some(resp) if resp.status_code.kind() == rsip::StatusCodeKind::Successful => {
+                sip_dialog::update_remote_target(dialog, &resp.headers);
+                if let Some(sdp) = sdp {
+                    self.record_offer(dialog, sdp.as_bytes().to_vec());
+                }
+                Ok(response_sdp(&resp))
+            }
+            Some(resp) => Err(CallError::rejected(&resp.status_code)),
+            None => Err(CallError::NotConnected),
+        }
+    }
+
+    /// 通过 re-INVITE 保持通话（本端声明 `a=sendonly`）
+    ///
+    /// offer 基于媒体会话当前的本地描述生成，端口不变；对端以 `recvonly` 或 `inactive`
+    /// 应答均视为保持成功。返回对端 answer 中声明的方向
+    pub async fn hold(
+        &self,
+        dialog: &ClientInviteDialog,
+        rtp_player: &mut RtpPlayer,
+    ) -> CallResult<MediaDirection> {
+        self.update_media_direction(dialog, rtp_player, MediaDirection::SendOnly)
+            .await
+    }
+
+    /// 通过 re-INVITE 恢复被保持的通话（本端声明 `a=sendrecv`）
+    pub async fn resume(
+        &self,
+        dialog: &ClientInviteDialog,
+        rtp_player: &mut RtpPlayer,
+    ) -> CallResult<MediaDirection> {
+        self.update_media_direction(dialog, rtp_player, MediaDirection::SendRecv)
+            .await
+    }
+
+    async fn update_media_direction(
+        &self,
+        dialog: &ClientInviteDialog,
+        rtp_player: &mut RtpPlayer,
+        direction: MediaDirection,
+    ) -> CallResult<MediaDirection> {
+        if !dialog.state().is_confirmed() {
+            return Err(CallError::NotConnected);
+        }
+
+        let previous = rtp_player.local_direction();
+        let offer = rtp_player
+            .create_direction_offer(direction)
+            .await
+            .map_err(|e| CallError::invalid_sdp(e.to_string()))?;
+
+        info!("发送 re-INVITE 更新媒体方向为 {}: {}", direction, dialog.id());
+        let headers = vec![rsip::Header::ContentType("application/sdp".into())];
+        let response = match dialog.reinvite(Some(headers), Some(offer.clone().into_bytes())).await {
+            Ok(response) => response,
+            Err(e) => {
+                Self::restore_media_direction(rtp_player, previous).await;
+                return Err(e.into());
+            }
+        };
+
+        match response {
+            Some(resp) if resp.status_code.kind() == rsip::StatusCodeKind::Successful => {
+                sip_dialog::update_remote_target(dialog, &resp.headers);
+                let answer = response_sdp(&resp).unwrap_or_default();
+                rtp_player
+                    .apply_answer(&answer)
+                    .await
+                    .map_err(|e| CallError::invalid_sdp(e.to_string()))?;
+                self.record_offer(dialog, offer.into_bytes());
+                let negotiated = rtp_player.negotiated_direction();
+                if !direction.accepts_answer(negotiated) {
+                    warn!("对端以 {} 应答 {} offer", negotiated, direction);
+                }
+                info!("✅ 媒体方向已更新: 本端 {}，对端 {}", direction, negotiated);
+                Ok(negotiated)
+            }
+            Some(resp) => {
+                Self::restore_media_direction(rtp_player, previous).await;
+                Err(CallError::rejected(&resp.status_code))
+            }
+            None => {
+                Self::restore_media_direction(rtp_player, previous).await;
+                Err(CallError::NotConnected)
+            }
+        }
+    }
+
+    /// 记录对话内最近一次被接受的 SDP offer，供会话刷新的 re-INVITE 重发
+    fn record_offer(&self, dialog: &ClientInviteDialog, offer: Vec<u8>) {
+        if let Some(media) = self.media_sessions.lock().unwrap().get_mut(&dialog.id()) {
+            media.offer = Some(offer);
+        }
+    }
+
+    /// 将媒体会话附加到已接通的对话
+    ///
+    /// 对端不支持 UPDATE 时，会话定时器的刷新 re-INVITE 由该媒体会话按当前本地描述
+    /// 生成 offer（如 [`hold`](Self::hold) 之后保持 `sendonly`），并将 2xx 中的 answer
+    /// 应用到该媒体会话。对话终止后自动解除
+    pub fn attach_media(&self, dialog: &ClientInviteDialog, player: SharedRtpPlayer) {
+        self.media_sessions.lock().unwrap().entry(dialog.id()).or_default().player = Some(player);
+    }
+
+    async fn restore_media_direction(rtp_player: &mut RtpPlayer, previous: MediaDirection) {
+        if let Err(e) = rtp_player.restore_direction(previous).await {
+            warn!("恢复媒体方向失败: {}", e);
+        }
+    }
+
+    /// 注销
+    ///
+    /// 同时停止 `start_auto_register` 启动的自动注册任务
+    pub async fn unregister(&self) -> CallResult<Response> {
+        info!("正在从SIP服务器注销...");
+        if let Some(token) = self.auto_register.lock().unwrap().take() {
+            token.cancel();
+        }
+        
+        let _actual_local_addr = self
+            .endpoint
+            .get_addrs()
+            .first()
+            .ok_or(CallError::NotInitialized)?
+            .addr
+            .clone();
+        
+        // 构造注册URI（从 config.server 复制并移除 transport 参数）
+        let mut register_uri = self.config.server.clone();
+        
+        // 移除 transport 参数（如果有）registrar 不需要 transport 参数
+        register_uri
+            .params
+            .retain(|p| !matches!(p, rsip::Param::Transport(_)));
+        
+        info!("Unregister URI: {}", register_uri);
+        
+        // 按认证模式创建凭证（IP 认证且无密码时不应答挑战）
+        let credential = self.credential();
+        
+        // 创建注册会话（全局 route_set 已在 Endpoint 层面配置）
+        let registration =
+            SipRegistration::new(self.endpoint.inner.clone(), credential)
+                .with_expires_mode(self.config.expires_mode)
+                .with_stale_nonce_retry(self.config.stale_nonce_retry)
+                .with_rport(self.config.rport)
+                .with_contact_q(self.config.contact_q)
+                .with_contact_uri(self.config.contact_uri.clone())
+                .with_aor(Some(self.aor_uri().as_str().try_into()?))
+                .with_digest_session(self.state.lock().unwrap().digest.clone())
+                .with_credential_provider(self.credential_provider.lock().unwrap().clone());
+        #[cfg(feature = "aka")]
+        let registration = registration.with_aka_key(self.aka_key.lock().unwrap().clone());
+        let mut registration = registration;
+        // 沿用已知的公网地址（rport 学习或 STUN 映射）作为 Contact
+        registration.public_address = self.public_address();
+        self.resume_binding(&mut registration);
+        
+        // 执行注销（expires=0表示注销）
+        self.reset_tcp_connection();
+        let result = registration.register(register_uri, Some(0)).await;
+        self.save_registration_session(&registration);
+        let response = result?;
         
         if response.status_code == rsip::StatusCode::OK {
             info!("✔ 注销成功,响应状态: {}", response.status_code);
+            let mut state = self.state.lock().unwrap();
+            state.registered_at = None;
+            state.registration_expires = None;
+            state.bindings.clear();
+            state.binding = None;
+            self.set_registration_status(RegistrationStatus::Unregistered);
         } else {
             warn!("注销响应: {}", response.status_code);
         }
@@ -373,9 +2223,2321 @@ impl SipClient {
         Ok(response)
     }
 
+    /// 传输层实际绑定的本地地址（UDP/TCP），WS/WSS/TLS 时为 `None`
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// 实际使用的出站传输协议
+    ///
+    /// 配置了 Outbound 代理时取自代理 URI，否则取自服务器 URI
+    pub fn transport_protocol(&self) -> rsip::transport::Transport {
+        self.transport
+    }
+
+    /// 传输连接的目标地址（`host:port`），即 Outbound 代理或服务器地址
+    pub fn connection_target(&self) -> &str {
+        &self.connection_target
+    }
+
+    /// 获取客户端状态快照（不产生网络 I/O）
+    pub fn status(&self) -> ClientStatus {
+        let public_address = self.public_address().map(|a| a.to_string());
+        let state = self.state.lock().unwrap();
+        let remaining = match (state.registered_at, state.registration_expires) {
+            (Some(at), Some(expires)) => {
+                Some(u64::from(expires).saturating_sub(at.elapsed().as_secs()))
+            }
+            _ => None,
+        };
+        let local_address = self.endpoint.get_addrs().first().map(|a| a.addr.to_string());
+
+        ClientStatus {
+            registered: remaining.is_some_and(|r| r > 0),
+            registration_expires: state.registration_expires,
+            registration_remaining: remaining,
+            transport_alive: !self.cancel_token.is_cancelled() && local_address.is_some(),
+            active_calls: self.dialog_layer.len(),
+            last_error: state.last_error.clone(),
+            local_address,
+            public_address,
+            reachable: state.reachable,
+            auth_scheme: state.digest.as_ref().map(|digest| digest.scheme().name()),
+        }
+    }
+
+    /// 最近一次注册成功时服务器返回的全部绑定（含其他设备，带 q 值）
+    pub fn bindings(&self) -> Vec<ContactBinding> {
+        self.state.lock().unwrap().bindings.clone()
+    }
+
+    /// 公网地址：优先使用通过 Via `received`/`rport` 学习到的地址，其次为 STUN 映射地址
+    ///
+    /// 两者都不可用（未启用 rport 且未配置 STUN，或尚未收到响应）时返回 `None`
+    pub fn public_address(&self) -> Option<rsip::HostWithPort> {
+        let learned = if self.config.rport {
+            self.state.lock().unwrap().public_address.clone()
+        } else {
+            None
+        };
+        learned.or_else(|| self.stun_address.map(Into::into))
+    }
+
+    /// 学习到的公网 IP，可作为媒体 SDP 地址的回退值
+    /// （如 `MediaSessionOption::external_ip` 未配置时）
+    pub fn public_ip(&self) -> Option<std::net::IpAddr> {
+        match self.public_address()?.host {
+            rsip::Host::IpAddr(ip) => Some(ip),
+            rsip::Host::Domain(_) => None,
+        }
+    }
+
+    /// 记录操作失败原因，供 `status()` 查询
+    fn record_result<T>(&self, result: &CallResult<T>) {
+        if let Err(e) = result {
+            self.state.lock().unwrap().last_error = Some(e.to_string());
+        }
+    }
+
+    /// 按认证模式构造凭证
+    fn credential(&self) -> Option<Credential> {
+        self.config
+            .auth_mode
+            .credential(
+                self.config.auth_username.as_deref().unwrap_or(&self.config.username),
+                &self.config.password,
+            )
+    }
+
     /// 关闭客户端
+    ///
+    /// 启用 `bye_on_shutdown` 时先并发向所有已接通的通话（主叫与被叫）发送 BYE，
+    /// 每个 BYE 最多等待 `SHUTDOWN_BYE_TIMEOUT`，避免对端（如 PBX）残留挂起的通道
     pub async fn shutdown(&self) {
+        if self.config.bye_on_shutdown {
+            self.hangup_all().await;
+        }
         self.cancel_token.cancel();
         tokio::time::sleep(Duration::from_millis(500)).await;
     }
 }
+
+/// 按重试策略执行 `call`，仅在可恢复错误时重试
+async fn retry_call<T, F, Fut>(policy: &RetryPolicy, mut call: F) -> CallResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = CallResult<T>>,
+{
+    let max_attempts = policy.max_attempts.max(1);
+    let mut attempt = 0u32;
+    loop {
+        match call().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt + 1 < max_attempts && e.is_recoverable() => {
+                let delay = policy.delay(attempt);
+                attempt += 1;
+                warn!("呼叫失败，{:?} 后重试 (第 {}/{} 次): {}", delay, attempt, max_attempts - 1, e);
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// MESSAGE 的内容类型，未指定时使用 `text/plain`
+fn message_content_type(content_type: &str) -> &str {
+    if content_type.trim().is_empty() {
+        TEXT_CONTENT_TYPE
+    } else {
+        content_type
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::backoff::FixedBackoff;
+    use rsip::prelude::{HeadersExt, UntypedHeader};
+    use std::net::SocketAddr;
+    use tokio::net::UdpSocket;
+
+    /// 桩服务器的一次发送
+    pub(crate) struct StubReply {
+        delay: Duration,
+        message: String,
+    }
+
+    impl StubReply {
+        /// 立即发送
+        pub(crate) fn now(message: impl ToString) -> Self {
+            Self::after(Duration::ZERO, message)
+        }
+
+        /// 在上一条发送之后等待 `delay` 再发送
+        pub(crate) fn after(delay: Duration, message: impl ToString) -> Self {
+            Self {
+                delay,
+                message: message.to_string(),
+            }
+        }
+    }
+
+    /// 可配置的 UDP 桩服务器，各测试的模拟 UAS / 注册服务器 / 代理均基于此实现
+    ///
+    /// 收到的每个请求（含 ACK，响应忽略）交给 `handler`，参数依次为请求、桩服务器自身地址与请求来源地址；
+    /// 返回的消息按顺序发回请求来源。含延迟的发送在后台任务中进行，不阻塞后续请求
+    pub(crate) async fn spawn_stub<F>(ip: std::net::IpAddr, mut handler: F) -> SocketAddr
+    where
+        F: FnMut(rsip::Request, SocketAddr, SocketAddr) -> Vec<StubReply> + Send + 'static,
+    {
+        let socket = Arc::new(UdpSocket::bind((ip, 0)).await.unwrap());
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; 8192];
+            while let Ok((len, peer)) = socket.recv_from(&mut buf).await {
+                let Ok(rsip::SipMessage::Request(req)) = rsip::SipMessage::try_from(&buf[..len]) else {
+                    continue;
+                };
+                let replies = handler(req, addr, peer);
+                if replies.iter().all(|reply| reply.delay.is_zero()) {
+                    for reply in replies {
+                        let _ = socket.send_to(reply.message.as_bytes(), peer).await;
+                    }
+                    continue;
+                }
+                let socket = socket.clone();
+                tokio::spawn(async move {
+                    for reply in replies {
+                        tokio::time::sleep(reply.delay).await;
+                        let _ = socket.send_to(reply.message.as_bytes(), peer).await;
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    /// 测试使用的本地地址，客户端与桩服务器都绑定回环地址，不依赖外部网卡
+    pub(crate) fn test_ip() -> std::net::IpAddr {
+        std::net::Ipv4Addr::LOCALHOST.into()
+    }
+
+    /// 桩服务器的 Contact 头
+    fn stub_contact(addr: SocketAddr) -> rsip::Header {
+        rsip::headers::Contact::new(format!("<sip:stub@{}>", addr)).into()
+    }
+
+    /// 构造从 `uac` 发往客户端 `client` 的呼入 INVITE；`id` 用作 branch、From tag 与 Call-ID，
+    /// `sdp` 非空时作为 offer 携带
+    fn incoming_invite(client: SocketAddr, uac: SocketAddr, id: &str, sdp: &str) -> String {
+        let content_type = if sdp.is_empty() { "" } else { "Content-Type: application/sdp\r\n" };
+        format!(
+            "INVITE sip:alice@{client} SIP/2.0\r\n\
+             Via: SIP/2.0/UDP {uac};branch=z9hG4bK{id}\r\n\
+             From: <sip:bob@{uac}>;tag={id}\r\n\
+             To: <sip:alice@{client}>\r\n\
+             Call-ID: {id}@{uac}\r\n\
+             CSeq: 1 INVITE\r\n\
+             Contact: <sip:bob@{uac}>\r\n\
+             Max-Forwards: 70\r\n\
+             {content_type}\
+             Content-Length: {len}\r\n\r\n{sdp}",
+            len = sdp.len(),
+        )
+    }
+
+    /// 极简 UAS：对 INVITE / BYE 回复 200 OK，忽略 ACK，并上报收到的请求方法
+    pub(crate) async fn spawn_uas_stub(ip: std::net::IpAddr) -> (SocketAddr, mpsc::UnboundedReceiver<rsip::Method>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let addr = spawn_stub(ip, move |req, addr, _| {
+            let _ = tx.send(req.method);
+            if req.method == rsip::Method::Ack {
+                return vec![];
+            }
+            vec![StubReply::now(stub_response(&req, rsip::StatusCode::OK, vec![stub_contact(addr)]))]
+        })
+        .await;
+        (addr, rx)
+    }
+
+    /// 转接桩服务器：REFER 以 202 接受，随后发送 100 Trying 与最终 sipfrag 的 NOTIFY，
+    /// 其他请求回复 200 OK
+    async fn spawn_refer_stub(ip: std::net::IpAddr, final_status: u16) -> SocketAddr {
+        spawn_stub(ip, move |req, addr, peer| {
+            let status = match req.method {
+                rsip::Method::Ack => return vec![],
+                rsip::Method::Refer => rsip::StatusCode::Accepted,
+                _ => rsip::StatusCode::OK,
+            };
+            let mut replies = vec![StubReply::now(stub_response(&req, status, vec![stub_contact(addr)]))];
+            if req.method != rsip::Method::Refer {
+                return replies;
+            }
+
+            for (seq, (code, sub_state)) in [(100, "active;expires=60"), (final_status, "terminated")]
+                .into_iter()
+                .enumerate()
+            {
+                let sipfrag = format!("SIP/2.0 {} {}\r\n", code, rsip::StatusCode::from(code));
+                let notify = format!(
+                    "NOTIFY sip:alice@{peer} SIP/2.0\r\n\
+                     Via: SIP/2.0/UDP {addr};branch=z9hG4bK-notify-{seq}\r\n\
+                     From: {from}\r\n\
+                     To: {to}\r\n\
+                     Call-ID: {call_id}\r\n\
+                     CSeq: {seq} NOTIFY\r\n\
+                     Max-Forwards: 70\r\n\
+                     Contact: <sip:stub@{addr}>\r\n\
+                     Event: refer\r\n\
+                     Subscription-State: {sub_state}\r\n\
+                     Content-Type: message/sipfrag\r\n\
+                     Content-Length: {len}\r\n\r\n{sipfrag}",
+                    from = req.to_header().unwrap().value(),
+                    to = req.from_header().unwrap().value(),
+                    call_id = req.call_id_header().unwrap().value(),
+                    seq = seq + 1,
+                    len = sipfrag.len(),
+                );
+                replies.push(StubReply::now(notify));
+            }
+            replies
+        })
+        .await
+    }
+
+    /// 按请求构造桩服务器的响应（无 To tag 时补上）
+    fn stub_response(
+        req: &rsip::Request,
+        status: rsip::StatusCode,
+        extra: Vec<rsip::Header>,
+    ) -> rsip::Response {
+        let mut to = req.to_header().unwrap().clone();
+        if to.tag().ok().flatten().is_none() {
+            to = rsip::headers::To::new(format!("{};tag=uas-stub", to.value()));
+        }
+        let mut headers: Vec<rsip::Header> = vec![
+            req.via_header().unwrap().clone().into(),
+            req.from_header().unwrap().clone().into(),
+            to.into(),
+            req.call_id_header().unwrap().clone().into(),
+            req.cseq_header().unwrap().clone().into(),
+        ];
+        headers.extend(extra);
+        headers.push(rsip::headers::ContentLength::from(0u32).into());
+        rsip::Response {
+            status_code: status,
+            version: rsip::Version::V2,
+            headers: headers.into(),
+            body: vec![],
+        }
+    }
+
+    /// 极简注册服务器：未携带认证的 REGISTER 以固定 nonce 挑战，否则回复 200 OK，
+    /// 并上报每个 REGISTER 的 Authorization 头
+    async fn spawn_registrar_stub(
+        ip: std::net::IpAddr,
+    ) -> (SocketAddr, mpsc::UnboundedReceiver<Option<String>>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let addr = spawn_stub(ip, move |req, _, _| {
+            let authorization = req.headers.iter().find_map(|h| match h {
+                rsip::Header::Authorization(a) => Some(a.value().to_string()),
+                _ => None,
+            });
+            let resp = if authorization.is_some() {
+                stub_response(&req, rsip::StatusCode::OK, vec![])
+            } else {
+                let challenge = rsip::headers::WwwAuthenticate::new(
+                    r#"Digest realm="stub", nonce="fixed-nonce", qop="auth", algorithm=MD5"#,
+                );
+                stub_response(&req, rsip::StatusCode::Unauthorized, vec![challenge.into()])
+            };
+            let _ = tx.send(authorization);
+            vec![StubReply::now(resp)]
+        })
+        .await;
+        (addr, rx)
+    }
+
+    /// 记录注册服务器：所有请求回复 200 OK，并上报收到的每个 REGISTER
+    async fn spawn_recording_registrar(
+        ip: std::net::IpAddr,
+    ) -> (SocketAddr, mpsc::UnboundedReceiver<rsip::Request>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let addr = spawn_stub(ip, move |req, _, _| {
+            let resp = stub_response(&req, rsip::StatusCode::OK, vec![]);
+            if req.method == rsip::Method::Register {
+                let _ = tx.send(req);
+            }
+            vec![StubReply::now(resp)]
+        })
+        .await;
+        (addr, rx)
+    }
+
+    /// TCP 注册服务器：所有请求回复 200 OK，并上报每个新建连接的对端地址；
+    /// `close_after_response` 为真时每次应答后关闭连接
+    async fn spawn_tcp_registrar(
+        ip: std::net::IpAddr,
+        close_after_response: bool,
+    ) -> (SocketAddr, mpsc::UnboundedReceiver<SocketAddr>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind((ip, 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut stream, peer)) = listener.accept().await {
+                let _ = tx.send(peer);
+                tokio::spawn(async move {
+                    let mut buf = Vec::new();
+                    let mut chunk = vec![0u8; 4096];
+                    'serve: while let Ok(len) = stream.read(&mut chunk).await {
+                        if len == 0 {
+                            break;
+                        }
+                        buf.extend_from_slice(&chunk[..len]);
+                        // 请求均不带消息体，以空行分帧
+                        while let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                            let frame: Vec<u8> = buf.drain(..end + 4).collect();
+                            let Ok(rsip::SipMessage::Request(req)) = rsip::SipMessage::try_from(frame.as_slice()) else {
+                                continue;
+                            };
+                            if req.method == rsip::Method::Ack {
+                                continue;
+                            }
+                            let resp = stub_response(&req, rsip::StatusCode::OK, vec![]);
+                            let _ = stream.write_all(resp.to_string().as_bytes()).await;
+                            if close_after_response {
+                                break 'serve;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        (addr, rx)
+    }
+
+    /// 要求最短注册时长的注册服务器：Expires 小于 `min` 时回复 423，否则回复 200 OK，
+    /// 并上报每个 REGISTER 的 Expires 值
+    async fn spawn_min_expires_registrar(
+        ip: std::net::IpAddr,
+        min: u32,
+    ) -> (SocketAddr, mpsc::UnboundedReceiver<Option<u32>>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let addr = spawn_stub(ip, move |req, _, _| {
+            let expires = req.headers.iter().find_map(|h| match h {
+                rsip::Header::Expires(e) => e.value().parse::<u32>().ok(),
+                _ => None,
+            });
+            let resp = if expires.is_some_and(|e| e < min) {
+                let min_expires = rsip::headers::MinExpires::new(min.to_string());
+                stub_response(&req, rsip::StatusCode::IntervalTooBrief, vec![min_expires.into()])
+            } else {
+                stub_response(&req, rsip::StatusCode::OK, vec![])
+            };
+            let _ = tx.send(expires);
+            vec![StubReply::now(resp)]
+        })
+        .await;
+        (addr, rx)
+    }
+
+    /// 测试呼叫使用的最小合法 SDP
+    pub(crate) const TEST_SDP: &str =
+        "v=0\r\no=- 1 1 IN IP4 192.0.2.1\r\ns=-\r\nc=IN IP4 192.0.2.1\r\nt=0 0\r\nm=audio 4000 RTP/AVP 0\r\n";
+
+    pub(crate) fn test_config(server: SocketAddr) -> SipClientConfig {
+        SipClientConfig {
+            server: format!("sip:{}", server).as_str().try_into().unwrap(),
+            outbound_proxy: None,
+            username: "alice".to_string(),
+            domain: None,
+            auth_username: None,
+            password: "secret".to_string(),
+            user_agent: "sip-caller-test".to_string(),
+            expires_mode: ExpiresMode::default(),
+            register_expires: crate::config::DEFAULT_REGISTER_EXPIRES,
+            register_backoff: Backoff::default(),
+            auth_mode: AuthMode::default(),
+            stale_nonce_retry: true,
+            rport: true,
+            contact_q: None,
+            contact_uri: None,
+            transfer_timeout: Duration::from_secs(5),
+            register_timeout: Duration::from_secs(32),
+            tls: TlsOptions::default(),
+            local_bind_addr: Some(SocketAddr::new(server.ip(), 0)),
+            stun_server: None,
+            session_expires: None,
+            bye_on_shutdown: true,
+            dscp_sip: None,
+            enable_100rel: false,
+            tcp_fresh_connection: false,
+        }
+    }
+
+    /// INVITE 桩服务器：按顺序以 `finals` 中的状态与附加头部应答 INVITE（用尽后重复最后一个），
+    /// 其他请求回复 200 OK，并上报收到的每个 INVITE
+    async fn spawn_invite_stub(
+        ip: std::net::IpAddr,
+        finals: Vec<(rsip::StatusCode, Vec<rsip::Header>)>,
+    ) -> (SocketAddr, mpsc::UnboundedReceiver<rsip::Request>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut invites = 0;
+        let addr = spawn_stub(ip, move |req, addr, _| {
+            if req.method == rsip::Method::Ack {
+                return vec![];
+            }
+            let mut extra = vec![stub_contact(addr)];
+            let mut status = rsip::StatusCode::OK;
+            if req.method == rsip::Method::Invite {
+                let _ = tx.send(req.clone());
+                let (code, headers) = finals[invites.min(finals.len() - 1)].clone();
+                invites += 1;
+                status = code;
+                extra.extend(headers);
+            }
+            vec![StubReply::now(stub_response(&req, status, extra))]
+        })
+        .await;
+        (addr, rx)
+    }
+
+    /// 媒体应答桩服务器：以带 SDP answer 与 `Session-Expires: 120;refresher=uac` 的 200 OK
+    /// 应答每个 INVITE（offer 为 `sendonly` 时应答 `recvonly`，offer 中的视频 m 行以端口 0 拒绝），
+    /// 并上报收到的每个 INVITE
+    async fn spawn_sdp_answer_stub(ip: std::net::IpAddr) -> (SocketAddr, mpsc::UnboundedReceiver<rsip::Request>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let addr = spawn_stub(ip, move |req, addr, _| {
+            if req.method == rsip::Method::Ack {
+                return vec![];
+            }
+            let mut resp = stub_response(&req, rsip::StatusCode::OK, vec![stub_contact(addr)]);
+            if req.method == rsip::Method::Invite {
+                let direction = if String::from_utf8_lossy(&req.body).contains("a=sendonly") {
+                    "recvonly"
+                } else {
+                    "sendrecv"
+                };
+                let mut answer = format!(
+                    "v=0\r\no=- 1 1 IN IP4 127.0.0.1\r\ns=-\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\n\
+                     m=audio 40000 RTP/AVP 0\r\na=rtpmap:0 PCMU/8000\r\na={}\r\n",
+                    direction
+                );
+                if String::from_utf8_lossy(&req.body).contains("m=video") {
+                    answer.push_str("m=video 0 RTP/AVP 96\r\n");
+                }
+                resp.headers.retain(|h| !matches!(h, rsip::Header::ContentLength(_)));
+                resp.headers.push(rsip::Header::Other("Session-Expires".into(), "120;refresher=uac".into()));
+                resp.headers.push(rsip::Header::ContentType(SDP_CONTENT_TYPE.into()));
+                resp.headers.push(rsip::headers::ContentLength::from(answer.len() as u32).into());
+                resp.body = answer.into_bytes();
+                let _ = tx.send(req);
+            }
+            vec![StubReply::now(resp)]
+        })
+        .await;
+        (addr, rx)
+    }
+
+    #[tokio::test]
+    async fn test_make_call_with_custom_headers() {
+        let ip = test_ip();
+        let (addr, mut invites) = spawn_invite_stub(ip, vec![(rsip::StatusCode::OK, vec![])]).await;
+        let client = SipClient::new(test_config(addr)).await.unwrap();
+
+        let reserved = CallOptions::default().with_header(rsip::headers::CallId::new("forged"));
+        assert!(matches!(
+            client.make_call_with_options("bob", TEST_SDP, &reserved).await,
+            Err(CallError::InvalidConfig { .. })
+        ));
+
+        let options = CallOptions::default()
+            .with_header(rsip::Header::Other("X-Call-Reason".into(), "survey".into()))
+            .with_user_agent("ivr/2.0");
+        tokio::time::timeout(Duration::from_secs(5), client.make_call_with_options("bob", TEST_SDP, &options))
+            .await
+            .unwrap()
+            .unwrap();
+        let invite = invites.recv().await.unwrap();
+        let agents: Vec<String> = invite
+            .headers
+            .iter()
+            .filter_map(|h| match h {
+                rsip::Header::UserAgent(ua) => Some(ua.value().to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(agents, vec!["ivr/2.0".to_string()]);
+        assert!(invite.to_string().contains("X-Call-Reason: survey"));
+
+        // From 显示名加引号转义，参数追加在 tag 之前
+        let tagged = CallOptions::default().with_from_param(rsip::Param::Tag("forged".into()));
+        assert!(matches!(
+            client.make_call_with_options("bob", TEST_SDP, &tagged).await,
+            Err(CallError::InvalidConfig { .. })
+        ));
+        let options = CallOptions::default()
+            .with_display_name("Support \"Desk\"")
+            .with_from_param(rsip::Param::Other("x-team".into(), Some("ops".into())));
+        tokio::time::timeout(Duration::from_secs(5), client.make_call_with_options("bob", TEST_SDP, &options))
+            .await
+            .unwrap()
+            .unwrap();
+        let invite = invites.recv().await.unwrap();
+        let from = invite.from_header().unwrap().value().to_string();
+        assert!(
+            from.starts_with(&format!(r#""Support \"Desk\"" <sip:alice@{}>;x-team=ops;tag="#, addr)),
+            "{}",
+            from
+        );
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_user_domain_used_for_aor() {
+        let ip = test_ip();
+        let (addr, mut invites) = spawn_invite_stub(ip, vec![(rsip::StatusCode::OK, vec![])]).await;
+        let mut config = test_config(addr);
+        config.domain = Some("example.com".to_string());
+        let client = SipClient::new(config).await.unwrap();
+
+        // From 使用用户所属域，未带域名的目标仍发往服务器
+        tokio::time::timeout(Duration::from_secs(5), client.make_call("bob", TEST_SDP))
+            .await
+            .unwrap()
+            .unwrap();
+        let invite = invites.recv().await.unwrap();
+        let from = invite.from_header().unwrap().typed().unwrap();
+        assert_eq!(from.uri.to_string(), "sip:alice@example.com");
+        let to = invite.to_header().unwrap().typed().unwrap();
+        assert_eq!(to.uri.host_with_port.to_string(), addr.to_string());
+
+        assert!(matches!(
+            client.make_call("a@b@c", TEST_SDP).await,
+            Err(CallError::InvalidTarget { target }) if target == "a@b@c"
+        ));
+        client.shutdown().await;
+
+        // REGISTER 的 From/To 为 AOR，Digest 使用完整的认证用户名
+        let (registrar, mut authorizations) = spawn_registrar_stub(ip).await;
+        let (recorder, mut registers) = spawn_recording_registrar(ip).await;
+        for server in [registrar, recorder] {
+            let mut config = test_config(server);
+            config.domain = Some("example.com".to_string());
+            config.auth_username = Some("alice@example.com".to_string());
+            let client = SipClient::new(config).await.unwrap();
+            tokio::time::timeout(Duration::from_secs(5), client.register())
+                .await
+                .expect("REGISTER 超时")
+                .unwrap();
+            client.shutdown().await;
+        }
+        let register = registers.recv().await.unwrap();
+        assert_eq!(register.to_header().unwrap().typed().unwrap().uri.to_string(), "sip:alice@example.com");
+        assert_eq!(register.from_header().unwrap().typed().unwrap().uri.to_string(), "sip:alice@example.com");
+        assert_eq!(register.uri.host_with_port.to_string(), recorder.to_string());
+        assert!(authorizations.try_recv().unwrap().is_none());
+        let authorization = authorizations.try_recv().unwrap().unwrap();
+        assert!(authorization.contains(r#"username="alice@example.com""#), "{}", authorization);
+    }
+
+    /// 可靠临时响应桩服务器：INVITE 先以携带 `Require: 100rel` 与 RSeq 1 的 180 应答，
+    /// 确认 180 的 PRACK 之后发送 RSeq 2 的 183，确认 183 后发送最终的 200 OK。上报收到的 INVITE 与 PRACK
+    async fn spawn_100rel_stub(ip: std::net::IpAddr) -> (SocketAddr, mpsc::UnboundedReceiver<rsip::Request>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut invite = None;
+        let addr = spawn_stub(ip, move |req, addr, _| {
+            let contact = stub_contact(addr);
+            let reliable = |req: &rsip::Request, status, rseq: u32| {
+                let headers = vec![
+                    contact.clone(),
+                    rsip::Header::Other("Require".into(), "100rel".into()),
+                    rsip::Header::Other("RSeq".into(), rseq.to_string()),
+                ];
+                StubReply::now(stub_response(req, status, headers))
+            };
+            match req.method {
+                rsip::Method::Ack => vec![],
+                rsip::Method::Invite => {
+                    let _ = tx.send(req.clone());
+                    invite = Some(req.clone());
+                    vec![reliable(&req, rsip::StatusCode::Ringing, 1)]
+                }
+                rsip::Method::PRack => {
+                    let _ = tx.send(req.clone());
+                    let invite = invite.as_ref().unwrap();
+                    let next = if req.to_string().contains("RAck: 1 ") {
+                        reliable(invite, rsip::StatusCode::SessionProgress, 2)
+                    } else {
+                        StubReply::now(stub_response(invite, rsip::StatusCode::OK, vec![contact.clone()]))
+                    };
+                    vec![StubReply::now(stub_response(&req, rsip::StatusCode::OK, vec![])), next]
+                }
+                _ => vec![StubReply::now(stub_response(&req, rsip::StatusCode::OK, vec![]))],
+            }
+        })
+        .await;
+        (addr, rx)
+    }
+
+    #[tokio::test]
+    async fn test_prack_reliable_provisional_responses() {
+        let ip = test_ip();
+        let (addr, mut requests) = spawn_100rel_stub(ip).await;
+        let mut config = test_config(addr);
+        config.enable_100rel = true;
+        let client = SipClient::new(config).await.unwrap();
+
+        let (_, response) = tokio::time::timeout(Duration::from_secs(5), client.make_call("bob", TEST_SDP))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.unwrap().status_code, rsip::StatusCode::OK);
+
+        let invite = requests.recv().await.unwrap();
+        assert!(invite.to_string().contains("Supported: 100rel"), "{}", invite);
+        let invite_cseq = invite.cseq_header().unwrap().seq().unwrap();
+        // 180 与随后的 183 都被确认，RAck 引用各自的 RSeq 与 INVITE 的 CSeq
+        let mut prack_cseq = invite_cseq;
+        for rseq in [1, 2] {
+            let prack = requests.recv().await.unwrap();
+            assert_eq!(prack.method, rsip::Method::PRack);
+            let rack = format!("RAck: {} {} INVITE", rseq, invite_cseq);
+            assert!(prack.to_string().contains(&rack), "{}", prack);
+            let cseq = prack.cseq_header().unwrap().seq().unwrap();
+            assert!(cseq > prack_cseq, "{}", prack);
+            prack_cseq = cseq;
+        }
+        assert!(requests.try_recv().is_err());
+        client.shutdown().await;
+
+        // 默认不声明 100rel
+        let (addr, mut invites) = spawn_invite_stub(ip, vec![(rsip::StatusCode::OK, vec![])]).await;
+        let client = SipClient::new(test_config(addr)).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), client.make_call("bob", TEST_SDP))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!invites.recv().await.unwrap().to_string().contains("100rel"));
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_session_timer_retries_after_422() {
+        let ip = test_ip();
+        let (addr, mut invites) = spawn_invite_stub(
+            ip,
+            vec![
+                (
+                    rsip::StatusCode::SessionIntervalTooSmall,
+                    vec![rsip::Header::Other("Min-SE".into(), "1800".into())],
+                ),
+                (
+                    rsip::StatusCode::OK,
+                    vec![rsip::Header::Other("Session-Expires".into(), "1800;refresher=uas".into())],
+                ),
+            ],
+        )
+        .await;
+        let mut config = test_config(addr);
+        config.session_expires = Some(300);
+        let client = SipClient::new(config).await.unwrap();
+
+        let (_, response) = tokio::time::timeout(Duration::from_secs(5), client.make_call("bob", TEST_SDP))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.unwrap().status_code, rsip::StatusCode::OK);
+
+        let first = invites.recv().await.unwrap();
+        let second = invites.recv().await.unwrap();
+        assert_eq!(
+            SessionExpires::parse(&first.headers).unwrap(),
+            SessionExpires::uac(300)
+        );
+        assert!(first.to_string().contains("Supported: timer"));
+        assert_eq!(SessionExpires::parse(&second.headers).unwrap().interval, 1800);
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_early_media_forwarded_before_answer() {
+        let ip = test_ip();
+        let addr = spawn_stub(ip, move |req, addr, _| {
+            if req.method == rsip::Method::Ack {
+                return vec![];
+            }
+            let mut statuses = vec![rsip::StatusCode::OK];
+            if req.method == rsip::Method::Invite {
+                statuses.insert(0, rsip::StatusCode::SessionProgress);
+            }
+            // 呼叫 carol 时 200 OK 不带 SDP
+            let final_sdp = !req.uri.to_string().contains("carol");
+            statuses
+                .into_iter()
+                .map(|status| {
+                    let mut resp = stub_response(&req, status.clone(), vec![stub_contact(addr)]);
+                    if req.method == rsip::Method::Invite && (final_sdp || status != rsip::StatusCode::OK) {
+                        let sdp = format!(
+                            "v=0\r\no=- {} 1 IN IP4 {}\r\ns=-\r\nc=IN IP4 {}\r\nt=0 0\r\nm=audio 5000 RTP/AVP 0\r\n",
+                            status.code(),
+                            ip,
+                            ip
+                        );
+                        resp.headers.retain(|h| !matches!(h, rsip::Header::ContentLength(_)));
+                        resp.headers.push(rsip::headers::ContentType::new("application/sdp").into());
+                        resp.headers.push(rsip::headers::ContentLength::from(sdp.len() as u32).into());
+                        resp.body = sdp.into_bytes();
+                    }
+                    StubReply::now(resp)
+                })
+                .collect()
+        })
+        .await;
+        let client = SipClient::new(test_config(addr)).await.unwrap();
+
+        let (tx, mut early) = mpsc::unbounded_channel();
+        let options = CallOptions::default().with_early_media(tx);
+        let (_, response) = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.make_call_with_options("bob", TEST_SDP, &options),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        let media = tokio::time::timeout(Duration::from_secs(1), early.recv()).await.unwrap().unwrap();
+        assert_eq!(media.status, rsip::StatusCode::SessionProgress);
+        assert!(media.sdp.contains("o=- 183 1"));
+        assert!(response_sdp(&response.unwrap()).unwrap().contains("o=- 200 1"));
+
+        // 200 OK 不带 SDP 时返回此前 183 中的 SDP
+        let (_, answer) = tokio::time::timeout(Duration::from_secs(5), client.make_call_with_answer("carol", TEST_SDP))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(answer.status, rsip::StatusCode::OK);
+        assert!(answer.early);
+        assert!(answer.sdp_text().unwrap().contains("o=- 183 1"));
+        client.shutdown().await;
+    }
+
+    #[test]
+    fn test_call_answer_prefers_final_sdp() {
+        let response = |status: rsip::StatusCode, sdp: Option<&str>| {
+            let mut headers: Vec<rsip::Header> = Vec::new();
+            if sdp.is_some() {
+                headers.push(rsip::headers::ContentType::new("application/sdp").into());
+            }
+            rsip::Response {
+                status_code: status,
+                version: rsip::Version::V2,
+                headers: headers.into(),
+                body: sdp.unwrap_or_default().as_bytes().to_vec(),
+            }
+        };
+        let early_sdp = TEST_SDP.replace("m=audio 4000", "m=audio 5000");
+        let early = || EarlyMedia {
+            dialog_id: DialogId {
+                call_id: "c".into(),
+                local_tag: "l".into(),
+                remote_tag: "r".into(),
+            },
+            status: rsip::StatusCode::SessionProgress,
+            sdp: early_sdp.clone(),
+        };
+        let port = |answer: &CallAnswer| answer.sdp.as_ref().unwrap().media_sections[0].port;
+
+        let answer = CallAnswer::new(&response(rsip::StatusCode::OK, Some(TEST_SDP)), Some(early())).unwrap();
+        assert_eq!(answer.status, rsip::StatusCode::OK);
+        assert_eq!((port(&answer), answer.early), (4000, false));
+
+        // 200 OK 不带 SDP 时使用 183 中的 SDP
+        let answer = CallAnswer::new(&response(rsip::StatusCode::OK, None), Some(early())).unwrap();
+        assert_eq!((port(&answer), answer.early), (5000, true));
+        let text = answer.sdp_text().unwrap();
+        assert!(text.contains("m=audio 5000"));
+        assert_eq!(crate::utils::validate_sdp(&text), Ok(()));
+
+        let rejected = CallAnswer::new(&response(rsip::StatusCode::BusyHere, None), Some(early())).unwrap();
+        assert_eq!((rejected.sdp, rejected.early), (None, false));
+        assert!(CallAnswer::new(&response(rsip::StatusCode::OK, Some("garbage")), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_call_and_register_logs_carry_ids() {
+        let ip = test_ip();
+        #[derive(Clone, Default)]
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+        impl std::io::Write for Buffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let (uas_addr, _methods) = spawn_uas_stub(ip).await;
+        let caller = SipClient::new(test_config(uas_addr)).await.unwrap();
+        let (dialog, _) = tokio::time::timeout(Duration::from_secs(5), caller.make_call("bob", TEST_SDP))
+            .await
+            .expect("INVITE 超时")
+            .unwrap();
+
+        let (registrar, _authorizations) = spawn_registrar_stub(ip).await;
+        let client = SipClient::new(test_config(registrar)).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), client.register())
+            .await
+            .expect("REGISTER 超时")
+            .unwrap();
+        let register_call_id = client.state.lock().unwrap().binding.as_ref().unwrap().call_id.clone();
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let id = dialog.id();
+        let answered = output
+            .lines()
+            .find(|line| line.contains("INVITE 请求已发送"))
+            .expect("缺少接通日志");
+        assert!(answered.contains(&format!("call{{call_id={} dialog_id={}}}", id.call_id, id)), "{}", answered);
+        assert!(output.contains(&format!("register{{call_id={}}}", register_call_id.value())), "{}", output);
+        caller.shutdown().await;
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_metrics_track_calls_and_registration() {
+        let ip = test_ip();
+        let (addr, _invites) = spawn_invite_stub(
+            ip,
+            vec![(rsip::StatusCode::OK, vec![]), (rsip::StatusCode::BusyHere, vec![])],
+        )
+        .await;
+        let client = SipClient::new(test_config(addr)).await.unwrap();
+        let metrics = client.metrics_handle();
+        for _ in 0..2 {
+            tokio::time::timeout(Duration::from_secs(5), client.make_call("bob", TEST_SDP))
+                .await
+                .expect("INVITE 超时")
+                .unwrap();
+        }
+        assert_eq!(metrics.calls_placed(), 2);
+        assert_eq!(metrics.calls_answered(), 1);
+        assert_eq!(metrics.calls_failed("CALL_REJECTED"), 1);
+        // 空 SDP 以外的非法 offer 在发送前失败
+        assert!(client.make_call("bob", "garbage").await.is_err());
+        assert_eq!(metrics.calls_failed("INVALID_SDP"), 1);
+        let text = metrics.render();
+        assert!(text.contains(&format!("sip_active_calls {}", client.status().active_calls)), "{}", text);
+        assert!(text.contains("sip_registered 0"));
+
+        let (registrar, _authorizations) = spawn_registrar_stub(ip).await;
+        let client = SipClient::new(test_config(registrar)).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), client.register())
+            .await
+            .expect("REGISTER 超时")
+            .unwrap();
+        assert!(client.metrics_handle().render().contains("sip_registered 1"));
+    }
+
+    #[tokio::test]
+    async fn test_call_events() {
+        let ip = test_ip();
+        let (addr, _invites) = spawn_invite_stub(
+            ip,
+            vec![(rsip::StatusCode::OK, vec![]), (rsip::StatusCode::BusyHere, vec![])],
+        )
+        .await;
+        let client = SipClient::new(test_config(addr)).await.unwrap();
+        let mut events = client.events();
+
+        let (dialog, _) = tokio::time::timeout(Duration::from_secs(5), client.make_call("bob", TEST_SDP))
+            .await
+            .expect("INVITE 超时")
+            .unwrap();
+        match events.recv().await.unwrap() {
+            CallEvent::Answered { dialog_id, setup_time, remote_sdp } => {
+                assert_eq!(dialog_id, dialog.id());
+                assert!(setup_time < Duration::from_secs(5));
+                assert_eq!(remote_sdp, None);
+            }
+            other => panic!("应为 Answered: {:?}", other),
+        }
+
+        client.hangup(&dialog).await.unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv()).await.unwrap().unwrap();
+        assert!(matches!(
+            event,
+            CallEvent::Terminated { ref dialog_id, reason: TerminatedReason::UacBye } if *dialog_id == dialog.id()
+        ));
+
+        // 被拒绝的呼叫只发布 Failed，不发布 Terminated
+        client.make_call("bob", TEST_SDP).await.unwrap();
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            CallEvent::Failed { error_code: "CALL_REJECTED", .. }
+        ));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_update_respects_peer_allow() {
+        let ip = test_ip();
+        // 对端声明支持 UPDATE：会话更新不再发送 INVITE
+        let allow = rsip::headers::Allow::new("INVITE, ACK, BYE, UPDATE");
+        let (addr, mut invites) = spawn_invite_stub(ip, vec![(rsip::StatusCode::OK, vec![allow.into()])]).await;
+        let client = SipClient::new(test_config(addr)).await.unwrap();
+        let (dialog, _) = client.make_call("bob", TEST_SDP).await.unwrap();
+        let invite = invites.recv().await.unwrap();
+        assert!(allows_method(&invite.headers, rsip::Method::Update));
+        assert_eq!(client.send_update(&dialog, Some(TEST_SDP)).await.unwrap(), None);
+        assert_eq!(client.send_update(&dialog, None).await.unwrap(), None);
+        assert!(invites.try_recv().is_err());
+        client.shutdown().await;
+
+        // 未声明时回退到 re-INVITE，且不带 SDP 的刷新被拒绝
+        let (addr, mut invites) = spawn_invite_stub(ip, vec![(rsip::StatusCode::OK, vec![])]).await;
+        let client = SipClient::new(test_config(addr)).await.unwrap();
+        let (dialog, _) = client.make_call("bob", TEST_SDP).await.unwrap();
+        invites.recv().await.unwrap();
+        client.send_update(&dialog, Some(TEST_SDP)).await.unwrap();
+        let reinvite = invites.recv().await.unwrap();
+        assert_eq!(reinvite.body, TEST_SDP.as_bytes());
+        assert!(matches!(
+            client.send_update(&dialog, None).await,
+            Err(CallError::InvalidSdp { .. })
+        ));
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_session_refresh_after_hold() {
+        let ip = test_ip();
+        let (addr, mut invites) = spawn_sdp_answer_stub(ip).await;
+        let mut config = test_config(addr);
+        config.session_expires = Some(90);
+        let client = SipClient::new(config).await.unwrap();
+
+        let mut player = RtpPlayer::new(rustrtc::media::MediaKind::Audio).await.unwrap();
+        let offer = player.get_local_sdp().unwrap();
+        let (dialog, response) = client.make_call("bob", &offer).await.unwrap();
+        player.apply_answer(&response_sdp(&response.unwrap()).unwrap()).await.unwrap();
+        let player: SharedRtpPlayer = Arc::new(tokio::sync::Mutex::new(player));
+        client.attach_media(&dialog, player.clone());
+        client.hold(&dialog, &mut *player.lock().await).await.unwrap();
+
+        // 对端不支持 UPDATE：刷新 re-INVITE 沿用保持后的本地描述，内容未变时 o= 版本不变
+        let session = session_timer::refresh_session(&dialog, SessionExpires::uac(90), false, &client.media_sessions)
+            .await
+            .unwrap();
+        assert_eq!(session, Some(SessionExpires::uac(120)));
+
+        let origin = |req: &rsip::Request| -> Vec<String> {
+            let body = String::from_utf8_lossy(&req.body).to_string();
+            let line = body.lines().find_map(|l| l.strip_prefix("o=")).unwrap().to_string();
+            line.split(' ').map(str::to_string).collect()
+        };
+        let initial = origin(&invites.recv().await.unwrap());
+        let hold = origin(&invites.recv().await.unwrap());
+        let refresh_req = invites.recv().await.unwrap();
+        let refresh = origin(&refresh_req);
+        assert_eq!(hold[1], initial[1]);
+        assert_ne!(hold[2], initial[2]);
+        assert_eq!(refresh, hold);
+        assert!(String::from_utf8_lossy(&refresh_req.body).contains("a=sendonly"));
+        assert_eq!(SessionExpires::parse(&refresh_req.headers), Some(SessionExpires::uac(90)));
+
+        // 刷新 2xx 中的 answer 已应用到媒体会话
+        let player = player.lock().await;
+        assert_eq!(player.local_direction(), MediaDirection::SendOnly);
+        assert_eq!(player.negotiated_direction(), MediaDirection::RecvOnly);
+        drop(player);
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_add_video_declined_in_answer() {
+        let ip = test_ip();
+        let (addr, mut invites) = spawn_sdp_answer_stub(ip).await;
+        let client = SipClient::new(test_config(addr)).await.unwrap();
+
+        let mut player = RtpPlayer::new(rustrtc::media::MediaKind::Audio).await.unwrap();
+        let offer = player.get_local_sdp().unwrap();
+        let (dialog, response) = client.make_call("bob", &offer).await.unwrap();
+        player.apply_answer(&response_sdp(&response.unwrap()).unwrap()).await.unwrap();
+        invites.recv().await.unwrap();
+        let mut call = crate::call::CallHandle::new(dialog, player);
+
+        // 对端以端口 0 拒绝视频：再次 re-INVITE 停用视频 m 行，反复升级不累积收发器
+        for _ in 0..2 {
+            let source = RtpPlayer::new(rustrtc::media::MediaKind::Video).await.unwrap();
+            assert!(!call.add_video(Box::new(source)).await.unwrap());
+            assert!(!call.has_video());
+
+            let upgrade = String::from_utf8_lossy(&invites.recv().await.unwrap().body).to_string();
+            let downgrade = String::from_utf8_lossy(&invites.recv().await.unwrap().body).to_string();
+            for (sdp, direction) in [(upgrade, "a=sendrecv"), (downgrade, "a=inactive")] {
+                assert_eq!(sdp.matches("m=video").count(), 1);
+                let (_, video) = sdp.split_once("m=video").unwrap();
+                assert!(video.contains(direction));
+            }
+        }
+        assert!(invites.try_recv().is_err());
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_hangup_sends_bye() {
+        let ip = test_ip();
+        let (uas_addr, mut methods) = spawn_uas_stub(ip).await;
+        let client = SipClient::new(test_config(uas_addr)).await.unwrap();
+
+        let (dialog, response) = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.make_call("bob", TEST_SDP),
+        )
+        .await
+        .expect("INVITE 超时")
+        .unwrap();
+        assert_eq!(response.unwrap().status_code, rsip::StatusCode::OK);
+        assert!(dialog.state().is_confirmed());
+        // 已接通的呼叫不能 CANCEL
+        assert!(matches!(client.cancel(&dialog).await, Err(CallError::CallInProgress)));
+
+        tokio::time::timeout(Duration::from_secs(5), client.hangup(&dialog))
+            .await
+            .expect("BYE 超时")
+            .unwrap();
+
+        let mut seen = Vec::new();
+        while let Ok(method) = methods.try_recv() {
+            seen.push(method);
+        }
+        assert!(seen.contains(&rsip::Method::Invite));
+        assert!(seen.contains(&rsip::Method::Bye));
+        assert!(matches!(dialog.state(), DialogState::Terminated(_, _)));
+
+        // 对话已终止后不能再发送 DTMF
+        assert!(matches!(
+            client.send_dtmf(&dialog, '1', 160).await,
+            Err(CallError::NotConnected)
+        ));
+
+        // 对话已终止时再次挂断视为成功
+        client.hangup(&dialog).await.unwrap();
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_in_dialog_requests_follow_record_route() {
+        let ip = test_ip();
+        // 模拟 SBC：记录经过的请求并以 200 OK 应答（ACK 除外）
+        let (routed_tx, mut routed) = mpsc::unbounded_channel();
+        let proxy_addr = spawn_stub(ip, move |req, _, _| {
+            let _ = routed_tx.send(req.clone());
+            if req.method == rsip::Method::Ack {
+                return vec![];
+            }
+            vec![StubReply::now(stub_response(&req, rsip::StatusCode::OK, vec![]))]
+        })
+        .await;
+
+        let record_route = rsip::headers::RecordRoute::new(format!("<sip:{};lr>", proxy_addr));
+        let (uas_addr, _invites) =
+            spawn_invite_stub(ip, vec![(rsip::StatusCode::OK, vec![record_route.into()])]).await;
+        let client = SipClient::new(test_config(uas_addr)).await.unwrap();
+        let (dialog, _) = tokio::time::timeout(Duration::from_secs(5), client.make_call("bob", TEST_SDP))
+            .await
+            .expect("INVITE 超时")
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), client.send_dtmf(&dialog, '5', 160))
+            .await
+            .expect("INFO 超时")
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), client.hangup(&dialog))
+            .await
+            .expect("BYE 超时")
+            .unwrap();
+
+        // ACK、INFO、BYE 都发往 Route 首跳，Request-URI 仍是对端 Contact
+        let mut methods = Vec::new();
+        while let Ok(req) = routed.try_recv() {
+            assert_eq!(req.uri.host_with_port.to_string(), uas_addr.to_string());
+            let route = req.route_header().expect("缺少 Route 头");
+            assert!(route.value().contains(&proxy_addr.to_string()), "{}", route.value());
+            methods.push(req.method);
+        }
+        assert!(methods.contains(&rsip::Method::Ack), "{:?}", methods);
+        assert!(methods.contains(&rsip::Method::Info), "{:?}", methods);
+        assert!(methods.contains(&rsip::Method::Bye), "{:?}", methods);
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_forked_2xx_acked_and_released() {
+        let ip = test_ip();
+        // 落选终端：记录收到的请求，BYE 回复 200 OK
+        let (loser_tx, mut loser_requests) = mpsc::unbounded_channel();
+        let loser_addr = spawn_stub(ip, move |req, _, _| {
+            let _ = loser_tx.send(req.clone());
+            if req.method != rsip::Method::Bye {
+                return vec![];
+            }
+            vec![StubReply::now(stub_response(&req, rsip::StatusCode::OK, vec![]))]
+        })
+        .await;
+
+        // 分叉代理：对 INVITE 先以胜出 tag 应答，随后立即和延迟各发送一个落选分叉的 2xx
+        let (bye_tx, mut winner_byes) = mpsc::unbounded_channel();
+        let proxy_addr = spawn_stub(ip, move |req, proxy_addr, _| {
+            if req.method == rsip::Method::Bye {
+                let _ = bye_tx.send(());
+            }
+            if req.method != rsip::Method::Invite {
+                return vec![];
+            }
+            let answer = |tag: &str, contact: SocketAddr| {
+                let mut tagged = req.clone();
+                let to = format!("{};tag={}", req.to_header().unwrap().value(), tag);
+                tagged.headers.unique_push(rsip::headers::To::new(to).into());
+                let contact = rsip::headers::Contact::new(format!("<sip:bob@{}>", contact));
+                stub_response(&tagged, rsip::StatusCode::OK, vec![contact.into()])
+            };
+            vec![
+                StubReply::now(answer("winner", proxy_addr)),
+                StubReply::now(answer("fork-1", loser_addr)),
+                StubReply::after(Duration::from_millis(300), answer("fork-2", loser_addr)),
+            ]
+        })
+        .await;
+
+        let client = SipClient::new(test_config(proxy_addr)).await.unwrap();
+        let (events_tx, mut events) = mpsc::unbounded_channel();
+        let options = CallOptions::default().with_fork_events(events_tx);
+        let (dialog, _) = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.make_call_with_options("bob", TEST_SDP, &options),
+        )
+        .await
+        .expect("INVITE 超时")
+        .unwrap();
+        assert_eq!(dialog.id().remote_tag, "winner");
+
+        let mut released = Vec::new();
+        for _ in 0..2 {
+            let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+                .await
+                .expect("未收到分叉通知")
+                .unwrap();
+            assert_eq!(event.winner, dialog.id());
+            released.push(event.released.remote_tag);
+        }
+        released.sort();
+        assert_eq!(released, vec!["fork-1", "fork-2"]);
+
+        // 每个落选分叉收到一个 ACK 和一个 BYE，发往其 Contact
+        let mut seen = Vec::new();
+        while let Ok(req) = loser_requests.try_recv() {
+            assert_eq!(req.uri.host_with_port.to_string(), loser_addr.to_string());
+            let tag = req.to_header().unwrap().tag().unwrap().unwrap().to_string();
+            seen.push((req.method.to_string(), tag));
+        }
+        seen.sort();
+        let expected: Vec<(String, String)> = [("ACK", "fork-1"), ("ACK", "fork-2"), ("BYE", "fork-1"), ("BYE", "fork-2")]
+            .iter()
+            .map(|(m, t)| (m.to_string(), t.to_string()))
+            .collect();
+        assert_eq!(seen, expected);
+
+        // 胜出对话不受影响
+        assert!(dialog.state().is_confirmed());
+        assert!(winner_byes.try_recv().is_err());
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_register_refresh_reuses_nonce_with_incrementing_nc() {
+        let ip = test_ip();
+        let (registrar, mut authorizations) = spawn_registrar_stub(ip).await;
+        let client = SipClient::new(test_config(registrar)).await.unwrap();
+        assert_eq!(client.status().auth_scheme, None);
+
+        for _ in 0..2 {
+            tokio::time::timeout(Duration::from_secs(5), client.register())
+                .await
+                .expect("REGISTER 超时")
+                .unwrap();
+        }
+
+        let mut seen = Vec::new();
+        while let Ok(auth) = authorizations.try_recv() {
+            seen.push(auth);
+        }
+        // 首次注册：无认证 -> 401 -> nc=1；刷新：复用 nonce，nc=2
+        assert_eq!(seen.len(), 3, "{:?}", seen);
+        assert!(seen[0].is_none());
+        let first = seen[1].as_deref().unwrap();
+        let refresh = seen[2].as_deref().unwrap();
+        assert!(first.contains("nc=00000001") && first.contains("fixed-nonce"));
+        assert!(refresh.contains("nc=00000002") && refresh.contains("fixed-nonce"));
+        assert_eq!(client.status().auth_scheme.as_deref(), Some("Digest MD5"));
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_make_call_rejects_invalid_sdp() {
+        let ip = test_ip();
+        let (uas_addr, mut methods) = spawn_uas_stub(ip).await;
+        let client = SipClient::new(test_config(uas_addr)).await.unwrap();
+
+        let missing_connection = TEST_SDP.replace("c=IN IP4 192.0.2.1\r\n", "");
+        for sdp in ["garbage", missing_connection.as_str()] {
+            assert!(matches!(
+                client.make_call("bob", sdp).await,
+                Err(CallError::InvalidSdp { .. })
+            ));
+        }
+        // 校验失败时不发送 INVITE
+        assert!(methods.try_recv().is_err());
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_sends_bye_to_active_calls() {
+        let ip = test_ip();
+        for bye_on_shutdown in [true, false] {
+            let (uas_addr, mut methods) = spawn_uas_stub(ip).await;
+            let mut config = test_config(uas_addr);
+            config.bye_on_shutdown = bye_on_shutdown;
+            let client = SipClient::new(config).await.unwrap();
+            let (dialog, _) = tokio::time::timeout(Duration::from_secs(5), client.make_call("bob", TEST_SDP))
+                .await
+                .expect("INVITE 超时")
+                .unwrap();
+            assert!(dialog.state().is_confirmed());
+
+            client.shutdown().await;
+            let mut seen = Vec::new();
+            while let Ok(method) = methods.try_recv() {
+                seen.push(method);
+            }
+            assert_eq!(seen.contains(&rsip::Method::Bye), bye_on_shutdown, "{:?}", seen);
+            assert_eq!(dialog.state().is_terminated(), bye_on_shutdown);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_credential_provider_answers_challenge() {
+        use crate::sip_auth::{extract_param, DigestChallenge, DigestInput};
+        use rsip::headers::auth::{Algorithm, AuthQop};
+        let ip = test_ip();
+        let (registrar, mut authorizations) = spawn_registrar_stub(ip).await;
+        let client = SipClient::new(test_config(registrar)).await.unwrap();
+        let (calls_tx, mut calls) = mpsc::unbounded_channel();
+        client.on_auth_challenge(move |realm, nonce| {
+            let _ = calls_tx.send((realm.to_string(), nonce.to_string()));
+            Box::pin(async { "rotating-token".to_string() })
+        });
+
+        tokio::time::timeout(Duration::from_secs(5), client.register())
+            .await
+            .expect("REGISTER 超时")
+            .unwrap();
+
+        assert_eq!(calls.try_recv().unwrap(), ("stub".to_string(), "fixed-nonce".to_string()));
+        assert!(authorizations.try_recv().unwrap().is_none());
+        let header = authorizations.try_recv().unwrap().unwrap();
+        let challenge = DigestChallenge::parse(
+            r#"Digest realm="stub", nonce="fixed-nonce", qop="auth", algorithm=MD5"#,
+        )
+        .unwrap();
+        let cnonce = extract_param(&header, "cnonce").unwrap();
+        let expected = DigestInput {
+            algorithm: Algorithm::Md5,
+            username: "alice",
+            realm: "stub",
+            password: "rotating-token",
+            method: "REGISTER",
+            uri: &extract_param(&header, "uri").unwrap(),
+            nonce: &challenge.nonce,
+            qop: Some(&AuthQop::Auth { cnonce: cnonce.clone(), nc: 1 }),
+            body: &[],
+        };
+        assert_eq!(extract_param(&header, "response").unwrap(), expected.response());
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_dtmf_via_info() {
+        let ip = test_ip();
+        let (uas_addr, mut methods) = spawn_uas_stub(ip).await;
+        let client = SipClient::new(test_config(uas_addr)).await.unwrap();
+
+        let (dialog, _) = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.make_call("bob", TEST_SDP),
+        )
+        .await
+        .expect("INVITE 超时")
+        .unwrap();
+
+        assert!(matches!(
+            client.send_dtmf(&dialog, 'E', 160).await,
+            Err(CallError::InvalidTarget { .. })
+        ));
+        tokio::time::timeout(Duration::from_secs(5), client.send_dtmf(&dialog, '5', 160))
+            .await
+            .expect("INFO 超时")
+            .unwrap();
+
+        let mut seen = Vec::new();
+        while let Ok(method) = methods.try_recv() {
+            seen.push(method);
+        }
+        assert_eq!(seen.iter().filter(|m| **m == rsip::Method::Info).count(), 1);
+        client.shutdown().await;
+    }
+
+    /// 事件通知桩服务器：SUBSCRIBE 以 200 OK（`Expires: 300`）接受，随后发送 NOTIFY；
+    /// `Expires: 0` 时发送 `terminated` 的最终 NOTIFY。上报每个 SUBSCRIBE 的 Expires 值
+    async fn spawn_notifier_stub(ip: std::net::IpAddr) -> (SocketAddr, mpsc::UnboundedReceiver<u32>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut notify_seq = 0;
+        let addr = spawn_stub(ip, move |req, addr, _| {
+            let expires = crate::sip_subscription::granted_expires(&rsip::Response {
+                headers: req.headers.clone(),
+                ..Default::default()
+            })
+            .unwrap();
+            let _ = tx.send(expires);
+            let granted = expires.min(300);
+            let contact = rsip::headers::Contact::new(format!("<sip:notifier@{}>", addr));
+            let resp = stub_response(
+                &req,
+                rsip::StatusCode::OK,
+                vec![contact.into(), rsip::Header::Expires(granted.into())],
+            );
+
+            let (state, body) = if expires == 0 {
+                ("terminated;reason=timeout".to_string(), "")
+            } else {
+                (format!("active;expires={}", granted), "Messages-Waiting: yes\r\n")
+            };
+            notify_seq += 1;
+            let to = rsip::headers::To::new(req.from_header().unwrap().value());
+            let from = rsip::headers::From::new(format!("{};tag=uas-stub", req.to_header().unwrap().value()));
+            let notify = rsip::Request {
+                method: rsip::Method::Notify,
+                uri: req.contact_header().unwrap().typed().unwrap().uri,
+                version: rsip::Version::V2,
+                headers: vec![
+                    rsip::headers::Via::new(format!("SIP/2.0/UDP {};branch=z9hG4bK-notify{}", addr, notify_seq)).into(),
+                    from.into(),
+                    to.into(),
+                    req.call_id_header().unwrap().clone().into(),
+                    rsip::headers::CSeq::new(format!("{} NOTIFY", notify_seq)).into(),
+                    rsip::Header::Event("message-summary".into()),
+                    rsip::Header::SubscriptionState(state.into()),
+                    rsip::Header::ContentType("application/simple-message-summary".into()),
+                    rsip::headers::ContentLength::from(body.len() as u32).into(),
+                ]
+                .into(),
+                body: body.as_bytes().to_vec(),
+            };
+            vec![StubReply::now(resp), StubReply::after(Duration::from_millis(100), notify)]
+        })
+        .await;
+        (addr, rx)
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_notify_refresh_and_unsubscribe() {
+        use crate::sip_subscription::SubscriptionState;
+        let ip = test_ip();
+        let (notifier, mut expires) = spawn_notifier_stub(ip).await;
+        let client = SipClient::new(test_config(notifier)).await.unwrap();
+
+        let mut subscription = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.subscribe("alice", "message-summary", 600),
+        )
+        .await
+        .expect("SUBSCRIBE 超时")
+        .unwrap();
+        assert_eq!(subscription.event(), "message-summary");
+        assert_eq!(subscription.expires(), 300);
+        let mut notifications = subscription.notifications().unwrap();
+        assert!(subscription.notifications().is_none());
+
+        let wait = Duration::from_secs(5);
+        let notification = tokio::time::timeout(wait, notifications.recv())
+            .await
+            .expect("NOTIFY 超时")
+            .unwrap();
+        assert_eq!(notification.event, "message-summary");
+        assert_eq!(notification.state, SubscriptionState::Active);
+        assert_eq!(notification.body_text(), "Messages-Waiting: yes\r\n");
+
+        assert_eq!(subscription.refresh().await.unwrap(), 300);
+        tokio::time::timeout(wait, notifications.recv())
+            .await
+            .expect("刷新后的 NOTIFY 超时")
+            .unwrap();
+
+        subscription.unsubscribe().await.unwrap();
+        assert!(!subscription.is_active());
+        let last = tokio::time::timeout(wait, notifications.recv())
+            .await
+            .expect("最终 NOTIFY 超时")
+            .unwrap();
+        assert_eq!(last.state, SubscriptionState::Terminated(Some("timeout".into())));
+        assert!(tokio::time::timeout(wait, notifications.recv()).await.unwrap().is_none());
+
+        let mut seen = Vec::new();
+        while let Ok(value) = expires.try_recv() {
+            seen.push(value);
+        }
+        assert_eq!(seen, vec![600, 600, 0]);
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let ip = test_ip();
+
+        // 对话外：先被 401 挑战，携带 Authorization 重发后成功
+        let (registrar, mut authorizations) = spawn_registrar_stub(ip).await;
+        let client = SipClient::new(test_config(registrar)).await.unwrap();
+        let resp = tokio::time::timeout(Duration::from_secs(5), client.send_message("bob", "disk full", ""))
+            .await
+            .expect("MESSAGE 超时")
+            .unwrap();
+        assert_eq!(resp.status_code, rsip::StatusCode::OK);
+        assert!(authorizations.try_recv().unwrap().is_none());
+        assert!(authorizations.try_recv().unwrap().is_some());
+        client.shutdown().await;
+
+        // 消息体与默认内容类型，拒绝时返回状态码
+        let (requests_tx, mut requests) = mpsc::unbounded_channel();
+        let addr = spawn_stub(ip, move |req, _, _| {
+            let status = if req.body == b"spam" {
+                rsip::StatusCode::Forbidden
+            } else {
+                rsip::StatusCode::OK
+            };
+            let resp = stub_response(&req, status, vec![]);
+            let _ = requests_tx.send(req);
+            vec![StubReply::now(resp)]
+        })
+        .await;
+        let client = SipClient::new(test_config(addr)).await.unwrap();
+        client.send_message("bob", "disk full", "").await.unwrap();
+        let req = requests.recv().await.unwrap();
+        assert_eq!(req.method, rsip::Method::Message);
+        assert_eq!(req.uri.to_string(), format!("sip:bob@{}", addr));
+        assert_eq!(req.body, b"disk full");
+        assert_eq!(rsip::header_opt!(req.headers.iter(), rsip::Header::ContentType).unwrap().value(), "text/plain");
+
+        let err = client.send_message("bob", "spam", "text/html").await.unwrap_err();
+        assert_eq!(err.sip_status_code(), Some(403));
+        let req = requests.recv().await.unwrap();
+        assert_eq!(rsip::header_opt!(req.headers.iter(), rsip::Header::ContentType).unwrap().value(), "text/html");
+        client.shutdown().await;
+
+        // 对话内
+        let (uas_addr, mut methods) = spawn_uas_stub(ip).await;
+        let client = SipClient::new(test_config(uas_addr)).await.unwrap();
+        let (dialog, _) = tokio::time::timeout(Duration::from_secs(5), client.make_call("bob", TEST_SDP))
+            .await
+            .expect("INVITE 超时")
+            .unwrap();
+        let resp = client.send_message_in_dialog(&dialog, "hello", "").await.unwrap();
+        assert_eq!(resp.status_code, rsip::StatusCode::OK);
+        let mut seen = Vec::new();
+        while let Ok(method) = methods.try_recv() {
+            seen.push(method);
+        }
+        assert!(seen.contains(&rsip::Method::Message), "{:?}", seen);
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_auto_register_refreshes() {
+        let ip = test_ip();
+        let (registrar, mut auth_headers) = spawn_registrar_stub(ip).await;
+        let client = Arc::new(SipClient::new(test_config(registrar)).await.unwrap());
+
+        // 授予 2 秒，约 1 秒后应再次注册
+        let mut status = client.start_auto_register(2);
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            status.wait_for(|s| matches!(s, RegistrationStatus::Registered { expires: 2 })),
+        )
+        .await
+        .expect("首次注册超时")
+        .unwrap();
+
+        let mut authorized = 0;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while authorized < 2 {
+                if auth_headers.recv().await.unwrap().is_some() {
+                    authorized += 1;
+                }
+            }
+        })
+        .await
+        .expect("未收到刷新 REGISTER");
+
+        client.shutdown().await;
+        while auth_headers.try_recv().is_ok() {}
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(auth_headers.try_recv().is_err(), "shutdown 后不应继续注册");
+    }
+
+    #[tokio::test]
+    async fn test_register_uses_configured_expires() {
+        let ip = test_ip();
+        for (mode, expected) in [(ExpiresMode::Header, Some(120)), (ExpiresMode::ContactParam, None)] {
+            let (registrar, mut requested) = spawn_min_expires_registrar(ip, 0).await;
+            let mut config = test_config(registrar);
+            config.register_expires = 120;
+            config.expires_mode = mode;
+            let client = SipClient::new(config).await.unwrap();
+
+            tokio::time::timeout(Duration::from_secs(5), client.register())
+                .await
+                .expect("注册超时")
+                .unwrap();
+            // Contact 参数模式下不携带 Expires 头
+            assert_eq!(requested.recv().await.unwrap(), expected);
+
+            for absurd in [0, crate::config::MAX_REGISTER_EXPIRES + 1] {
+                assert!(matches!(
+                    client.register_with_expires(absurd).await,
+                    Err(CallError::InvalidConfig { .. })
+                ));
+            }
+            assert!(requested.try_recv().is_err());
+            client.shutdown().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_times_out_and_cancels() {
+        let ip = test_ip();
+        // 只绑定不应答的注册服务器
+        let silent = UdpSocket::bind((ip, 0)).await.unwrap();
+        let mut config = test_config(silent.local_addr().unwrap());
+        config.register_timeout = Duration::from_millis(300);
+        let client = SipClient::new(config).await.unwrap();
+
+        let started = Instant::now();
+        let result = tokio::time::timeout(Duration::from_secs(5), client.register())
+            .await
+            .expect("注册超时未生效");
+        assert!(matches!(result, Err(CallError::NetworkTimeout { duration: 300 })), "{:?}", result);
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        assert!(matches!(
+            *client.registration_status().borrow(),
+            RegistrationStatus::Failed(_)
+        ));
+
+        // 关闭客户端会中止等待中的注册
+        let (result, _) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(client.register(), async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                client.shutdown().await;
+            })
+        })
+        .await
+        .expect("关闭后注册仍在等待");
+        assert!(matches!(result, Err(CallError::NotConnected)), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_and_unregister_reuse_binding() {
+        let ip = test_ip();
+        let (registrar, mut requests) = spawn_recording_registrar(ip).await;
+        let mut config = test_config(registrar);
+        config.register_expires = 300;
+        let client = SipClient::new(config).await.unwrap();
+        assert!(matches!(client.refresh().await, Err(CallError::InvalidConfig { .. })));
+
+        let limit = Duration::from_secs(5);
+        tokio::time::timeout(limit, client.register()).await.unwrap().unwrap();
+        tokio::time::timeout(limit, client.refresh()).await.unwrap().unwrap();
+        tokio::time::timeout(limit, client.unregister()).await.unwrap().unwrap();
+
+        let summary = |req: rsip::Request| {
+            use rsip::prelude::ToTypedHeader;
+            let cseq = req.cseq_header().unwrap().typed().unwrap().seq;
+            let from_tag = req.from_header().unwrap().tag().unwrap().unwrap().to_string();
+            let expires = req.expires_header().map(|h| h.value().to_string());
+            (req.call_id_header().unwrap().value().to_string(), from_tag, cseq, expires)
+        };
+        let (call_id, tag, first, expires) = summary(requests.recv().await.unwrap());
+        assert_eq!(expires.as_deref(), Some("300"));
+        let refresh = summary(requests.recv().await.unwrap());
+        assert_eq!(refresh, (call_id.clone(), tag.clone(), first + 1, Some("300".to_string())));
+        let unregister = summary(requests.recv().await.unwrap());
+        assert_eq!(unregister, (call_id, tag, first + 2, Some("0".to_string())));
+
+        // 注销后绑定清除，不能再刷新
+        assert!(matches!(client.refresh().await, Err(CallError::InvalidConfig { .. })));
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_custom_contact_uri() {
+        use rsip::prelude::ToTypedHeader;
+
+        let ip = test_ip();
+        let (registrar, mut registers) = spawn_recording_registrar(ip).await;
+        let fixed: rsip::Uri = "sip:alice@203.0.113.7:5070".try_into().unwrap();
+        let mut config = test_config(registrar);
+        config.contact_uri = Some(rsip::Uri {
+            scheme: Some(rsip::Scheme::Other("tel".into())),
+            ..fixed.clone()
+        });
+        assert!(SipClient::new(config).await.is_err());
+
+        let mut config = test_config(registrar);
+        config.contact_uri = Some(fixed.clone());
+        let client = SipClient::new(config).await.unwrap();
+        let limit = Duration::from_secs(5);
+        tokio::time::timeout(limit, client.register()).await.unwrap().unwrap();
+        let contact = registers.recv().await.unwrap().contact_header().unwrap().typed().unwrap();
+        assert_eq!(contact.uri, fixed);
+        client.shutdown().await;
+
+        let (uas, mut invites) = spawn_invite_stub(ip, vec![(rsip::StatusCode::OK, vec![])]).await;
+        let mut config = test_config(uas);
+        config.contact_uri = Some(fixed.clone());
+        let client = SipClient::new(config).await.unwrap();
+        tokio::time::timeout(limit, client.make_call("bob", TEST_SDP)).await.unwrap().unwrap();
+        let contact = invites.recv().await.unwrap().contact_header().unwrap().typed().unwrap();
+        assert_eq!(contact.uri, fixed);
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_register_retries_with_min_expires() {
+        let ip = test_ip();
+        let (registrar, mut requested) = spawn_min_expires_registrar(ip, 1800).await;
+        let client = SipClient::new(test_config(registrar)).await.unwrap();
+
+        let response = tokio::time::timeout(Duration::from_secs(5), client.register_with_expires(60))
+            .await
+            .expect("注册超时")
+            .unwrap();
+        assert_eq!(response.status_code, rsip::StatusCode::OK);
+        assert_eq!(requested.recv().await.unwrap(), Some(60));
+        assert_eq!(requested.recv().await.unwrap(), Some(1800));
+        assert_eq!(client.status().registration_expires, Some(1800));
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_transfer_waits_for_final_notify() {
+        let ip = test_ip();
+        for (final_status, accepted) in [(200, true), (486, false)] {
+            let stub_addr = spawn_refer_stub(ip, final_status).await;
+            let client = SipClient::new(test_config(stub_addr)).await.unwrap();
+            let (dialog, _) = tokio::time::timeout(
+                Duration::from_secs(5),
+                client.make_call("bob", TEST_SDP),
+            )
+            .await
+            .expect("INVITE 超时")
+            .unwrap();
+
+            let result = client.transfer(&dialog, "carol").await;
+            if accepted {
+                result.unwrap();
+            } else {
+                assert!(matches!(result, Err(CallError::CallRejected { code: 486, .. })));
+            }
+            client.shutdown().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_options_keepalive() {
+        let ip = test_ip();
+        let (uas_addr, mut methods) = spawn_uas_stub(ip).await;
+        let client = Arc::new(SipClient::new(test_config(uas_addr)).await.unwrap());
+        let resp = client.send_options(None).await.unwrap();
+        assert_eq!(resp.status_code, rsip::StatusCode::OK);
+        assert_eq!(methods.recv().await, Some(rsip::Method::Options));
+        assert_eq!(client.status().reachable, None);
+
+        client.start_keepalive(Duration::from_millis(200));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(client.status().reachable, Some(true));
+        client.stop_keepalive();
+        assert_eq!(client.status().reachable, None);
+        client.shutdown().await;
+
+        // 不回应任何请求的服务器
+        let silent = UdpSocket::bind((ip, 0)).await.unwrap();
+        let client = Arc::new(SipClient::new(test_config(silent.local_addr().unwrap())).await.unwrap());
+        client.start_keepalive(Duration::from_millis(200));
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(!client.is_reachable());
+        assert!(matches!(
+            client.make_call("bob", TEST_SDP).await,
+            Err(CallError::NotConnected)
+        ));
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_make_call_timeout_sends_cancel() {
+        let ip = test_ip();
+        // 不回应任何请求的服务器
+        let silent = UdpSocket::bind((ip, 0)).await.unwrap();
+        let client = SipClient::new(test_config(silent.local_addr().unwrap()))
+            .await
+            .unwrap();
+
+        let options = CallOptions::default().with_timeout(Duration::from_millis(300));
+        let result = client.make_call_with_options("bob", TEST_SDP, &options).await;
+        assert!(matches!(
+            result,
+            Err(CallError::NetworkTimeout { duration: 300 })
+        ));
+
+        let mut buf = vec![0u8; 4096];
+        let cancel = tokio::time::timeout(Duration::from_secs(3), async {
+            loop {
+                let (len, _) = silent.recv_from(&mut buf).await.unwrap();
+                if let Ok(rsip::SipMessage::Request(req)) = rsip::SipMessage::try_from(&buf[..len]) {
+                    if req.method == rsip::Method::Cancel {
+                        break req;
+                    }
+                }
+            }
+        })
+        .await
+        .expect("超时后未发送 CANCEL");
+        assert_eq!(cancel.uri.to_string(), format!("sip:bob@{}", silent.local_addr().unwrap()));
+        assert!(client.pending_calls().is_empty());
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_retry_call_only_on_recoverable_errors() {
+        let policy = RetryPolicy::default()
+            .with_max_attempts(3)
+            .with_backoff(Backoff::Fixed(FixedBackoff {
+                delay: Duration::from_millis(1),
+                jitter: 0.0,
+            }));
+
+        // NetworkTimeout 触发重试，第三次成功
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let result = retry_call(&policy, || async {
+            match attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                0 | 1 => Err(CallError::network_timeout(100)),
+                n => Ok(n),
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 2);
+
+        // 次数用尽时返回最后一次的错误
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let result: CallResult<()> = retry_call(&policy, || async {
+            attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Err(CallError::network_timeout(100))
+        })
+        .await;
+        assert!(matches!(result, Err(CallError::NetworkTimeout { .. })));
+        assert_eq!(attempts.into_inner(), 3);
+
+        // CallRejected 与认证失败不重试
+        for error in [
+            CallError::CallRejected { code: 486, phrase: "Busy Here".into() },
+            CallError::authentication_failed("403"),
+        ] {
+            let attempts = std::sync::atomic::AtomicU32::new(0);
+            let mut error = Some(error);
+            let result: CallResult<()> = retry_call(&policy, || {
+                attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let error = error.take().unwrap();
+                async move { Err(error) }
+            })
+            .await;
+            assert!(result.is_err());
+            assert_eq!(attempts.into_inner(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_make_call_with_retry_reinvites_after_timeout() {
+        let ip = test_ip();
+        let silent = UdpSocket::bind((ip, 0)).await.unwrap();
+        let client = SipClient::new(test_config(silent.local_addr().unwrap()))
+            .await
+            .unwrap();
+        let policy = RetryPolicy::default()
+            .with_max_attempts(2)
+            .with_backoff(Backoff::Fixed(FixedBackoff {
+                delay: Duration::from_millis(10),
+                jitter: 0.0,
+            }));
+        let options = CallOptions::default()
+            .with_retry(policy)
+            .with_timeout(Duration::from_millis(200));
+        let result = client.make_call_with_options("bob", TEST_SDP, &options).await;
+        assert!(matches!(result, Err(CallError::NetworkTimeout { .. })));
+
+        // 每次重试都是新的 INVITE（不同 Call-ID）
+        let mut call_ids = std::collections::HashSet::new();
+        let mut buf = vec![0u8; 4096];
+        while let Ok(Ok((len, _))) =
+            tokio::time::timeout(Duration::from_millis(200), silent.recv_from(&mut buf)).await
+        {
+            if let Ok(rsip::SipMessage::Request(req)) = rsip::SipMessage::try_from(&buf[..len]) {
+                if req.method == rsip::Method::Invite {
+                    call_ids.insert(req.call_id_header().unwrap().value().to_string());
+                }
+            }
+        }
+        assert_eq!(call_ids.len(), 2);
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_transport_protocol_follows_outbound_proxy() {
+        let ip = test_ip();
+        let (uas_addr, _methods) = spawn_uas_stub(ip).await;
+        let client = SipClient::new(test_config(uas_addr)).await.unwrap();
+        assert_eq!(client.transport_protocol(), rsip::transport::Transport::Udp);
+        assert_eq!(client.connection_target(), uas_addr.to_string());
+        client.shutdown().await;
+
+        // Outbound 代理的 transport 覆盖服务器 URI
+        let listener = tokio::net::TcpListener::bind((ip, 0)).await.unwrap();
+        let proxy = listener.local_addr().unwrap();
+        let mut config = test_config(uas_addr);
+        config.outbound_proxy = Some(format!("sip:{};transport=tcp", proxy).as_str().try_into().unwrap());
+        let client = SipClient::new(config).await.unwrap();
+        assert_eq!(client.transport_protocol(), rsip::transport::Transport::Tcp);
+        assert_eq!(client.connection_target(), proxy.to_string());
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_local_bind_addr() {
+        let ip = test_ip();
+        let (uas_addr, _methods) = spawn_uas_stub(ip).await;
+
+        // 固定 UDP 本地端口
+        let port = UdpSocket::bind((ip, 0)).await.unwrap().local_addr().unwrap().port();
+        let mut config = test_config(uas_addr);
+        config.local_bind_addr = Some(SocketAddr::new(ip, port));
+        let client = SipClient::new(config).await.unwrap();
+        assert_eq!(client.local_addr(), Some(SocketAddr::new(ip, port)));
+
+        // 端口被占用
+        let mut config = test_config(uas_addr);
+        config.local_bind_addr = Some(SocketAddr::new(ip, port));
+        let err = SipClient::new(config).await.err().unwrap();
+        assert!(matches!(
+            err.downcast_ref::<CallError>(),
+            Some(CallError::AddressInUse { .. })
+        ));
+        client.shutdown().await;
+
+        // TCP 同样从固定的本地端口发起连接
+        let listener = tokio::net::TcpListener::bind((ip, 0)).await.unwrap();
+        let port = std::net::TcpListener::bind((ip, 0)).unwrap().local_addr().unwrap().port();
+        let mut config = test_config(listener.local_addr().unwrap());
+        config.server = format!("sip:{};transport=tcp", listener.local_addr().unwrap())
+            .as_str()
+            .try_into()
+            .unwrap();
+        config.local_bind_addr = Some(SocketAddr::new(ip, port));
+        let client = SipClient::new(config).await.unwrap();
+        let (_, peer) = listener.accept().await.unwrap();
+        assert_eq!(peer, SocketAddr::new(ip, port));
+        assert_eq!(client.local_addr(), Some(peer));
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_tcp_connection_reused_across_transactions() {
+        let ip = test_ip();
+        let (registrar, mut connections) = spawn_tcp_registrar(ip, false).await;
+        let mut config = test_config(registrar);
+        config.server = format!("sip:{};transport=tcp", registrar).as_str().try_into().unwrap();
+        let client = SipClient::new(config).await.unwrap();
+
+        client.register().await.unwrap();
+        client.send_options(None).await.unwrap();
+        client.register().await.unwrap();
+        client.unregister().await.unwrap();
+
+        // 所有事务共用启动时建立的连接
+        assert_eq!(connections.recv().await, client.local_addr());
+        assert!(connections.try_recv().is_err());
+        client.shutdown().await;
+
+        // 调试选项：每个事务重新建立连接
+        let mut config = test_config(registrar);
+        config.server = format!("sip:{};transport=tcp", registrar).as_str().try_into().unwrap();
+        config.tcp_fresh_connection = true;
+        let client = SipClient::new(config).await.unwrap();
+        client.register().await.unwrap();
+        client.send_options(None).await.unwrap();
+        let peers: Vec<_> = std::iter::from_fn(|| connections.try_recv().ok()).collect();
+        assert_eq!(peers.len(), 3);
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_tcp_reconnects_after_server_close() {
+        let ip = test_ip();
+        // 服务器在每次应答后关闭连接：下一个事务重新建立连接而不是写入已关闭的连接
+        let (registrar, mut connections) = spawn_tcp_registrar(ip, true).await;
+        let mut config = test_config(registrar);
+        config.server = format!("sip:{};transport=tcp", registrar).as_str().try_into().unwrap();
+        config.register_timeout = Duration::from_secs(5);
+        let client = SipClient::new(config).await.unwrap();
+
+        client.register().await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), client.register())
+            .await
+            .expect("连接关闭后注册未完成")
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), client.send_options(None))
+            .await
+            .expect("连接关闭后 OPTIONS 未完成")
+            .unwrap();
+        assert_eq!(connections.recv().await, client.local_addr());
+        let peers: Vec<_> = std::iter::from_fn(|| connections.try_recv().ok()).collect();
+        assert_eq!(peers.len(), 2);
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_stun_mapped_contact() {
+        let ip = test_ip();
+        if !ip.is_ipv4() {
+            return;
+        }
+        let stun = crate::utils::spawn_stun_stub(ip).await;
+        let (uas_addr, _methods) = spawn_uas_stub(ip).await;
+        let mut config = test_config(uas_addr);
+        config.rport = false;
+        config.stun_server = Some(stun.to_string());
+        let client = SipClient::new(config).await.unwrap();
+
+        // STUN 查询与 SIP 传输使用同一端口
+        let local = client.local_addr().unwrap();
+        assert_eq!(client.public_address(), Some(local.into()));
+        assert_eq!(client.public_ip(), Some(ip));
+        assert_eq!(client.status().public_address, Some(local.to_string()));
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_make_call_strict_maps_rejection() {
+        let ip = test_ip();
+        // INVITE 以 486 拒绝的桩服务器
+        let stub_addr = spawn_stub(ip, |req, _, _| {
+            let status = match req.method {
+                rsip::Method::Ack => return vec![],
+                rsip::Method::Invite => rsip::StatusCode::BusyHere,
+                _ => rsip::StatusCode::OK,
+            };
+            vec![StubReply::now(stub_response(&req, status, vec![]))]
+        })
+        .await;
+
+        let client = SipClient::new(test_config(stub_addr)).await.unwrap();
+        let (_, response) = client.make_call("bob", TEST_SDP).await.unwrap();
+        assert_eq!(response.unwrap().status_code, rsip::StatusCode::BusyHere);
+        let strict = CallOptions::default().with_strict(true);
+        let err = client.make_call_with_options("bob", TEST_SDP, &strict).await.err().unwrap();
+        assert!(matches!(
+            &err,
+            CallError::CallRejected { code: 486, phrase } if phrase == "Busy Here"
+        ));
+        assert_eq!(client.status().last_error, Some(err.to_string()));
+        client.shutdown().await;
+
+        let (uas_addr, _methods) = spawn_uas_stub(ip).await;
+        let client = SipClient::new(test_config(uas_addr)).await.unwrap();
+        let (_, response) = client.make_call_with_options("bob", TEST_SDP, &strict).await.unwrap();
+        assert_eq!(response.unwrap().status_code, rsip::StatusCode::OK);
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_make_call_with_multipart_body() {
+        let ip = test_ip();
+        let (addr, mut invites) = spawn_invite_stub(ip, vec![(rsip::StatusCode::OK, vec![])]).await;
+        let client = SipClient::new(test_config(addr)).await.unwrap();
+        let body = MultipartBody::new()
+            .with_boundary("test-boundary")
+            .with_part(crate::sip_body::BodyPart::sdp(TEST_SDP))
+            .with_part(crate::sip_body::BodyPart::new("application/pidf+xml", "<presence/>"));
+        let options = CallOptions::default().with_multipart(body);
+        tokio::time::timeout(Duration::from_secs(5), client.make_call_with_options("bob", "", &options))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let invite = invites.recv().await.unwrap();
+        let content_type = invite
+            .headers
+            .iter()
+            .find_map(|h| match h {
+                rsip::Header::ContentType(ct) => Some(ct.value().to_string()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(content_type, "multipart/mixed;boundary=test-boundary");
+        let parsed = MultipartBody::parse(&content_type, &invite.body).unwrap();
+        assert_eq!(parsed.sdp().as_deref(), Some(TEST_SDP));
+        assert!(parsed.find("application/pidf+xml").is_some());
+        client.shutdown().await;
+    }
+
+    /// 振铃桩服务器：INVITE 以 180 应答；收到 CANCEL 时以 200 应答 CANCEL，
+    /// 再以 487 结束 INVITE。`answer_first` 为真时模拟交错：先以 200 OK 接听再处理 CANCEL
+    async fn spawn_ringing_stub(
+        ip: std::net::IpAddr,
+        answer_first: bool,
+    ) -> (SocketAddr, mpsc::UnboundedReceiver<rsip::Method>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut invite = None;
+        let addr = spawn_stub(ip, move |req, addr, _| {
+            let _ = tx.send(req.method);
+            let responses = match req.method {
+                rsip::Method::Ack => vec![],
+                rsip::Method::Invite => {
+                    invite = Some(req.clone());
+                    vec![stub_response(&req, rsip::StatusCode::Ringing, vec![stub_contact(addr)])]
+                }
+                rsip::Method::Cancel => {
+                    let invite = invite.take().unwrap();
+                    let invite_final = if answer_first {
+                        rsip::StatusCode::OK
+                    } else {
+                        rsip::StatusCode::RequestTerminated
+                    };
+                    let mut responses = vec![stub_response(&invite, invite_final, vec![stub_contact(addr)])];
+                    responses.insert(
+                        if answer_first { 1 } else { 0 },
+                        stub_response(&req, rsip::StatusCode::OK, vec![]),
+                    );
+                    responses
+                }
+                _ => vec![stub_response(&req, rsip::StatusCode::OK, vec![])],
+            };
+            responses.into_iter().map(StubReply::now).collect()
+        })
+        .await;
+        (addr, rx)
+    }
+
+    #[tokio::test]
+    async fn test_cancel_unanswered_call() {
+        let ip = test_ip();
+        for answer_first in [false, true] {
+            let (stub_addr, mut methods) = spawn_ringing_stub(ip, answer_first).await;
+            let client = Arc::new(SipClient::new(test_config(stub_addr)).await.unwrap());
+            let caller = client.clone();
+            let call = tokio::spawn(async move { caller.make_call("bob", TEST_SDP).await });
+
+            let dialog = tokio::time::timeout(Duration::from_secs(5), async {
+                loop {
+                    let early = client
+                        .pending_calls()
+                        .into_iter()
+                        .find(|d| matches!(d.state(), DialogState::Early(_, _)));
+                    if let Some(dialog) = early {
+                        break dialog;
+                    }
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            })
+            .await
+            .expect("未进入振铃状态");
+
+            tokio::time::timeout(Duration::from_secs(5), client.cancel(&dialog))
+                .await
+                .expect("CANCEL 超时")
+                .unwrap();
+            let (_, response) = call.await.unwrap().unwrap();
+            let expected = if answer_first {
+                rsip::StatusCode::OK
+            } else {
+                rsip::StatusCode::RequestTerminated
+            };
+            assert_eq!(response.unwrap().status_code, expected);
+            assert!(matches!(dialog.state(), DialogState::Terminated(_, _)));
+
+            let mut seen = Vec::new();
+            while let Ok(method) = methods.try_recv() {
+                seen.push(method);
+            }
+            assert!(seen.contains(&rsip::Method::Cancel));
+            assert_eq!(seen.contains(&rsip::Method::Bye), answer_first);
+            assert!(client.pending_calls().is_empty());
+            client.shutdown().await;
+        }
+    }
+
+    /// 从 UAC 套接字读取下一个 SIP 响应
+    async fn recv_response(socket: &UdpSocket) -> rsip::Response {
+        let mut buf = vec![0u8; 4096];
+        loop {
+            let (len, _) = tokio::time::timeout(Duration::from_secs(5), socket.recv_from(&mut buf))
+                .await
+                .expect("等待响应超时")
+                .unwrap();
+            if let Ok(rsip::SipMessage::Response(resp)) = rsip::SipMessage::try_from(&buf[..len]) {
+                return resp;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_incoming_invite_answer_and_ack() {
+        let ip = test_ip();
+        let (uas_addr, _methods) = spawn_uas_stub(ip).await;
+        let client = SipClient::new(test_config(uas_addr)).await.unwrap();
+        let (call_tx, mut call_rx) = mpsc::unbounded_channel();
+        client.on_incoming_call(move |call| {
+            let _ = call_tx.send(call);
+        });
+        let client_addr: SocketAddr = client.status().local_address.unwrap().parse().unwrap();
+
+        let uac = UdpSocket::bind((ip, 0)).await.unwrap();
+        let uac_addr = uac.local_addr().unwrap();
+        let invite = incoming_invite(client_addr, uac_addr, "incoming-test", "v=0\r\n");
+        uac.send_to(invite.as_bytes(), client_addr).await.unwrap();
+
+        let call = tokio::time::timeout(Duration::from_secs(5), call_rx.recv())
+            .await
+            .expect("未收到呼入回调")
+            .unwrap();
+        assert_eq!(call.offer_sdp(), "v=0\r\n");
+
+        // 应答前应先收到 180 Ringing
+        while recv_response(&uac).await.status_code != rsip::StatusCode::Ringing {}
+
+        // 应答在收到 ACK 后才返回
+        let call = Arc::new(call);
+        let answering = tokio::spawn({
+            let call = call.clone();
+            async move { call.answer_with_media("v=0\r\ns=answer\r\n").await }
+        });
+        let ok = loop {
+            let resp = recv_response(&uac).await;
+            if resp.status_code == rsip::StatusCode::OK {
+                break resp;
+            }
+        };
+        assert!(String::from_utf8_lossy(&ok.body).contains("s=answer"));
+
+        let to = ok.to_header().unwrap().value().to_string();
+        let ack = format!(
+            "ACK sip:alice@{client_addr} SIP/2.0\r\n\
+             Via: SIP/2.0/UDP {uac_addr};branch=z9hG4bKack1\r\n\
+             From: <sip:bob@{uac_addr}>;tag=incoming-test\r\n\
+             To: {to}\r\n\
+             Call-ID: incoming-test@{uac_addr}\r\n\
+             CSeq: 1 ACK\r\n\
+             Max-Forwards: 70\r\n\
+             Content-Length: 0\r\n\r\n"
+        );
+        assert!(!answering.is_finished());
+        uac.send_to(ack.as_bytes(), client_addr).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), answering)
+            .await
+            .expect("收到 ACK 后应答应返回")
+            .unwrap()
+            .unwrap();
+        assert!(call.dialog().state().is_confirmed());
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_incoming_invite_reject_with_retry_after() {
+        let ip = test_ip();
+        let (uas_addr, _methods) = spawn_uas_stub(ip).await;
+        let client = SipClient::new(test_config(uas_addr)).await.unwrap();
+        let (call_tx, mut call_rx) = mpsc::unbounded_channel();
+        client.on_incoming_call(move |call| {
+            let _ = call_tx.send(call);
+        });
+        let client_addr: SocketAddr = client.status().local_address.unwrap().parse().unwrap();
+
+        let uac = UdpSocket::bind((ip, 0)).await.unwrap();
+        let uac_addr = uac.local_addr().unwrap();
+        let invite = incoming_invite(client_addr, uac_addr, "reject-test", "");
+        uac.send_to(invite.as_bytes(), client_addr).await.unwrap();
+        let call = tokio::time::timeout(Duration::from_secs(5), call_rx.recv())
+            .await
+            .expect("未收到呼入回调")
+            .unwrap();
+
+        // 非 3xx-6xx 状态码被拒绝，不发送任何响应
+        assert!(matches!(
+            call.reject(rsip::StatusCode::OK, None).await,
+            Err(CallError::InvalidConfig { .. })
+        ));
+        call.reject(rsip::StatusCode::BusyHere, Some(30)).await.unwrap();
+        let busy = loop {
+            let resp = recv_response(&uac).await;
+            if resp.status_code.code() >= 300 {
+                break resp;
+            }
+        };
+        assert_eq!(busy.status_code, rsip::StatusCode::BusyHere);
+        assert!(busy
+            .headers
+            .iter()
+            .any(|h| matches!(h, rsip::Header::RetryAfter(v) if v.value() == "30")));
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_incoming_invite_without_handler_rejected() {
+        let ip = test_ip();
+        let (uas_addr, _methods) = spawn_uas_stub(ip).await;
+        let client = SipClient::new(test_config(uas_addr)).await.unwrap();
+        let client_addr: SocketAddr = client.status().local_address.unwrap().parse().unwrap();
+
+        let uac = UdpSocket::bind((ip, 0)).await.unwrap();
+        let uac_addr = uac.local_addr().unwrap();
+        let invite = incoming_invite(client_addr, uac_addr, "rejected-test", "");
+        uac.send_to(invite.as_bytes(), client_addr).await.unwrap();
+
+        let resp = loop {
+            let resp = recv_response(&uac).await;
+            if resp.status_code != rsip::StatusCode::Trying {
+                break resp;
+            }
+        };
+        assert_eq!(resp.status_code, rsip::StatusCode::TemporarilyUnavailable);
+        client.shutdown().await;
+    }
+}
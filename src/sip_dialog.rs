@@ -1,30 +1,177 @@
 /// SIP 对话处理模块
 ///
 /// 处理 SIP 对话状态变化和会话管理
-use rsipstack::dialog::{client_dialog::ClientInviteDialog, dialog::DialogState};
+use crate::dtmf::parse_dtmf_relay;
+use crate::sip_body::response_sdp;
+use crate::sip_headers::expand_compact_header;
+use rsip::prelude::{HeadersExt, ToTypedHeader, UntypedHeader};
+use rsipstack::dialog::dialog::{Dialog, DialogState};
+use rsipstack::dialog::{client_dialog::ClientInviteDialog, DialogId};
 use std::sync::Arc;
-use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, info};
 
+/// 获取对话当前的远端目标（对端 Contact URI）
+///
+/// BYE、re-INVITE、INFO 等对话内请求以该地址为 Request-URI；若 2xx 携带 Record-Route，
+/// rsipstack 会记录路由集并将请求实际发往首个 Route 跳（RFC 3261 §12.2.1.1）
+pub fn remote_target(dialog: &ClientInviteDialog) -> Option<rsip::Uri> {
+    Dialog::ClientInvite(dialog.clone()).remote_contact()
+}
+
+/// 设置对话的远端目标
+pub fn set_remote_target(dialog: &ClientInviteDialog, contact: rsip::headers::Contact) -> bool {
+    match contact.typed() {
+        Ok(typed) => {
+            info!("🔀 更新对话远端目标: {}", typed.uri);
+            Dialog::ClientInvite(dialog.clone()).set_remote_target(typed.uri, Some(contact));
+            true
+        }
+        Err(e) => {
+            debug!("无法解析 Contact 头，保留原远端目标: {}", e);
+            false
+        }
+    }
+}
+
+/// 根据对端消息中的 Contact 头刷新远端目标
+///
+/// 对端在 re-INVITE / UPDATE 或其 2xx 响应中更换 Contact 时调用（如移动端切换网络）
+///
+/// # 返回
+/// 消息携带可解析的 Contact 且已更新时返回 `true`
+pub fn update_remote_target(dialog: &ClientInviteDialog, headers: &rsip::Headers) -> bool {
+    match contact_header(headers) {
+        Some(contact) if remote_target(dialog).as_ref() != contact_uri(&contact).as_ref() => {
+            set_remote_target(dialog, contact)
+        }
+        _ => false,
+    }
+}
+
+fn contact_header(headers: &rsip::Headers) -> Option<rsip::headers::Contact> {
+    headers
+        .iter()
+        .cloned()
+        .map(expand_compact_header)
+        .find_map(|h| match h {
+            rsip::Header::Contact(c) => Some(c),
+            _ => None,
+        })
+}
+
+fn contact_uri(contact: &rsip::headers::Contact) -> Option<rsip::Uri> {
+    contact.typed().ok().map(|c| c.uri)
+}
+
+/// 转接进度（来自 REFER 隐式订阅的 NOTIFY，RFC 3515）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferProgress {
+    /// sipfrag 中的状态码（如 100 Trying、200 OK）
+    pub status: rsip::StatusCode,
+    /// 订阅是否已终止（`Subscription-State: terminated`）
+    pub terminated: bool,
+}
+
+impl ReferProgress {
+    /// sipfrag 状态是否为最终响应
+    pub fn is_final(&self) -> bool {
+        self.status.kind() != rsip::StatusCodeKind::Provisional
+    }
+}
+
+/// 解析 `Event: refer` 的 NOTIFY，非转接通知返回 `None`
+pub fn parse_refer_notify(request: &rsip::Request) -> Option<ReferProgress> {
+    let header_value = |name: &str| {
+        request.headers.iter().find_map(|h| match h {
+            rsip::Header::Event(event) if name == "event" => Some(event.value().to_string()),
+            rsip::Header::SubscriptionState(state) if name == "subscription-state" => {
+                Some(state.value().to_string())
+            }
+            rsip::Header::Other(n, v) if n.eq_ignore_ascii_case(name) => Some(v.clone()),
+            _ => None,
+        })
+        .map(|v| v.trim().to_ascii_lowercase())
+    };
+
+    let event = header_value("event")?;
+    // Event 可带 id 参数，如 "refer;id=93809824"
+    if event.split(';').next().map(str::trim) != Some("refer") {
+        return None;
+    }
+    let terminated = header_value("subscription-state")
+        .map(|s| s.starts_with("terminated"))
+        .unwrap_or(false);
+
+    let body = String::from_utf8_lossy(&request.body);
+    let status_line = body.lines().find(|l| l.trim_start().starts_with("SIP/2.0"))?;
+    let code: u16 = status_line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(ReferProgress {
+        status: code.into(),
+        terminated,
+    })
+}
+
+/// 早期媒体：携带 SDP 的 1xx 响应（通常为 183 Session Progress），
+/// 对端在接听前通过它播放回铃音或 IVR 提示
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EarlyMedia {
+    /// 早期对话标识
+    pub dialog_id: DialogId,
+    /// 临时响应状态码
+    pub status: rsip::StatusCode,
+    /// 临时响应中的 SDP answer
+    pub sdp: String,
+}
+
+/// 从对话状态中提取早期媒体，非 `Early` 或不带 SDP 时返回 `None`
+pub fn early_media(state: &DialogState) -> Option<EarlyMedia> {
+    let DialogState::Early(id, resp) = state else {
+        return None;
+    };
+    Some(EarlyMedia {
+        dialog_id: id.clone(),
+        status: resp.status_code.clone(),
+        sdp: response_sdp(resp)?,
+    })
+}
+
+/// 判断对话内请求是否由对端发起
+///
+/// 本端发出的 re-INVITE 成功后同样会产生 `Updated` 状态，需要排除
+fn is_remote_request(request: &rsip::Request, id: &DialogId) -> bool {
+    request
+        .from_header()
+        .ok()
+        .and_then(|from| from.tag().ok().flatten())
+        .map(|tag| tag.value() == id.remote_tag)
+        .unwrap_or(false)
+}
+
 /// 处理对话状态变化
 ///
 /// 异步监听对话状态变化，处理振铃、确认、终止等事件
 ///
 /// # 参数
-/// - `_dialog`: 客户端邀请对话的 Arc 引用（当前未使用）
+/// - `dialog`: 客户端邀请对话的 Arc 引用，用于维护远端目标
 /// - `state_receiver`: 对话状态接收器
 /// - `rtp_cancel`: RTP 取消令牌，用于在对话终止时停止 RTP 流
+/// - `dtmf_sender`: 收到 DTMF 按键时写入的通道（如 `CallHandle::dtmf_sender()`）
 ///
 /// # 状态处理
 /// - `Confirmed`: 对话已确认，通话建立
 /// - `Terminated`: 对话已终止，通话结束
-/// - `Early`: 振铃中（180 Ringing）
+/// - `Early`: 振铃中（180 Ringing），携带 SDP 时为早期媒体（183 Session Progress）
+/// - `Updated`: 对端 re-INVITE / UPDATE，若 Contact 变化则更新远端目标
+/// - `Info`: 解析 `application/dtmf-relay` 按键并转发到 `dtmf_sender`
+/// - `Notify`: 记录 REFER 转接进度并以 200 OK 应答
 /// - 其他状态：仅记录日志
 pub async fn process_dialog(
-    _dialog: Arc<ClientInviteDialog>,
+    dialog: Arc<ClientInviteDialog>,
     mut state_receiver: UnboundedReceiver<DialogState>,
     rtp_cancel: CancellationToken,
+    dtmf_sender: Option<UnboundedSender<char>>,
 ) {
     while let Some(state) = state_receiver.recv().await {
         match &state {
@@ -37,7 +184,31 @@ pub async fn process_dialog(
                 break;
             }
             DialogState::Early(_, resp) => {
-                info!("📲 振铃中 (状态码: {})", resp.status_code);
+                if early_media(&state).is_some() {
+                    info!("📲 收到早期媒体 (状态码: {})", resp.status_code);
+                } else {
+                    info!("📲 振铃中 (状态码: {})", resp.status_code);
+                }
+            }
+            DialogState::Updated(id, request, _) if is_remote_request(request, id) => {
+                debug!("收到对端 {} 请求", request.method);
+                update_remote_target(&dialog, &request.headers);
+            }
+            DialogState::Info(_, request, handle) => {
+                let body = String::from_utf8_lossy(&request.body);
+                if let Some(digit) = parse_dtmf_relay(&body) {
+                    info!("☎️ 收到 DTMF 按键: {}", digit);
+                    if let Some(sender) = &dtmf_sender {
+                        let _ = sender.send(digit);
+                    }
+                }
+                let _ = handle.reply(rsip::StatusCode::OK).await;
+            }
+            DialogState::Notify(_, request, handle) => {
+                if let Some(progress) = parse_refer_notify(request) {
+                    info!("🔀 转接进度: {}", progress.status);
+                }
+                let _ = handle.reply(rsip::StatusCode::OK).await;
             }
             _ => {
                 debug!("对话状态变更");
@@ -53,6 +224,74 @@ mod tests {
     #[test]
     fn test_dialog_module_exists() {
         // 简单的编译时测试，确保模块可用
-        assert!(true);
+        let _ = process_dialog;
+    }
+
+    fn request(headers: Vec<rsip::Header>) -> rsip::Request {
+        rsip::Request {
+            method: rsip::Method::Invite,
+            uri: "sip:alice@192.168.1.10:5060".try_into().unwrap(),
+            version: rsip::Version::V2,
+            headers: headers.into(),
+            body: vec![],
+        }
+    }
+
+    #[test]
+    fn test_contact_header_uri() {
+        let headers: rsip::Headers =
+            vec![rsip::headers::Contact::new("<sip:bob@10.0.0.7:5062;transport=udp>").into()].into();
+        let contact = contact_header(&headers).unwrap();
+        assert_eq!(
+            contact_uri(&contact).unwrap().host_with_port.to_string(),
+            "10.0.0.7:5062"
+        );
+        assert!(contact_header(&rsip::Headers::default()).is_none());
+
+        let compact: rsip::Headers =
+            vec![rsip::Header::Other("m".into(), "<sip:bob@10.0.0.8>".into())].into();
+        assert!(contact_header(&compact).is_some());
+    }
+
+    #[test]
+    fn test_parse_refer_notify() {
+        let mut notify = request(vec![
+            rsip::headers::Event::new("refer;id=7").into(),
+            rsip::headers::SubscriptionState::new("active;expires=60").into(),
+        ]);
+        notify.method = rsip::Method::Notify;
+        notify.body = b"SIP/2.0 100 Trying\r\n".to_vec();
+        let progress = parse_refer_notify(&notify).unwrap();
+        assert_eq!(progress.status, rsip::StatusCode::Trying);
+        assert!(!progress.terminated && !progress.is_final());
+
+        notify.headers = vec![
+            rsip::Header::Other("Event".into(), "refer".into()),
+            rsip::Header::Other("Subscription-State".into(), "terminated;reason=noresource".into()),
+        ]
+        .into();
+        notify.body = b"SIP/2.0 486 Busy Here\r\n".to_vec();
+        let progress = parse_refer_notify(&notify).unwrap();
+        assert_eq!(progress.status.code(), 486);
+        assert!(progress.terminated && progress.is_final());
+
+        notify.headers = vec![rsip::headers::Event::new("presence").into()].into();
+        assert!(parse_refer_notify(&notify).is_none());
+    }
+
+    #[test]
+    fn test_is_remote_request() {
+        let id = DialogId {
+            call_id: "call-1".to_string(),
+            local_tag: "local".to_string(),
+            remote_tag: "remote".to_string(),
+        };
+        let from_remote =
+            request(vec![rsip::headers::From::new("<sip:bob@example.com>;tag=remote").into()]);
+        let from_local =
+            request(vec![rsip::headers::From::new("<sip:alice@example.com>;tag=local").into()]);
+        assert!(is_remote_request(&from_remote, &id));
+        assert!(!is_remote_request(&from_local, &id));
+        assert!(!is_remote_request(&request(vec![]), &id));
     }
 }
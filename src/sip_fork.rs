@@ -0,0 +1,313 @@
+/// 分叉应答处理模块
+///
+/// 代理将 INVITE 分叉到多个终端时，可能先后收到多个 To tag 不同的 2xx。
+/// 第一个 2xx 建立的对话胜出（即 `make_call` 返回的对话），其余 2xx 各自建立的对话
+/// 先 ACK 再立即 BYE，避免落选终端残留无人挂断的对话
+use rsip::prelude::{HeadersExt, ToTypedHeader, UntypedHeader};
+use rsip::{Header, Request, Response, SipMessage};
+use rsipstack::dialog::DialogId;
+use rsipstack::rsip_ext::destination_from_request;
+use rsipstack::transaction::endpoint::EndpointInnerRef;
+use rsipstack::transaction::key::{TransactionKey, TransactionRole};
+use rsipstack::transaction::transaction::Transaction;
+use rsipstack::transport::SipAddr;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn, Instrument};
+
+/// 被释放的分叉对话
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForkedDialog {
+    /// 胜出的对话
+    pub winner: DialogId,
+    /// 已 ACK 并发送 BYE 的落选对话
+    pub released: DialogId,
+}
+
+/// 单个呼叫的分叉状态
+#[derive(Debug, Default)]
+struct ForkedCall {
+    /// 第一个 2xx 的 To tag
+    winner_tag: Option<String>,
+    /// 已发送 BYE 的落选 To tag
+    released: HashSet<String>,
+    events: Option<mpsc::UnboundedSender<ForkedDialog>>,
+}
+
+/// 分叉跟踪表，键为 Call-ID
+///
+/// 消息检查器按到达顺序记录每个呼叫的胜出 tag，并把其余 2xx 交给 [`ForkTracker::spawn_handler`]
+/// 启动的后台任务处理
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ForkTracker {
+    calls: Arc<Mutex<HashMap<String, ForkedCall>>>,
+}
+
+impl ForkTracker {
+    /// 发送 INVITE 前登记呼叫
+    pub(crate) fn track(&self, call_id: &str, events: Option<mpsc::UnboundedSender<ForkedDialog>>) {
+        let call = ForkedCall {
+            events,
+            ..Default::default()
+        };
+        self.calls.lock().unwrap().insert(call_id.to_string(), call);
+    }
+
+    /// 停止跟踪呼叫
+    pub(crate) fn untrack(&self, call_id: &str) {
+        self.calls.lock().unwrap().remove(call_id);
+    }
+
+    /// 检查收到的响应，返回是否需要交给后台任务（ACK，落选时再 BYE）
+    ///
+    /// 已登记呼叫的第一个 INVITE 2xx 记为胜出并交由 rsipstack 正常处理
+    pub(crate) fn on_response(&self, resp: &Response) -> bool {
+        if resp.status_code.kind() != rsip::StatusCodeKind::Successful || !is_invite_response(resp) {
+            return false;
+        }
+        let (Ok(call_id), Some(tag)) = (resp.call_id_header(), to_tag(resp)) else {
+            return false;
+        };
+        let mut calls = self.calls.lock().unwrap();
+        let Some(call) = calls.get_mut(call_id.value()) else {
+            return false;
+        };
+        match &call.winner_tag {
+            None => {
+                call.winner_tag = Some(tag);
+                false
+            }
+            Some(_) => true,
+        }
+    }
+
+    /// 呼叫接通后接管后续 2xx
+    ///
+    /// rsipstack 在 INVITE 事务结束后缓存 ACK，并对之后到达的任意 2xx 原样重发（落选终端收不到
+    /// 正确的 ACK，也不会收到 BYE）。这里清除胜出对话的缓存 ACK，使后续 2xx 经过消息检查器，
+    /// 由本模块应答；`window` 后停止跟踪
+    pub(crate) fn answered(&self, endpoint: &EndpointInnerRef, dialog_id: &DialogId, window: Duration) {
+        if let Ok(mut finished) = endpoint.finished_transactions.write() {
+            for message in finished.values_mut() {
+                let is_winner_ack = matches!(
+                    message,
+                    Some(SipMessage::Request(ack)) if ack.method == rsip::Method::Ack
+                        && ack.call_id_header().is_ok_and(|h| h.value() == dialog_id.call_id)
+                        && ack.to_header().ok().and_then(|h| h.tag().ok().flatten())
+                            .is_some_and(|tag| tag.value() == dialog_id.remote_tag)
+                );
+                if is_winner_ack {
+                    *message = None;
+                }
+            }
+        }
+
+        let tracker = self.clone();
+        let call_id = dialog_id.call_id.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+            tracker.untrack(&call_id);
+        });
+    }
+
+    /// 启动处理分叉 2xx 的后台任务
+    pub(crate) fn spawn_handler(
+        &self,
+        endpoint: EndpointInnerRef,
+        mut responses: mpsc::UnboundedReceiver<Response>,
+        cancel_token: CancellationToken,
+    ) {
+        let tracker = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let resp = tokio::select! {
+                    resp = responses.recv() => match resp {
+                        Some(resp) => resp,
+                        None => break,
+                    },
+                    _ = cancel_token.cancelled() => break,
+                };
+                let Some((winner, fork)) = tracker.classify(&resp) else {
+                    continue;
+                };
+                let endpoint = endpoint.clone();
+                let span = tracing::info_span!("fork", call_id = %winner.call_id);
+                tokio::spawn(release(endpoint, resp, winner, fork).instrument(span));
+            }
+        });
+    }
+
+    /// 返回胜出对话，以及首次出现的落选分叉（需要 BYE 时）
+    fn classify(&self, resp: &Response) -> Option<(DialogId, Option<ForkEvent>)> {
+        let id = DialogId::from_uac_response(resp).ok()?;
+        let mut calls = self.calls.lock().unwrap();
+        let call = calls.get_mut(&id.call_id)?;
+        let winner = DialogId {
+            remote_tag: call.winner_tag.clone()?,
+            ..id.clone()
+        };
+        let fork = (id.remote_tag != winner.remote_tag && call.released.insert(id.remote_tag.clone()))
+            .then(|| ForkEvent {
+                dialog: ForkedDialog {
+                    winner: winner.clone(),
+                    released: id,
+                },
+                events: call.events.clone(),
+            });
+        Some((winner, fork))
+    }
+}
+
+/// 需要 BYE 的落选分叉
+struct ForkEvent {
+    dialog: ForkedDialog,
+    events: Option<mpsc::UnboundedSender<ForkedDialog>>,
+}
+
+/// ACK 收到的 2xx，落选分叉首次出现时再发送 BYE
+async fn release(endpoint: EndpointInnerRef, resp: Response, winner: DialogId, fork: Option<ForkEvent>) {
+    if let Err(e) = send_ack(&endpoint, &resp).await {
+        warn!("ACK 分叉 2xx 失败: {}", e);
+    }
+    let Some(fork) = fork else {
+        debug!("重发 2xx 的 ACK: {}", winner);
+        return;
+    };
+    info!("🔀 INVITE 分叉: {} 胜出，释放 {}", winner, fork.dialog.released);
+    match send_bye(&endpoint, &resp).await {
+        Ok(status) => debug!("落选分叉 BYE 完成: {}", status),
+        Err(e) => warn!("落选分叉 BYE 失败: {}", e),
+    }
+    if let Some(events) = fork.events {
+        let _ = events.send(fork.dialog);
+    }
+}
+
+async fn send_ack(endpoint: &EndpointInnerRef, resp: &Response) -> rsipstack::Result<()> {
+    let ack = dialog_request(endpoint.get_via(None, None)?, resp, rsip::Method::Ack)?;
+    let target = request_target(&ack)?;
+    let (connection, addr) = endpoint.transport_layer.lookup(&target, None).await?;
+    connection.send(ack.into(), Some(&addr)).await
+}
+
+async fn send_bye(endpoint: &EndpointInnerRef, resp: &Response) -> rsipstack::Result<rsip::StatusCode> {
+    let bye = dialog_request(endpoint.get_via(None, None)?, resp, rsip::Method::Bye)?;
+    let target = request_target(&bye)?;
+    let key = TransactionKey::from_request(&bye, TransactionRole::Client)?;
+    let mut tx = Transaction::new_client(key, bye, endpoint.clone(), None);
+    tx.destination = Some(target);
+    tx.send().await?;
+    while let Some(msg) = tx.receive().await {
+        if let SipMessage::Response(resp) = msg {
+            if resp.status_code.kind() != rsip::StatusCodeKind::Provisional {
+                return Ok(resp.status_code);
+            }
+        }
+    }
+    Ok(rsip::StatusCode::RequestTimeout)
+}
+
+/// 按 2xx 建立的对话构造对话内请求：Request-URI 为其 Contact，路由集取自 Record-Route（反序）
+fn dialog_request(
+    via: rsip::typed::Via,
+    resp: &Response,
+    method: rsip::Method,
+) -> Result<Request, rsip::Error> {
+    let cseq = resp.cseq_header()?.typed()?;
+    let seq = if method == rsip::Method::Ack { cseq.seq } else { cseq.seq + 1 };
+    let uri = resp.contact_header()?.typed()?.uri;
+
+    let mut headers: Vec<Header> = vec![
+        Header::Via(via.into()),
+        resp.from_header()?.clone().into(),
+        resp.to_header()?.clone().into(),
+        resp.call_id_header()?.clone().into(),
+        rsip::typed::CSeq { seq, method }.into(),
+        Header::MaxForwards(70.into()),
+    ];
+    let mut routes: Vec<Header> = resp
+        .headers
+        .iter()
+        .filter_map(|h| match h {
+            Header::RecordRoute(rr) => Some(Header::Route(rr.value().into())),
+            _ => None,
+        })
+        .collect();
+    routes.reverse();
+    headers.extend(routes);
+    headers.push(rsip::headers::ContentLength::from(0u32).into());
+
+    Ok(Request {
+        method,
+        uri,
+        version: rsip::Version::V2,
+        headers: headers.into(),
+        body: vec![],
+    })
+}
+
+/// 请求的实际发送目标：首个 Route 跳，否则为 Request-URI
+fn request_target(req: &Request) -> Result<SipAddr, rsip::Error> {
+    let uri = destination_from_request(req).unwrap_or(std::borrow::Cow::Borrowed(&req.uri));
+    SipAddr::try_from(uri.as_ref()).map_err(|e| rsip::Error::Unexpected(e.to_string()))
+}
+
+fn is_invite_response(resp: &Response) -> bool {
+    resp.cseq_header()
+        .ok()
+        .and_then(|cseq| cseq.typed().ok())
+        .is_some_and(|cseq| cseq.method == rsip::Method::Invite)
+}
+
+fn to_tag(resp: &Response) -> Option<String> {
+    resp.to_header().ok()?.tag().ok()??.value().to_string().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok_response(tag: &str, method: rsip::Method) -> Response {
+        Response {
+            status_code: rsip::StatusCode::OK,
+            version: rsip::Version::V2,
+            headers: vec![
+                rsip::headers::From::new("<sip:alice@example.com>;tag=local").into(),
+                rsip::headers::To::new(format!("<sip:bob@example.com>;tag={}", tag)).into(),
+                rsip::headers::CallId::new("fork-call").into(),
+                rsip::typed::CSeq { seq: 1, method }.into(),
+            ]
+            .into(),
+            body: vec![],
+        }
+    }
+
+    #[test]
+    fn test_first_2xx_wins() {
+        let tracker = ForkTracker::default();
+        // 未登记的呼叫不处理
+        assert!(!tracker.on_response(&ok_response("a", rsip::Method::Invite)));
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        tracker.track("fork-call", Some(tx));
+        assert!(!tracker.on_response(&ok_response("a", rsip::Method::Bye)));
+        assert!(!tracker.on_response(&ok_response("a", rsip::Method::Invite)));
+        assert!(tracker.on_response(&ok_response("b", rsip::Method::Invite)));
+        assert!(tracker.on_response(&ok_response("a", rsip::Method::Invite)));
+
+        // 同一落选分叉只 BYE 一次，胜出 2xx 的重传只 ACK
+        let (winner, fork) = tracker.classify(&ok_response("b", rsip::Method::Invite)).unwrap();
+        assert_eq!(winner.remote_tag, "a");
+        assert_eq!(fork.unwrap().dialog.released.remote_tag, "b");
+        let (_, fork) = tracker.classify(&ok_response("b", rsip::Method::Invite)).unwrap();
+        assert!(fork.is_none());
+        let (_, fork) = tracker.classify(&ok_response("a", rsip::Method::Invite)).unwrap();
+        assert!(fork.is_none());
+
+        tracker.untrack("fork-call");
+        assert!(!tracker.on_response(&ok_response("c", rsip::Method::Invite)));
+    }
+}
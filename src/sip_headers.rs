@@ -0,0 +1,330 @@
+/// SIP 头部辅助模块
+///
+/// rsip 只按完整头部名解析，紧凑形式（RFC 3261 §7.3.3，如 `i`、`m`、`v`）
+/// 会被解析为 `Header::Other`，导致 `call_id_header()`、`contact_header()` 等类型化访问失败。
+/// 这里将紧凑形式展开为对应的类型化头部
+///
+/// 另提供呼叫自定义头部的保留头部校验、User-Agent 去重、`Allow` 方法检查与拒绝响应的附加头部
+use rsip::headers::*;
+use rsip::Header;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// 将单个紧凑形式头部展开为完整形式，其他头部原样返回
+pub fn expand_compact_header(header: Header) -> Header {
+    let Header::Other(name, value) = header else {
+        return header;
+    };
+    match name.trim() {
+        "i" | "I" => Header::CallId(CallId::new(value)),
+        "m" | "M" => Header::Contact(Contact::new(value)),
+        "f" | "F" => Header::From(From::new(value)),
+        "t" | "T" => Header::To(To::new(value)),
+        "v" | "V" => Header::Via(Via::new(value)),
+        "l" | "L" => Header::ContentLength(ContentLength::new(value)),
+        "c" | "C" => Header::ContentType(ContentType::new(value)),
+        "e" | "E" => Header::ContentEncoding(ContentEncoding::new(value)),
+        "s" | "S" => Header::Subject(Subject::new(value)),
+        "k" | "K" => Header::Supported(Supported::new(value)),
+        "o" | "O" => Header::Event(Event::new(value)),
+        _ => Header::Other(name, value),
+    }
+}
+
+/// 就地展开头部列表中的所有紧凑形式头部
+pub fn expand_compact_headers(headers: &mut rsip::Headers) {
+    let expanded: Vec<Header> = headers
+        .iter()
+        .cloned()
+        .map(expand_compact_header)
+        .collect();
+    *headers = expanded.into();
+}
+
+/// 从响应的顶层 Via 中解析服务器观察到的源地址（RFC 3581）
+///
+/// 仅当 Via 携带 `received` 或带值的 `rport` 时返回，
+/// 地址由 `received`（缺省为 sent-by 主机）与 `rport`（缺省为 sent-by 端口）组合而成
+pub fn via_observed_address(resp: &rsip::Response) -> Option<rsip::HostWithPort> {
+    use rsip::prelude::{HeadersExt, ToTypedHeader};
+
+    let via = resp.via_header().ok()?.typed().ok()?;
+    let mut address = via.uri.host_with_port.clone();
+    let mut observed = false;
+    for param in &via.params {
+        match param {
+            rsip::Param::Received(received) => {
+                if let Ok(ip) = received.value().parse::<std::net::IpAddr>() {
+                    address.host = ip.into();
+                    observed = true;
+                }
+            }
+            rsip::Param::Other(name, Some(value)) if name.value().eq_ignore_ascii_case("rport") => {
+                if let Ok(port) = value.value().parse::<u16>() {
+                    address.port = Some(port.into());
+                    observed = true;
+                }
+            }
+            _ => {}
+        }
+    }
+    observed.then_some(address)
+}
+
+/// 移除 Via 上的 `rport` 参数
+pub fn strip_rport(via: &mut rsip::typed::Via) {
+    via.params.retain(|p| {
+        !matches!(p, rsip::Param::Other(name, _) if name.value().eq_ignore_ascii_case("rport"))
+    });
+}
+
+/// 本端在 INVITE 的 `Allow` 头部中声明支持的方法
+pub const ALLOWED_METHODS: &str = "INVITE, ACK, CANCEL, BYE, OPTIONS, INFO, UPDATE, REFER, NOTIFY, MESSAGE";
+
+/// `Allow` 头部是否列出 `method`（不区分大小写），未携带 `Allow` 时返回 `false`
+pub fn allows_method(headers: &rsip::Headers, method: rsip::Method) -> bool {
+    let method = method.to_string();
+    headers.iter().any(|header| match header {
+        Header::Allow(allow) => allow
+            .value()
+            .split(',')
+            .any(|allowed| allowed.trim().eq_ignore_ascii_case(&method)),
+        _ => false,
+    })
+}
+
+/// 自定义头部不得覆盖的对话标识类头部
+const RESERVED_HEADERS: &[&str] = &["Via", "From", "To", "Call-ID", "CSeq"];
+
+/// 检查调用方提供的自定义头部，返回第一个试图覆盖保留头部（Via/From/To/Call-ID/CSeq）的头部名
+///
+/// `Header::Other` 按名称（含紧凑形式）比较，不区分大小写
+pub fn find_reserved_header(headers: &[Header]) -> Option<&'static str> {
+    headers.iter().find_map(|header| match expand_compact_header(header.clone()) {
+        Header::Via(_) => Some("Via"),
+        Header::From(_) => Some("From"),
+        Header::To(_) => Some("To"),
+        Header::CallId(_) => Some("Call-ID"),
+        Header::CSeq(_) => Some("CSeq"),
+        Header::Other(name, _) => RESERVED_HEADERS
+            .iter()
+            .find(|reserved| name.trim().eq_ignore_ascii_case(reserved))
+            .copied(),
+        _ => None,
+    })
+}
+
+/// 将显示名编码为 quoted-string（RFC 3261 §25.1），用于 From/To 头部
+///
+/// 转义反斜杠与双引号，并去除会截断头部的 CR/LF 等控制字符
+pub fn quote_display_name(name: &str) -> String {
+    let mut quoted = String::with_capacity(name.len() + 2);
+    quoted.push('"');
+    for c in name.chars().filter(|c| !c.is_control()) {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// 存在多个 User-Agent 头部时只保留最后一个
+pub fn keep_last_user_agent(headers: &mut rsip::Headers) {
+    let count = headers.iter().filter(|h| matches!(h, Header::UserAgent(_))).count();
+    if count <= 1 {
+        return;
+    }
+    let mut seen = 0;
+    headers.retain(|h| {
+        if !matches!(h, Header::UserAgent(_)) {
+            return true;
+        }
+        seen += 1;
+        seen == count
+    });
+}
+
+/// 等待附加到 INVITE 拒绝响应（3xx-6xx）上的头部，键为 Call-ID
+///
+/// rsipstack 的 `ServerInviteDialog::reject` 只能携带 Reason 头部，
+/// 其他头部（如 Retry-After）先暂存于此，由 [`OutgoingHeaders`] 在发送时写入
+pub(crate) type RejectHeaders = Arc<Mutex<HashMap<String, Vec<Header>>>>;
+
+/// 发送前整理头部的消息检查器
+///
+/// rsipstack 总是写入端点级 User-Agent，单次呼叫追加的 User-Agent 排在其后，这里保留后者；
+/// 同时为 INVITE 的拒绝响应附加 [`RejectHeaders`] 中暂存的头部（只附加一次，
+/// 重传复用事务保存的已处理响应）；收到的分叉 2xx 转交 `fork_responses`，
+/// rsipstack 不会确认的可靠 1xx 转交 `prack_responses`
+#[derive(Default)]
+pub(crate) struct OutgoingHeaders {
+    pub(crate) reject_headers: RejectHeaders,
+    pub(crate) forks: crate::sip_fork::ForkTracker,
+    pub(crate) fork_responses: Option<tokio::sync::mpsc::UnboundedSender<rsip::Response>>,
+    pub(crate) pracks: crate::sip_prack::PrackTracker,
+    pub(crate) prack_responses: Option<tokio::sync::mpsc::UnboundedSender<rsip::Response>>,
+}
+
+impl rsipstack::transaction::endpoint::MessageInspector for OutgoingHeaders {
+    fn before_send(
+        &self,
+        mut msg: rsip::SipMessage,
+        _dest: Option<&rsipstack::transport::SipAddr>,
+    ) -> rsip::SipMessage {
+        use rsip::prelude::{HeadersExt, ToTypedHeader};
+
+        match &mut msg {
+            rsip::SipMessage::Request(req) => keep_last_user_agent(&mut req.headers),
+            rsip::SipMessage::Response(resp) if resp.status_code.code() >= 300 => {
+                let is_invite = resp
+                    .cseq_header()
+                    .ok()
+                    .and_then(|cseq| cseq.typed().ok())
+                    .is_some_and(|cseq| cseq.method == rsip::Method::Invite);
+                let call_id = resp.call_id_header().map(|h| h.value().to_string());
+                if let (true, Ok(call_id)) = (is_invite, call_id) {
+                    if let Some(extra) = self.reject_headers.lock().unwrap().remove(&call_id) {
+                        resp.headers.extend(extra);
+                    }
+                }
+            }
+            _ => {}
+        }
+        msg
+    }
+
+    fn after_received(
+        &self,
+        msg: rsip::SipMessage,
+        _from: &rsipstack::transport::SipAddr,
+    ) -> rsip::SipMessage {
+        if let (rsip::SipMessage::Response(resp), Some(sender)) = (&msg, &self.fork_responses) {
+            if self.forks.on_response(resp) {
+                let _ = sender.send(resp.clone());
+            }
+        }
+        if let (rsip::SipMessage::Response(resp), Some(sender)) = (&msg, &self.prack_responses) {
+            if self.pracks.on_response(resp) {
+                let _ = sender.send(resp.clone());
+            }
+        }
+        msg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsip::prelude::{HeadersExt, ToTypedHeader};
+
+    const COMPACT_RESPONSE: &str = "SIP/2.0 200 OK\r\n\
+        v: SIP/2.0/UDP 192.168.1.10:5060;branch=z9hG4bK776asdhds;received=203.0.113.5\r\n\
+        f: <sip:alice@example.com>;tag=1928301774\r\n\
+        t: <sip:alice@example.com>;tag=a6c85cf\r\n\
+        i: a84b4c76e66710@pc33.example.com\r\n\
+        CSeq: 1 REGISTER\r\n\
+        m: <sip:alice@192.168.1.10:5060>;expires=600\r\n\
+        l: 0\r\n\r\n";
+
+    #[test]
+    fn test_compact_response_headers() {
+        let msg = rsip::SipMessage::try_from(COMPACT_RESPONSE).unwrap();
+        let rsip::SipMessage::Response(mut resp) = msg else {
+            panic!("应为响应");
+        };
+        assert!(resp.call_id_header().is_err());
+
+        expand_compact_headers(&mut resp.headers);
+        assert_eq!(
+            resp.call_id_header().unwrap().to_string(),
+            "Call-ID: a84b4c76e66710@pc33.example.com"
+        );
+        let contact = resp.contact_header().unwrap().typed().unwrap();
+        assert_eq!(contact.uri.host_with_port.to_string(), "192.168.1.10:5060");
+        assert!(resp.via_header().is_ok());
+        assert!(resp.from_header().is_ok() && resp.to_header().is_ok());
+    }
+
+    #[test]
+    fn test_via_observed_address() {
+        let msg = rsip::SipMessage::try_from(COMPACT_RESPONSE).unwrap();
+        let rsip::SipMessage::Response(mut resp) = msg else {
+            panic!("应为响应");
+        };
+        expand_compact_headers(&mut resp.headers);
+        // 只有 received，端口沿用 sent-by
+        let addr = via_observed_address(&resp).unwrap();
+        assert_eq!(addr.to_string(), "203.0.113.5:5060");
+
+        resp.headers.retain(|h| !matches!(h, Header::Via(_)));
+        resp.headers.push(
+            Via::new("SIP/2.0/UDP 192.168.1.10:5060;branch=z9hG4bK1;rport=40123;received=203.0.113.5")
+                .into(),
+        );
+        assert_eq!(via_observed_address(&resp).unwrap().to_string(), "203.0.113.5:40123");
+
+        resp.headers.retain(|h| !matches!(h, Header::Via(_)));
+        resp.headers
+            .push(Via::new("SIP/2.0/UDP 192.168.1.10:5060;branch=z9hG4bK1;rport").into());
+        assert!(via_observed_address(&resp).is_none());
+    }
+
+    #[test]
+    fn test_strip_rport() {
+        let mut via: rsip::typed::Via =
+            Via::new("SIP/2.0/UDP 192.168.1.10:5060;branch=z9hG4bK1;rport")
+                .typed()
+                .unwrap();
+        strip_rport(&mut via);
+        assert!(!via.to_string().contains("rport"));
+        assert!(via.to_string().contains("branch=z9hG4bK1"));
+    }
+
+    #[test]
+    fn test_unknown_header_kept() {
+        let header = expand_compact_header(Header::Other("X-Custom".into(), "1".into()));
+        assert_eq!(header, Header::Other("X-Custom".into(), "1".into()));
+    }
+
+    #[test]
+    fn test_reserved_and_user_agent_headers() {
+        let custom: Vec<Header> = vec![
+            Header::Other("X-Call-Reason".into(), "test".into()),
+            Header::Other("P-Asserted-Identity".into(), "<sip:alice@example.com>".into()),
+        ];
+        assert_eq!(find_reserved_header(&custom), None);
+        assert_eq!(find_reserved_header(&[CallId::new("x").into()]), Some("Call-ID"));
+        assert_eq!(find_reserved_header(&[Header::Other("cseq".into(), "1 INVITE".into())]), Some("CSeq"));
+        assert_eq!(find_reserved_header(&[Header::Other("v".into(), "SIP/2.0/UDP a".into())]), Some("Via"));
+
+        let allow: rsip::Headers = vec![Header::Allow(Allow::new("INVITE, ACK,bye, update"))].into();
+        assert!(allows_method(&allow, rsip::Method::Update));
+        assert!(allows_method(&allow, rsip::Method::Bye));
+        assert!(!allows_method(&allow, rsip::Method::Refer));
+        assert!(!allows_method(&rsip::Headers::default(), rsip::Method::Update));
+
+        let mut headers: rsip::Headers = vec![
+            Header::UserAgent("global".into()),
+            Header::Other("X-Call-Reason".into(), "test".into()),
+            Header::UserAgent("per-call".into()),
+        ]
+        .into();
+        keep_last_user_agent(&mut headers);
+        let agents: Vec<_> = headers.iter().filter(|h| matches!(h, Header::UserAgent(_))).collect();
+        assert_eq!(agents, vec![&Header::UserAgent("per-call".into())]);
+        assert_eq!(headers.iter().count(), 2);
+    }
+
+    #[test]
+    fn test_quote_display_name() {
+        assert_eq!(quote_display_name("Alice"), "\"Alice\"");
+        assert_eq!(quote_display_name("张三"), "\"张三\"");
+        assert_eq!(
+            quote_display_name("Bob \"The\" \\Builder"),
+            r#""Bob \"The\" \\Builder""#
+        );
+        assert_eq!(quote_display_name("Eve\r\nVia: x"), "\"EveVia: x\"");
+    }
+}
@@ -0,0 +1,195 @@
+/// 可靠临时响应（RFC 3262 100rel）确认模块
+///
+/// rsipstack 的 INVITE 客户端事务只把第一个非 100 的临时响应交给对话层，之后到达的 1xx
+/// 都按重复响应丢弃，因此只有第一个可靠 1xx 会被 PRACK。180(100rel) 之后的 183(100rel)
+/// 得不到确认，UAS 持续重传直至放弃呼叫。这里由消息检查器按对话记录 RSeq，
+/// 对 rsipstack 看不到的可靠 1xx 在对话内补发 PRACK
+use rsip::prelude::{HeadersExt, ToTypedHeader, UntypedHeader};
+use rsip::{Header, Response};
+use rsipstack::dialog::dialog_layer::DialogLayer;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+/// 单个 INVITE 事务收到的临时响应
+#[derive(Debug, Default)]
+struct PendingInvite {
+    /// 第一个非 100 临时响应已交给 rsipstack
+    surfaced: bool,
+    /// 各 To tag 已确认的最大 RSeq
+    rseq: HashMap<String, u32>,
+}
+
+/// PRACK 跟踪表，键为 Call-ID 与 INVITE 的 CSeq
+///
+/// 收到 INVITE 的最终响应后删除对应条目
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PrackTracker {
+    invites: Arc<Mutex<HashMap<(String, u32), PendingInvite>>>,
+}
+
+impl PrackTracker {
+    /// 检查收到的响应，返回是否需要由后台任务发送 PRACK
+    ///
+    /// 第一个非 100 临时响应由 rsipstack 处理（可靠时由其发送 PRACK），只记录其 RSeq；
+    /// 之后的可靠 1xx 在 RSeq 递增时交给后台任务，重传与乱序的旧 RSeq 忽略
+    pub(crate) fn on_response(&self, resp: &Response) -> bool {
+        let Some(cseq) = resp
+            .cseq_header()
+            .ok()
+            .and_then(|cseq| cseq.typed().ok())
+            .filter(|cseq| cseq.method == rsip::Method::Invite)
+        else {
+            return false;
+        };
+        let Ok(call_id) = resp.call_id_header() else {
+            return false;
+        };
+        let key = (call_id.value().to_string(), cseq.seq);
+        let mut invites = self.invites.lock().unwrap();
+        if resp.status_code.kind() != rsip::StatusCodeKind::Provisional {
+            invites.remove(&key);
+            return false;
+        }
+        if resp.status_code == rsip::StatusCode::Trying {
+            return false;
+        }
+
+        let invite = invites.entry(key).or_default();
+        let surfaced = std::mem::replace(&mut invite.surfaced, true);
+        let (Some(rseq), Some(tag)) = (reliable_rseq(resp), to_tag(resp)) else {
+            return false;
+        };
+        let last = invite.rseq.entry(tag).or_insert(0);
+        if rseq <= *last {
+            return false;
+        }
+        *last = rseq;
+        surfaced
+    }
+
+    /// 启动后台任务，为检查器转交的可靠 1xx 在对应的早期对话内发送 PRACK
+    pub(crate) fn spawn_handler(
+        dialog_layer: Arc<DialogLayer>,
+        mut responses: mpsc::UnboundedReceiver<Response>,
+        cancel_token: CancellationToken,
+    ) {
+        tokio::spawn(async move {
+            loop {
+                let resp = tokio::select! {
+                    resp = responses.recv() => match resp {
+                        Some(resp) => resp,
+                        None => break,
+                    },
+                    _ = cancel_token.cancelled() => break,
+                };
+                tokio::spawn(send_prack(dialog_layer.clone(), resp));
+            }
+        });
+    }
+}
+
+/// 在 To tag 匹配的客户端对话内发送 PRACK，CSeq 与路由集由对话维护
+async fn send_prack(dialog_layer: Arc<DialogLayer>, resp: Response) {
+    let (Ok(call_id), Some(tag), Some(rseq)) = (resp.call_id_header(), to_tag(&resp), reliable_rseq(&resp)) else {
+        return;
+    };
+    let Ok(cseq) = resp.cseq_header().and_then(|cseq| cseq.seq()) else {
+        return;
+    };
+    let Some(dialog) = dialog_layer
+        .get_client_dialog_by_call_id(call_id.value())
+        .into_iter()
+        .find(|dialog| dialog.id().remote_tag == tag)
+    else {
+        debug!("可靠临时响应 {} 没有匹配的对话（To tag {}），不发送 PRACK", resp.status_code, tag);
+        return;
+    };
+
+    let headers = vec![
+        Header::Other("RAck".into(), format!("{} {} INVITE", rseq, cseq)),
+        Header::Other("Supported".into(), "100rel".into()),
+    ];
+    // 早期对话尚未确认，ClientInviteDialog::request 会直接跳过，这里借用不检查对话状态的订阅视图
+    match dialog.as_subscription().request(rsip::Method::PRack, Some(headers), None).await {
+        Ok(Some(prack)) => debug!("PRACK（RSeq {}）完成: {}", rseq, prack.status_code),
+        Ok(None) => warn!("PRACK（RSeq {}）未收到响应", rseq),
+        Err(e) => warn!("PRACK（RSeq {}）发送失败: {}", rseq, e),
+    }
+}
+
+/// 携带 `Require: 100rel` 的临时响应返回其 RSeq
+fn reliable_rseq(resp: &Response) -> Option<u32> {
+    let mut require = false;
+    let mut rseq = None;
+    for header in resp.headers.iter() {
+        match header {
+            Header::Require(value) => {
+                require |= value.value().split(',').any(|token| token.trim().eq_ignore_ascii_case("100rel"))
+            }
+            Header::Other(name, value) if name.eq_ignore_ascii_case("Require") => {
+                require |= value.split(',').any(|token| token.trim().eq_ignore_ascii_case("100rel"))
+            }
+            Header::Other(name, value) if name.eq_ignore_ascii_case("RSeq") => rseq = value.trim().parse().ok(),
+            _ => {}
+        }
+    }
+    rseq.filter(|_| require)
+}
+
+fn to_tag(resp: &Response) -> Option<String> {
+    resp.to_header().ok()?.tag().ok()??.value().to_string().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provisional(status: rsip::StatusCode, cseq: u32, rseq: Option<u32>) -> Response {
+        let mut headers: Vec<Header> = vec![
+            rsip::headers::From::new("<sip:alice@example.com>;tag=local").into(),
+            rsip::headers::To::new("<sip:bob@example.com>;tag=remote").into(),
+            rsip::headers::CallId::new("prack-call").into(),
+            rsip::typed::CSeq {
+                seq: cseq,
+                method: rsip::Method::Invite,
+            }
+            .into(),
+        ];
+        if let Some(rseq) = rseq {
+            headers.push(Header::Other("Require".into(), "100rel".into()));
+            headers.push(Header::Other("RSeq".into(), rseq.to_string()));
+        }
+        Response {
+            status_code: status,
+            version: rsip::Version::V2,
+            headers: headers.into(),
+            body: vec![],
+        }
+    }
+
+    #[test]
+    fn test_prack_reliable_after_first_provisional() {
+        let tracker = PrackTracker::default();
+        assert!(!tracker.on_response(&provisional(rsip::StatusCode::Trying, 1, None)));
+        // 第一个 1xx 由 rsipstack 确认
+        assert!(!tracker.on_response(&provisional(rsip::StatusCode::Ringing, 1, Some(1))));
+        // 之后递增的 RSeq 需要补发 PRACK，重传与旧 RSeq 忽略
+        assert!(tracker.on_response(&provisional(rsip::StatusCode::SessionProgress, 1, Some(2))));
+        assert!(!tracker.on_response(&provisional(rsip::StatusCode::SessionProgress, 1, Some(2))));
+        assert!(!tracker.on_response(&provisional(rsip::StatusCode::Ringing, 1, Some(1))));
+        // 不可靠的 1xx 不需要 PRACK
+        assert!(!tracker.on_response(&provisional(rsip::StatusCode::Ringing, 1, None)));
+
+        // 第一个 1xx 不可靠时，之后的可靠 1xx 同样由这里确认
+        assert!(!tracker.on_response(&provisional(rsip::StatusCode::Ringing, 2, None)));
+        assert!(tracker.on_response(&provisional(rsip::StatusCode::SessionProgress, 2, Some(1))));
+
+        // 最终响应清除跟踪状态
+        tracker.on_response(&provisional(rsip::StatusCode::OK, 1, None));
+        tracker.on_response(&provisional(rsip::StatusCode::OK, 2, None));
+        assert!(tracker.invites.lock().unwrap().is_empty());
+    }
+}
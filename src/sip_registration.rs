@@ -0,0 +1,661 @@
+/// SIP 注册事务模块
+///
+/// 在 rsipstack 的 `Registration` 基础上实现注册请求循环，
+/// 以便控制 expires 的携带方式并解析服务器实际授予的注册时长
+use crate::config::{ExpiresMode, QValue};
+#[cfg(feature = "aka")]
+use crate::sip_aka::AkaKey;
+use crate::sip_auth::{
+    provide_credential, AuthRetryState, CredentialProvider, DigestChallenge, DigestSession,
+};
+use crate::sip_headers::{
+    expand_compact_header, expand_compact_headers, strip_rport, via_observed_address,
+};
+use rsip::prelude::{HeadersExt, ToTypedHeader, UntypedHeader};
+use rsip::{Response, SipMessage, StatusCode};
+use rsipstack::dialog::authenticate::Credential;
+use rsipstack::dialog::DialogId;
+use rsipstack::transaction::endpoint::EndpointInnerRef;
+use rsipstack::transaction::key::{TransactionKey, TransactionRole};
+use rsipstack::transaction::transaction::Transaction;
+use rsipstack::transaction::{make_call_id, make_tag, make_via_branch};
+use tracing::debug;
+
+/// 收到 423 Interval Too Brief 后按 `Min-Expires` 重试的最大次数
+const MAX_INTERVAL_RETRIES: u32 = 1;
+
+/// SIP 注册会话
+///
+/// 维护注册所需的 CSeq、Call-ID、Contact 等状态，
+/// 同一实例多次调用 `register` 即为注册刷新
+pub struct SipRegistration {
+    pub last_seq: u32,
+    pub endpoint: EndpointInnerRef,
+    pub credential: Option<Credential>,
+    pub contact: Option<rsip::typed::Contact>,
+    pub allow: rsip::headers::Allow,
+    pub public_address: Option<rsip::HostWithPort>,
+    pub call_id: rsip::headers::CallId,
+    /// From 头部的 tag，同一注册会话（刷新、注销）的所有 REGISTER 保持不变
+    pub from_tag: rsip::param::Tag,
+    /// expires 的携带方式（Expires 头 / Contact 参数 / 两者）
+    pub expires_mode: ExpiresMode,
+    /// 认证后收到 `stale=true` 挑战时是否使用新 nonce 再重试一次
+    pub stale_nonce_retry: bool,
+    /// 是否在 Via 中请求 `rport` 并根据响应学习公网地址（RFC 3581）
+    pub rport: bool,
+    /// Contact 的 `q` 参数
+    pub contact_q: Option<QValue>,
+    /// 固定的 Contact URI，设置后不再按公网/本地地址生成
+    pub contact_uri: Option<rsip::Uri>,
+    /// 注册的 AOR（REGISTER 的 From/To），未设置时使用注册服务器 URI 与认证用户名
+    pub aor: Option<rsip::Uri>,
+    granted_expires: Option<u32>,
+    requested_expires: Option<u32>,
+    bindings: Vec<ContactBinding>,
+    /// 最近一次接受的认证挑战，刷新时复用其 nonce
+    digest: Option<DigestSession>,
+    /// 动态凭证回调，未设置时使用静态密码
+    credential_provider: Option<CredentialProvider>,
+    /// ISIM 密钥，服务器要求 AKAv1-MD5 时使用
+    #[cfg(feature = "aka")]
+    aka_key: Option<AkaKey>,
+}
+
+impl SipRegistration {
+    /// 创建新的注册会话
+    pub fn new(endpoint: EndpointInnerRef, credential: Option<Credential>) -> Self {
+        let call_id = make_call_id(endpoint.option.callid_suffix.as_deref());
+        Self {
+            last_seq: 0,
+            endpoint,
+            credential,
+            contact: None,
+            allow: Default::default(),
+            public_address: None,
+            call_id,
+            from_tag: make_tag(),
+            expires_mode: ExpiresMode::default(),
+            stale_nonce_retry: true,
+            rport: true,
+            contact_q: None,
+            contact_uri: None,
+            aor: None,
+            granted_expires: None,
+            requested_expires: None,
+            bindings: Vec::new(),
+            digest: None,
+            credential_provider: None,
+            #[cfg(feature = "aka")]
+            aka_key: None,
+        }
+    }
+
+    /// 设置 expires 的携带方式
+    pub fn with_expires_mode(mut self, expires_mode: ExpiresMode) -> Self {
+        self.expires_mode = expires_mode;
+        self
+    }
+
+    /// 设置是否允许 stale nonce 重试
+    pub fn with_stale_nonce_retry(mut self, stale_nonce_retry: bool) -> Self {
+        self.stale_nonce_retry = stale_nonce_retry;
+        self
+    }
+
+    /// 设置是否启用 rport（RFC 3581）
+    pub fn with_rport(mut self, rport: bool) -> Self {
+        self.rport = rport;
+        self
+    }
+
+    /// 设置 Contact 的 `q` 参数
+    pub fn with_contact_q(mut self, contact_q: Option<QValue>) -> Self {
+        self.contact_q = contact_q;
+        self
+    }
+
+    /// 设置固定的 Contact URI
+    pub fn with_contact_uri(mut self, contact_uri: Option<rsip::Uri>) -> Self {
+        self.contact_uri = contact_uri;
+        self
+    }
+
+    /// 设置注册的 AOR
+    pub fn with_aor(mut self, aor: Option<rsip::Uri>) -> Self {
+        self.aor = aor;
+        self
+    }
+
+    /// 最近一次 200 OK 中列出的全部绑定
+    pub fn bindings(&self) -> &[ContactBinding] {
+        &self.bindings
+    }
+
+    /// 沿用之前注册得到的认证会话（nonce 与 nonce-count）
+    pub fn with_digest_session(mut self, digest: Option<DigestSession>) -> Self {
+        self.digest = digest;
+        self
+    }
+
+    /// 设置动态凭证回调，应答挑战时以 realm 与 nonce 获取密码
+    pub fn with_credential_provider(mut self, provider: Option<CredentialProvider>) -> Self {
+        self.credential_provider = provider;
+        self
+    }
+
+    /// 设置 ISIM 密钥，挑战中有 AKAv1-MD5 时按 AKA 应答，否则仍使用普通 Digest
+    #[cfg(feature = "aka")]
+    pub fn with_aka_key(mut self, key: Option<AkaKey>) -> Self {
+        self.aka_key = key;
+        self
+    }
+
+    /// 当前的认证会话
+    pub fn digest_session(&self) -> Option<&DigestSession> {
+        self.digest.as_ref()
+    }
+
+    /// 服务器在最近一次 200 OK 中授予的注册时长（秒）
+    pub fn granted_expires(&self) -> Option<u32> {
+        self.granted_expires
+    }
+
+    /// 最近一次 REGISTER 实际请求的注册时长（收到 423 后会调整为 `Min-Expires`）
+    pub fn requested_expires(&self) -> Option<u32> {
+        self.requested_expires
+    }
+
+    /// 发送 REGISTER 请求并处理认证挑战
+    ///
+    /// 收到 423 Interval Too Brief 时按 `Min-Expires` 增大注册时长重试，
+    /// 最多重试 `MAX_INTERVAL_RETRIES` 次，返回最后一次的响应
+    ///
+    /// # 参数
+    /// - `server`: 注册服务器 URI
+    /// - `expires`: 请求的注册时长，`Some(0)` 表示注销
+    pub async fn register(
+        &mut self,
+        server: rsip::Uri,
+        expires: Option<u32>,
+    ) -> rsipstack::Result<Response> {
+        let mut expires = expires;
+        let mut retries = 0;
+        loop {
+            self.requested_expires = expires;
+            let resp = self.send_register(server.clone(), expires).await?;
+            if resp.status_code != StatusCode::IntervalTooBrief || retries >= MAX_INTERVAL_RETRIES {
+                return Ok(resp);
+            }
+            match min_expires(&resp) {
+                Some(min) if expires.is_none_or(|e| min > e) => {
+                    debug!(?expires, min_expires = min, "注册时长过短 (423)，按 Min-Expires 重试");
+                    expires = Some(min);
+                    retries += 1;
+                }
+                _ => return Ok(resp),
+            }
+        }
+    }
+
+    async fn send_register(
+        &mut self,
+        server: rsip::Uri,
+        expires: Option<u32>,
+    ) -> rsipstack::Result<Response> {
+        self.last_seq += 1;
+
+        let mut to = rsip::typed::To {
+            display_name: None,
+            uri: server.clone(),
+            params: vec![],
+        };
+
+        if let Some(aor) = &self.aor {
+            to.uri = aor.clone();
+        } else if let Some(cred) = &self.credential {
+            to.uri.auth = Some(rsip::auth::Auth {
+                user: cred.username.clone(),
+                password: None,
+            });
+        }
+
+        let from = rsip::typed::From {
+            display_name: None,
+            uri: to.uri.clone(),
+            params: vec![],
+        }
+        .with_tag(self.from_tag.clone());
+
+        let mut via = self.endpoint.get_via(None, None)?;
+        if !self.rport {
+            strip_rport(&mut via);
+        }
+
+        // Contact 优先级：配置的固定 Contact > 服务器返回的 Contact > 发现的公网地址 > 本地地址
+        let fixed_contact = self.contact_uri.clone().map(|uri| rsip::typed::Contact {
+            display_name: None,
+            uri,
+            params: vec![],
+        });
+        let mut contact = fixed_contact.or_else(|| self.contact.clone()).unwrap_or_else(|| {
+            let contact_host_with_port = self
+                .public_address
+                .clone()
+                .unwrap_or_else(|| via.uri.host_with_port.clone());
+            rsip::typed::Contact {
+                display_name: None,
+                uri: rsip::Uri {
+                    auth: to.uri.auth.clone(),
+                    scheme: Some(rsip::Scheme::Sip),
+                    host_with_port: contact_host_with_port,
+                    params: vec![],
+                    headers: vec![],
+                },
+                params: vec![],
+            }
+        });
+
+        contact
+            .params
+            .retain(|p| !matches!(p, rsip::Param::Expires(_) | rsip::Param::Q(_)));
+        if let Some(q) = self.contact_q {
+            contact.params.push(rsip::Param::Q(q.to_string().into()));
+        }
+        if let Some(expires) = expires {
+            if self.expires_mode.use_contact_param() {
+                contact
+                    .params
+                    .push(rsip::Param::Expires(expires.to_string().into()));
+            }
+        }
+        let contact_uri = contact.uri.clone();
+
+        let mut request = self.endpoint.make_request(
+            rsip::Method::Register,
+            server,
+            via,
+            from,
+            to,
+            self.last_seq,
+            None,
+        );
+
+        request.headers.unique_push(self.call_id.clone().into());
+        request.headers.unique_push(contact.into());
+        request.headers.unique_push(self.allow.clone().into());
+        if let Some(expires) = expires {
+            if self.expires_mode.use_header() {
+                request
+                    .headers
+                    .unique_push(rsip::headers::Expires::from(expires).into());
+            }
+        }
+
+        // 复用上次的 nonce 预先携带认证，nonce-count 递增
+        if let (Some(cred), Some(digest)) = (&self.credential, self.digest.as_ref()) {
+            let cred = provide_credential(
+                cred,
+                self.credential_provider.as_ref(),
+                digest.realm(),
+                digest.nonce(),
+            )
+            .await;
+            if let Some(header) = self.digest.as_mut().and_then(|digest| {
+                digest.authorization_header(&cred, &rsip::Method::Register, &request.uri, &request.body)
+            }) {
+                request.headers.push(header);
+            }
+        }
+
+        let key = TransactionKey::from_request(&request, TransactionRole::Client)?;
+        let mut tx = Transaction::new_client(key, request, self.endpoint.clone(), None);
+
+        tx.send().await?;
+        let mut auth_state = AuthRetryState::new(self.stale_nonce_retry);
+
+        while let Some(msg) = tx.receive().await {
+            match msg {
+                SipMessage::Response(mut resp) => {
+                    expand_compact_headers(&mut resp.headers);
+                    match resp.status_code {
+                        StatusCode::Trying => {
+                            continue;
+                        }
+                        StatusCode::ProxyAuthenticationRequired | StatusCode::Unauthorized => {
+                            self.update_public_address(&resp);
+
+                            let stale = DigestChallenge::from_response(&resp)
+                                .map(|c| c.stale)
+                                .unwrap_or(false);
+                            if self.credential.is_some() && !auth_state.on_challenge(stale) {
+                                debug!(status = %resp.status_code, stale, "认证已发送后再次收到挑战");
+                                return Ok(resp);
+                            }
+
+                            if let Some(cred) = &self.credential {
+                                if stale {
+                                    debug!("nonce 已过期 (stale=true)，使用新 nonce 重试认证");
+                                }
+                                let cred = cred.clone();
+                                self.last_seq += 1;
+                                tx = self.authenticate(&tx, &resp, &cred).await?;
+                                tx.send().await?;
+                                continue;
+                            } else {
+                                debug!(status = %resp.status_code, "收到认证挑战但未配置凭证");
+                                return Ok(resp);
+                            }
+                        }
+                        StatusCode::OK => {
+                            self.update_public_address(&resp);
+                            if let Ok(header) = resp.contact_header() {
+                                self.contact = header.typed().ok();
+                            }
+
+                            self.granted_expires = parse_granted_expires(&resp, &contact_uri);
+                            self.bindings = parse_bindings(&resp);
+                            if let Some(contact) = self.contact.as_mut() {
+                                contact.params.retain(|p| {
+                                    !matches!(p, rsip::Param::Expires(_) | rsip::Param::Q(_))
+                                });
+                            }
+                            debug!(
+                                status = %resp.status_code,
+                                granted_expires = ?self.granted_expires,
+                                bindings = self.bindings.len(),
+                                "注册请求完成"
+                            );
+                            return Ok(resp);
+                        }
+                        _ => {
+                            debug!(status = %resp.status_code, "注册请求完成");
+                            return Ok(resp);
+                        }
+                    }
+                }
+                _ => break,
+            }
+        }
+        Err(rsipstack::Error::DialogError(
+            "registration transaction is already terminated".to_string(),
+            DialogId::from_uac_request(&tx.original)?,
+            StatusCode::BadRequest,
+        ))
+    }
+
+    /// 应答认证挑战，生成携带认证头的新事务
+    ///
+    /// 配置了动态凭证回调时以挑战的 realm 与 nonce 获取密码；同一 nonce 的重复使用会递增 nonce-count，nonce 变化时重新计数
+    async fn authenticate(
+        &mut self,
+        tx: &Transaction,
+        resp: &Response,
+        cred: &Credential,
+    ) -> rsipstack::Result<Transaction> {
+        let updated = match self.digest.as_mut() {
+            Some(digest) => digest.update(resp),
+            None => {
+                let digest = DigestSession::default();
+                #[cfg(feature = "aka")]
+                let digest = digest.with_aka_key(self.aka_key.clone());
+                let mut digest = digest;
+                let updated = digest.update(resp);
+                self.digest = updated.then_some(digest);
+                updated
+            }
+        };
+        let digest = match self.digest.as_mut() {
+            Some(digest) if updated => digest,
+            _ => {
+                return Err(rsipstack::Error::DialogError(
+                    "missing proxy/www authenticate".to_string(),
+                    DialogId::from_uac_request(&tx.original)?,
+                    resp.status_code.clone(),
+                ))
+            }
+        };
+
+        let cred = provide_credential(
+            cred,
+            self.credential_provider.as_ref(),
+            digest.realm(),
+            digest.nonce(),
+        )
+        .await;
+
+        let mut request = tx.original.clone();
+        request.cseq_header_mut()?.mut_seq(self.last_seq)?;
+
+        let mut via = request.via_header()?.typed()?;
+        via.params.retain(|p| !matches!(p, rsip::Param::Branch(_)));
+        via.params.push(make_via_branch());
+        request.headers.unique_push(via.into());
+
+        request.headers.retain(|h| {
+            !matches!(
+                h,
+                rsip::Header::Authorization(_) | rsip::Header::ProxyAuthorization(_)
+            )
+        });
+        let method = request.method;
+        let uri = request.uri.clone();
+        match digest.authorization_header(&cred, &method, &uri, &request.body) {
+            Some(header) => request.headers.push(header),
+            None => {
+                return Err(rsipstack::Error::DialogError(
+                    "nonce-count exhausted or AKA authentication failed".to_string(),
+                    DialogId::from_uac_request(&tx.original)?,
+                    resp.status_code.clone(),
+                ))
+            }
+        }
+
+        let key = TransactionKey::from_request(&request, TransactionRole::Client)?;
+        let mut new_tx =
+            Transaction::new_client(key, request, self.endpoint.clone(), tx.connection.clone());
+        new_tx.destination = tx.destination.clone();
+        Ok(new_tx)
+    }
+
+    /// 根据响应 Via 的 `received`/`rport` 更新公网地址
+    ///
+    /// 地址变化时丢弃缓存的 Contact，下一次请求使用新地址重新生成
+    fn update_public_address(&mut self, resp: &Response) {
+        if !self.rport {
+            return;
+        }
+        let Some(observed) = via_observed_address(resp) else {
+            return;
+        };
+        if self.public_address.as_ref() != Some(&observed) {
+            debug!(old = ?self.public_address, new = %observed, "更新公网地址");
+            self.public_address = Some(observed);
+            self.contact = None;
+        }
+    }
+}
+
+/// 从注册响应中解析服务器授予的注册时长
+///
+/// 优先使用与本地 Contact 匹配的 `expires` 参数，
+/// 其次使用唯一 Contact 的 `expires` 参数，最后回退到 `Expires` 头
+pub fn parse_granted_expires(resp: &Response, local_contact: &rsip::Uri) -> Option<u32> {
+    let contacts = response_contacts(resp);
+
+    let contact_expires = |c: &rsip::typed::Contact| c.expires().and_then(|e| e.seconds().ok());
+
+    let matched = contacts
+        .iter()
+        .find(|c| {
+            c.uri.host_with_port == local_contact.host_with_port
+                && c.uri.user() == local_contact.user()
+        })
+        .and_then(contact_expires);
+
+    let single = match contacts.as_slice() {
+        [only] => contact_expires(only),
+        _ => None,
+    };
+
+    matched.or(single).or_else(|| {
+        resp.headers.iter().find_map(|h| match h {
+            rsip::Header::Expires(e) => e.seconds().ok(),
+            _ => None,
+        })
+    })
+}
+
+/// 注册响应中的一个绑定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContactBinding {
+    pub uri: rsip::Uri,
+    pub expires: Option<u32>,
+    /// 绑定的 q 值，缺失或格式非法时为 `None`
+    pub q: Option<QValue>,
+}
+
+/// 解析 200 OK 中列出的全部绑定（含其他设备的绑定）
+pub fn parse_bindings(resp: &Response) -> Vec<ContactBinding> {
+    response_contacts(resp)
+        .into_iter()
+        .map(|contact| {
+            let expires = contact.expires().and_then(|e| e.seconds().ok());
+            let q = contact.params.iter().find_map(|p| match p {
+                rsip::Param::Q(q) => q.value().parse().ok(),
+                _ => None,
+            });
+            ContactBinding {
+                uri: contact.uri,
+                expires,
+                q,
+            }
+        })
+        .collect()
+}
+
+/// 取出响应中的全部 Contact（展开紧凑形式与逗号分隔的列表）
+fn response_contacts(resp: &Response) -> Vec<rsip::typed::Contact> {
+    resp.headers
+        .iter()
+        .cloned()
+        .map(expand_compact_header)
+        .filter_map(|h| match h {
+            rsip::Header::Contact(c) => Some(c.value().to_string()),
+            _ => None,
+        })
+        .flat_map(|value| split_header_list(&value))
+        .filter_map(|value| rsip::headers::Contact::new(value).typed().ok())
+        .collect()
+}
+
+/// 解析 423 响应中的 `Min-Expires`
+fn min_expires(resp: &Response) -> Option<u32> {
+    resp.headers.iter().find_map(|h| match h {
+        rsip::Header::MinExpires(min) => min.value().trim().parse().ok(),
+        _ => None,
+    })
+}
+
+/// 按逗号拆分头部列表，忽略尖括号和引号内的逗号
+fn split_header_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut in_angle = false;
+
+    for ch in value.chars() {
+        match ch {
+            '"' => in_quotes = !in_quotes,
+            '<' if !in_quotes => in_angle = true,
+            '>' if !in_quotes => in_angle = false,
+            ',' if !in_quotes && !in_angle => {
+                items.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(ch);
+    }
+    if !current.trim().is_empty() {
+        items.push(current.trim().to_string());
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok_response(headers: Vec<rsip::Header>) -> Response {
+        Response {
+            status_code: StatusCode::OK,
+            version: rsip::Version::V2,
+            headers: headers.into(),
+            body: vec![],
+        }
+    }
+
+    fn local_contact() -> rsip::Uri {
+        "sip:alice@192.168.1.10:5060".try_into().unwrap()
+    }
+
+    #[test]
+    fn test_granted_expires_from_matching_contact() {
+        let resp = ok_response(vec![
+            rsip::headers::Contact::new(
+                "<sip:alice@10.0.0.1:5060>;expires=60, <sip:alice@192.168.1.10:5060>;expires=120",
+            )
+            .into(),
+            rsip::headers::Expires::from(3600).into(),
+        ]);
+        assert_eq!(parse_granted_expires(&resp, &local_contact()), Some(120));
+    }
+
+    #[test]
+    fn test_granted_expires_from_single_contact() {
+        let resp = ok_response(vec![rsip::headers::Contact::new(
+            "<sip:alice@203.0.113.5:40000>;expires=300",
+        )
+        .into()]);
+        assert_eq!(parse_granted_expires(&resp, &local_contact()), Some(300));
+    }
+
+    #[test]
+    fn test_granted_expires_falls_back_to_header() {
+        let resp = ok_response(vec![
+            rsip::headers::Contact::new("<sip:alice@192.168.1.10:5060>").into(),
+            rsip::headers::Expires::from(1800).into(),
+        ]);
+        assert_eq!(parse_granted_expires(&resp, &local_contact()), Some(1800));
+    }
+
+    #[test]
+    fn test_granted_expires_missing() {
+        let resp = ok_response(vec![]);
+        assert_eq!(parse_granted_expires(&resp, &local_contact()), None);
+    }
+
+    #[test]
+    fn test_parse_bindings_with_q() {
+        let resp = ok_response(vec![rsip::headers::Contact::new(
+            "<sip:alice@192.168.1.10:5060>;expires=120;q=0.5, <sip:alice@10.0.0.1:5060>;q=1;expires=60, <sip:alice@10.0.0.2>",
+        )
+        .into()]);
+        let bindings = parse_bindings(&resp);
+        assert_eq!(bindings.len(), 3);
+        assert_eq!(bindings[0].q, Some("0.5".parse().unwrap()));
+        assert_eq!(bindings[0].expires, Some(120));
+        assert_eq!(bindings[1].q, Some(QValue::MAX));
+        assert_eq!(bindings[2].q, None);
+        assert_eq!(bindings[2].uri.host_with_port.to_string(), "10.0.0.2");
+    }
+
+    #[test]
+    fn test_split_header_list() {
+        let items = split_header_list("\"Bob, Jr\" <sip:bob@a.com>;q=0.5, <sip:bob@b.com>");
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1], "<sip:bob@b.com>");
+    }
+}
@@ -0,0 +1,347 @@
+/// SIP 事件订阅模块（RFC 6665）
+///
+/// 以 SUBSCRIBE 建立订阅对话（如 `presence` 在线状态 / BLF、`message-summary` 语音信箱 MWI），
+/// 收到的 NOTIFY 以 200 OK 应答并通过通道上报，到期前自动刷新，`unsubscribe` 以 `Expires: 0` 结束订阅
+use crate::error::{CallError, CallResult};
+use rsip::prelude::UntypedHeader;
+use rsipstack::dialog::dialog::DialogState;
+use rsipstack::dialog::dialog_layer::DialogLayer;
+use rsipstack::dialog::subscription::ClientSubscriptionDialog;
+use rsipstack::dialog::DialogId;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn, Instrument};
+
+/// 刷新订阅时相对到期时间的提前量（秒），预留一个事务超时（64*T1）
+const REFRESH_MARGIN_SECS: u32 = 32;
+
+/// 退订后等待最终 NOTIFY（`terminated`）的时间，超时后移除对话
+const FINAL_NOTIFY_WAIT: Duration = Duration::from_secs(5);
+
+/// `Subscription-State` 头部中的订阅状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubscriptionState {
+    /// 订阅已被接受
+    Active,
+    /// 订阅等待授权
+    Pending,
+    /// 订阅已结束，附带 `reason` 参数（如 `timeout`、`noresource`）
+    Terminated(Option<String>),
+}
+
+impl SubscriptionState {
+    /// 解析 `Subscription-State` 头部的值，未知状态按 `Active` 处理
+    pub fn parse(value: &str) -> Self {
+        let mut parts = value.split(';').map(str::trim);
+        match parts.next().unwrap_or_default().to_ascii_lowercase().as_str() {
+            "pending" => SubscriptionState::Pending,
+            "terminated" => SubscriptionState::Terminated(
+                parts
+                    .filter_map(|param| param.split_once('='))
+                    .find(|(name, _)| name.trim().eq_ignore_ascii_case("reason"))
+                    .map(|(_, reason)| reason.trim().to_string()),
+            ),
+            _ => SubscriptionState::Active,
+        }
+    }
+}
+
+/// 收到的 NOTIFY 内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    /// `Event` 头部的值（如 `presence`、`message-summary`）
+    pub event: String,
+    /// 通知携带的订阅状态，缺少 `Subscription-State` 时为 `Active`
+    pub state: SubscriptionState,
+    /// 消息体的内容类型（如 `application/pidf+xml`）
+    pub content_type: Option<String>,
+    /// 消息体
+    pub body: Vec<u8>,
+}
+
+impl Notification {
+    /// 从 NOTIFY 请求中提取通知内容
+    pub fn from_request(request: &rsip::Request) -> Self {
+        let mut notification = Notification {
+            event: String::new(),
+            state: SubscriptionState::Active,
+            content_type: None,
+            body: request.body.clone(),
+        };
+        for header in request.headers.iter() {
+            match header {
+                rsip::Header::Event(event) => notification.event = event.value().trim().to_string(),
+                rsip::Header::SubscriptionState(state) => {
+                    notification.state = SubscriptionState::parse(state.value())
+                }
+                // rsip 不解析 Subscription-State，收到的头部为 Other
+                rsip::Header::Other(name, value) if name.trim().eq_ignore_ascii_case("Subscription-State") => {
+                    notification.state = SubscriptionState::parse(value)
+                }
+                rsip::Header::ContentType(content_type) => {
+                    notification.content_type = Some(content_type.value().trim().to_string())
+                }
+                _ => {}
+            }
+        }
+        notification
+    }
+
+    /// 以文本形式返回消息体
+    pub fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// 常见事件包默认接受的通知内容类型
+pub fn accept_for_event(event: &str) -> Option<&'static str> {
+    match event.split(';').next().unwrap_or_default().trim() {
+        "presence" => Some("application/pidf+xml"),
+        "message-summary" => Some("application/simple-message-summary"),
+        "dialog" => Some("application/dialog-info+xml"),
+        _ => None,
+    }
+}
+
+/// 距到期多久发送刷新：提前 [`REFRESH_MARGIN_SECS`]，但不早于订阅时长的一半
+fn refresh_delay(expires: u32) -> Duration {
+    let secs = expires.saturating_sub(REFRESH_MARGIN_SECS).max(expires / 2).max(1);
+    Duration::from_secs(secs.into())
+}
+
+/// 已建立的事件订阅
+///
+/// 由 `SipClient::subscribe` 创建；丢弃后仍按期刷新，需调用 [`unsubscribe`](Self::unsubscribe) 结束
+pub struct Subscription {
+    dialog: ClientSubscriptionDialog,
+    dialog_layer: Arc<DialogLayer>,
+    event: String,
+    requested: u32,
+    expires: Arc<AtomicU32>,
+    notifications: Option<UnboundedReceiver<Notification>>,
+    refresher: CancellationToken,
+}
+
+impl Subscription {
+    /// 以 SUBSCRIBE 的 2xx 建立的对话启动订阅：NOTIFY 转发到通知通道，并按 `expires` 定时刷新
+    pub(crate) fn start(
+        dialog: ClientSubscriptionDialog,
+        dialog_layer: Arc<DialogLayer>,
+        state_receiver: rsipstack::dialog::dialog::DialogStateReceiver,
+        event: &str,
+        requested: u32,
+        expires: u32,
+    ) -> Self {
+        let (sender, notifications) = mpsc::unbounded_channel();
+        let subscription = Self {
+            dialog,
+            dialog_layer,
+            event: event.to_string(),
+            requested,
+            expires: Arc::new(AtomicU32::new(expires)),
+            notifications: Some(notifications),
+            refresher: CancellationToken::new(),
+        };
+        subscription.spawn_watcher(state_receiver, sender);
+        subscription.spawn_refresher();
+        subscription
+    }
+
+    /// 订阅对话 ID
+    pub fn id(&self) -> DialogId {
+        self.dialog.id()
+    }
+
+    /// 订阅的事件包
+    pub fn event(&self) -> &str {
+        &self.event
+    }
+
+    /// 服务器最近一次确认的订阅时长（秒）
+    pub fn expires(&self) -> u32 {
+        self.expires.load(Ordering::Relaxed)
+    }
+
+    /// 订阅是否仍然有效（未退订且未被对端终止）
+    pub fn is_active(&self) -> bool {
+        !self.refresher.is_cancelled() && !self.dialog.state().is_terminated()
+    }
+
+    /// 获取 NOTIFY 通知接收端（只能获取一次）
+    ///
+    /// 对端以 `terminated` 结束订阅后通道关闭
+    pub fn notifications(&mut self) -> Option<UnboundedReceiver<Notification>> {
+        self.notifications.take()
+    }
+
+    /// 立即刷新订阅，返回服务器确认的订阅时长
+    pub async fn refresh(&self) -> CallResult<u32> {
+        refresh(&self.dialog, &self.event, self.requested, &self.expires).await
+    }
+
+    /// 退订：发送 `Expires: 0` 的 SUBSCRIBE 并停止刷新
+    ///
+    /// 对端随后的最终 NOTIFY 仍会上报；未收到时对话在短暂等待后移除
+    pub async fn unsubscribe(&self) -> CallResult<()> {
+        if !self.is_active() {
+            return Ok(());
+        }
+        self.refresher.cancel();
+        info!("📴 退订 {}: {}", self.event, self.id());
+        let result = send_subscribe(&self.dialog, &self.event, 0).await.map(|_| ());
+
+        let dialog_layer = self.dialog_layer.clone();
+        let id = self.id();
+        tokio::spawn(async move {
+            tokio::time::sleep(FINAL_NOTIFY_WAIT).await;
+            dialog_layer.remove_dialog(&id);
+        });
+        result
+    }
+
+    /// 处理对话状态：NOTIFY 以 200 OK 应答并转发，收到 `terminated` 后移除对话
+    fn spawn_watcher(
+        &self,
+        mut state_receiver: rsipstack::dialog::dialog::DialogStateReceiver,
+        sender: mpsc::UnboundedSender<Notification>,
+    ) {
+        let dialog_layer = self.dialog_layer.clone();
+        let refresher = self.refresher.clone();
+        let removed = self.dialog.cancel_token().clone();
+        let watcher = async move {
+            loop {
+                let state = tokio::select! {
+                    state = state_receiver.recv() => state,
+                    _ = removed.cancelled() => None,
+                };
+                match state {
+                    Some(DialogState::Notify(id, request, handle)) => {
+                        if let Err(e) = handle.reply(rsip::StatusCode::OK).await {
+                            warn!("回复 NOTIFY 失败: {}", e);
+                        }
+                        let notification = Notification::from_request(&request);
+                        debug!("收到 NOTIFY ({}, {:?}): {}", notification.event, notification.state, id);
+                        let terminated = matches!(notification.state, SubscriptionState::Terminated(_));
+                        let _ = sender.send(notification);
+                        if terminated {
+                            info!("订阅已结束: {}", id);
+                            refresher.cancel();
+                            dialog_layer.remove_dialog(&id);
+                            break;
+                        }
+                    }
+                    Some(DialogState::Terminated(..)) | None => break,
+                    Some(_) => {}
+                }
+            }
+        };
+        tokio::spawn(watcher.in_current_span());
+    }
+
+    /// 在到期前刷新订阅，刷新失败或退订后退出
+    fn spawn_refresher(&self) {
+        let dialog = self.dialog.clone();
+        let event = self.event.clone();
+        let requested = self.requested;
+        let expires = self.expires.clone();
+        let cancel = self.refresher.clone();
+        let refresher = async move {
+            loop {
+                let delay = refresh_delay(expires.load(Ordering::Relaxed));
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tokio::time::sleep(delay) => {}
+                }
+                if let Err(e) = refresh(&dialog, &event, requested, &expires).await {
+                    warn!("刷新订阅失败，停止刷新: {}", e);
+                    cancel.cancel();
+                    break;
+                }
+            }
+        };
+        tokio::spawn(refresher.in_current_span());
+    }
+}
+
+async fn refresh(
+    dialog: &ClientSubscriptionDialog,
+    event: &str,
+    requested: u32,
+    expires: &AtomicU32,
+) -> CallResult<u32> {
+    let resp = send_subscribe(dialog, event, requested).await?;
+    let granted = granted_expires(&resp).unwrap_or(requested);
+    expires.store(granted, Ordering::Relaxed);
+    info!("🔁 订阅已刷新 ({}, {}s)", event, granted);
+    Ok(granted)
+}
+
+/// 在订阅对话内发送 SUBSCRIBE，非 2xx 最终响应返回 `CallRejected`
+async fn send_subscribe(
+    dialog: &ClientSubscriptionDialog,
+    event: &str,
+    expires: u32,
+) -> CallResult<rsip::Response> {
+    let headers = vec![
+        rsip::Header::Event(event.into()),
+        rsip::Header::Expires(expires.into()),
+    ];
+    match dialog.subscribe(Some(headers), None).await? {
+        Some(resp) if resp.status_code.kind() == rsip::StatusCodeKind::Successful => Ok(resp),
+        Some(resp) => Err(CallError::rejected(&resp.status_code)),
+        None => Err(CallError::NotConnected),
+    }
+}
+
+/// SUBSCRIBE 的 2xx 中服务器确认的订阅时长
+pub(crate) fn granted_expires(resp: &rsip::Response) -> Option<u32> {
+    resp.headers.iter().find_map(|header| match header {
+        rsip::Header::Expires(expires) => expires.value().trim().parse().ok(),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_notification() {
+        assert_eq!(SubscriptionState::parse("active;expires=3600"), SubscriptionState::Active);
+        assert_eq!(SubscriptionState::parse("Pending"), SubscriptionState::Pending);
+        assert_eq!(
+            SubscriptionState::parse("terminated;reason=timeout"),
+            SubscriptionState::Terminated(Some("timeout".into()))
+        );
+        assert_eq!(SubscriptionState::parse("terminated"), SubscriptionState::Terminated(None));
+
+        let request = rsip::Request {
+            method: rsip::Method::Notify,
+            uri: "sip:alice@192.0.2.1".try_into().unwrap(),
+            version: rsip::Version::V2,
+            headers: vec![
+                rsip::Header::Event("message-summary".into()),
+                rsip::Header::Other("Subscription-State".into(), "terminated;reason=noresource".into()),
+                rsip::Header::ContentType("application/simple-message-summary".into()),
+            ]
+            .into(),
+            body: b"Messages-Waiting: yes\r\n".to_vec(),
+        };
+        let notification = Notification::from_request(&request);
+        assert_eq!(notification.event, "message-summary");
+        assert_eq!(notification.state, SubscriptionState::Terminated(Some("noresource".into())));
+        assert_eq!(
+            notification.content_type.as_deref(),
+            Some("application/simple-message-summary")
+        );
+        assert!(notification.body_text().starts_with("Messages-Waiting: yes"));
+
+        assert_eq!(accept_for_event("presence"), Some("application/pidf+xml"));
+        assert_eq!(accept_for_event("foo"), None);
+        assert_eq!(refresh_delay(3600), Duration::from_secs(3568));
+        assert_eq!(refresh_delay(40), Duration::from_secs(20));
+    }
+}
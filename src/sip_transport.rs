@@ -2,47 +2,188 @@
 ///
 /// 包含创建各种传输连接和 SDP 解析的辅助函数
 use crate::config::Protocol;
+use crate::error::{CallError, ConfigError};
+use crate::utils::mark_dscp;
 use rsipstack::transport::{
-    tcp::TcpConnection, udp::UdpConnection, websocket::WebSocketConnection, SipAddr,
+    channel::ChannelConnection,
+    stream::{SipCodec, SipCodecType},
+    tcp::TcpConnection,
+    tls::TlsConnection,
+    udp::{UdpConnection, UdpInner},
+    websocket::WebSocketConnection,
+    SipAddr, SipConnection, TransportEvent,
 };
-use std::net::SocketAddr;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, RootCertStore, SignatureScheme};
+use socket2::SockRef;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_util::codec::Decoder;
 use tokio_util::sync::CancellationToken;
-use tracing::info;
+use tracing::{debug, info, warn, Instrument};
+
+/// TLS 传输的证书校验选项
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    /// PEM 格式的根证书（可包含多个），用于校验服务器证书；未设置时使用内置的 Mozilla 根证书
+    pub ca_certs: Option<Vec<u8>>,
+    /// 跳过服务器证书校验（接受自签名证书），仅用于测试环境
+    pub allow_self_signed: bool,
+}
+
+impl TlsOptions {
+    /// 从 PEM 文件加载根证书
+    pub fn with_ca_file(mut self, path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        self.ca_certs = Some(std::fs::read(path)?);
+        Ok(self)
+    }
+
+    /// 设置是否接受自签名证书
+    pub fn with_allow_self_signed(mut self, allow: bool) -> Self {
+        self.allow_self_signed = allow;
+        self
+    }
+
+    /// 构造服务器证书校验器
+    ///
+    /// rsipstack 的 TLS 客户端使用空的根证书库，这里按 `ca_certs` 构造根证书库；
+    /// 未提供根证书时使用内置的 Mozilla 根证书（webpki-roots）
+    pub fn server_cert_verifier(&self) -> Result<Arc<dyn ServerCertVerifier>, ConfigError> {
+        let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+        if self.allow_self_signed {
+            return Ok(Arc::new(AcceptAnyServerCert(provider)));
+        }
+
+        let mut roots = RootCertStore::empty();
+        match &self.ca_certs {
+            Some(pem) => {
+                for cert in CertificateDer::pem_slice_iter(pem) {
+                    let cert = cert.map_err(|e| ConfigError::Invalid(format!("无效的 PEM 根证书: {}", e)))?;
+                    roots
+                        .add(cert)
+                        .map_err(|e| ConfigError::Invalid(format!("无法加载根证书: {}", e)))?;
+                }
+                if roots.is_empty() {
+                    return Err(ConfigError::Invalid("根证书文件中没有证书".to_string()));
+                }
+            }
+            None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+        }
+        WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider)
+            .build()
+            .map(|verifier| verifier as Arc<dyn ServerCertVerifier>)
+            .map_err(|e| ConfigError::Invalid(format!("无法创建证书校验器: {}", e)))
+    }
+}
+
+/// 接受任意服务器证书的校验器（仍校验握手签名），对应 `allow_self_signed`
+#[derive(Debug)]
+struct AcceptAnyServerCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyServerCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
 
 /// 根据协议类型创建传输连接
 ///
 /// # 参数
-/// - `protocol`: 传输协议类型（UDP/TCP/WS/WSS）
-/// - `local_addr`: 本地绑定地址
-/// - `server_addr`: 服务器地址
+/// - `protocol`: 传输协议类型（UDP/TCP/WS/WSS/TLS）
+/// - `local_addr`: 本地绑定地址（端口为 0 时自动分配）。UDP 与 TCP 绑定该地址；
+///   WS/WSS/TLS 由 rsipstack 自行建立连接，只能使用临时端口
+/// - `server_addr`: 服务器地址（TLS 未带端口时连接 5061）
+/// - `tls`: TLS 证书校验选项，仅 TLS 协议使用
+/// - `dscp`: 信令报文的 DSCP 标记，仅 UDP 与 TCP 生效；设置失败时只记录警告
 /// - `cancel_token`: 取消令牌用于优雅关闭
 ///
 /// # 返回
-/// 返回对应协议的 SIP 连接
+/// 返回对应协议的 SIP 连接；本地端口被占用时返回 `CallError::AddressInUse`
 pub async fn create_transport_connection(
     protocol: Protocol,
     local_addr: SocketAddr,
     server_addr: &str,
+    tls: &TlsOptions,
+    dscp: Option<u8>,
     cancel_token: CancellationToken,
 ) -> Result<rsipstack::transport::SipConnection, Box<dyn std::error::Error>> {
+    if matches!(protocol, Protocol::Ws | Protocol::Wss | Protocol::Tls) {
+        if local_addr.port() != 0 {
+            warn!("{} 传输不支持固定本地端口，忽略 {}", protocol, local_addr);
+        }
+        if let Some(dscp) = dscp {
+            warn!("{} 传输不支持 DSCP 标记，忽略 {}", protocol, dscp);
+        }
+    }
     match protocol {
         Protocol::Udp => {
             info!("创建 UDP 连接: {}", local_addr);
-            let connection = UdpConnection::create_connection(
-                local_addr,
+            let socket = tokio::net::UdpSocket::bind(local_addr)
+                .await
+                .map_err(|e| bind_error(local_addr, e))?;
+            if let Some(dscp) = dscp {
+                mark_dscp(SockRef::from(&socket), local_addr.is_ipv6(), dscp, "SIP UDP 套接字");
+            }
+            let addr = SipAddr {
+                r#type: Some(rsip::transport::Transport::Udp),
+                addr: SipConnection::resolve_bind_address(socket.local_addr()?).into(),
+            };
+            let connection = UdpConnection::attach(
+                UdpInner { conn: socket, addr },
                 None, // external address
                 Some(cancel_token.child_token()),
             )
-            .await?;
+            .await;
             Ok(connection.into())
         }
         Protocol::Tcp => {
-            info!("创建 TCP 连接到服务器: {}", server_addr);
+            info!("创建 TCP 连接到服务器: {} (本地 {})", server_addr, local_addr);
             // 将服务器地址转换为 SipAddr
             let server_sip_addr =
                 SipAddr::new(rsip::transport::Transport::Tcp, server_addr.try_into()?);
+            let stream = connect_tcp(local_addr, server_sip_addr.get_socketaddr()?, dscp).await?;
+            let bound = SipAddr {
+                r#type: Some(rsip::transport::Transport::Tcp),
+                addr: stream.local_addr()?.into(),
+            };
             let connection =
-                TcpConnection::connect(&server_sip_addr, Some(cancel_token.child_token())).await?;
+                TcpConnection::from_stream(stream, bound, Some(cancel_token.child_token()))?;
             Ok(connection.into())
         }
         Protocol::Ws => {
@@ -65,6 +206,274 @@ pub async fn create_transport_connection(
                     .await?;
             Ok(connection.into())
         }
+        Protocol::Tls => {
+            info!("创建 TLS 连接到服务器: {}", server_addr);
+            let verifier = tls.server_cert_verifier()?;
+            // rsipstack 使用进程级默认 CryptoProvider 构造 ClientConfig；
+            // 同时启用了多个 provider 时必须显式安装，已安装时忽略
+            let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+            let server_sip_addr =
+                SipAddr::new(rsip::transport::Transport::Tls, server_addr.try_into()?);
+            let connection = TlsConnection::connect(
+                &server_sip_addr,
+                Some(verifier),
+                Some(cancel_token.child_token()),
+            )
+            .await?;
+            Ok(connection.into())
+        }
+    }
+}
+
+/// 传输连接实际绑定的本地地址
+///
+/// rsipstack 流式连接的 `get_addr` 返回对端地址，TCP 需读取连接内部的本地地址；
+/// WS/WSS/TLS 无法取得本地地址时返回 `None`
+pub fn connection_local_addr(connection: &rsipstack::transport::SipConnection) -> Option<SocketAddr> {
+    match connection {
+        SipConnection::Udp(udp) => udp.get_addr().get_socketaddr().ok(),
+        SipConnection::Tcp(tcp) => tcp.inner.local_addr.get_socketaddr().ok(),
+        _ => None,
+    }
+}
+
+/// 先绑定本地地址再连接，以便固定本地端口
+async fn connect_tcp(
+    local_addr: SocketAddr,
+    server: SocketAddr,
+    dscp: Option<u8>,
+) -> Result<TcpStream, Box<dyn std::error::Error>> {
+    let socket = if local_addr.is_ipv4() {
+        tokio::net::TcpSocket::new_v4()?
+    } else {
+        tokio::net::TcpSocket::new_v6()?
+    };
+    socket.set_reuseaddr(true)?;
+    socket.bind(local_addr).map_err(|e| bind_error(local_addr, e))?;
+    if let Some(dscp) = dscp {
+        mark_dscp(SockRef::from(&socket), local_addr.is_ipv6(), dscp, "SIP TCP 套接字");
+    }
+    Ok(socket.connect(server).await?)
+}
+
+/// 断线后自动重连的 TCP 出口连接
+///
+/// rsipstack 的连接池不会移除已关闭的 TCP 连接，之后的请求会一直写入失效的连接。
+/// 这里以内存通道连接（`SipConnection::Channel`）接入传输层，由后台任务持有实际的 TCP 流：
+/// 对端关闭连接（EOF）、读取出错或写入失败时丢弃该流，下一条待发消息重新建立连接后发送
+pub struct TcpLink {
+    connection: SipConnection,
+    local_addr: SocketAddr,
+    reset: mpsc::UnboundedSender<()>,
+}
+
+impl TcpLink {
+    /// 建立到 `server_addr` 的 TCP 连接并启动收发任务，`cancel_token` 取消时关闭连接
+    pub async fn connect(
+        local_addr: SocketAddr,
+        server_addr: &str,
+        dscp: Option<u8>,
+        cancel_token: CancellationToken,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        info!("创建 TCP 连接到服务器: {} (本地 {})", server_addr, local_addr);
+        let remote = SipAddr::new(rsip::transport::Transport::Tcp, server_addr.try_into()?);
+        let server = remote.get_socketaddr()?;
+        let stream = connect_tcp(local_addr, server, dscp).await?;
+        let bound = stream.local_addr()?;
+
+        let (incoming_tx, incoming_rx) = mpsc::unbounded_channel();
+        let (outgoing_tx, outgoing_rx) = mpsc::unbounded_channel();
+        let (reset, reset_rx) = mpsc::unbounded_channel();
+        let channel = ChannelConnection::create_connection(
+            incoming_rx,
+            outgoing_tx,
+            remote.clone(),
+            Some(cancel_token.child_token()),
+        )
+        .await?;
+        let connection = SipConnection::Channel(channel);
+        let link = TcpLinkTask {
+            connection: connection.clone(),
+            remote,
+            local_addr,
+            server,
+            dscp,
+            incoming: incoming_tx,
+        };
+        tokio::spawn(link.run(stream, outgoing_rx, reset_rx, cancel_token).in_current_span());
+
+        Ok(Self {
+            connection,
+            local_addr: bound,
+            reset,
+        })
+    }
+
+    /// 接入传输层的连接，地址为服务器地址
+    pub fn connection(&self) -> SipConnection {
+        self.connection.clone()
+    }
+
+    /// 首次建立连接时的本地地址
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// 关闭当前 TCP 流，下一条消息重新建立连接
+    pub fn reset(&self) {
+        let _ = self.reset.send(());
+    }
+}
+
+/// [`TcpLink`] 的后台收发任务
+struct TcpLinkTask {
+    connection: SipConnection,
+    remote: SipAddr,
+    local_addr: SocketAddr,
+    server: SocketAddr,
+    dscp: Option<u8>,
+    incoming: mpsc::UnboundedSender<TransportEvent>,
+}
+
+impl TcpLinkTask {
+    async fn run(
+        self,
+        stream: TcpStream,
+        mut outgoing: mpsc::UnboundedReceiver<TransportEvent>,
+        mut reset: mpsc::UnboundedReceiver<()>,
+        cancel_token: CancellationToken,
+    ) {
+        let mut stream = Some(stream);
+        let mut codec = SipCodec::new();
+        let mut buffer = bytes::BytesMut::new();
+        let mut chunk = vec![0u8; 8192];
+        loop {
+            tokio::select! {
+                biased;
+                _ = cancel_token.cancelled() => break,
+                Some(()) = reset.recv() => {
+                    if let Some(mut closed) = stream.take() {
+                        debug!("关闭 TCP 连接以重新建立: {}", self.server);
+                        let _ = closed.shutdown().await;
+                    }
+                    buffer.clear();
+                }
+                // 先处理已到达的数据与 EOF，避免向已被对端关闭的连接写入
+                read = async { stream.as_mut().unwrap().read(&mut chunk).await }, if stream.is_some() => {
+                    match read {
+                        Ok(0) | Err(_) => {
+                            info!("TCP 连接已断开，下次发送时重新连接: {}", self.server);
+                            stream = None;
+                            buffer.clear();
+                        }
+                        Ok(len) => {
+                            buffer.extend_from_slice(&chunk[..len]);
+                            if !self.dispatch(&mut codec, &mut buffer, &mut stream).await {
+                                stream = None;
+                                buffer.clear();
+                            }
+                        }
+                    }
+                }
+                event = outgoing.recv() => {
+                    let Some(TransportEvent::Incoming(msg, ..)) = event else {
+                        if event.is_none() {
+                            break;
+                        }
+                        continue;
+                    };
+                    if self.send(&mut stream, msg.to_string().as_bytes()).await {
+                        buffer.clear();
+                    }
+                }
+            }
+        }
+        if let Some(mut stream) = stream {
+            let _ = stream.shutdown().await;
+        }
+    }
+
+    /// 发送一条消息：没有可用连接时先重新连接，写入失败时重连后重试一次。
+    /// 返回是否新建了连接
+    async fn send(&self, stream: &mut Option<TcpStream>, data: &[u8]) -> bool {
+        let mut reconnected = false;
+        for _ in 0..2 {
+            if stream.is_none() {
+                match connect_tcp(self.local_addr, self.server, self.dscp).await {
+                    Ok(connected) => {
+                        info!("已重新建立 TCP 连接: {} -> {}", connected.local_addr().map(|a| a.to_string()).unwrap_or_default(), self.server);
+                        *stream = Some(connected);
+                        reconnected = true;
+                    }
+                    Err(e) => {
+                        warn!("重新建立 TCP 连接失败: {}: {}", self.server, e);
+                        return reconnected;
+                    }
+                }
+            }
+            let Some(current) = stream.as_mut() else {
+                break;
+            };
+            match current.write_all(data).await {
+                Ok(()) => return reconnected,
+                Err(e) => {
+                    warn!("TCP 写入失败，重新连接: {}: {}", self.server, e);
+                    *stream = None;
+                }
+            }
+        }
+        reconnected
+    }
+
+    /// 解析缓冲区中的完整消息并交给传输层；连接不可用时返回 `false`
+    async fn dispatch(
+        &self,
+        codec: &mut SipCodec,
+        buffer: &mut bytes::BytesMut,
+        stream: &mut Option<TcpStream>,
+    ) -> bool {
+        loop {
+            match codec.decode(buffer) {
+                Ok(Some(SipCodecType::Message(msg))) => {
+                    let msg = match SipConnection::update_msg_received(msg, self.server, rsip::transport::Transport::Tcp) {
+                        Ok(msg) => msg,
+                        Err(e) => {
+                            warn!("丢弃无效的 SIP 消息: {}", e);
+                            continue;
+                        }
+                    };
+                    let event = TransportEvent::Incoming(msg, self.connection.clone(), self.remote.clone());
+                    if self.incoming.send(event).is_err() {
+                        return false;
+                    }
+                }
+                Ok(Some(SipCodecType::KeepaliveRequest)) => {
+                    let Some(current) = stream.as_mut() else {
+                        return false;
+                    };
+                    if current.write_all(b"\r\n").await.is_err() {
+                        return false;
+                    }
+                }
+                Ok(Some(SipCodecType::KeepaliveResponse)) => {}
+                Ok(None) => return true,
+                Err(e) => {
+                    warn!("解析 TCP 消息失败，断开连接: {}", e);
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+/// 将绑定失败转换为错误，端口被占用时返回 `CallError::AddressInUse`
+pub(crate) fn bind_error(local_addr: SocketAddr, e: std::io::Error) -> Box<dyn std::error::Error> {
+    if e.kind() == std::io::ErrorKind::AddrInUse {
+        Box::new(CallError::AddressInUse {
+            addr: local_addr.to_string(),
+        })
+    } else {
+        Box::new(e)
     }
 }
 
@@ -74,10 +483,12 @@ pub async fn create_transport_connection(
 /// - `sdp`: SDP 消息内容
 ///
 /// # 返回
-/// 返回 IP:Port 格式的 RTP 地址，如果解析失败则返回 None
+/// 返回 IP:Port 格式的 RTP 地址（IPv6 为 `[IP]:Port`），如果解析失败则返回 None
 ///
 /// # 示例
 /// ```
+/// use sip_caller::sip_transport::extract_peer_rtp_addr;
+///
 /// let sdp = r#"
 /// v=0
 /// o=- 123 456 IN IP4 192.168.1.100
@@ -98,9 +509,9 @@ pub fn extract_peer_rtp_addr(sdp: &str) -> Option<String> {
         let line = line.trim();
         // 解析 c= 行获取 IP
         if line.starts_with("c=") {
-            // c=IN IP4 192.168.1.100
+            // c=IN IP4 192.168.1.100 / c=IN IP6 2001:db8::1
             if let Some(addr) = line.split_whitespace().last() {
-                ip = Some(addr.to_string());
+                ip = Some(addr.trim_start_matches('[').trim_end_matches(']').to_string());
             }
         }
         // 解析 m= 行获取端口
@@ -113,17 +524,590 @@ pub fn extract_peer_rtp_addr(sdp: &str) -> Option<String> {
         }
     }
 
-    if let (Some(ip), Some(port)) = (ip, port) {
-        Some(format!("{}:{}", ip, port))
-    } else {
-        None
+    let (ip, port) = (ip?, port?);
+    match ip.parse::<IpAddr>() {
+        Ok(ip) => Some(SocketAddr::new(ip, port).to_string()),
+        Err(_) => Some(format!("{}:{}", ip, port)),
+    }
+}
+
+/// 按实际地址族修正 `o=` / `c=` 行的地址类型（`IP4`/`IP6`），并去掉 IPv6 地址的方括号
+///
+/// rustrtc 生成的 SDP 总是写 `IN IP4`，本地 RTP 地址为 IPv6 时需在发出前修正
+pub fn normalize_address_types(sdp: &str) -> String {
+    let mut lines = Vec::new();
+    for line in sdp.lines().map(str::trim_end).filter(|line| !line.is_empty()) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let n = fields.len();
+        let is_address_line = (line.starts_with("o=") || line.starts_with("c="))
+            && n >= 3
+            && (fields[n - 3] == "IN" || fields[n - 3].ends_with("=IN"));
+        let ip = fields
+            .last()
+            .and_then(|addr| addr.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>().ok());
+        match ip {
+            Some(ip) if is_address_line => {
+                let family = if ip.is_ipv6() { "IP6" } else { "IP4" };
+                lines.push(format!("{} {} {}", fields[..n - 2].join(" "), family, ip));
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+    let mut result = lines.join("\r\n");
+    result.push_str("\r\n");
+    result
+}
+
+/// 解析 `c=` 行的连接地址，兼容带方括号的 IPv6 地址
+///
+/// 多播地址的 `/ttl` 后缀会被忽略；不是 `c=` 行或地址无法解析时返回 `None`
+pub fn connection_line_ip(line: &str) -> Option<IpAddr> {
+    let address = line.trim().strip_prefix("c=")?.split_whitespace().nth(2)?;
+    let address = address.split('/').next()?;
+    address.trim_start_matches('[').trim_end_matches(']').parse().ok()
+}
+
+/// 从 SDP 中提取指定媒体类型的端口
+///
+/// # 参数
+/// - `sdp`: SDP 消息内容
+/// - `media`: 媒体类型（如 `audio`、`video`）
+///
+/// # 返回
+/// 返回第一个匹配 m= 行的端口；端口为 0 表示该媒体流被拒绝
+pub fn extract_media_port(sdp: &str, media: &str) -> Option<u16> {
+    sdp.lines()
+        .map(str::trim)
+        .filter_map(|line| line.strip_prefix("m="))
+        .map(|m| m.split_whitespace().collect::<Vec<_>>())
+        .find(|parts| parts.first() == Some(&media))
+        .and_then(|parts| parts.get(1).and_then(|p| p.parse::<u16>().ok()))
+}
+
+/// 列出 SDP 中各媒体段的类型及是否被接受
+///
+/// 按 RFC 3264，answer 中端口为 0 的 m= 行表示该媒体流被拒绝
+pub fn media_stream_states(sdp: &str) -> Vec<(String, bool)> {
+    sdp.lines()
+        .map(str::trim)
+        .filter_map(|line| line.strip_prefix("m="))
+        .filter_map(|m| {
+            let mut parts = m.split_whitespace();
+            let media = parts.next()?.to_string();
+            let port = parts.next()?.split('/').next()?.parse::<u16>().ok()?;
+            Some((media, port != 0))
+        })
+        .collect()
+}
+
+/// SDP 媒体方向属性（RFC 3264）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MediaDirection {
+    /// 双向（未声明方向时的默认值）
+    #[default]
+    SendRecv,
+    /// 仅发送（保持通话时本端使用）
+    SendOnly,
+    /// 仅接收
+    RecvOnly,
+    /// 不收不发
+    Inactive,
+}
+
+impl MediaDirection {
+    /// 方向属性名（不含 `a=`）
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaDirection::SendRecv => "sendrecv",
+            MediaDirection::SendOnly => "sendonly",
+            MediaDirection::RecvOnly => "recvonly",
+            MediaDirection::Inactive => "inactive",
+        }
+    }
+
+    /// 从属性名解析方向
+    pub fn from_attribute(attr: &str) -> Option<Self> {
+        match attr.trim() {
+            "sendrecv" => Some(MediaDirection::SendRecv),
+            "sendonly" => Some(MediaDirection::SendOnly),
+            "recvonly" => Some(MediaDirection::RecvOnly),
+            "inactive" => Some(MediaDirection::Inactive),
+            _ => None,
+        }
+    }
+
+    /// 作为对本方向 offer 的 answer 是否合法
+    ///
+    /// 例如对 `sendonly`（保持）的 offer，answer 可以是 `recvonly` 或 `inactive`
+    pub fn accepts_answer(&self, answer: MediaDirection) -> bool {
+        match self {
+            MediaDirection::SendRecv => true,
+            MediaDirection::SendOnly => {
+                matches!(answer, MediaDirection::RecvOnly | MediaDirection::Inactive)
+            }
+            MediaDirection::RecvOnly => {
+                matches!(answer, MediaDirection::SendOnly | MediaDirection::Inactive)
+            }
+            MediaDirection::Inactive => answer == MediaDirection::Inactive,
+        }
+    }
+}
+
+impl std::fmt::Display for MediaDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 解析 SDP 中指定媒体段的方向
+///
+/// 媒体级属性优先于会话级属性，均未声明时为 `sendrecv`
+pub fn media_direction(sdp: &str, media: &str) -> MediaDirection {
+    let mut session = None;
+    let mut section = None;
+    let mut in_media = false;
+    let mut in_target = false;
+    for line in sdp.lines().map(str::trim) {
+        if let Some(m) = line.strip_prefix("m=") {
+            if in_target {
+                break;
+            }
+            in_media = true;
+            in_target = m.split_whitespace().next() == Some(media);
+            continue;
+        }
+        let Some(direction) = line.strip_prefix("a=").and_then(MediaDirection::from_attribute) else {
+            continue;
+        };
+        if !in_media {
+            session = Some(direction);
+        } else if in_target {
+            section = Some(direction);
+        }
+    }
+    section.or(session).unwrap_or_default()
+}
+
+/// 将 SDP 中所有未被拒绝的媒体段方向改写为 `direction`
+///
+/// 移除会话级和媒体级的原有方向属性，端口等其他内容保持不变
+pub fn set_media_direction(sdp: &str, direction: MediaDirection) -> String {
+    let mut out = String::with_capacity(sdp.len() + 16);
+    let mut pending = false;
+    let push_direction = |out: &mut String, pending: &mut bool| {
+        if *pending {
+            out.push_str(&format!("a={}\r\n", direction));
+            *pending = false;
+        }
+    };
+    for line in sdp.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if trimmed
+            .strip_prefix("a=")
+            .and_then(MediaDirection::from_attribute)
+            .is_some()
+        {
+            continue;
+        }
+        if let Some(m) = trimmed.strip_prefix("m=") {
+            push_direction(&mut out, &mut pending);
+            let port = m.split_whitespace().nth(1).and_then(|p| p.split('/').next());
+            pending = port != Some("0");
+        }
+        out.push_str(trimmed);
+        out.push_str("\r\n");
     }
+    push_direction(&mut out, &mut pending);
+    out
+}
+
+/// 从 SDP 中提取指定媒体类型 m= 行上的载荷类型列表
+pub fn extract_payload_types(sdp: &str, media: &str) -> Vec<u8> {
+    sdp.lines()
+        .map(str::trim)
+        .filter_map(|line| line.strip_prefix("m="))
+        .map(|m| m.split_whitespace().collect::<Vec<_>>())
+        .find(|parts| parts.first() == Some(&media))
+        .map(|parts| {
+            parts
+                .iter()
+                .skip(3)
+                .filter_map(|pt| pt.parse::<u8>().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 按 `a=rtpmap` 编码名称（不区分大小写）查找指定媒体段的载荷类型
+///
+/// 如 `find_rtpmap_payload_type(sdp, "audio", "telephone-event")`
+pub fn find_rtpmap_payload_type(sdp: &str, media: &str, encoding: &str) -> Option<u8> {
+    let mut in_section = false;
+    for line in sdp.lines().map(str::trim) {
+        if let Some(m) = line.strip_prefix("m=") {
+            in_section = m.split_whitespace().next() == Some(media);
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        let Some((pt, format)) = line
+            .strip_prefix("a=rtpmap:")
+            .and_then(|rest| rest.split_once(char::is_whitespace))
+        else {
+            continue;
+        };
+        let name = format.trim().split('/').next().unwrap_or_default();
+        if name.eq_ignore_ascii_case(encoding) {
+            return pt.parse().ok();
+        }
+    }
+    None
+}
+
+/// 查找指定媒体段中载荷类型 `payload_type` 的 `a=rtpmap` 编码名称
+///
+/// 没有 rtpmap 行时按 RFC 3551 静态载荷类型推断，未知时返回 `None`
+pub fn rtpmap_encoding(sdp: &str, media: &str, payload_type: u8) -> Option<String> {
+    rtpmap_format(sdp, media, payload_type).map(|(name, _, _)| name)
+}
+
+/// 查找指定媒体段中载荷类型 `payload_type` 的编码名称、时钟频率与声道数
+///
+/// 取自 `a=rtpmap:<pt> <编码>/<时钟频率>[/<声道数>]`，省略声道数时为 1；
+/// 没有 rtpmap 行时按 RFC 3551 静态载荷类型推断，未知时返回 `None`
+pub fn rtpmap_format(sdp: &str, media: &str, payload_type: u8) -> Option<(String, u32, u8)> {
+    let mut in_section = false;
+    for line in sdp.lines().map(str::trim) {
+        if let Some(m) = line.strip_prefix("m=") {
+            in_section = m.split_whitespace().next() == Some(media);
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        let Some((pt, format)) = line
+            .strip_prefix("a=rtpmap:")
+            .and_then(|rest| rest.split_once(char::is_whitespace))
+        else {
+            continue;
+        };
+        if pt.parse::<u8>().ok() == Some(payload_type) {
+            let mut parts = format.trim().split('/');
+            let name = parts.next()?.to_string();
+            let clock_rate = parts.next()?.parse().ok()?;
+            let channels = parts.next().map_or(Some(1), |c| c.parse().ok())?;
+            return Some((name, clock_rate, channels));
+        }
+    }
+    let name = match payload_type {
+        0 => "PCMU",
+        8 => "PCMA",
+        9 => "G722",
+        13 => "CN",
+        18 => "G729",
+        _ => return None,
+    };
+    Some((name.to_string(), 8000, 1))
+}
+
+/// 按 `map` 中的 `(原载荷类型, 新载荷类型)` 改写指定媒体段的载荷类型
+///
+/// 同时改写 m 行与对应的 `a=rtpmap` / `a=fmtp` / `a=rtcp-fb` 行，未列出的载荷类型与其他媒体段保持不变
+pub fn remap_payload_types(sdp: &str, media: &str, map: &[(u8, u8)]) -> String {
+    let remap = |pt: &str| -> String {
+        pt.parse::<u8>()
+            .ok()
+            .and_then(|pt| map.iter().find(|(from, _)| *from == pt))
+            .map_or_else(|| pt.to_string(), |(_, to)| to.to_string())
+    };
+    let mut in_section = false;
+    let mut lines = Vec::new();
+
+    for line in sdp.lines() {
+        let trimmed = line.trim();
+        if let Some(m) = trimmed.strip_prefix("m=") {
+            let parts: Vec<&str> = m.split_whitespace().collect();
+            in_section = parts.first() == Some(&media);
+            if in_section && parts.len() >= 3 {
+                let mut rebuilt = format!("m={} {} {}", parts[0], parts[1], parts[2]);
+                for pt in &parts[3..] {
+                    rebuilt.push(' ');
+                    rebuilt.push_str(&remap(pt));
+                }
+                lines.push(rebuilt);
+                continue;
+            }
+        } else if in_section {
+            let attribute = ["a=rtpmap:", "a=fmtp:", "a=rtcp-fb:"]
+                .iter()
+                .find_map(|prefix| trimmed.strip_prefix(prefix).map(|rest| (prefix, rest)));
+            if let Some((prefix, rest)) = attribute {
+                let (pt, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let value = if value.is_empty() { String::new() } else { format!(" {}", value) };
+                lines.push(format!("{}{}{}", prefix, remap(pt), value));
+                continue;
+            }
+        }
+        lines.push(trimmed.to_string());
+    }
+
+    let mut result = lines.join("\r\n");
+    result.push_str("\r\n");
+    result
+}
+
+/// 将指定媒体段的载荷类型限制为 `allowed` 中的值
+///
+/// 同时移除被删除载荷类型对应的 `a=rtpmap` / `a=fmtp` / `a=rtcp-fb` 行，
+/// 其他媒体段保持不变
+pub fn restrict_payload_types(sdp: &str, media: &str, allowed: &[u8]) -> String {
+    let mut in_section = false;
+    let mut lines = Vec::new();
+
+    for line in sdp.lines() {
+        let trimmed = line.trim();
+        if let Some(m) = trimmed.strip_prefix("m=") {
+            let parts: Vec<&str> = m.split_whitespace().collect();
+            in_section = parts.first() == Some(&media);
+            if in_section && parts.len() >= 3 {
+                let formats: Vec<&str> = parts[3..]
+                    .iter()
+                    .copied()
+                    .filter(|pt| pt.parse::<u8>().is_ok_and(|pt| allowed.contains(&pt)))
+                    .collect();
+                let mut rebuilt = format!("m={} {} {}", parts[0], parts[1], parts[2]);
+                for pt in formats {
+                    rebuilt.push(' ');
+                    rebuilt.push_str(pt);
+                }
+                lines.push(rebuilt);
+                continue;
+            }
+        } else if in_section {
+            let pt = ["a=rtpmap:", "a=fmtp:", "a=rtcp-fb:"]
+                .iter()
+                .find_map(|prefix| trimmed.strip_prefix(prefix))
+                .and_then(|rest| rest.split_whitespace().next())
+                .and_then(|pt| pt.parse::<u8>().ok());
+            if pt.is_some_and(|pt| !allowed.contains(&pt)) {
+                continue;
+            }
+        }
+        lines.push(trimmed.to_string());
+    }
+
+    let mut result = lines.join("\r\n");
+    result.push_str("\r\n");
+    result
+}
+
+/// 需要额外注入到本地 SDP 中的属性
+///
+/// 用于对接要求非标准属性的设备（如 `a=X-nat:0`、`a=label:1`）。
+/// 注入位置：
+/// - 会话级属性插入到第一个 `m=` 行之前，即所有自动生成的会话级属性之后
+/// - 媒体级属性追加到对应媒体段末尾，即该段所有自动生成的属性之后
+///
+/// 同一级别内按添加顺序输出
+///
+/// 设置了连接地址时（如 STUN 发现的公网 IP），同时改写 `o=` 与 `c=` 行中的地址
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SdpAttributes {
+    session: Vec<String>,
+    media: Vec<(String, String)>,
+    connection_address: Option<IpAddr>,
+}
+
+impl SdpAttributes {
+    /// 添加会话级属性，`attr` 可带或不带 `a=` 前缀
+    pub fn add_session(&mut self, attr: &str) -> Result<(), ConfigError> {
+        self.session.push(validate_sdp_attribute(attr)?);
+        Ok(())
+    }
+
+    /// 添加媒体级属性，`media` 为媒体类型（如 `audio`、`video`）
+    pub fn add_media(&mut self, media: &str, attr: &str) -> Result<(), ConfigError> {
+        if media.is_empty() || !media.chars().all(is_token_char) {
+            return Err(ConfigError::Invalid(format!("无效的媒体类型: {:?}", media)));
+        }
+        self.media.push((media.to_string(), validate_sdp_attribute(attr)?));
+        Ok(())
+    }
+
+    /// 设置对外通告的连接地址（NAT 后的公网 IP），`None` 时保留原地址
+    pub fn set_connection_address(&mut self, address: Option<IpAddr>) {
+        self.connection_address = address;
+    }
+
+    /// 对外通告的连接地址
+    pub fn connection_address(&self) -> Option<IpAddr> {
+        self.connection_address
+    }
+
+    /// 是否没有任何需要注入的属性或改写
+    pub fn is_empty(&self) -> bool {
+        self.session.is_empty() && self.media.is_empty() && self.connection_address.is_none()
+    }
+
+    /// 将属性注入到 SDP 中
+    pub fn apply(&self, sdp: &str) -> String {
+        if self.is_empty() {
+            return sdp.to_string();
+        }
+
+        let mut lines: Vec<String> = Vec::new();
+        let mut current_media: Option<String> = None;
+        let mut session_done = false;
+
+        let flush_media = |lines: &mut Vec<String>, media: &Option<String>| {
+            if let Some(media) = media {
+                lines.extend(
+                    self.media
+                        .iter()
+                        .filter(|(m, _)| m == media)
+                        .map(|(_, attr)| attr.clone()),
+                );
+            }
+        };
+
+        for line in sdp.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let Some(m) = line.strip_prefix("m=") {
+                if !session_done {
+                    lines.extend(self.session.iter().cloned());
+                    session_done = true;
+                }
+                flush_media(&mut lines, &current_media);
+                current_media = m.split_whitespace().next().map(str::to_string);
+            }
+            lines.push(self.rewrite_address(line));
+        }
+        if !session_done {
+            lines.extend(self.session.iter().cloned());
+        }
+        flush_media(&mut lines, &current_media);
+
+        let mut result = lines.join("\r\n");
+        result.push_str("\r\n");
+        result
+    }
+}
+
+impl SdpAttributes {
+    /// 将 `o=` / `c=` 行末尾的 `IN IP4|IP6 <addr>` 改写为连接地址
+    fn rewrite_address(&self, line: &str) -> String {
+        let Some(address) = self.connection_address else {
+            return line.to_string();
+        };
+        if !(line.starts_with("o=") || line.starts_with("c=")) {
+            return line.to_string();
+        }
+        let mut fields: Vec<String> = line.split_whitespace().map(str::to_string).collect();
+        let n = fields.len();
+        if n < 3 || fields[n - 3] != "IN" && !fields[n - 3].ends_with("=IN") {
+            return line.to_string();
+        }
+        fields[n - 2] = if address.is_ipv4() { "IP4" } else { "IP6" }.to_string();
+        fields[n - 1] = address.to_string();
+        fields.join(" ")
+    }
+}
+
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`{|}~".contains(c)
+}
+
+/// 校验并规范化 SDP 属性行
+///
+/// 接受 `name` 或 `name:value` 形式（可带 `a=` 前缀），
+/// 属性名必须为 token 字符，值不允许包含 CR/LF 等控制字符
+///
+/// # 返回
+/// 返回带 `a=` 前缀的属性行
+pub fn validate_sdp_attribute(attr: &str) -> Result<String, ConfigError> {
+    let body = attr.strip_prefix("a=").unwrap_or(attr);
+    if body.chars().any(|c| c.is_control()) {
+        return Err(ConfigError::Invalid(format!("SDP 属性包含控制字符: {:?}", attr)));
+    }
+    let name = body.split_once(':').map(|(name, _)| name).unwrap_or(body);
+    if name.is_empty() || !name.chars().all(is_token_char) {
+        return Err(ConfigError::Invalid(format!("无效的 SDP 属性名: {:?}", attr)));
+    }
+    Ok(format!("a={}", body))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_tls_protocol_and_verifier() {
+        let uri: rsip::Uri = "sip:example.com:5061;transport=tls".try_into().unwrap();
+        assert_eq!(crate::utils::extract_protocol_from_uri(&uri), Protocol::Tls);
+        let uri: rsip::Uri = "sips:example.com".try_into().unwrap();
+        assert_eq!(crate::utils::extract_protocol_from_uri(&uri), Protocol::Tls);
+        let uri: rsip::Uri = "sips:example.com;transport=tcp".try_into().unwrap();
+        assert_eq!(crate::utils::extract_protocol_from_uri(&uri), Protocol::Tcp);
+
+        // 未提供根证书时使用内置根证书
+        assert!(TlsOptions::default().server_cert_verifier().is_ok());
+        let empty = TlsOptions {
+            ca_certs: Some(Vec::new()),
+            allow_self_signed: false,
+        };
+        assert!(empty.server_cert_verifier().is_err());
+        let invalid = TlsOptions {
+            ca_certs: Some(b"not a certificate".to_vec()),
+            allow_self_signed: false,
+        };
+        assert!(invalid.server_cert_verifier().is_err());
+
+        let insecure = TlsOptions::default().with_allow_self_signed(true);
+        let verifier = insecure.server_cert_verifier().unwrap();
+        assert!(verifier
+            .verify_server_cert(
+                &CertificateDer::from(vec![0u8; 4]),
+                &[],
+                &ServerName::try_from("example.com").unwrap(),
+                &[],
+                UnixTime::now(),
+            )
+            .is_ok());
+        assert!(!verifier.supported_verify_schemes().is_empty());
+    }
+
+    #[test]
+    fn test_media_direction() {
+        let sdp = "v=0\r\na=sendonly\r\nm=audio 4000 RTP/AVP 0\r\nm=video 4002 RTP/AVP 96\r\na=inactive\r\n";
+        assert_eq!(media_direction(sdp, "audio"), MediaDirection::SendOnly);
+        assert_eq!(media_direction(sdp, "video"), MediaDirection::Inactive);
+        assert_eq!(
+            media_direction("m=audio 4000 RTP/AVP 0\r\n", "audio"),
+            MediaDirection::SendRecv
+        );
+
+        assert!(MediaDirection::SendOnly.accepts_answer(MediaDirection::RecvOnly));
+        assert!(MediaDirection::SendOnly.accepts_answer(MediaDirection::Inactive));
+        assert!(!MediaDirection::SendOnly.accepts_answer(MediaDirection::SendRecv));
+    }
+
+    #[test]
+    fn test_set_media_direction_keeps_ports() {
+        let sdp = "v=0\r\nm=audio 4000 RTP/AVP 0\r\na=rtpmap:0 PCMU/8000\r\na=sendrecv\r\nm=video 0 RTP/AVP 96\r\n";
+        let held = set_media_direction(sdp, MediaDirection::SendOnly);
+        assert_eq!(
+            held,
+            "v=0\r\nm=audio 4000 RTP/AVP 0\r\na=rtpmap:0 PCMU/8000\r\na=sendonly\r\nm=video 0 RTP/AVP 96\r\n"
+        );
+        assert_eq!(extract_media_port(&held, "audio"), Some(4000));
+        let resumed = set_media_direction(&held, MediaDirection::SendRecv);
+        assert_eq!(media_direction(&resumed, "audio"), MediaDirection::SendRecv);
+        assert!(!resumed.contains("sendonly"));
+    }
+
     #[test]
     fn test_extract_peer_rtp_addr() {
         let sdp = r#"v=0
@@ -137,6 +1121,25 @@ m=audio 20000 RTP/AVP 0"#;
         assert_eq!(addr, Some("192.168.1.100:20000".to_string()));
     }
 
+    #[test]
+    fn test_ipv6_connection_address() {
+        let sdp = "v=0\r\no=- 1 1 IN IP6 2001:db8::1\r\nc=IN IP6 2001:db8::1\r\nm=audio 20000 RTP/AVP 0\r\n";
+        assert_eq!(extract_peer_rtp_addr(sdp), Some("[2001:db8::1]:20000".to_string()));
+        let bracketed = sdp.replace("c=IN IP6 2001:db8::1", "c=IN IP6 [2001:db8::1]");
+        assert_eq!(extract_peer_rtp_addr(&bracketed), Some("[2001:db8::1]:20000".to_string()));
+
+        assert_eq!(connection_line_ip("c=IN IP6 [::1]"), Some("::1".parse().unwrap()));
+        assert_eq!(connection_line_ip("c=IN IP4 224.2.1.1/127"), Some("224.2.1.1".parse().unwrap()));
+        assert_eq!(connection_line_ip("o=- 1 1 IN IP4 10.0.0.1"), None);
+
+        // rustrtc 对 IPv6 地址写出的 IN IP4 被修正，IPv4 行保持不变
+        let generated = "v=0\r\no=- 1 1 IN IP4 ::1\r\nc=IN IP4 [::1]\r\nm=audio 20000 RTP/AVP 0\r\nc=IN IP4 10.0.0.1\r\n";
+        assert_eq!(
+            normalize_address_types(generated),
+            "v=0\r\no=- 1 1 IN IP6 ::1\r\nc=IN IP6 ::1\r\nm=audio 20000 RTP/AVP 0\r\nc=IN IP4 10.0.0.1\r\n"
+        );
+    }
+
     #[test]
     fn test_extract_peer_rtp_addr_missing_ip() {
         let sdp = r#"v=0
@@ -160,4 +1163,103 @@ t=0 0"#;
         let addr = extract_peer_rtp_addr(sdp);
         assert_eq!(addr, None);
     }
+
+    #[test]
+    fn test_extract_media_port() {
+        let sdp = "v=0\r\nc=IN IP4 10.0.0.1\r\nm=audio 20000 RTP/AVP 0\r\nm=video 0 RTP/AVP 96\r\n";
+        assert_eq!(extract_media_port(sdp, "audio"), Some(20000));
+        assert_eq!(extract_media_port(sdp, "video"), Some(0));
+        assert_eq!(extract_media_port(sdp, "application"), None);
+    }
+
+    #[test]
+    fn test_rtpmap_format_and_remap() {
+        let sdp = "v=0\r\nm=audio 20000 RTP/AVP 96 8 97 98\r\na=rtpmap:96 PCMU/8000\r\n\
+                   a=rtpmap:97 telephone-event/8000\r\na=fmtp:97 0-16\r\na=rtpmap:98 opus/48000/2\r\n";
+        assert_eq!(rtpmap_format(sdp, "audio", 96), Some(("PCMU".to_string(), 8000, 1)));
+        assert_eq!(rtpmap_format(sdp, "audio", 8), Some(("PCMA".to_string(), 8000, 1)));
+        assert_eq!(rtpmap_format(sdp, "audio", 98), Some(("opus".to_string(), 48000, 2)));
+        assert_eq!(rtpmap_format(sdp, "audio", 99), None);
+
+        let remapped = remap_payload_types(sdp, "audio", &[(96, 0), (97, 101), (8, 96)]);
+        assert_eq!(extract_payload_types(&remapped, "audio"), vec![0, 96, 101, 98]);
+        assert!(remapped.contains("a=rtpmap:0 PCMU/8000\r\n"));
+        assert!(remapped.contains("a=fmtp:101 0-16\r\n"));
+        assert!(remapped.contains("a=rtpmap:98 opus/48000/2\r\n"));
+    }
+
+    #[test]
+    fn test_restrict_payload_types() {
+        let sdp = "v=0\r\nm=audio 20000 RTP/AVP 0 8 101\r\na=rtpmap:0 PCMU/8000\r\n\
+                   a=rtpmap:8 PCMA/8000\r\na=rtpmap:101 telephone-event/8000\r\n\
+                   a=fmtp:101 0-16\r\nm=video 30000 RTP/AVP 96\r\na=rtpmap:96 VP8/90000\r\n";
+        assert_eq!(extract_payload_types(sdp, "audio"), vec![0, 8, 101]);
+
+        assert_eq!(find_rtpmap_payload_type(sdp, "audio", "Telephone-Event"), Some(101));
+        assert_eq!(find_rtpmap_payload_type(sdp, "video", "telephone-event"), None);
+
+        let restricted = restrict_payload_types(sdp, "audio", &[0]);
+        assert_eq!(extract_payload_types(&restricted, "audio"), vec![0]);
+        assert!(!restricted.contains("PCMA"));
+        assert!(!restricted.contains("a=fmtp:101"));
+        assert!(restricted.contains("a=rtpmap:96 VP8/90000"));
+    }
+
+    #[test]
+    fn test_media_stream_states() {
+        let sdp = "v=0\r\nm=audio 4000 RTP/AVP 0\r\nm=video 0 RTP/AVP 96\r\nm=application 5000/2 UDP/BFCP *\r\n";
+        assert_eq!(
+            media_stream_states(sdp),
+            vec![
+                ("audio".to_string(), true),
+                ("video".to_string(), false),
+                ("application".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_sdp_attribute() {
+        assert_eq!(validate_sdp_attribute("X-nat:0").unwrap(), "a=X-nat:0");
+        assert_eq!(validate_sdp_attribute("a=label:main audio").unwrap(), "a=label:main audio");
+        assert_eq!(validate_sdp_attribute("a=recvonly").unwrap(), "a=recvonly");
+        assert!(validate_sdp_attribute("X-nat:0\r\na=evil").is_err());
+        assert!(validate_sdp_attribute("bad name:1").is_err());
+        assert!(validate_sdp_attribute("a=").is_err());
+        assert!(validate_sdp_attribute(":value").is_err());
+    }
+
+    #[test]
+    fn test_sdp_attributes_apply_ordering() {
+        let sdp = "v=0\r\no=- 0 0 IN IP4 10.0.0.1\r\ns=-\r\nt=0 0\r\na=group:BUNDLE 0\r\n\
+                   m=audio 4000 RTP/AVP 0\r\na=rtpmap:0 PCMU/8000\r\n\
+                   m=video 4002 RTP/AVP 96\r\na=rtpmap:96 VP8/90000\r\n";
+        let mut attrs = SdpAttributes::default();
+        attrs.add_session("X-nat:0").unwrap();
+        attrs.add_media("audio", "label:1").unwrap();
+        attrs.add_media("video", "a=label:2").unwrap();
+        assert!(attrs.add_media("", "label:3").is_err());
+
+        let result = attrs.apply(sdp);
+        let lines: Vec<&str> = result.lines().collect();
+        assert_eq!(lines[5], "a=X-nat:0");
+        assert_eq!(lines[6], "m=audio 4000 RTP/AVP 0");
+        assert_eq!(lines[8], "a=label:1");
+        assert_eq!(lines[9], "m=video 4002 RTP/AVP 96");
+        assert_eq!(lines[11], "a=label:2");
+        assert_eq!(SdpAttributes::default().apply(sdp), sdp);
+    }
+
+    #[test]
+    fn test_sdp_attributes_connection_address() {
+        let sdp = "v=0\r\no=- 1 1 IN IP4 10.0.0.1\r\ns=-\r\nc=IN IP4 10.0.0.1\r\nt=0 0\r\n\
+                   m=audio 4000 RTP/AVP 0\r\nc=IN IP4 10.0.0.1\r\n";
+        let mut attrs = SdpAttributes::default();
+        attrs.set_connection_address(Some("203.0.113.9".parse().unwrap()));
+        let result = attrs.apply(sdp);
+        assert!(result.contains("o=- 1 1 IN IP4 203.0.113.9\r\n"));
+        assert_eq!(result.matches("c=IN IP4 203.0.113.9").count(), 2);
+        assert_eq!(extract_peer_rtp_addr(&result), Some("203.0.113.9:4000".to_string()));
+        assert!(!result.contains("10.0.0.1"));
+    }
 }
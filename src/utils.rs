@@ -2,13 +2,23 @@
 ///
 /// 提供自定义的 SIP 相关辅助函数，用于覆盖 rsipstack 的默认行为
 use crate::config::Protocol;
-use std::net::IpAddr;
+use crate::error::SdpValidationError;
+use crate::rtp_play::MediaPlayError;
+use rustrtc::media::MediaKind;
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
+use tokio::net::UdpSocket;
 
 /// 从 SIP URI 中提取 transport 协议
 ///
 /// 按照以下优先级提取:
 /// 1. 显式的 transport 参数 (如 ;transport=tcp)
-/// 2. 根据 URI scheme 推断 (sips -> TCP, sip -> UDP)
+/// 2. 根据 URI scheme 推断 (sips -> TLS, sip -> UDP)
+///
+/// `sips:` URI 未携带 transport 参数时按 TLS 处理；若同时未指定端口，
+/// 连接目标使用 TLS 默认端口 5061 而非 5060
 ///
 /// # 参数
 /// - `uri`: SIP URI 对象引用
@@ -19,6 +29,7 @@ use std::net::IpAddr;
 /// # 示例
 /// ```rust,no_run
 /// use rsip::Uri;
+/// use sip_caller::config::Protocol;
 /// use sip_caller::utils::extract_protocol_from_uri;
 ///
 /// let uri: Uri = "sip:example.com:5060;transport=tcp".try_into().unwrap();
@@ -27,7 +38,7 @@ use std::net::IpAddr;
 ///
 /// let uri2: Uri = "sips:example.com:5061".try_into().unwrap();
 /// let protocol2 = extract_protocol_from_uri(&uri2);
-/// assert_eq!(protocol2, Protocol::Tcp); // sips 默认 TLS over TCP
+/// assert_eq!(protocol2, Protocol::Tls); // sips 未指定 transport 时默认 TLS
 /// ```
 pub fn extract_protocol_from_uri(uri: &rsip::Uri) -> Protocol {
     // 1. 优先从 transport 参数提取
@@ -37,25 +48,54 @@ pub fn extract_protocol_from_uri(uri: &rsip::Uri) -> Protocol {
             rsip::Param::Transport(t) => Some((*t).into()),
             _ => None,
         })
-        .unwrap_or_else(|| {
+        .unwrap_or(
             // 2. 根据 scheme 返回默认值
             match uri.scheme.as_ref() {
-                Some(rsip::Scheme::Sips) => Protocol::Tcp, // sips默认TLS over TCP
+                // sips 未指定 transport 时使用 TLS；URI 未带端口时连接 5061
+                Some(rsip::Scheme::Sips) => Protocol::Tls,
                 Some(rsip::Scheme::Sip) | Some(rsip::Scheme::Other(_)) | None => Protocol::Udp,
-            }
-        })
+            },
+        )
+}
+
+/// 日志输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// 便于阅读的文本格式（默认）
+    #[default]
+    Pretty,
+    /// 每行一个 JSON 对象，便于 ELK/Loki 等系统采集
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pretty" | "text" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("无效的日志格式 '{}', 支持的格式: pretty, json", s)),
+        }
+    }
 }
 
 /// 初始化日志系统
 ///
 /// # 参数
 /// - `log_level`: 日志级别字符串 (trace, debug, info, warn, error)
+/// - `log_format`: 输出格式；[`LogFormat::Json`] 时事件字段（`message`、`event` 等）平铺在顶层，
+///   当前 span 的字段（如 `call_id`、`dialog_id`）位于 `span`，外层 span 位于 `spans`
 ///
 /// # 示例
 /// ```rust,no_run
-/// initialize_logging("debug");
+/// use sip_caller::utils::{initialize_logging, LogFormat};
+///
+/// initialize_logging("debug", LogFormat::Json);
 /// ```
-pub fn initialize_logging(log_level: &str) {
+pub fn initialize_logging(log_level: &str, log_format: LogFormat) {
+    use tracing_subscriber::util::SubscriberInitExt;
+
     let level = match log_level.to_lowercase().as_str() {
         "trace" => tracing::Level::TRACE,
         "debug" => tracing::Level::DEBUG,
@@ -68,10 +108,29 @@ pub fn initialize_logging(log_level: &str) {
         }
     };
 
+    match log_format {
+        LogFormat::Pretty => tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_line_number(true)
+            .init(),
+        LogFormat::Json => json_subscriber(level, std::io::stdout).init(),
+    }
+}
+
+/// 构造 JSON 格式的日志订阅器
+fn json_subscriber<W>(level: tracing::Level, writer: W) -> impl tracing::Subscriber + Send + Sync
+where
+    W: for<'a> tracing_subscriber::fmt::MakeWriter<'a> + Send + Sync + 'static,
+{
     tracing_subscriber::fmt()
+        .json()
+        .flatten_event(true)
+        .with_current_span(true)
+        .with_span_list(true)
         .with_max_level(level)
         .with_line_number(true)
-        .init();
+        .with_writer(writer)
+        .finish()
 }
 
 /// 获取第一个非回环的网络接口 IP 地址
@@ -94,13 +153,9 @@ pub fn initialize_logging(log_level: &str) {
 /// ```rust,no_run
 /// use sip_caller::utils::get_first_non_loopback_interface;
 ///
-/// // 优先使用 IPv6，找不到则回退到 IPv4
-/// let local_ip = get_first_non_loopback_interface(true).unwrap();
+/// // 优先使用 IPv4，找不到则回退到 IPv6
+/// let local_ip = get_first_non_loopback_interface().unwrap();
 /// println!("本地IP: {}", local_ip);
-///
-/// // 只使用 IPv4
-/// let local_ip_v4 = get_first_non_loopback_interface(false).unwrap();
-/// println!("本地IPv4: {}", local_ip_v4);
 /// ```
 pub fn get_first_non_loopback_interface() -> Result<IpAddr, Box<dyn std::error::Error>> {
     let interfaces = get_if_addrs::get_if_addrs()?;
@@ -138,6 +193,387 @@ pub fn get_first_non_loopback_interface() -> Result<IpAddr, Box<dyn std::error::
     Err("未找到可用的网络接口".into())
 }
 
+/// STUN 绑定请求的默认超时
+pub const STUN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// STUN 服务器未指定端口时使用的默认端口
+const STUN_DEFAULT_PORT: u16 = 3478;
+
+/// STUN magic cookie (RFC 5389)
+const STUN_MAGIC_COOKIE: u32 = 0x2112_A442;
+
+/// 通过 STUN 绑定请求（RFC 5389）发现本机在 NAT 外的映射地址
+///
+/// 使用临时 UDP 端口查询，只适合获取公网 IP；需要某个具体端口的映射时使用 [`stun_binding`]。
+/// `stun_server` 形如 `stun.example.com:3478`，未带端口时使用 3478
+///
+/// # 示例
+/// ```rust,no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use sip_caller::utils::discover_public_address;
+///
+/// let mapped = discover_public_address("stun.l.google.com:19302").await?;
+/// println!("公网地址: {}", mapped);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn discover_public_address(stun_server: &str) -> io::Result<SocketAddr> {
+    let server = resolve_stun_server(stun_server).await?;
+    let local: SocketAddr = if server.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(local).await?;
+    stun_binding(&socket, stun_server, STUN_TIMEOUT).await
+}
+
+/// 在指定套接字上发送 STUN 绑定请求，返回服务器观察到的映射地址
+///
+/// 按 RFC 5389 的重传策略（初始 500ms，逐次翻倍）重发请求，
+/// 超过 `timeout` 仍无响应时返回 `ErrorKind::TimedOut`
+pub async fn stun_binding(
+    socket: &UdpSocket,
+    stun_server: &str,
+    timeout: Duration,
+) -> io::Result<SocketAddr> {
+    let server = resolve_stun_server(stun_server).await?;
+    let transaction_id: [u8; 12] = rand::random();
+    let mut request = Vec::with_capacity(20);
+    request.extend_from_slice(&0x0001u16.to_be_bytes()); // Binding Request
+    request.extend_from_slice(&0u16.to_be_bytes());
+    request.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(&transaction_id);
+
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut rto = Duration::from_millis(500);
+    let mut buf = [0u8; 1024];
+    loop {
+        socket.send_to(&request, server).await?;
+        let wait_until = (tokio::time::Instant::now() + rto).min(deadline);
+        while let Ok(received) = tokio::time::timeout_at(wait_until, socket.recv_from(&mut buf)).await {
+            let (len, from) = received?;
+            if from != server {
+                continue;
+            }
+            if let Some(mapped) = parse_stun_binding_response(&buf[..len], &transaction_id) {
+                return Ok(mapped);
+            }
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("STUN 服务器 {} 在 {:?} 内无响应", stun_server, timeout),
+            ));
+        }
+        rto *= 2;
+    }
+}
+
+async fn resolve_stun_server(stun_server: &str) -> io::Result<SocketAddr> {
+    let has_port = stun_server
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+    let target = if has_port {
+        stun_server.to_string()
+    } else {
+        format!("{}:{}", stun_server, STUN_DEFAULT_PORT)
+    };
+    tokio::net::lookup_host(target).await?.next().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("无法解析 STUN 服务器: {}", stun_server))
+    })
+}
+
+/// 解析 STUN 绑定成功响应，优先使用 XOR-MAPPED-ADDRESS，回退到 MAPPED-ADDRESS
+fn parse_stun_binding_response(data: &[u8], transaction_id: &[u8; 12]) -> Option<SocketAddr> {
+    if data.len() < 20
+        || u16::from_be_bytes([data[0], data[1]]) != 0x0101
+        || u32::from_be_bytes(data[4..8].try_into().ok()?) != STUN_MAGIC_COOKIE
+        || &data[8..20] != transaction_id
+    {
+        return None;
+    }
+    let body_len = usize::from(u16::from_be_bytes([data[2], data[3]]));
+    let body = data.get(20..20 + body_len)?;
+
+    let mut mapped = None;
+    let mut offset = 0;
+    while offset + 4 <= body.len() {
+        let attr_type = u16::from_be_bytes([body[offset], body[offset + 1]]);
+        let attr_len = usize::from(u16::from_be_bytes([body[offset + 2], body[offset + 3]]));
+        let value = body.get(offset + 4..offset + 4 + attr_len)?;
+        match attr_type {
+            0x0020 => return decode_stun_address(value, Some(&data[4..20])),
+            0x0001 => mapped = decode_stun_address(value, None),
+            _ => {}
+        }
+        // 属性按 4 字节对齐
+        offset += 4 + attr_len.div_ceil(4) * 4;
+    }
+    mapped
+}
+
+/// 解码 (XOR-)MAPPED-ADDRESS 属性值，`xor_key` 为 magic cookie + 事务 ID
+fn decode_stun_address(value: &[u8], xor_key: Option<&[u8]>) -> Option<SocketAddr> {
+    let family = *value.get(1)?;
+    let mut port = u16::from_be_bytes([*value.get(2)?, *value.get(3)?]);
+    let addr_len = match family {
+        0x01 => 4,
+        0x02 => 16,
+        _ => return None,
+    };
+    let mut addr = value.get(4..4 + addr_len)?.to_vec();
+    if let Some(key) = xor_key {
+        port ^= u16::from_be_bytes([key[0], key[1]]);
+        addr.iter_mut().zip(key).for_each(|(b, k)| *b ^= k);
+    }
+    let ip = match family {
+        0x01 => IpAddr::from(<[u8; 4]>::try_from(addr.as_slice()).ok()?),
+        _ => IpAddr::from(<[u8; 16]>::try_from(addr.as_slice()).ok()?),
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+/// 加速转发 EF（RFC 3246），RTP 媒体推荐使用的 DSCP
+pub const DSCP_EF: u8 = 46;
+
+/// 类选择器 CS3，SIP 信令推荐使用的 DSCP
+pub const DSCP_CS3: u8 = 24;
+
+/// 设置套接字发出报文的 DSCP（IPv4 TOS 或 IPv6 Traffic Class 字节的高 6 位）
+///
+/// `dscp` 超过 63 时返回 `ErrorKind::InvalidInput`；部分平台需要特权才能设置，
+/// 此时返回系统错误，由调用方决定是否忽略
+pub fn set_dscp(socket: socket2::SockRef<'_>, ipv6: bool, dscp: u8) -> io::Result<()> {
+    if dscp > 63 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("DSCP {} out of range 0-63", dscp),
+        ));
+    }
+    let tos = u32::from(dscp) << 2;
+    if ipv6 {
+        set_tclass_v6(socket, tos)
+    } else {
+        socket.set_tos_v4(tos)
+    }
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+))]
+fn set_tclass_v6(socket: socket2::SockRef<'_>, tclass: u32) -> io::Result<()> {
+    socket.set_tclass_v6(tclass)
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+)))]
+fn set_tclass_v6(_socket: socket2::SockRef<'_>, _tclass: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "IPv6 traffic class is not supported on this platform",
+    ))
+}
+
+/// 设置 DSCP，失败时只记录警告：无权限或平台不支持时报文按默认优先级发送
+pub(crate) fn mark_dscp(socket: socket2::SockRef<'_>, ipv6: bool, dscp: u8, what: &str) {
+    match set_dscp(socket, ipv6, dscp) {
+        Ok(()) => tracing::debug!("{} 已标记 DSCP {}", what, dscp),
+        Err(e) => tracing::warn!("无法为 {} 设置 DSCP {}: {}", what, dscp, e),
+    }
+}
+
+/// 发送前校验 SDP 的基本结构
+///
+/// 要求包含 v=、o=、s= 行与至少一个 m= 行；c= 行可在会话级给出，否则每个 m 段都需要；
+/// 每个 m 行须有合法端口（可带 `/数量`）和至少一个负载类型
+pub fn validate_sdp(sdp: &str) -> Result<(), SdpValidationError> {
+    let lines: Vec<&str> = sdp.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    for kind in ['v', 'o', 's'] {
+        if !lines.iter().any(|l| l.starts_with(&format!("{}=", kind))) {
+            return Err(SdpValidationError::MissingLine(kind));
+        }
+    }
+
+    let first_media = lines
+        .iter()
+        .position(|l| l.starts_with("m="))
+        .ok_or(SdpValidationError::NoMedia)?;
+    let session_connection = lines[..first_media].iter().any(|l| l.starts_with("c="));
+
+    let mut media_has_connection = true;
+    for line in &lines[first_media..] {
+        if let Some(media) = line.strip_prefix("m=") {
+            if !media_has_connection && !session_connection {
+                return Err(SdpValidationError::MissingLine('c'));
+            }
+            media_has_connection = false;
+
+            let fields: Vec<&str> = media.split_whitespace().collect();
+            let port = fields.get(1).and_then(|p| p.split('/').next());
+            if port.and_then(|p| p.parse::<u16>().ok()).is_none() {
+                return Err(SdpValidationError::InvalidPort { line: line.to_string() });
+            }
+            if fields.len() < 4 {
+                return Err(SdpValidationError::MissingPayloadType { line: line.to_string() });
+            }
+        } else if line.starts_with("c=") {
+            media_has_connection = true;
+        }
+    }
+    if !media_has_connection && !session_connection {
+        return Err(SdpValidationError::MissingLine('c'));
+    }
+    Ok(())
+}
+
+/// 按文件内容识别媒体类型，不依赖扩展名
+///
+/// RIFF/WAVE 文件为音频，IVF（`DKIF` 签名）为视频；MP3 仅在启用 `mp3` 特性时视为音频，
+/// 其他格式返回 `MediaPlayError::UnsupportedFormat`。扩展名与内容不符时记录警告并以内容为准
+pub fn sniff_media_kind(path: impl AsRef<Path>) -> Result<MediaKind, MediaPlayError> {
+    let path = path.as_ref();
+    let mut file = std::fs::File::open(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => MediaPlayError::FileNotFound(path.display().to_string()),
+        _ => MediaPlayError::Io(e),
+    })?;
+    let mut header = Vec::with_capacity(12);
+    file.by_ref().take(12).read_to_end(&mut header)?;
+
+    let kind = match header.as_slice() {
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => MediaKind::Audio,
+        [b'D', b'K', b'I', b'F', ..] => MediaKind::Video,
+        #[cfg(feature = "mp3")]
+        data if is_mp3_header(data) => MediaKind::Audio,
+        #[cfg(not(feature = "mp3"))]
+        data if is_mp3_header(data) => {
+            return Err(MediaPlayError::UnsupportedFormat(format!(
+                "{} 是 MP3 文件，需启用 mp3 特性",
+                path.display()
+            )))
+        }
+        _ => {
+            return Err(MediaPlayError::UnsupportedFormat(format!(
+                "无法识别 {} 的媒体格式",
+                path.display()
+            )))
+        }
+    };
+
+    let ext = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    let expected: &[&str] = match kind {
+        MediaKind::Audio => &["wav", "mp3"],
+        MediaKind::Video => &["ivf"],
+    };
+    if !expected.contains(&ext.as_str()) {
+        tracing::warn!("{} 的扩展名与内容不符，按 {:?} 处理", path.display(), kind);
+    }
+    Ok(kind)
+}
+
+/// 文件内容是否为 MP3（ID3 标签或 MPEG 音频帧同步字）
+pub fn is_mp3_file(path: impl AsRef<Path>) -> bool {
+    let mut header = Vec::with_capacity(3);
+    std::fs::File::open(path)
+        .and_then(|file| file.take(3).read_to_end(&mut header))
+        .is_ok_and(|_| is_mp3_header(&header))
+}
+
+fn is_mp3_header(header: &[u8]) -> bool {
+    match header {
+        [b'I', b'D', b'3', ..] => true,
+        [0xFF, sync, ..] => sync & 0xE0 == 0xE0,
+        _ => false,
+    }
+}
+
+/// 测试用的 STUN 服务器：以请求来源地址作为 XOR-MAPPED-ADDRESS 应答
+#[cfg(test)]
+pub(crate) async fn spawn_stun_stub(ip: IpAddr) -> SocketAddr {
+    let socket = UdpSocket::bind((ip, 0)).await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 1024];
+        while let Ok((len, peer)) = socket.recv_from(&mut buf).await {
+            if len < 20 {
+                continue;
+            }
+            let key = buf[4..20].to_vec();
+            let mut value = vec![0u8, 0x01];
+            value.extend_from_slice(&(peer.port() ^ u16::from_be_bytes([key[0], key[1]])).to_be_bytes());
+            let IpAddr::V4(v4) = peer.ip() else {
+                continue;
+            };
+            value.extend(v4.octets().iter().zip(&key).map(|(b, k)| b ^ k));
+
+            let mut resp = vec![0x01, 0x01];
+            resp.extend_from_slice(&((4 + value.len()) as u16).to_be_bytes());
+            resp.extend_from_slice(&key);
+            resp.extend_from_slice(&0x0020u16.to_be_bytes());
+            resp.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            resp.extend_from_slice(&value);
+            let _ = socket.send_to(&resp, peer).await;
+        }
+    });
+    addr
+}
+
+#[test]
+fn test_json_log_format() {
+    use std::sync::{Arc, Mutex};
+
+    assert_eq!("JSON".parse::<LogFormat>(), Ok(LogFormat::Json));
+    assert_eq!("pretty".parse::<LogFormat>(), Ok(LogFormat::Pretty));
+    assert!("xml".parse::<LogFormat>().is_err());
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let buffer = Buffer::default();
+    let writer = buffer.clone();
+    let subscriber = json_subscriber(tracing::Level::INFO, move || writer.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("call", call_id = "abc@host", dialog_id = "abc-1-2");
+        let _guard = span.enter();
+        tracing::info!(event = "answered", "通话已接通");
+        tracing::debug!("低于级别的日志不输出");
+    });
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 1, "{}", output);
+    let line = lines[0];
+    assert!(line.starts_with('{') && line.ends_with('}'), "{}", line);
+    assert!(line.contains(r#""event":"answered""#), "{}", line);
+    assert!(line.contains(r#""message":"通话已接通""#), "{}", line);
+    assert!(line.contains(r#""call_id":"abc@host""#), "{}", line);
+    assert!(line.contains(r#""dialog_id":"abc-1-2""#), "{}", line);
+}
+
 #[test]
 fn test_get_first_non_loopback_interface_ipv4() {
     // 测试优先 IPv4
@@ -169,3 +605,115 @@ fn test_get_first_non_loopback_interface_return_type() {
         assert!(!addr.is_loopback(), "返回的地址不应该是回环地址");
     }
 }
+
+#[tokio::test]
+async fn test_stun_binding() {
+    let ip: IpAddr = Ipv4Addr::LOCALHOST.into();
+    let stun = spawn_stun_stub(ip).await.to_string();
+    let socket = UdpSocket::bind((ip, 0)).await.unwrap();
+    let mapped = stun_binding(&socket, &stun, STUN_TIMEOUT).await.unwrap();
+    assert_eq!(mapped, socket.local_addr().unwrap());
+
+    // 无响应时按超时返回
+    let silent = UdpSocket::bind((ip, 0)).await.unwrap();
+    let err = stun_binding(&socket, &silent.local_addr().unwrap().to_string(), Duration::from_millis(200))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+}
+
+#[test]
+fn test_parse_stun_mapped_address() {
+    let transaction_id = [7u8; 12];
+    // MAPPED-ADDRESS 203.0.113.5:40000
+    let mut resp = vec![0x01, 0x01, 0x00, 0x0c];
+    resp.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    resp.extend_from_slice(&transaction_id);
+    resp.extend_from_slice(&[0x00, 0x01, 0x00, 0x08, 0x00, 0x01]);
+    resp.extend_from_slice(&40000u16.to_be_bytes());
+    resp.extend_from_slice(&[203, 0, 113, 5]);
+    assert_eq!(
+        parse_stun_binding_response(&resp, &transaction_id),
+        Some("203.0.113.5:40000".parse().unwrap())
+    );
+    assert_eq!(parse_stun_binding_response(&resp, &[0u8; 12]), None);
+    assert_eq!(parse_stun_binding_response(&resp[..20], &transaction_id), None);
+}
+
+#[tokio::test]
+async fn test_set_dscp() {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    set_dscp(socket2::SockRef::from(&socket), false, DSCP_EF).unwrap();
+    assert_eq!(socket2::SockRef::from(&socket).tos_v4().unwrap(), u32::from(DSCP_EF) << 2);
+    let err = set_dscp(socket2::SockRef::from(&socket), false, 64).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[cfg(test)]
+const VALID_SDP: &str = "v=0\r\no=- 1 1 IN IP4 192.0.2.1\r\ns=-\r\nc=IN IP4 192.0.2.1\r\nt=0 0\r\nm=audio 4000 RTP/AVP 0 101\r\n";
+
+#[test]
+fn test_validate_sdp_missing_c_line() {
+    assert_eq!(validate_sdp(VALID_SDP), Ok(()));
+    // c= 在媒体级给出同样有效
+    let media_level = VALID_SDP.replace("c=IN IP4 192.0.2.1\r\nt=0 0\r\nm=audio 4000 RTP/AVP 0 101\r\n", "t=0 0\r\nm=audio 4000 RTP/AVP 0 101\r\nc=IN IP4 192.0.2.1\r\n");
+    assert_eq!(validate_sdp(&media_level), Ok(()));
+
+    let missing = VALID_SDP.replace("c=IN IP4 192.0.2.1\r\n", "");
+    assert_eq!(validate_sdp(&missing), Err(SdpValidationError::MissingLine('c')));
+    assert_eq!(validate_sdp(""), Err(SdpValidationError::MissingLine('v')));
+    assert!(matches!(
+        crate::error::CallError::from(SdpValidationError::MissingLine('c')),
+        crate::error::CallError::InvalidSdp { .. }
+    ));
+}
+
+#[test]
+fn test_validate_sdp_empty_m_line() {
+    let empty = VALID_SDP.replace("m=audio 4000 RTP/AVP 0 101", "m=audio 4000 RTP/AVP");
+    assert!(matches!(
+        validate_sdp(&empty),
+        Err(SdpValidationError::MissingPayloadType { .. })
+    ));
+    let bad_port = VALID_SDP.replace("4000", "port");
+    assert!(matches!(validate_sdp(&bad_port), Err(SdpValidationError::InvalidPort { .. })));
+    let no_media = VALID_SDP.replace("m=audio 4000 RTP/AVP 0 101\r\n", "");
+    assert_eq!(validate_sdp(&no_media), Err(SdpValidationError::NoMedia));
+}
+
+#[test]
+fn test_sniff_media_kind_by_content() {
+    let dir = std::env::temp_dir();
+    let write = |name: &str, data: &[u8]| {
+        let path = dir.join(format!("sniff-{}-{}", uuid::Uuid::new_v4(), name));
+        std::fs::write(&path, data).unwrap();
+        path
+    };
+
+    // 扩展名错误的 WAV 与 IVF 仍按内容识别
+    let wav = write("audio.ivf", b"RIFF\x24\0\0\0WAVEfmt ");
+    let ivf = write("video.bin", b"DKIF\0\0\x20\0VP80");
+    assert_eq!(sniff_media_kind(&wav).unwrap(), MediaKind::Audio);
+    assert_eq!(sniff_media_kind(&ivf).unwrap(), MediaKind::Video);
+
+    // 伪装成 .wav 的 MP3 按内容识别：未启用 mp3 特性时被拒绝
+    let id3 = write("song.wav", b"ID3\x04\0\0\0\0\0\0");
+    let frame = write("frame.wav", &[0xFF, 0xFB, 0x90, 0x64]);
+    let short = write("short.wav", b"RI");
+    assert!(is_mp3_file(&id3) && is_mp3_file(&frame) && !is_mp3_file(&wav));
+    for path in [&id3, &frame] {
+        #[cfg(feature = "mp3")]
+        assert_eq!(sniff_media_kind(path).unwrap(), MediaKind::Audio);
+        #[cfg(not(feature = "mp3"))]
+        assert!(matches!(sniff_media_kind(path), Err(MediaPlayError::UnsupportedFormat(_))));
+    }
+    assert!(matches!(sniff_media_kind(&short), Err(MediaPlayError::UnsupportedFormat(_))));
+    assert!(matches!(
+        sniff_media_kind("/nonexistent/a.wav"),
+        Err(MediaPlayError::FileNotFound(_))
+    ));
+
+    for path in [wav, ivf, id3, frame, short] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
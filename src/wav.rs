@@ -0,0 +1,280 @@
+/// WAV 文件辅助模块
+///
+/// 提供 16 位线性 PCM WAV 文件的读取，以及单声道文件的写入（关闭时回填头部长度字段）
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// 标准 PCM WAV 头部长度
+pub const WAV_HEADER_LEN: u32 = 44;
+
+/// fmt 块的最大长度（WAVE_FORMAT_EXTENSIBLE 为 40 字节），更长的视为损坏文件
+const MAX_FMT_CHUNK_LEN: u32 = 40;
+
+/// WAV 音频格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WavFormat {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+}
+
+/// 读取到的 WAV 音频
+#[derive(Debug, Clone)]
+pub struct WavAudio {
+    pub format: WavFormat,
+    /// 交织排列的 PCM 样本
+    pub samples: Vec<i16>,
+}
+
+/// 读取 16 位线性 PCM WAV 文件
+pub fn read_wav(path: impl AsRef<Path>) -> io::Result<WavAudio> {
+    parse_wav(&std::fs::read(path)?)
+}
+
+/// 只读取 WAV 文件头部的 fmt 块，不加载 PCM 数据
+///
+/// 用于播放前校验采样率与声道数；非 16 位线性 PCM 时返回错误
+pub fn read_wav_format(path: impl AsRef<Path>) -> io::Result<WavFormat> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut riff = [0u8; 12];
+    reader.read_exact(&mut riff)?;
+    if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return Err(invalid("不是 RIFF/WAVE 文件"));
+    }
+    loop {
+        let mut chunk = [0u8; 8];
+        reader.read_exact(&mut chunk).map_err(|_| invalid("缺少 fmt 块"))?;
+        let len = u32::from_le_bytes(chunk[4..8].try_into().unwrap());
+        match &chunk[0..4] {
+            b"fmt " => {
+                if len > MAX_FMT_CHUNK_LEN {
+                    return Err(invalid("fmt 块长度异常"));
+                }
+                let mut body = vec![0u8; len as usize];
+                reader.read_exact(&mut body).map_err(|_| invalid("fmt 块长度不足"))?;
+                let format = parse_fmt(&body)?;
+                if format.bits_per_sample != 16 {
+                    return Err(invalid("仅支持 16 位采样"));
+                }
+                return Ok(format);
+            }
+            b"data" => return Err(invalid("data 块之前缺少 fmt 块")),
+            // 块按 2 字节对齐
+            _ => {
+                reader.seek_relative(len as i64 + (len & 1) as i64)?;
+            }
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// 解析 fmt 块，仅接受线性 PCM 编码
+fn parse_fmt(body: &[u8]) -> io::Result<WavFormat> {
+    if body.len() < 16 {
+        return Err(invalid("fmt 块长度不足"));
+    }
+    let audio_format = u16::from_le_bytes([body[0], body[1]]);
+    if audio_format != 1 {
+        return Err(invalid("仅支持线性 PCM 编码"));
+    }
+    Ok(WavFormat {
+        channels: u16::from_le_bytes([body[2], body[3]]),
+        sample_rate: u32::from_le_bytes(body[4..8].try_into().unwrap()),
+        bits_per_sample: u16::from_le_bytes([body[14], body[15]]),
+    })
+}
+
+/// 解析 16 位线性 PCM WAV 数据，跳过 fmt 与 data 之外的块
+pub fn parse_wav(data: &[u8]) -> io::Result<WavAudio> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err(invalid("不是 RIFF/WAVE 文件"));
+    }
+
+    let mut format = None;
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let id = &data[offset..offset + 4];
+        let len = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().unwrap()) as usize;
+        let body = &data[offset + 8..data.len().min(offset + 8 + len)];
+        match id {
+            b"fmt " => format = Some(parse_fmt(body)?),
+            b"data" => {
+                let format = format.ok_or_else(|| invalid("data 块之前缺少 fmt 块"))?;
+                if format.bits_per_sample != 16 {
+                    return Err(invalid("仅支持 16 位采样"));
+                }
+                let samples = body
+                    .chunks_exact(2)
+                    .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                    .collect();
+                return Ok(WavAudio { format, samples });
+            }
+            _ => {}
+        }
+        // 块按 2 字节对齐
+        offset += 8 + len + (len & 1);
+    }
+    Err(invalid("缺少 data 块"))
+}
+
+/// 16 位 PCM 单声道 WAV 写入器
+///
+/// 创建时写入长度为 0 的占位头部，[`WavWriter::finalize`] 时回填 RIFF 与 data 长度；
+/// 未显式关闭时在析构时尽力回填
+pub struct WavWriter {
+    writer: BufWriter<File>,
+    sample_rate: u32,
+    data_len: u32,
+    finalized: bool,
+}
+
+impl WavWriter {
+    /// 创建（或覆盖）WAV 文件
+    pub fn create(path: impl AsRef<Path>, sample_rate: u32) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_header(&mut writer, sample_rate, 0)?;
+        Ok(Self {
+            writer,
+            sample_rate,
+            data_len: 0,
+            finalized: false,
+        })
+    }
+
+    /// 追加 PCM 样本
+    ///
+    /// 在 [`WavWriter::finalize`] 之后继续写入时，下一次 `finalize` 或析构会重新回填头部
+    pub fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        for sample in samples {
+            self.writer.write_all(&sample.to_le_bytes())?;
+        }
+        let len = u32::try_from(samples.len()).unwrap_or(u32::MAX).saturating_mul(2);
+        self.data_len = self.data_len.saturating_add(len);
+        self.finalized = false;
+        Ok(())
+    }
+
+    /// 已写入的 PCM 数据字节数
+    pub fn data_len(&self) -> u32 {
+        self.data_len
+    }
+
+    /// 回填头部长度字段并刷新到磁盘，重复调用无副作用
+    pub fn finalize(&mut self) -> io::Result<()> {
+        if self.finalized {
+            return Ok(());
+        }
+        self.writer.seek(SeekFrom::Start(0))?;
+        write_header(&mut self.writer, self.sample_rate, self.data_len)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        self.finalized = true;
+        Ok(())
+    }
+}
+
+impl Drop for WavWriter {
+    fn drop(&mut self) {
+        let _ = self.finalize();
+    }
+}
+
+fn write_header(writer: &mut impl Write, sample_rate: u32, data_len: u32) -> io::Result<()> {
+    let channels: u16 = 1;
+    let bits_per_sample: u16 = 16;
+    let block_align = channels * bits_per_sample / 8;
+    let byte_rate = sample_rate * block_align as u32;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(WAV_HEADER_LEN - 8).saturating_add(data_len).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?; // PCM
+    writer.write_all(&channels.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&byte_rate.to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&bits_per_sample.to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_back_written_file() {
+        let path = std::env::temp_dir().join(format!("wav-read-{}.wav", uuid::Uuid::new_v4()));
+        let mut writer = WavWriter::create(&path, 8000).unwrap();
+        writer.write_samples(&[7, -7]).unwrap();
+        drop(writer);
+
+        let audio = read_wav(&path).unwrap();
+        let header = read_wav_format(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(header, audio.format);
+        assert_eq!(
+            audio.format,
+            WavFormat { channels: 1, sample_rate: 8000, bits_per_sample: 16 }
+        );
+        assert_eq!(audio.samples, vec![7, -7]);
+        assert!(parse_wav(b"RIFF\0\0\0\0WAVE").is_err());
+    }
+
+    #[test]
+    fn test_oversized_fmt_chunk_rejected() {
+        let path = std::env::temp_dir().join(format!("wav-fmt-{}.wav", uuid::Uuid::new_v4()));
+        let mut data = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&path, &data).unwrap();
+
+        let err = read_wav_format(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_header_fixed_up_on_finalize() {
+        let path = std::env::temp_dir().join(format!("wav-writer-{}.wav", uuid::Uuid::new_v4()));
+        let mut writer = WavWriter::create(&path, 8000).unwrap();
+        writer.write_samples(&[0, 1000, -1000]).unwrap();
+        writer.finalize().unwrap();
+        writer.write_samples(&[]).unwrap();
+        drop(writer);
+
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(data.len(), 44 + 6);
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(data[4..8].try_into().unwrap()), 36 + 6);
+        assert_eq!(u32::from_le_bytes(data[24..28].try_into().unwrap()), 8000);
+        assert_eq!(u32::from_le_bytes(data[40..44].try_into().unwrap()), 6);
+        assert_eq!(i16::from_le_bytes([data[46], data[47]]), 1000);
+    }
+
+    #[test]
+    fn test_write_after_finalize_rewrites_header() {
+        let path = std::env::temp_dir().join(format!("wav-reopen-{}.wav", uuid::Uuid::new_v4()));
+        let mut writer = WavWriter::create(&path, 8000).unwrap();
+        writer.write_samples(&[1, 2]).unwrap();
+        writer.finalize().unwrap();
+        writer.write_samples(&[3]).unwrap();
+        drop(writer);
+
+        let audio = read_wav(&path).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(audio.samples, vec![1, 2, 3]);
+        assert_eq!(u32::from_le_bytes(data[4..8].try_into().unwrap()), 36 + 6);
+        assert_eq!(u32::from_le_bytes(data[40..44].try_into().unwrap()), 6);
+    }
+}